@@ -1,17 +1,34 @@
 export interface LocationLike {
   protocol: string;
   host: string;
+  /**
+   * Page query string; `?room=<name>`, `?token=<join token>` and
+   * `?spectate=1` are forwarded to the server.
+   */
+  search?: string;
 }
 
 export function buildServerUrl(
   locationLike: LocationLike,
   envOverride?: string,
 ): string {
-  if (envOverride && envOverride.length > 0) {
-    return envOverride;
-  }
   const wsScheme = locationLike.protocol === "https:" ? "wss" : "ws";
-  return `${wsScheme}://${locationLike.host}/ws`;
+  const base =
+    envOverride && envOverride.length > 0
+      ? envOverride
+      : `${wsScheme}://${locationLike.host}/ws`;
+  const page = new URLSearchParams(locationLike.search ?? "");
+  const forwarded = ["room", "token", "spectate"].filter((key) =>
+    page.get(key),
+  );
+  if (forwarded.length === 0) {
+    return base;
+  }
+  const url = new URL(base);
+  for (const key of forwarded) {
+    url.searchParams.set(key, page.get(key)!);
+  }
+  return url.toString();
 }
 
 export function launcherStackScale(count: number): number {
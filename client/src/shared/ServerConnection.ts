@@ -12,10 +12,15 @@ import {
   SpaceBall3D,
 } from "./types";
 import { rotateNormalizeInPlace, slerpTo, type Vec3 } from "./vec3";
-import type { BallWire, PlayerWire, ServerMsg } from "./generated";
+import type {
+  BallWire,
+  PlayerWire,
+  ServerMsg,
+  SpaceStateMsg,
+} from "./generated";
 
 /** Must match server's PROTOCOL_VERSION in protocol.rs */
-const CLIENT_PROTOCOL_VERSION = 2;
+const CLIENT_PROTOCOL_VERSION = 5;
 
 /** Connection state for UI feedback */
 export type ConnectionState = "connected" | "connecting" | "disconnected";
@@ -39,6 +44,24 @@ interface Snapshot {
   idToIndex: Map<number, number>;
 }
 
+/** A ball as last sent explicitly in a space_state keyframe/delta, and when. */
+interface DeltaRecord {
+  ball: BallWire;
+  serverTime: number;
+}
+
+/** Advance a ball record along its great circle (mirrors shared/src/delta.rs). */
+function predictPos(
+  ball: BallWire,
+  fromTime: number,
+  toTime: number,
+): [number, number, number] {
+  const pos = { x: ball.pos[0], y: ball.pos[1], z: ball.pos[2] };
+  const axis = { x: ball.axis[0], y: ball.axis[1], z: ball.axis[2] };
+  rotateNormalizeInPlace(pos, axis, ball.omega * (toTime - fromTime));
+  return [pos.x, pos.y, pos.z];
+}
+
 function wireToPlayer(w: PlayerWire): Player {
   return {
     id: w.id,
@@ -63,6 +86,7 @@ export class ServerConnection {
   private balls: SpaceBall3D[] = [];
   private config: DeepSpaceConfig = DEFAULT_DEEP_SPACE_CONFIG;
   private serverVersion = "";
+  private resumeToken: string | null = null;
   private connectionState: ConnectionState = "connecting";
 
   // Reconnect state
@@ -71,6 +95,10 @@ export class ServerConnection {
   private shouldReconnect = true;
   private protocolMismatch = false;
 
+  // space_state delta chain (keyframe + deltas)
+  private deltaRecords: Map<number, DeltaRecord> = new Map();
+  private lastSeq: number | null = null;
+
   // Snapshot interpolation state
   private snapshots: Snapshot[] = [];
   private hasServerTimeOffset = false;
@@ -117,6 +145,14 @@ export class ServerConnection {
       this.setConnectionState("connected");
       this.reconnectDelay = RECONNECT_INITIAL_DELAY_MS; // Reset on successful connect
       this.resetInterpolationState();
+      this.resetDeltaState();
+      // Identify first so the server can restore our portal on reconnect
+      this.ws?.send(
+        JSON.stringify({
+          type: "hello",
+          resumeToken: this.resumeToken ?? undefined,
+        }),
+      );
       console.log("[ServerConnection] Connected to server");
     };
 
@@ -132,6 +168,7 @@ export class ServerConnection {
     this.ws.onclose = () => {
       this.setConnectionState("disconnected");
       this.resetInterpolationState();
+      this.resetDeltaState();
       console.log("[ServerConnection] Disconnected from server");
       this.scheduleReconnect();
     };
@@ -171,6 +208,68 @@ export class ServerConnection {
     this.interpolatedBalls.length = 0;
   }
 
+  private resetDeltaState() {
+    this.deltaRecords.clear();
+    this.lastSeq = null;
+  }
+
+  /**
+   * Rebuild the full ball list from a space_state keyframe or delta.
+   * Returns null if the delta chain is broken (missed seq, no baseline yet);
+   * the caller should then request a keyframe.
+   */
+  private applySpaceState(msg: SpaceStateMsg): BallWire[] | null {
+    const serverTime = msg.serverTime;
+
+    if (msg.keyframe) {
+      this.deltaRecords.clear();
+      for (const ball of msg.balls) {
+        this.deltaRecords.set(ball.id, { ball, serverTime });
+      }
+      this.lastSeq = msg.seq;
+      return msg.balls;
+    }
+
+    if (this.lastSeq === null || msg.seq !== (this.lastSeq + 1) >>> 0) {
+      this.resetDeltaState();
+      return null;
+    }
+
+    for (const id of msg.removed ?? []) {
+      if (!this.deltaRecords.delete(id)) {
+        this.resetDeltaState();
+        return null;
+      }
+    }
+    for (const ball of msg.balls) {
+      this.deltaRecords.set(ball.id, { ball, serverTime });
+    }
+
+    const unchanged = msg.unchanged ?? [];
+    if (this.deltaRecords.size !== msg.balls.length + unchanged.length) {
+      this.resetDeltaState();
+      return null;
+    }
+
+    const balls: BallWire[] = [];
+    for (const id of unchanged) {
+      const rec = this.deltaRecords.get(id);
+      if (!rec) {
+        this.resetDeltaState();
+        return null;
+      }
+      balls.push({
+        ...rec.ball,
+        pos: predictPos(rec.ball, rec.serverTime, serverTime),
+      });
+    }
+    for (const ball of msg.balls) {
+      balls.push(ball);
+    }
+    this.lastSeq = msg.seq;
+    return balls;
+  }
+
   /** Update snapshots and latest ball cache from a new server snapshot. */
   private updateBallsFromSnapshot(wireBalls: BallWire[], serverTime: number) {
     if (!Number.isFinite(serverTime)) {
@@ -352,6 +451,7 @@ export class ServerConnection {
         }
         this.selfId = msg.selfId;
         this.serverVersion = msg.serverVersion ?? "";
+        this.resumeToken = msg.resumeToken || null;
         this.players = msg.players.map(wireToPlayer);
         this.config = msg.config;
         this.onWelcome?.(this.selfId, this.players, this.config);
@@ -362,13 +462,26 @@ export class ServerConnection {
         this.onPlayersState?.(this.players);
         break;
 
-      case "space_state":
-        this.updateBallsFromSnapshot(msg.balls, msg.serverTime);
+      case "space_state": {
+        const balls = this.applySpaceState(msg);
+        if (!balls) {
+          this.ws?.send(JSON.stringify({ type: "request_keyframe" }));
+          break;
+        }
+        this.updateBallsFromSnapshot(balls, msg.serverTime);
         this.onSpaceState?.(this.balls);
         break;
+      }
 
       case "transfer_in":
         this.onTransferIn?.(msg.vx, msg.vy, msg.color);
+        // Unacked transfers are re-injected into deep space by the server
+        this.ws?.send(JSON.stringify({ type: "transfer_ack", seq: msg.seq }));
+        break;
+
+      case "server_going_away":
+        // Planned restart: the close follows and the usual reconnect takes over
+        console.log(`[ServerConnection] Server going away: ${msg.reason}`);
         break;
     }
   }
@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type BallWire = { id: number, ownerId: number, pos: [number, number, number], axis: [number, number, number], omega: number, };
+export type BallWire = { id: number, ownerId: number, pos: [number, number, number], axis: [number, number, number], omega: number, 
+/**
+ * Boards this ball has passed through since it was first launched.
+ */
+hops?: number, };
@@ -1,3 +1,19 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EmoteKind } from "./EmoteKind";
 
-export type ClientMsg = { "type": "ball_escaped", vx: number, vy: number, } | { "type": "set_paused", paused: boolean, } | { "type": "activity" };
+export type ClientMsg = { "type": "hello", resumeToken?: string | null, 
+/**
+ * Display name to join with. Invalid names are ignored.
+ */
+name?: string | null, } | { "type": "ball_escaped", vx: number, vy: number, 
+/**
+ * Gift the ball: reroutes steer it toward this player's portal
+ * while they're eligible. Unknown ids and yourself are ignored.
+ */
+targetPlayerId?: number | null, 
+/**
+ * `transfer_in.ballId` if this is a ball received from another
+ * player re-escaping; the server keeps its origin and hop count.
+ * Omit for balls launched on this board.
+ */
+ballId?: number | null, } | { "type": "set_paused", paused: boolean, } | { "type": "activity" } | { "type": "request_keyframe" } | { "type": "ping", clientTime: number, } | { "type": "transfer_ack", seq: number, } | { "type": "set_name", name: string, } | { "type": "emote", kind: EmoteKind, } | { "type": "bumper_hits", count: number, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The fixed set of emotes a player can send.
+ */
+export type EmoteKind = "wave" | "cheer" | "thanks";
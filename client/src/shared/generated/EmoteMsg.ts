@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EmoteKind } from "./EmoteKind";
+
+/**
+ * An emote from `player_id`, broadcast to everyone in the room.
+ */
+export type EmoteMsg = { playerId: number, kind: EmoteKind, };
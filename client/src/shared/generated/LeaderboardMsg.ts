@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LeaderboardEntry } from "./LeaderboardEntry";
+
+/**
+ * Scores of connected (non-bot) players, broadcast every few seconds.
+ * Points come from balls delivered to and received from other players
+ * and from reported bumper hits.
+ */
+export type LeaderboardMsg = { 
+/**
+ * Best first, ties broken by lower id; cut to the top entries, so a
+ * player missing here ranks below the last one.
+ */
+entries: Array<LeaderboardEntry>, 
+/**
+ * Number of ranked players, including those cut from `entries`
+ */
+total: number, 
+/**
+ * Seconds until every score resets to zero. Absent if they never do.
+ */
+resetsIn?: number | null, };
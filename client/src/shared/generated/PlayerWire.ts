@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type PlayerWire = { id: number, cellIndex: number, portalPos: [number, number, number], color: number, paused: boolean, ballsProduced: number, ballsInFlight: number, };
+export type PlayerWire = { id: number, cellIndex: number, portalPos: [number, number, number], color: number, paused: boolean, ballsProduced: number, ballsInFlight: number, 
+/**
+ * Display name, already validated by the server. Empty = no name.
+ */
+name: string, };
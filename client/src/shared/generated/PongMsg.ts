@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Reply to `ping`, sent straight from the connection task (not the game
+ * loop) so its latency reflects the network rather than the tick rate.
+ */
+export type PongMsg = { 
+/**
+ * Echo of `ping.clientTime`.
+ */
+clientTime: number, 
+/**
+ * Server elapsed time (same clock as `space_state.serverTime`) when the
+ * ping was answered.
+ */
+serverTime: number, };
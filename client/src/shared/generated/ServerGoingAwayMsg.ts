@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Sent to every client right before the server closes their connection
+ * for a shutdown or redeploy. Reconnecting later is expected to work.
+ */
+export type ServerGoingAwayMsg = { reason: string, };
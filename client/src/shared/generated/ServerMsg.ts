@@ -1,7 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { EmoteMsg } from "./EmoteMsg";
+import type { LeaderboardMsg } from "./LeaderboardMsg";
 import type { PlayersStateMsg } from "./PlayersStateMsg";
+import type { PongMsg } from "./PongMsg";
+import type { ServerGoingAwayMsg } from "./ServerGoingAwayMsg";
 import type { SpaceStateMsg } from "./SpaceStateMsg";
 import type { TransferInMsg } from "./TransferInMsg";
 import type { WelcomeMsg } from "./WelcomeMsg";
 
-export type ServerMsg = { "type": "welcome" } & WelcomeMsg | { "type": "players_state" } & PlayersStateMsg | { "type": "space_state" } & SpaceStateMsg | { "type": "transfer_in" } & TransferInMsg;
+export type ServerMsg = { "type": "welcome" } & WelcomeMsg | { "type": "players_state" } & PlayersStateMsg | { "type": "space_state" } & SpaceStateMsg | { "type": "transfer_in" } & TransferInMsg | { "type": "server_going_away" } & ServerGoingAwayMsg | { "type": "pong" } & PongMsg | { "type": "emote" } & EmoteMsg | { "type": "leaderboard" } & LeaderboardMsg;
@@ -1,10 +1,30 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { BallWire } from "./BallWire";
 
-export type SpaceStateMsg = {
-  /**
-   * Server elapsed time when this snapshot was created (seconds)
-   */
-  serverTime: number;
-  balls: Array<BallWire>;
-};
+export type SpaceStateMsg = { 
+/**
+ * Server elapsed time when this snapshot was created (seconds)
+ */
+serverTime: number, 
+/**
+ * Incremented per broadcast. A gap means a delta was missed.
+ */
+seq: number, 
+/**
+ * Keyframe: `balls` is every ball. Delta: only changes since `seq - 1`
+ * (see `delta.rs`).
+ */
+keyframe: boolean, 
+/**
+ * Keyframe: all balls. Delta: new, rerouted or drifted balls.
+ */
+balls: Array<BallWire>, 
+/**
+ * Delta only: balls whose axis/omega are unchanged since last sent;
+ * advance them analytically from their last record.
+ */
+unchanged?: Array<number>, 
+/**
+ * Delta only: balls that left space since the previous snapshot.
+ */
+removed?: Array<number>, };
@@ -1,3 +1,23 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type TransferInMsg = { vx: number, vy: number, ownerId: number, color: number, };
+export type TransferInMsg = { vx: number, vy: number, ownerId: number, color: number, 
+/**
+ * Echo in `transfer_ack` once the ball is on the board. Unacked
+ * transfers go back to deep space if the client disconnects or
+ * doesn't ack in time.
+ */
+seq: number, 
+/**
+ * Deep-space id of the ball. Echo it in `ball_escaped` when this ball
+ * leaves the board again so it keeps its identity.
+ */
+ballId: number, 
+/**
+ * Player who first launched the ball; `ownerId` is whoever sent it
+ * out last.
+ */
+originOwnerId: number, 
+/**
+ * Boards the ball had passed through before this one.
+ */
+hops: number, };
@@ -2,4 +2,14 @@
 import type { DeepSpaceConfig } from "./DeepSpaceConfig";
 import type { PlayerWire } from "./PlayerWire";
 
-export type WelcomeMsg = { protocolVersion: number, serverVersion: string, selfId: number, players: Array<PlayerWire>, config: DeepSpaceConfig, };
+export type WelcomeMsg = { protocolVersion: number, serverVersion: string, selfId: number, players: Array<PlayerWire>, config: DeepSpaceConfig, 
+/**
+ * Opaque token to send back in `hello` when reconnecting, so the
+ * player keeps their portal cell, color and stats.
+ */
+resumeToken: string, 
+/**
+ * True for `/ws?spectate=1` connections: no portal, `self_id` is 0 and
+ * `transfer_in` never arrives.
+ */
+spectator: boolean, };
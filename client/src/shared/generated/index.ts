@@ -4,8 +4,14 @@
 export type { BallWire } from "./BallWire";
 export type { ClientMsg } from "./ClientMsg";
 export type { DeepSpaceConfig } from "./DeepSpaceConfig";
+export type { EmoteKind } from "./EmoteKind";
+export type { EmoteMsg } from "./EmoteMsg";
+export type { LeaderboardEntry } from "./LeaderboardEntry";
+export type { LeaderboardMsg } from "./LeaderboardMsg";
 export type { PlayerWire } from "./PlayerWire";
 export type { PlayersStateMsg } from "./PlayersStateMsg";
+export type { PongMsg } from "./PongMsg";
+export type { ServerGoingAwayMsg } from "./ServerGoingAwayMsg";
 export type { ServerMsg } from "./ServerMsg";
 export type { SpaceStateMsg } from "./SpaceStateMsg";
 export type { TransferInMsg } from "./TransferInMsg";
@@ -20,6 +20,59 @@ describe("gameConfig", () => {
     ).toBe("wss://pinball.example.com/ws");
   });
 
+  it("forwards the room from the page query string", () => {
+    expect(
+      buildServerUrl({
+        protocol: "https:",
+        host: "pinball.example.com",
+        search: "?room=office",
+      }),
+    ).toBe("wss://pinball.example.com/ws?room=office");
+    expect(
+      buildServerUrl(
+        { protocol: "http:", host: "localhost:5173", search: "?room=a-b" },
+        "ws://127.0.0.1:9001/ws",
+      ),
+    ).toBe("ws://127.0.0.1:9001/ws?room=a-b");
+    expect(
+      buildServerUrl({ protocol: "http:", host: "localhost:5173", search: "" }),
+    ).toBe("ws://localhost:5173/ws");
+  });
+
+  it("forwards spectate mode from the page query string", () => {
+    expect(
+      buildServerUrl({
+        protocol: "https:",
+        host: "pinball.example.com",
+        search: "?spectate=1",
+      }),
+    ).toBe("wss://pinball.example.com/ws?spectate=1");
+    expect(
+      buildServerUrl({
+        protocol: "http:",
+        host: "localhost:5173",
+        search: "?spectate=1&room=lobby&debug=1",
+      }),
+    ).toBe("ws://localhost:5173/ws?room=lobby&spectate=1");
+  });
+
+  it("forwards a join token from the page query string", () => {
+    expect(
+      buildServerUrl({
+        protocol: "https:",
+        host: "pinball.example.com",
+        search: "?room=office&token=abc.def&name=Ada",
+      }),
+    ).toBe("wss://pinball.example.com/ws?room=office&token=abc.def");
+    expect(
+      buildServerUrl({
+        protocol: "https:",
+        host: "pinball.example.com",
+        search: "?token=abc.def",
+      }),
+    ).toBe("wss://pinball.example.com/ws?token=abc.def");
+  });
+
   it("uses quadratic launcher stack scale", () => {
     expect(launcherStackScale(0)).toBe(1);
     expect(launcherStackScale(1)).toBe(1);
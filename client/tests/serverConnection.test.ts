@@ -85,7 +85,7 @@ describe("ServerConnection", () => {
       }),
     );
 
-    expect(onMismatch).toHaveBeenCalledWith(999, 2);
+    expect(onMismatch).toHaveBeenCalledWith(999, 4);
     expect(ws.closed).toBe(true);
 
     vi.runAllTimers();
@@ -96,6 +96,7 @@ describe("ServerConnection", () => {
     const conn = new ServerConnection("ws://test");
     const ws = FakeWebSocket.instances[0];
     ws.emitOpen();
+    ws.sent.length = 0; // drop the hello
 
     conn.sendBallEscaped(100, -100);
     expect(ws.sent.length).toBe(1);
@@ -107,6 +108,56 @@ describe("ServerConnection", () => {
     expect(ws.sent.length).toBe(1);
   });
 
+  it("sends hello on open and echoes the resume token after reconnect", () => {
+    new ServerConnection("ws://test");
+    const ws = FakeWebSocket.instances[0];
+    ws.emitOpen();
+    expect(JSON.parse(ws.sent[0])).toEqual({ type: "hello" });
+
+    ws.emitMessage(
+      JSON.stringify({
+        type: "welcome",
+        protocolVersion: 4,
+        selfId: 1,
+        players: [],
+        config: DEFAULT_DEEP_SPACE_CONFIG,
+        resumeToken: "abc123",
+      }),
+    );
+    ws.close();
+    vi.runAllTimers();
+
+    const ws2 = FakeWebSocket.instances[1];
+    ws2.emitOpen();
+    expect(JSON.parse(ws2.sent[0])).toEqual({
+      type: "hello",
+      resumeToken: "abc123",
+    });
+  });
+
+  it("acks transfer_in after handing the ball to the board", () => {
+    const onTransferIn = vi.fn();
+    const conn = new ServerConnection("ws://test");
+    conn.onTransferIn = onTransferIn;
+    const ws = FakeWebSocket.instances[0];
+    ws.emitOpen();
+    ws.sent.length = 0; // drop the hello
+
+    ws.emitMessage(
+      JSON.stringify({
+        type: "transfer_in",
+        vx: 0.5,
+        vy: 1.2,
+        ownerId: 3,
+        color: 0xff6600,
+        seq: 7,
+      }),
+    );
+
+    expect(onTransferIn).toHaveBeenCalledWith(0.5, 1.2, 0xff6600);
+    expect(JSON.parse(ws.sent[0])).toEqual({ type: "transfer_ack", seq: 7 });
+  });
+
   it("fallback extrapolation clamps dt to 0.2s with single snapshot", () => {
     const rotateSpy = vi.spyOn(vec3, "rotateNormalizeInPlace");
 
@@ -121,6 +172,8 @@ describe("ServerConnection", () => {
     ws.emitMessage(
       JSON.stringify({
         type: "space_state",
+        seq: 1,
+        keyframe: true,
         serverTime: 1.0,
         balls: [
           {
@@ -158,6 +211,8 @@ describe("ServerConnection", () => {
     ws.emitMessage(
       JSON.stringify({
         type: "space_state",
+        seq: 1,
+        keyframe: true,
         serverTime: 1.0,
         balls: [
           {
@@ -176,6 +231,8 @@ describe("ServerConnection", () => {
     ws.emitMessage(
       JSON.stringify({
         type: "space_state",
+        seq: 1,
+        keyframe: true,
         serverTime: 1.1,
         balls: [
           {
@@ -215,6 +272,8 @@ describe("ServerConnection", () => {
     ws.emitMessage(
       JSON.stringify({
         type: "space_state",
+        seq: 1,
+        keyframe: true,
         serverTime: 1.0,
         balls: [
           {
@@ -232,6 +291,8 @@ describe("ServerConnection", () => {
     ws.emitMessage(
       JSON.stringify({
         type: "space_state",
+        seq: 1,
+        keyframe: true,
         serverTime: 1.0,
         balls: [
           {
@@ -266,6 +327,8 @@ describe("ServerConnection", () => {
     ws.emitMessage(
       JSON.stringify({
         type: "space_state",
+        seq: 1,
+        keyframe: true,
         serverTime: 1.0,
         balls: [
           {
@@ -283,6 +346,8 @@ describe("ServerConnection", () => {
     ws.emitMessage(
       JSON.stringify({
         type: "space_state",
+        seq: 1,
+        keyframe: true,
         serverTime: 1.1,
         balls: [
           {
@@ -301,6 +366,8 @@ describe("ServerConnection", () => {
     ws.emitMessage(
       JSON.stringify({
         type: "space_state",
+        seq: 1,
+        keyframe: true,
         serverTime: 0.9,
         balls: [
           {
@@ -332,6 +399,8 @@ describe("ServerConnection", () => {
       ws.emitMessage(
         JSON.stringify({
           type: "space_state",
+          seq: 1,
+          keyframe: true,
           serverTime: 1.0 + i * 0.1,
           balls: [],
         }),
@@ -345,4 +414,65 @@ describe("ServerConnection", () => {
     expect(snapshots[0].serverTime).toBeCloseTo(1.4, 6);
     expect(snapshots[7].serverTime).toBeCloseTo(2.1, 6);
   });
+
+  it("applies space_state deltas and requests a keyframe on a seq gap", () => {
+    const conn = new ServerConnection("ws://test");
+    const ws = FakeWebSocket.instances[0];
+    ws.emitOpen();
+    ws.sent.length = 0; // drop the hello
+
+    const ball = {
+      id: 1,
+      ownerId: 2,
+      pos: [1, 0, 0],
+      axis: [0, 0, 1],
+      omega: 1,
+    };
+    ws.emitMessage(
+      JSON.stringify({
+        type: "space_state",
+        seq: 5,
+        keyframe: true,
+        serverTime: 1.0,
+        balls: [ball],
+      }),
+    );
+    ws.emitMessage(
+      JSON.stringify({
+        type: "space_state",
+        seq: 6,
+        keyframe: false,
+        serverTime: 1.5,
+        balls: [],
+        unchanged: [1],
+      }),
+    );
+
+    const snapshots = (
+      conn as unknown as {
+        snapshots: Array<{ balls: Array<{ pos: number[] }> }>;
+      }
+    ).snapshots;
+    expect(snapshots.length).toBe(2);
+    // Advanced analytically by omega * dt = 0.5 rad around z
+    expect(snapshots[1].balls[0].pos[0]).toBeCloseTo(Math.cos(0.5), 6);
+    expect(snapshots[1].balls[0].pos[1]).toBeCloseTo(Math.sin(0.5), 6);
+    expect(ws.sent.length).toBe(0);
+
+    // seq 7 was lost
+    ws.emitMessage(
+      JSON.stringify({
+        type: "space_state",
+        seq: 8,
+        keyframe: false,
+        serverTime: 1.7,
+        balls: [],
+        unchanged: [1],
+      }),
+    );
+    expect(snapshots.length).toBe(2);
+    expect(ws.sent.map((m) => JSON.parse(m))).toEqual([
+      { type: "request_keyframe" },
+    ]);
+  });
 });
@@ -10,7 +10,7 @@ use crate::constants::{
 use crate::coord::{bevy_vel_to_wire, px_to_world, world_to_px, PxPos};
 use crate::shared::connection::NetTransport;
 
-use super::hud::HitCounter;
+use super::hud::{HitCounter, HudUiState};
 use super::network::NetworkState;
 use super::pins::{Bumper, PinHitTimer};
 use super::walls::{Drain, EscapeSlot};
@@ -26,6 +26,7 @@ struct CollisionQueries<'w, 's> {
     bumpers: Query<'w, 's, (), With<Bumper>>,
     pin_timers: Query<'w, 's, &'static mut PinHitTimer>,
     ball_shapes: Query<'w, 's, &'static Shape, With<Ball>>,
+    ball_states: Query<'w, 's, &'static BallState, With<Ball>>,
 }
 
 const LAUNCHER_SNAP_Y_TOLERANCE: f32 = 30.0;
@@ -41,6 +42,8 @@ pub(crate) struct SpawnBallMessage {
     pub(crate) in_launcher: bool,
     pub(crate) self_owned: bool,
     pub(crate) color: u32,
+    /// Deep-space id from `transfer_in`; None for balls launched here
+    pub(crate) ball_id: Option<u32>,
 }
 
 #[derive(Resource)]
@@ -63,6 +66,9 @@ pub(crate) struct Ball;
 pub(crate) struct BallState {
     pub(crate) in_launcher: bool,
     pub(crate) self_owned: bool,
+    /// Echoed in `ball_escaped` so the server keeps the ball's origin and
+    /// hop count
+    pub(crate) ball_id: Option<u32>,
 }
 
 impl Plugin for BallPlugin {
@@ -95,6 +101,7 @@ fn spawn_initial_ball(mut commands: Commands, net: Option<Res<NetworkState>>) {
             in_launcher: true,
             self_owned: true,
             color,
+            ball_id: None,
         },
     );
 }
@@ -144,6 +151,7 @@ fn do_spawn_ball(commands: &mut Commands, msg: SpawnBallMessage) {
         BallState {
             in_launcher: msg.in_launcher,
             self_owned: msg.self_owned,
+            ball_id: msg.ball_id,
         },
     ));
 }
@@ -184,6 +192,7 @@ fn collision_system(
     mut collision_queries: CollisionQueries,
     mut respawn: ResMut<RespawnState>,
     mut hits: Option<ResMut<HitCounter>>,
+    mut hud_ui: Option<ResMut<HudUiState>>,
     transport: Res<NetTransport>,
 ) {
     for event in collision_events.read() {
@@ -211,7 +220,14 @@ fn collision_system(
                     // Upward in Bevy (Y+) means escaping through the top slot.
                     if vel.linvel.y > 0.0 {
                         let wire = bevy_vel_to_wire(vel.linvel);
-                        transport.send_ball_escaped(wire.vx, wire.vy);
+                        // A gift only applies to the next ball
+                        let target = hud_ui.as_mut().and_then(|ui| ui.gift_target.take());
+                        let ball_id = collision_queries
+                            .ball_states
+                            .get(ball_entity)
+                            .ok()
+                            .and_then(|state| state.ball_id);
+                        transport.send_ball_escaped(wire.vx, wire.vy, target, ball_id);
                         commands.entity(ball_entity).despawn();
                         respawn.seconds_left = RESPAWN_DELAY;
                         continue;
@@ -263,6 +279,7 @@ fn respawn_system(
                 in_launcher: true,
                 self_owned: true,
                 color: net.self_color,
+                ball_id: None,
             });
             respawn.seconds_left = 0.0;
         }
@@ -290,6 +307,7 @@ mod tests {
                         in_launcher: true,
                         self_owned: true,
                         color: crate::constants::Colors::BALL,
+                        ball_id: None,
                     },
                 );
             })
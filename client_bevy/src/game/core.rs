@@ -14,7 +14,7 @@ use crate::shared::net_state::NetState;
 use super::ball::{RespawnState, SpawnBallMessage};
 use super::input::InputState;
 use super::launcher::LauncherRuntime;
-use super::network::NetworkState;
+use super::network::{EmoteMessage, NetworkState};
 
 #[derive(SystemSet, Debug, Hash, Eq, PartialEq, Clone)]
 pub(crate) enum UpdateSet {
@@ -31,6 +31,8 @@ pub(crate) enum FixedSet {
 
 pub struct CorePlugin {
     pub ws_url: String,
+    /// Display name to ask the server for once connected
+    pub player_name: Option<String>,
 }
 
 #[derive(Component)]
@@ -41,10 +43,14 @@ impl Plugin for CorePlugin {
         app.insert_resource(NetTransport::new(self.ws_url.clone()))
             .init_resource::<NetState>()
             .init_resource::<InputState>()
-            .init_resource::<NetworkState>()
+            .insert_resource(NetworkState {
+                player_name: self.player_name.clone(),
+                ..default()
+            })
             .init_resource::<LauncherRuntime>()
             .init_resource::<RespawnState>()
             .add_message::<SpawnBallMessage>()
+            .add_message::<EmoteMessage>()
             .insert_resource(ClearColor(color_from_hex(Colors::DEEP_SPACE_BG)))
             .insert_resource(Time::<Fixed>::from_seconds(PHYSICS_DT as f64))
             .insert_resource(TimestepMode::Fixed {
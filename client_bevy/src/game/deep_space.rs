@@ -8,7 +8,9 @@ use crate::board::geometry::playfield_center_x;
 use crate::constants::{color_from_hex, Colors, CANVAS_HEIGHT, CANVAS_WIDTH};
 use crate::coord::{px_to_world, PxPos};
 use crate::shared::net_state::NetState;
+use crate::shared::protocol::EmoteKind;
 
+use super::network::EmoteMessage;
 use super::UpdateSet;
 
 pub struct DeepSpacePlugin;
@@ -16,6 +18,19 @@ pub struct DeepSpacePlugin;
 const STAR_COUNT: usize = 150;
 const MAX_PORTAL_DOTS: usize = 60;
 const MAX_BALL_DOTS: usize = 60;
+/// Names next to portal dots are cut to this many characters
+const PORTAL_LABEL_MAX_CHARS: usize = 10;
+/// Label offset above its portal dot (px)
+const PORTAL_LABEL_OFFSET_Y: f32 = 12.0;
+/// Balls that have passed through at least this many boards get a hop label
+const HOP_LABEL_MIN_HOPS: u32 = 3;
+/// Hop label offset below its ball dot (px)
+const HOP_LABEL_OFFSET_Y: f32 = 14.0;
+/// Emotes animating at once; older ones are dropped first
+const MAX_EMOTES: usize = 8;
+const EMOTE_DURATION: f64 = 2.0;
+/// How far an emote floats up from its portal over its lifetime (px)
+const EMOTE_RISE_PX: f32 = 30.0;
 const THETA_MAX: f64 = 0.8;
 const PIXELS_PER_RADIAN: f32 = 400.0;
 const STAR_MIN_RADIUS: f32 = 0.5;
@@ -47,11 +62,33 @@ struct DeepSpacePortalDot {
     index: usize,
 }
 
+#[derive(Component)]
+struct DeepSpacePortalLabel {
+    index: usize,
+}
+
+#[derive(Component)]
+struct DeepSpaceEmoteLabel {
+    index: usize,
+}
+
+/// An emote currently floating up from a portal.
+struct ActiveEmote {
+    player_id: u32,
+    kind: EmoteKind,
+    started_at: f64,
+}
+
 #[derive(Component)]
 struct DeepSpaceBallDot {
     index: usize,
 }
 
+#[derive(Component)]
+struct DeepSpaceBallHopLabel {
+    index: usize,
+}
+
 #[derive(Component)]
 struct DeepSpaceBallTailDot {
     ball_index: usize,
@@ -72,7 +109,10 @@ impl Plugin for DeepSpacePlugin {
                 regenerate_stars_on_resize,
                 animate_stars,
                 update_portal_dots,
+                update_portal_labels,
+                update_emotes,
                 update_ball_dots,
+                update_ball_hop_labels,
                 update_ball_trails,
                 update_self_marker,
             )
@@ -204,6 +244,30 @@ fn spawn_deep_space(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
         ));
     }
 
+    // Portal name labels (pre-allocated, hidden)
+    for i in 0..MAX_PORTAL_DOTS {
+        commands.spawn((
+            Text2d::new(""),
+            TextFont::from_font_size(10.0),
+            TextColor(Color::srgba(1.0, 1.0, 1.0, 0.6)),
+            Transform::from_xyz(center_world.x, center_world.y, 1.6),
+            Visibility::Hidden,
+            DeepSpacePortalLabel { index: i },
+        ));
+    }
+
+    // Emote labels (pre-allocated, hidden)
+    for i in 0..MAX_EMOTES {
+        commands.spawn((
+            Text2d::new(""),
+            TextFont::from_font_size(14.0),
+            TextColor(Color::WHITE),
+            Transform::from_xyz(center_world.x, center_world.y, 1.9),
+            Visibility::Hidden,
+            DeepSpaceEmoteLabel { index: i },
+        ));
+    }
+
     // Ball dots (pre-allocated, hidden)
     for i in 0..MAX_BALL_DOTS {
         commands.spawn((
@@ -219,6 +283,18 @@ fn spawn_deep_space(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
         ));
     }
 
+    // Hop count labels under well-travelled balls (pre-allocated, hidden)
+    for i in 0..MAX_BALL_DOTS {
+        commands.spawn((
+            Text2d::new(""),
+            TextFont::from_font_size(9.0),
+            TextColor(Color::srgba(1.0, 1.0, 1.0, 0.5)),
+            Transform::from_xyz(center_world.x, center_world.y, 1.85),
+            Visibility::Hidden,
+            DeepSpaceBallHopLabel { index: i },
+        ));
+    }
+
     // Ball tail dots (pre-allocated, hidden)
     for ball_index in 0..MAX_BALL_DOTS {
         for segment in 1..=TAIL_SEGMENTS {
@@ -373,6 +449,147 @@ fn update_portal_dots(
     }
 }
 
+/// Display names above portal dots; unnamed players get no label.
+fn update_portal_labels(
+    conn: Res<NetState>,
+    deep: Res<DeepSpaceState>,
+    mut q_labels: Query<(
+        &DeepSpacePortalLabel,
+        &mut Text2d,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+) {
+    let self_pos = conn
+        .players
+        .iter()
+        .find(|p| p.id == conn.self_id)
+        .map(|p| p.portal_pos)
+        .unwrap_or(crate::shared::vec3::Vec3::new(1.0, 0.0, 0.0));
+
+    let (e1, e2) = crate::shared::vec3::build_tangent_basis(self_pos);
+    let cos_theta_max = THETA_MAX.cos();
+
+    for (label, mut text, mut tf, mut vis) in &mut q_labels {
+        let projected = conn.players.get(label.index).and_then(|p| {
+            let name = p.short_name(PORTAL_LABEL_MAX_CHARS)?;
+            let pos = project(
+                self_pos,
+                p.portal_pos,
+                e1,
+                e2,
+                deep.center_px,
+                cos_theta_max,
+            )?;
+            Some((name, pos))
+        });
+
+        let Some((name, (sx, sy))) = projected else {
+            if *vis != Visibility::Hidden {
+                *vis = Visibility::Hidden;
+            }
+            continue;
+        };
+
+        let world = px_to_world(PxPos::new(sx, sy - PORTAL_LABEL_OFFSET_Y), 0.0);
+        tf.translation.x = world.x;
+        tf.translation.y = world.y;
+        if text.0 != name {
+            text.0 = name;
+        }
+        if *vis != Visibility::Visible {
+            *vis = Visibility::Visible;
+        }
+    }
+}
+
+fn emote_text(kind: EmoteKind) -> &'static str {
+    match kind {
+        EmoteKind::Wave => "hi!",
+        EmoteKind::Cheer => "woo!",
+        EmoteKind::Thanks => "thanks!",
+    }
+}
+
+/// Float incoming emotes up from the sender's portal dot, fading out.
+fn update_emotes(
+    time: Res<Time>,
+    conn: Res<NetState>,
+    deep: Res<DeepSpaceState>,
+    mut emotes: MessageReader<EmoteMessage>,
+    mut active: Local<Vec<ActiveEmote>>,
+    mut q_labels: Query<(
+        &DeepSpaceEmoteLabel,
+        &mut Text2d,
+        &mut TextColor,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+) {
+    let now = time.elapsed_secs_f64();
+    for emote in emotes.read() {
+        if active.len() == MAX_EMOTES {
+            active.remove(0);
+        }
+        active.push(ActiveEmote {
+            player_id: emote.player_id,
+            kind: emote.kind,
+            started_at: now,
+        });
+    }
+    active.retain(|e| now - e.started_at < EMOTE_DURATION);
+
+    let self_pos = conn
+        .players
+        .iter()
+        .find(|p| p.id == conn.self_id)
+        .map(|p| p.portal_pos)
+        .unwrap_or(crate::shared::vec3::Vec3::new(1.0, 0.0, 0.0));
+
+    let (e1, e2) = crate::shared::vec3::build_tangent_basis(self_pos);
+    let cos_theta_max = THETA_MAX.cos();
+
+    for (label, mut text, mut color, mut tf, mut vis) in &mut q_labels {
+        let shown = active.get(label.index).and_then(|e| {
+            let sender = conn.players.iter().find(|p| p.id == e.player_id)?;
+            let pos = project(
+                self_pos,
+                sender.portal_pos,
+                e1,
+                e2,
+                deep.center_px,
+                cos_theta_max,
+            )?;
+            Some((e, sender.color, pos))
+        });
+
+        let Some((emote, sender_color, (sx, sy))) = shown else {
+            if *vis != Visibility::Hidden {
+                *vis = Visibility::Hidden;
+            }
+            continue;
+        };
+
+        let t = ((now - emote.started_at) / EMOTE_DURATION) as f32;
+        let world = px_to_world(
+            PxPos::new(sx, sy - PORTAL_LABEL_OFFSET_Y - t * EMOTE_RISE_PX),
+            0.0,
+        );
+        tf.translation.x = world.x;
+        tf.translation.y = world.y;
+        // Pop in over the first 10%, then hold
+        tf.scale = Vec3::splat((t * 10.0).min(1.0) * 0.5 + 0.5);
+        let label_text = emote_text(emote.kind);
+        if text.0 != label_text {
+            text.0 = label_text.to_string();
+        }
+        color.0 = color_from_hex(sender_color).with_alpha(1.0 - t * t);
+        if *vis != Visibility::Visible {
+            *vis = Visibility::Visible;
+        }
+    }
+}
+
 fn player_color_signature(players: &[crate::shared::types::Player]) -> u64 {
     let mut sig: u64 = 0;
     for p in players {
@@ -444,6 +661,57 @@ fn update_ball_dots(
     }
 }
 
+fn hop_label_text(hops: u32) -> Option<String> {
+    (hops >= HOP_LABEL_MIN_HOPS).then(|| format!("{hops} hops"))
+}
+
+/// "12 hops" under balls that have passed through several boards.
+fn update_ball_hop_labels(
+    conn: Res<NetState>,
+    deep: Res<DeepSpaceState>,
+    mut q_labels: Query<(
+        &DeepSpaceBallHopLabel,
+        &mut Text2d,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+) {
+    let self_pos = conn
+        .players
+        .iter()
+        .find(|p| p.id == conn.self_id)
+        .map(|p| p.portal_pos)
+        .unwrap_or(crate::shared::vec3::Vec3::new(1.0, 0.0, 0.0));
+
+    let (e1, e2) = crate::shared::vec3::build_tangent_basis(self_pos);
+    let cos_theta_max = THETA_MAX.cos();
+
+    for (label, mut text, mut tf, mut vis) in &mut q_labels {
+        let projected = conn.interpolated_balls.get(label.index).and_then(|b| {
+            let hops = hop_label_text(b.hops)?;
+            let pos = project(self_pos, b.pos, e1, e2, deep.center_px, cos_theta_max)?;
+            Some((hops, pos))
+        });
+
+        let Some((hops, (sx, sy))) = projected else {
+            if *vis != Visibility::Hidden {
+                *vis = Visibility::Hidden;
+            }
+            continue;
+        };
+
+        let world = px_to_world(PxPos::new(sx, sy + HOP_LABEL_OFFSET_Y), 0.0);
+        tf.translation.x = world.x;
+        tf.translation.y = world.y;
+        if text.0 != hops {
+            text.0 = hops;
+        }
+        if *vis != Visibility::Visible {
+            *vis = Visibility::Visible;
+        }
+    }
+}
+
 fn update_ball_trails(
     conn: Res<NetState>,
     deep: Res<DeepSpaceState>,
@@ -598,6 +866,7 @@ mod tests {
             paused: false,
             balls_produced: 0,
             balls_in_flight: 1,
+            name: String::new(),
         }];
         let ball = SpaceBall3D {
             id: 7,
@@ -605,6 +874,7 @@ mod tests {
             pos: Vec3::new(1.0, 0.0, 0.0),
             axis: Vec3::new(0.0, 0.0, 1.0),
             omega: 0.5,
+            hops: 0,
         };
         s.interpolated_balls = vec![ball];
         s
@@ -642,4 +912,148 @@ mod tests {
         let visibility = app.world().get::<Visibility>(dot_entity).unwrap();
         assert_eq!(*visibility, Visibility::Visible);
     }
+
+    #[test]
+    fn portal_label_shows_name_only_when_set() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins);
+        app.insert_resource(test_net_state_for_visible_ball());
+
+        let ring = app.world_mut().spawn_empty().id();
+        let core = app.world_mut().spawn_empty().id();
+        app.insert_resource(DeepSpaceState {
+            center_px: Vec2::new(playfield_center_x(), CANVAS_HEIGHT * 0.5),
+            self_marker_ring: ring,
+            self_marker_core: core,
+            last_window_size: Vec2::ZERO,
+            dot_image: Handle::default(),
+        });
+
+        let label = app
+            .world_mut()
+            .spawn((
+                Text2d::new(""),
+                Transform::default(),
+                Visibility::Hidden,
+                DeepSpacePortalLabel { index: 0 },
+            ))
+            .id();
+
+        app.add_systems(Update, update_portal_labels);
+        app.update();
+        assert_eq!(
+            *app.world().get::<Visibility>(label).unwrap(),
+            Visibility::Hidden
+        );
+
+        app.world_mut().resource_mut::<NetState>().players[0].name = "Ada".to_string();
+        app.update();
+        assert_eq!(
+            *app.world().get::<Visibility>(label).unwrap(),
+            Visibility::Visible
+        );
+        assert_eq!(app.world().get::<Text2d>(label).unwrap().0, "Ada");
+    }
+
+    #[test]
+    fn hop_label_shows_only_for_well_travelled_balls() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins);
+        app.insert_resource(test_net_state_for_visible_ball());
+
+        let ring = app.world_mut().spawn_empty().id();
+        let core = app.world_mut().spawn_empty().id();
+        app.insert_resource(DeepSpaceState {
+            center_px: Vec2::new(playfield_center_x(), CANVAS_HEIGHT * 0.5),
+            self_marker_ring: ring,
+            self_marker_core: core,
+            last_window_size: Vec2::ZERO,
+            dot_image: Handle::default(),
+        });
+
+        let label = app
+            .world_mut()
+            .spawn((
+                Text2d::new(""),
+                Transform::default(),
+                Visibility::Hidden,
+                DeepSpaceBallHopLabel { index: 0 },
+            ))
+            .id();
+
+        app.add_systems(Update, update_ball_hop_labels);
+        app.world_mut()
+            .resource_mut::<NetState>()
+            .interpolated_balls[0]
+            .hops = 2;
+        app.update();
+        assert_eq!(
+            *app.world().get::<Visibility>(label).unwrap(),
+            Visibility::Hidden
+        );
+
+        app.world_mut()
+            .resource_mut::<NetState>()
+            .interpolated_balls[0]
+            .hops = 12;
+        app.update();
+        assert_eq!(
+            *app.world().get::<Visibility>(label).unwrap(),
+            Visibility::Visible
+        );
+        assert_eq!(app.world().get::<Text2d>(label).unwrap().0, "12 hops");
+    }
+
+    #[test]
+    fn emote_shows_at_sender_portal_then_expires() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins);
+        app.insert_resource(test_net_state_for_visible_ball());
+        app.add_message::<EmoteMessage>();
+
+        let ring = app.world_mut().spawn_empty().id();
+        let core = app.world_mut().spawn_empty().id();
+        app.insert_resource(DeepSpaceState {
+            center_px: Vec2::new(playfield_center_x(), CANVAS_HEIGHT * 0.5),
+            self_marker_ring: ring,
+            self_marker_core: core,
+            last_window_size: Vec2::ZERO,
+            dot_image: Handle::default(),
+        });
+
+        let label = app
+            .world_mut()
+            .spawn((
+                Text2d::new(""),
+                TextColor(Color::NONE),
+                Transform::default(),
+                Visibility::Hidden,
+                DeepSpaceEmoteLabel { index: 0 },
+            ))
+            .id();
+
+        app.add_systems(Update, update_emotes);
+        app.world_mut().write_message(EmoteMessage {
+            player_id: 1,
+            kind: EmoteKind::Thanks,
+        });
+        app.update();
+        assert_eq!(
+            *app.world().get::<Visibility>(label).unwrap(),
+            Visibility::Visible
+        );
+        assert_eq!(app.world().get::<Text2d>(label).unwrap().0, "thanks!");
+
+        // Virtual time clamps each step to 250ms, so walk past the lifetime
+        app.insert_resource(bevy::time::TimeUpdateStrategy::ManualDuration(
+            std::time::Duration::from_millis(200),
+        ));
+        for _ in 0..12 {
+            app.update();
+        }
+        assert_eq!(
+            *app.world().get::<Visibility>(label).unwrap(),
+            Visibility::Hidden
+        );
+    }
 }
@@ -21,8 +21,10 @@ impl Plugin for HudPlugin {
                     systems::update_connection_ui,
                     systems::update_hit_ui,
                     systems::update_players_ui,
+                    systems::update_leaderboard_ui,
                     systems::update_info_panel_ui,
                     systems::update_bot_button_ui,
+                    systems::update_toast_ui,
                 )
                     .chain(),
             );
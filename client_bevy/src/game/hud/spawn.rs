@@ -5,10 +5,12 @@ use crate::constants::{color_from_hex, Colors};
 use super::types::{
     panel_bg, panel_border, HudBotButton, HudBotButtonText, HudConnectionDot, HudConnectionGlow,
     HudHitCountText, HudInfoButton, HudInfoPanel, HudInfoPanelBotText, HudInfoPanelClientText,
-    HudInfoPanelServerText, HudMoreCountText, HudPlayerEntryDot, HudPlayerEntryText,
-    HudPlayersSummaryText, BOT_BUTTON_LEFT, BUTTON_BOTTOM, BUTTON_SIZE, HIT_TOP, INFO_BUTTON_LEFT,
+    HudInfoPanelRttText, HudInfoPanelServerText, HudLeaderText, HudMoreCountText,
+    HudPlayerEntryDot, HudPlayerEntryText, HudPlayersSummaryText, HudRankText, HudToast,
+    HudToastText, BOT_BUTTON_LEFT, BUTTON_BOTTOM, BUTTON_SIZE, HIT_TOP, INFO_BUTTON_LEFT,
+    LEADERBOARD_LEFT, LEADERBOARD_ROWS, LEADERBOARD_ROW_SPACING, LEADERBOARD_TOP,
     MAX_VISIBLE_PLAYERS, PANEL_BOTTOM, PANEL_LEFT, PANEL_WIDTH, PLAYERS_SUMMARY_TOP,
-    PLAYER_LIST_TOP, PLAYER_ROW_SPACING, STATUS_CONNECTING, UI_DIM,
+    PLAYER_LIST_TOP, PLAYER_ROW_SPACING, STATUS_CONNECTING, TOAST_TOP, UI_DIM,
 };
 
 pub(super) fn spawn_hud(mut commands: Commands) {
@@ -120,11 +122,15 @@ pub(super) fn spawn_hud(mut commands: Commands) {
                 top: Val::Px(PLAYER_LIST_TOP + index as f32 * PLAYER_ROW_SPACING),
                 ..default()
             },
+            Button,
             Text::new(""),
             small.clone(),
             TextColor(color_from_hex(UI_DIM)),
             Visibility::Hidden,
-            HudPlayerEntryText { index },
+            HudPlayerEntryText {
+                index,
+                player_id: None,
+            },
         ));
     }
 
@@ -142,6 +148,56 @@ pub(super) fn spawn_hud(mut commands: Commands) {
         HudMoreCountText,
     ));
 
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(LEADERBOARD_LEFT),
+            top: Val::Px(LEADERBOARD_TOP),
+            ..default()
+        },
+        Text::new(""),
+        small.clone(),
+        TextColor(color_from_hex(Colors::WALL).with_alpha(0.95)),
+        HudRankText,
+    ));
+
+    for index in 0..LEADERBOARD_ROWS {
+        commands.spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(LEADERBOARD_LEFT),
+                top: Val::Px(LEADERBOARD_TOP + (index + 1) as f32 * LEADERBOARD_ROW_SPACING),
+                ..default()
+            },
+            Text::new(""),
+            small.clone(),
+            TextColor(color_from_hex(UI_DIM).with_alpha(0.9)),
+            HudLeaderText { index },
+        ));
+    }
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(0.0),
+                right: Val::Px(0.0),
+                top: Val::Px(TOAST_TOP),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            Visibility::Hidden,
+            HudToast,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                medium.clone(),
+                TextColor(Color::WHITE),
+                HudToastText,
+            ));
+        });
+
     commands
         .spawn((
             Button,
@@ -234,6 +290,12 @@ pub(super) fn spawn_hud(mut commands: Commands) {
                 TextColor(Color::srgb(0.55, 0.8, 0.8)),
                 HudInfoPanelServerText,
             ));
+            parent.spawn((
+                Text::new(""),
+                small.clone(),
+                TextColor(Color::srgb(0.55, 0.8, 0.8)),
+                HudInfoPanelRttText,
+            ));
             parent.spawn((
                 Text::new(""),
                 small.clone(),
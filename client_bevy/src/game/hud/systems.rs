@@ -2,15 +2,19 @@ use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
 use crate::constants::color_from_hex;
-use crate::game::network::NetworkState;
+use crate::game::network::{EmoteMessage, NetworkState};
+use crate::shared::connection::now_mono_secs;
 use crate::shared::net_state::NetState;
+use crate::shared::protocol::{EmoteKind, LeaderboardMsg};
 use crate::shared::types::Player;
 
 use super::types::{
     connection_color, panel_border, HitCounter, HudBotButton, HudBotButtonText, HudConnectionDot,
     HudConnectionGlow, HudHitCountText, HudInfoButton, HudInfoPanel, HudInfoPanelBotText,
-    HudInfoPanelClientText, HudInfoPanelServerText, HudMoreCountText, HudPlayerEntryDot,
-    HudPlayerEntryText, HudPlayersSummaryText, HudUiState, MAX_VISIBLE_PLAYERS, UI_DIM,
+    HudInfoPanelClientText, HudInfoPanelRttText, HudInfoPanelServerText, HudLeaderText,
+    HudMoreCountText, HudPlayerEntryDot, HudPlayerEntryText, HudPlayersSummaryText, HudRankText,
+    HudToast, HudToastText, HudUiState, MAX_VISIBLE_PLAYERS, PLAYER_NAME_MAX_CHARS, TOAST_SECS,
+    UI_DIM,
 };
 
 type ButtonInteractionQuery<'w, 's> = Query<
@@ -20,6 +24,7 @@ type ButtonInteractionQuery<'w, 's> = Query<
         &'static Interaction,
         Option<&'static HudInfoButton>,
         Option<&'static HudBotButton>,
+        Option<&'static HudPlayerEntryText>,
     ),
     (Changed<Interaction>, With<Button>),
 >;
@@ -39,7 +44,7 @@ type PlayerRowsQuery<'w, 's> = Query<
     'w,
     's,
     (
-        &'static HudPlayerEntryText,
+        &'static mut HudPlayerEntryText,
         &'static mut Text,
         &'static mut TextColor,
         &'static mut Visibility,
@@ -70,6 +75,7 @@ type PlayersTextSet<'w, 's> = ParamSet<
 
 type InfoClientTextQuery<'w, 's> = Query<'w, 's, &'static mut Text, With<HudInfoPanelClientText>>;
 type InfoServerTextQuery<'w, 's> = Query<'w, 's, &'static mut Text, With<HudInfoPanelServerText>>;
+type InfoRttTextQuery<'w, 's> = Query<'w, 's, &'static mut Text, With<HudInfoPanelRttText>>;
 type InfoBotTextQuery<'w, 's> = Query<'w, 's, &'static mut Text, With<HudInfoPanelBotText>>;
 type InfoTextSet<'w, 's> = ParamSet<
     'w,
@@ -77,10 +83,20 @@ type InfoTextSet<'w, 's> = ParamSet<
     (
         InfoClientTextQuery<'w, 's>,
         InfoServerTextQuery<'w, 's>,
+        InfoRttTextQuery<'w, 's>,
         InfoBotTextQuery<'w, 's>,
     ),
 >;
 
+type LeaderboardTextSet<'w, 's> = ParamSet<
+    'w,
+    's,
+    (
+        Query<'w, 's, &'static mut Text, With<HudRankText>>,
+        Query<'w, 's, (&'static HudLeaderText, &'static mut Text)>,
+    ),
+>;
+
 #[derive(SystemParam)]
 pub(super) struct ButtonQueries<'w, 's> {
     query: ButtonInteractionQuery<'w, 's>,
@@ -97,6 +113,11 @@ pub(super) struct PlayerUiQueries<'w, 's> {
     text_sets: PlayersTextSet<'w, 's>,
 }
 
+#[derive(SystemParam)]
+pub(super) struct LeaderboardQueries<'w, 's> {
+    texts: LeaderboardTextSet<'w, 's>,
+}
+
 #[derive(SystemParam)]
 pub(super) struct InfoPanelTextQueries<'w, 's> {
     texts: InfoTextSet<'w, 's>,
@@ -105,8 +126,9 @@ pub(super) struct InfoPanelTextQueries<'w, 's> {
 pub(super) fn handle_button_interactions(
     mut buttons: ButtonQueries,
     mut hud_ui: ResMut<HudUiState>,
+    state: Res<NetState>,
 ) {
-    for (interaction, info_button, bot_button) in &mut buttons.query {
+    for (interaction, info_button, bot_button, player_row) in &mut buttons.query {
         if *interaction != Interaction::Pressed {
             continue;
         }
@@ -117,6 +139,15 @@ pub(super) fn handle_button_interactions(
         if bot_button.is_some() {
             hud_ui.bot_enabled = !hud_ui.bot_enabled;
         }
+        if let Some(player_id) = player_row.and_then(|row| row.player_id) {
+            if player_id != state.self_id {
+                hud_ui.gift_target = if hud_ui.gift_target == Some(player_id) {
+                    None
+                } else {
+                    Some(player_id)
+                };
+            }
+        }
     }
 }
 
@@ -148,10 +179,20 @@ pub(super) fn update_hit_ui(
 
 pub(super) fn update_players_ui(
     state: Res<NetState>,
+    mut hud_ui: ResMut<HudUiState>,
     mut queries: PlayerUiQueries,
     mut last_signature: Local<Option<u64>>,
 ) {
+    // Forget the gift target once they leave
+    if let Some(target) = hud_ui.gift_target {
+        if !state.players.iter().any(|p| p.id == target) {
+            hud_ui.gift_target = None;
+        }
+    }
+    let gift_target = hud_ui.gift_target;
+
     let mut signature = (state.self_id as u64).wrapping_mul(0x9e3779b185ebca87);
+    signature ^= gift_target.map_or(0, |id| id as u64 + 1) << 48;
     for player in &state.players {
         signature = signature
             .wrapping_mul(0x9e3779b185ebca87)
@@ -160,6 +201,9 @@ pub(super) fn update_players_ui(
             .wrapping_add((player.paused as u64) << 40)
             .wrapping_add((player.balls_in_flight as u64) << 20)
             .wrapping_add((player.balls_produced as u64) << 28);
+        for b in player.name.bytes() {
+            signature = signature.wrapping_mul(31).wrapping_add(b as u64);
+        }
     }
 
     if *last_signature == Some(signature) {
@@ -194,18 +238,29 @@ pub(super) fn update_players_ui(
         }
     }
 
-    for (entry, mut text, mut text_color, mut visibility) in &mut queries.text_sets.p1() {
+    for (mut entry, mut text, mut text_color, mut visibility) in &mut queries.text_sets.p1() {
         if entry.index < visible_count {
             let player = sorted_players[entry.index];
-            let self_mark = if player.id == state.self_id { "*" } else { " " };
+            entry.player_id = Some(player.id);
+            let self_mark = if player.id == state.self_id {
+                "*"
+            } else if gift_target == Some(player.id) {
+                ">"
+            } else {
+                " "
+            };
+            let label = player
+                .short_name(PLAYER_NAME_MAX_CHARS)
+                .unwrap_or_else(|| format!("{:02}", player.id));
             text.0 = format!(
-                "{self_mark}{:02} {}/{}",
-                player.id, player.balls_in_flight, player.balls_produced
+                "{self_mark}{label} {}/{}",
+                player.balls_in_flight, player.balls_produced
             );
             text_color.0 =
                 color_from_hex(UI_DIM).with_alpha(if player.paused { 0.45 } else { 0.95 });
             *visibility = Visibility::Visible;
         } else {
+            entry.player_id = None;
             *visibility = Visibility::Hidden;
         }
     }
@@ -220,6 +275,52 @@ pub(super) fn update_players_ui(
     }
 }
 
+pub(super) fn update_leaderboard_ui(
+    state: Res<NetState>,
+    net: Res<NetworkState>,
+    mut queries: LeaderboardQueries,
+) {
+    let lb = net.leaderboard.as_ref();
+    if let Ok(mut text) = queries.texts.p0().single_mut() {
+        let rank = lb
+            .map(|lb| rank_text(lb, state.self_id))
+            .unwrap_or_default();
+        if text.0 != rank {
+            text.0 = rank;
+        }
+    }
+
+    for (row, mut text) in &mut queries.texts.p1() {
+        let line = lb
+            .and_then(|lb| lb.entries.get(row.index))
+            .map(|entry| {
+                let label = state
+                    .players
+                    .iter()
+                    .find(|p| p.id == entry.player_id)
+                    .and_then(|p| p.short_name(PLAYER_NAME_MAX_CHARS))
+                    .unwrap_or_else(|| format!("{:02}", entry.player_id));
+                format!("{}. {label} {}", row.index + 1, entry.score)
+            })
+            .unwrap_or_default();
+        if text.0 != line {
+            text.0 = line;
+        }
+    }
+}
+
+/// `#rank/total score` for us; `-` as rank past the listed entries.
+/// Empty for spectators.
+fn rank_text(lb: &LeaderboardMsg, self_id: u32) -> String {
+    if self_id == 0 {
+        return String::new();
+    }
+    match lb.entries.iter().position(|e| e.player_id == self_id) {
+        Some(i) => format!("#{}/{} {}", i + 1, lb.total, lb.entries[i].score),
+        None => format!("#-/{}", lb.total),
+    }
+}
+
 pub(super) fn update_info_panel_ui(
     state: Res<NetState>,
     hud_ui: Res<HudUiState>,
@@ -250,10 +351,68 @@ pub(super) fn update_info_panel_ui(
     }
 
     if let Ok(mut text) = info_texts.texts.p2().single_mut() {
+        text.0 = match state.rtt() {
+            Some(rtt) => format!("RTT: {:.0} ms", rtt * 1000.0),
+            None => "RTT: -".to_string(),
+        };
+    }
+
+    if let Ok(mut text) = info_texts.texts.p3().single_mut() {
         text.0 = format!("Bot: {}", if hud_ui.bot_enabled { "ON" } else { "OFF" });
     }
 }
 
+/// Toast for emotes from players whose ball we just received, so a
+/// "thanks" reads as a reply.
+pub(super) fn update_toast_ui(
+    time: Res<Time>,
+    state: Res<NetState>,
+    net: Res<NetworkState>,
+    mut emotes: MessageReader<EmoteMessage>,
+    mut shown_until: Local<f64>,
+    mut q_toast: Query<&mut Visibility, With<HudToast>>,
+    mut q_text: Query<&mut Text, With<HudToastText>>,
+) {
+    let now = time.elapsed_secs_f64();
+    let mono_now = now_mono_secs();
+    for emote in emotes.read() {
+        if emote.player_id == state.self_id
+            || !net.got_ball_recently_from(emote.player_id, mono_now)
+        {
+            continue;
+        }
+        let name = state
+            .players
+            .iter()
+            .find(|p| p.id == emote.player_id)
+            .and_then(|p| p.short_name(PLAYER_NAME_MAX_CHARS))
+            .unwrap_or_else(|| format!("Player {:02}", emote.player_id));
+        if let Ok(mut text) = q_text.single_mut() {
+            text.0 = toast_text(&name, emote.kind);
+        }
+        *shown_until = now + TOAST_SECS;
+    }
+
+    if let Ok(mut visibility) = q_toast.single_mut() {
+        let target = if now < *shown_until {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+}
+
+fn toast_text(name: &str, kind: EmoteKind) -> String {
+    match kind {
+        EmoteKind::Wave => format!("{name} waves!"),
+        EmoteKind::Cheer => format!("{name} cheers!"),
+        EmoteKind::Thanks => format!("{name} says thanks!"),
+    }
+}
+
 pub(super) fn update_bot_button_ui(
     hud_ui: Res<HudUiState>,
     mut q_bot_button: Query<&mut BorderColor, With<HudBotButton>>,
@@ -316,6 +475,7 @@ mod tests {
             paused,
             balls_produced: produced,
             balls_in_flight: in_flight,
+            name: String::new(),
         }
     }
 
@@ -326,9 +486,55 @@ mod tests {
         app.insert_resource(NetworkState::default());
         app.init_resource::<HitCounter>();
         app.init_resource::<HudUiState>();
+        app.add_message::<EmoteMessage>();
         app
     }
 
+    #[test]
+    fn toast_only_for_emotes_from_recent_ball_senders() {
+        let mut app = make_test_app();
+        app.add_systems(Update, update_toast_ui);
+
+        let toast = app.world_mut().spawn((HudToast, Visibility::Hidden)).id();
+        let text = app.world_mut().spawn((HudToastText, Text::new(""))).id();
+        {
+            let mut conn = app.world_mut().resource_mut::<NetState>();
+            conn.self_id = 1;
+            let mut ada = make_player(7, false, 0, 0, 0xffffff);
+            ada.name = "Ada".to_string();
+            conn.players = vec![make_player(1, false, 0, 0, 0), ada];
+        }
+
+        // No ball from player 7 yet
+        app.world_mut().write_message(EmoteMessage {
+            player_id: 7,
+            kind: EmoteKind::Thanks,
+        });
+        app.update();
+        assert_eq!(
+            *app.world().get::<Visibility>(toast).unwrap(),
+            Visibility::Hidden
+        );
+
+        app.world_mut()
+            .resource_mut::<NetworkState>()
+            .last_ball_from
+            .insert(7, now_mono_secs());
+        app.world_mut().write_message(EmoteMessage {
+            player_id: 7,
+            kind: EmoteKind::Thanks,
+        });
+        app.update();
+        assert_eq!(
+            *app.world().get::<Visibility>(toast).unwrap(),
+            Visibility::Visible
+        );
+        assert_eq!(
+            &app.world().get::<Text>(text).unwrap().0,
+            "Ada says thanks!"
+        );
+    }
+
     #[test]
     fn connection_ui_uses_state_color() {
         let mut app = make_test_app();
@@ -424,7 +630,10 @@ mod tests {
         let row0 = app
             .world_mut()
             .spawn((
-                HudPlayerEntryText { index: 0 },
+                HudPlayerEntryText {
+                    index: 0,
+                    player_id: None,
+                },
                 Text::new(""),
                 TextColor(Color::NONE),
                 Visibility::Hidden,
@@ -433,7 +642,10 @@ mod tests {
         let row1 = app
             .world_mut()
             .spawn((
-                HudPlayerEntryText { index: 1 },
+                HudPlayerEntryText {
+                    index: 1,
+                    player_id: None,
+                },
                 Text::new(""),
                 TextColor(Color::NONE),
                 Visibility::Hidden,
@@ -442,7 +654,10 @@ mod tests {
         let row2 = app
             .world_mut()
             .spawn((
-                HudPlayerEntryText { index: 2 },
+                HudPlayerEntryText {
+                    index: 2,
+                    player_id: None,
+                },
                 Text::new(""),
                 TextColor(Color::NONE),
                 Visibility::Hidden,
@@ -452,8 +667,10 @@ mod tests {
         {
             let mut conn = app.world_mut().resource_mut::<NetState>();
             conn.self_id = 2;
+            let mut named = make_player(5, false, 1, 8, 0x33ccaa);
+            named.name = "Ada".to_string();
             conn.players = vec![
-                make_player(5, false, 1, 8, 0x33ccaa),
+                named,
                 make_player(2, false, 3, 9, 0xe5f26d),
                 make_player(1, true, 0, 4, 0xaa66ff),
             ];
@@ -464,7 +681,12 @@ mod tests {
         assert_eq!(&app.world().get::<Text>(summary).unwrap().0, "2/3");
         assert_eq!(&app.world().get::<Text>(row0).unwrap().0, "*02 3/9");
         assert_eq!(&app.world().get::<Text>(row1).unwrap().0, " 01 0/4");
-        assert_eq!(&app.world().get::<Text>(row2).unwrap().0, " 05 1/8");
+        assert_eq!(&app.world().get::<Text>(row2).unwrap().0, " Ada 1/8");
+
+        // A rename alone must refresh the rows
+        app.world_mut().resource_mut::<NetState>().players[0].name = "Grace".to_string();
+        app.update();
+        assert_eq!(&app.world().get::<Text>(row2).unwrap().0, " Grace 1/8");
         assert_eq!(
             *app.world().get::<Visibility>(row0).unwrap(),
             Visibility::Visible
@@ -500,7 +722,10 @@ mod tests {
             Visibility::Hidden,
         ));
         app.world_mut().spawn((
-            HudPlayerEntryText { index: 0 },
+            HudPlayerEntryText {
+                index: 0,
+                player_id: None,
+            },
             Text::new(""),
             TextColor(Color::NONE),
             Visibility::Hidden,
@@ -543,6 +768,130 @@ mod tests {
         assert!(ui.bot_enabled);
     }
 
+    #[test]
+    fn clicking_a_player_row_picks_and_clears_the_gift_target() {
+        let mut app = make_test_app();
+        app.add_systems(
+            Update,
+            (handle_button_interactions, update_players_ui).chain(),
+        );
+        {
+            let mut conn = app.world_mut().resource_mut::<NetState>();
+            conn.self_id = 1;
+            conn.players = vec![
+                make_player(1, false, 0, 0, 0x44ff44),
+                make_player(2, false, 0, 0, 0x44ff44),
+            ];
+        }
+        let self_row = app
+            .world_mut()
+            .spawn((
+                Button,
+                Interaction::None,
+                HudPlayerEntryText {
+                    index: 0,
+                    player_id: None,
+                },
+                Text::new(""),
+                TextColor(Color::NONE),
+                Visibility::Hidden,
+            ))
+            .id();
+        let other_row = app
+            .world_mut()
+            .spawn((
+                Button,
+                Interaction::None,
+                HudPlayerEntryText {
+                    index: 1,
+                    player_id: None,
+                },
+                Text::new(""),
+                TextColor(Color::NONE),
+                Visibility::Hidden,
+            ))
+            .id();
+        app.update();
+
+        let press = |app: &mut App, row: Entity| {
+            *app.world_mut().get_mut::<Interaction>(row).unwrap() = Interaction::Pressed;
+            app.update();
+            *app.world_mut().get_mut::<Interaction>(row).unwrap() = Interaction::None;
+            app.update();
+        };
+        let gift_target = |app: &App| app.world().resource::<HudUiState>().gift_target;
+
+        press(&mut app, self_row);
+        assert_eq!(gift_target(&app), None, "can't gift to yourself");
+
+        press(&mut app, other_row);
+        assert_eq!(gift_target(&app), Some(2));
+        assert_eq!(&app.world().get::<Text>(other_row).unwrap().0, ">02 0/0");
+
+        press(&mut app, other_row);
+        assert_eq!(gift_target(&app), None, "second click clears it");
+        assert_eq!(&app.world().get::<Text>(other_row).unwrap().0, " 02 0/0");
+
+        press(&mut app, other_row);
+        app.world_mut().resource_mut::<NetState>().players.pop();
+        app.update();
+        assert_eq!(gift_target(&app), None, "target left");
+    }
+
+    #[test]
+    fn leaderboard_ui_shows_rank_and_top_players() {
+        use crate::shared::protocol::{LeaderboardEntry, LeaderboardMsg};
+
+        let mut app = make_test_app();
+        app.add_systems(Update, update_leaderboard_ui);
+        let rank = app.world_mut().spawn((HudRankText, Text::new(""))).id();
+        let rows: Vec<Entity> = (0..2)
+            .map(|index| {
+                app.world_mut()
+                    .spawn((HudLeaderText { index }, Text::new("")))
+                    .id()
+            })
+            .collect();
+        {
+            let mut conn = app.world_mut().resource_mut::<NetState>();
+            conn.self_id = 2;
+            let mut named = make_player(5, false, 0, 0, 0x33ccaa);
+            named.name = "Ada".to_string();
+            conn.players = vec![named, make_player(2, false, 0, 0, 0xe5f26d)];
+        }
+        app.update();
+        assert_eq!(&app.world().get::<Text>(rank).unwrap().0, "");
+
+        let entry = |player_id, score| LeaderboardEntry { player_id, score };
+        app.world_mut().resource_mut::<NetworkState>().leaderboard = Some(LeaderboardMsg {
+            entries: vec![entry(5, 120), entry(2, 35)],
+            total: 4,
+            resets_in: None,
+        });
+        app.update();
+
+        assert_eq!(&app.world().get::<Text>(rank).unwrap().0, "#2/4 35");
+        assert_eq!(&app.world().get::<Text>(rows[0]).unwrap().0, "1. Ada 120");
+        assert_eq!(&app.world().get::<Text>(rows[1]).unwrap().0, "2. 02 35");
+    }
+
+    #[test]
+    fn rank_text_outside_listed_entries_and_for_spectators() {
+        use crate::shared::protocol::{LeaderboardEntry, LeaderboardMsg};
+
+        let lb = LeaderboardMsg {
+            entries: vec![LeaderboardEntry {
+                player_id: 1,
+                score: 10,
+            }],
+            total: 150,
+            resets_in: None,
+        };
+        assert_eq!(rank_text(&lb, 1), "#1/150 10");
+        assert_eq!(rank_text(&lb, 9), "#-/150");
+        assert_eq!(rank_text(&lb, 0), "");
+    }
+
     #[test]
     fn info_panel_ui_shows_versions_and_bot_state() {
         let mut app = make_test_app();
@@ -560,6 +909,10 @@ mod tests {
             .world_mut()
             .spawn((HudInfoPanelServerText, Text::new("")))
             .id();
+        let rtt = app
+            .world_mut()
+            .spawn((HudInfoPanelRttText, Text::new("")))
+            .id();
         let bot = app
             .world_mut()
             .spawn((HudInfoPanelBotText, Text::new("")))
@@ -568,6 +921,7 @@ mod tests {
         {
             let mut conn = app.world_mut().resource_mut::<NetState>();
             conn.server_version = "1.2.3".to_string();
+            conn.record_pong(10.0, 3.0, 10.042);
         }
         {
             let mut ui = app.world_mut().resource_mut::<HudUiState>();
@@ -589,6 +943,7 @@ mod tests {
             &app.world().get::<Text>(server).unwrap().0,
             "Server: v1.2.3"
         );
+        assert_eq!(&app.world().get::<Text>(rtt).unwrap().0, "RTT: 42 ms");
         assert_eq!(&app.world().get::<Text>(bot).unwrap().0, "Bot: ON");
     }
 
@@ -4,12 +4,24 @@ use crate::constants::color_from_hex;
 use crate::shared::types::ConnectionState;
 
 pub(super) const MAX_VISIBLE_PLAYERS: usize = 20;
+/// Longer display names are cut to fit the player list
+pub(super) const PLAYER_NAME_MAX_CHARS: usize = 12;
 
 pub(super) const HIT_TOP: f32 = 10.0;
 pub(super) const PLAYERS_SUMMARY_TOP: f32 = 36.0;
 pub(super) const PLAYER_LIST_TOP: f32 = 60.0;
 pub(super) const PLAYER_ROW_SPACING: f32 = 16.0;
 
+pub(super) const LEADERBOARD_LEFT: f32 = 12.0;
+pub(super) const LEADERBOARD_TOP: f32 = 54.0;
+pub(super) const LEADERBOARD_ROW_SPACING: f32 = 14.0;
+/// Top players listed under our own rank
+pub(super) const LEADERBOARD_ROWS: usize = 3;
+
+pub(super) const TOAST_TOP: f32 = 12.0;
+/// How long an emote toast stays up (seconds)
+pub(super) const TOAST_SECS: f64 = 3.0;
+
 pub(super) const INFO_BUTTON_LEFT: f32 = 12.0;
 pub(super) const BOT_BUTTON_LEFT: f32 = 48.0;
 pub(super) const BUTTON_BOTTOM: f32 = 12.0;
@@ -33,6 +45,8 @@ pub(crate) struct HitCounter {
 pub(crate) struct HudUiState {
     pub(crate) info_visible: bool,
     pub(crate) bot_enabled: bool,
+    /// Player picked in the list to receive our next escaped ball
+    pub(crate) gift_target: Option<u32>,
 }
 
 #[derive(Component)]
@@ -47,9 +61,12 @@ pub(super) struct HudHitCountText;
 #[derive(Component)]
 pub(super) struct HudPlayersSummaryText;
 
+/// Player list row; clicking it picks that player as the gift target
 #[derive(Component)]
 pub(super) struct HudPlayerEntryText {
     pub(super) index: usize,
+    /// Player currently shown in this row
+    pub(super) player_id: Option<u32>,
 }
 
 #[derive(Component)]
@@ -60,6 +77,22 @@ pub(super) struct HudPlayerEntryDot {
 #[derive(Component)]
 pub(super) struct HudMoreCountText;
 
+/// Our rank, player count and score
+#[derive(Component)]
+pub(super) struct HudRankText;
+
+#[derive(Component)]
+pub(super) struct HudLeaderText {
+    pub(super) index: usize,
+}
+
+/// Container of the emote toast; carries its visibility
+#[derive(Component)]
+pub(super) struct HudToast;
+
+#[derive(Component)]
+pub(super) struct HudToastText;
+
 #[derive(Component)]
 pub(super) struct HudInfoButton;
 
@@ -78,6 +111,9 @@ pub(super) struct HudInfoPanelClientText;
 #[derive(Component)]
 pub(super) struct HudInfoPanelServerText;
 
+#[derive(Component)]
+pub(super) struct HudInfoPanelRttText;
+
 #[derive(Component)]
 pub(super) struct HudInfoPanelBotText;
 
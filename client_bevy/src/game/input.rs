@@ -6,6 +6,7 @@ use crate::board::geometry::{flippers, launcher_wall};
 use crate::constants::{BOARD_CENTER_X, BOARD_HALF_WIDTH, CANVAS_HEIGHT, CANVAS_WIDTH, PPM};
 use crate::coord::{bevy_vel_to_wire, world_to_px};
 use crate::shared::connection::NetTransport;
+use crate::shared::protocol::EmoteKind;
 
 use super::ball::{Ball, BallState};
 use super::client_bot::{BotBallInfo, ClientBot};
@@ -13,6 +14,13 @@ use super::hud::HudUiState;
 
 pub struct InputPlugin;
 
+/// Number keys that send an emote
+const EMOTE_KEYS: [(KeyCode, EmoteKind); 3] = [
+    (KeyCode::Digit1, EmoteKind::Wave),
+    (KeyCode::Digit2, EmoteKind::Cheer),
+    (KeyCode::Digit3, EmoteKind::Thanks),
+];
+
 #[derive(Resource, Default)]
 pub(crate) struct InputState {
     pub(crate) left: bool,
@@ -24,10 +32,11 @@ pub(crate) struct InputState {
 
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(PreUpdate, input_system);
+        app.add_systems(PreUpdate, (input_system, emote_input_system));
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn input_system(
     mut input: ResMut<InputState>,
     keys: Res<ButtonInput<KeyCode>>,
@@ -109,6 +118,14 @@ fn input_system(
     }
 }
 
+fn emote_input_system(keys: Res<ButtonInput<KeyCode>>, transport: Res<NetTransport>) {
+    for (key, kind) in EMOTE_KEYS {
+        if keys.just_pressed(key) {
+            transport.send_emote(kind);
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 enum Zone {
     Left,
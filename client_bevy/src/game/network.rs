@@ -1,15 +1,16 @@
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::Shape;
+use std::collections::HashMap;
 
 use crate::constants::{color_from_hex, Colors, BALL_FILL_ALPHA};
 use crate::coord::{wire_vel_to_bevy, WireVel};
 use crate::shared::connection::{now_mono_secs, NetEvent, NetTransport};
 use crate::shared::net_state::NetState;
-use crate::shared::protocol::ServerMsg;
+use crate::shared::protocol::{EmoteKind, LeaderboardMsg, ServerMsg};
 use crate::shared::types::{wire_to_player, SpaceBall3D};
 
 use super::ball::{Ball, BallState, SpawnBallMessage};
-use super::hud::HudUiState;
+use super::hud::{HitCounter, HudUiState};
 use super::input::InputState;
 use super::{FixedSet, UpdateSet};
 
@@ -17,15 +18,59 @@ const CAPTURE_SPAWN_X: f32 = 200.0;
 const CAPTURE_SPAWN_Y: f32 = 80.0;
 const ACTIVITY_SEND_INTERVAL: f64 = 5.0;
 const ACTIVITY_TIMEOUT: f64 = 30.0;
+/// Pings sent in quick succession after connecting, to seed the clock estimate
+const PING_BURST_COUNT: u32 = 4;
+const PING_BURST_INTERVAL: f64 = 0.25;
+const PING_INTERVAL: f64 = 2.0;
+/// An emote counts as a reply to a ball received this recently (seconds)
+const RECENT_BALL_SECS: f64 = 10.0;
+/// Bumper hits are reported in batches this often (seconds)
+const HITS_REPORT_INTERVAL: f64 = 1.0;
 
 pub struct NetworkPlugin;
 
+/// An emote broadcast by `player_id`, for the deep-space view and HUD toast.
+#[derive(Message, Clone, Copy)]
+pub(crate) struct EmoteMessage {
+    pub(crate) player_id: u32,
+    pub(crate) kind: EmoteKind,
+}
+
 #[derive(Resource)]
 pub(crate) struct NetworkState {
     pub(crate) self_color: u32,
     pub(crate) protocol_mismatch: bool,
     pub(crate) connection_label: String,
     pub(crate) last_activity_sent_time: f64,
+    /// Pings sent on the current connection, and when the last one went out
+    /// (`now_mono_secs`)
+    pub(crate) pings_sent: u32,
+    pub(crate) last_ping_sent_time: f64,
+    /// Display name sent with `set_name` after each welcome
+    pub(crate) player_name: Option<String>,
+    /// When (`now_mono_secs`) a ball owned by each player last reached our board
+    pub(crate) last_ball_from: HashMap<u32, f64>,
+    /// Latest `leaderboard`; None until one arrives on this connection
+    pub(crate) leaderboard: Option<LeaderboardMsg>,
+    /// `HitCounter` value already reported with `bumper_hits`
+    pub(crate) hits_reported: u32,
+    pub(crate) last_hits_report_time: f64,
+}
+
+impl NetworkState {
+    /// Whether a ball from `player_id` reached our board in the last
+    /// `RECENT_BALL_SECS`.
+    pub(crate) fn got_ball_recently_from(&self, player_id: u32, now: f64) -> bool {
+        self.last_ball_from
+            .get(&player_id)
+            .is_some_and(|t| now - t < RECENT_BALL_SECS)
+    }
+
+    fn record_ball_from(&mut self, player_id: u32, now: f64) {
+        self.last_ball_from
+            .retain(|_, t| now - *t < RECENT_BALL_SECS);
+        self.last_ball_from.insert(player_id, now);
+    }
 }
 
 impl Default for NetworkState {
@@ -35,17 +80,29 @@ impl Default for NetworkState {
             protocol_mismatch: false,
             connection_label: "connecting".to_string(),
             last_activity_sent_time: 0.0,
+            pings_sent: 0,
+            last_ping_sent_time: 0.0,
+            player_name: None,
+            last_ball_from: HashMap::new(),
+            leaderboard: None,
+            hits_reported: 0,
+            last_hits_report_time: 0.0,
         }
     }
 }
 
 impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, network_event_system.in_set(UpdateSet::Network))
-            .add_systems(
-                FixedUpdate,
-                activity_heartbeat_system.in_set(FixedSet::Simulate),
-            );
+        app.add_systems(
+            Update,
+            (network_event_system, ping_system)
+                .chain()
+                .in_set(UpdateSet::Network),
+        )
+        .add_systems(
+            FixedUpdate,
+            (activity_heartbeat_system, bumper_hits_report_system).in_set(FixedSet::Simulate),
+        );
     }
 }
 
@@ -54,6 +111,7 @@ fn network_event_system(
     mut state: ResMut<NetState>,
     mut net: ResMut<NetworkState>,
     mut ball_writer: MessageWriter<SpawnBallMessage>,
+    mut emote_writer: MessageWriter<EmoteMessage>,
     mut q_balls: Query<(&BallState, &mut Shape), With<Ball>>,
 ) {
     let events = transport.poll_events();
@@ -67,9 +125,11 @@ fn network_event_system(
                 net.protocol_mismatch = false;
                 state.protocol_mismatch = false;
                 state.reset_interpolation();
+                net.pings_sent = 0;
             }
             NetEvent::Disconnected => {
                 net.connection_label = "disconnected".to_string();
+                net.leaderboard = None;
                 state.state = crate::shared::types::ConnectionState::Disconnected;
                 state.reset_interpolation();
             }
@@ -93,24 +153,37 @@ fn network_event_system(
                     );
                     state.self_id = w.self_id;
                     state.server_version = w.server_version.clone();
-                    state.players = w.players.iter().map(|p| wire_to_player(p)).collect();
+                    state.players = w.players.iter().map(wire_to_player).collect();
                     if let Some(me) = state.players.iter().find(|p| p.id == state.self_id) {
                         update_self_color(me.color, &mut net, &mut q_balls);
+                        // A resumed identity may already carry the name
+                        if let Some(name) = net.player_name.as_ref().filter(|n| **n != me.name) {
+                            transport.send_set_name(name);
+                        }
                     }
                 }
                 ServerMsg::PlayersState(ps) => {
-                    state.players = ps.players.iter().map(|p| wire_to_player(p)).collect();
+                    state.players = ps.players.iter().map(wire_to_player).collect();
                     if let Some(me) = state.players.iter().find(|p| p.id == state.self_id) {
                         update_self_color(me.color, &mut net, &mut q_balls);
                     }
                 }
-                ServerMsg::SpaceState(ss) => {
-                    state.push_snapshot(
-                        ss.server_time,
-                        *recv_time_secs,
-                        decode_space_balls(&ss.balls),
-                    );
-                }
+                ServerMsg::SpaceState(ss) => match state.apply_space_state(ss) {
+                    Ok(balls) => {
+                        state.push_snapshot(
+                            ss.server_time,
+                            *recv_time_secs,
+                            decode_space_balls(&balls),
+                        );
+                    }
+                    Err(err) => {
+                        debug!(
+                            "space_state seq {} not applied ({:?}), requesting keyframe",
+                            ss.seq, err
+                        );
+                        transport.send_request_keyframe();
+                    }
+                },
                 ServerMsg::TransferIn(t) => {
                     let bevy_vel = wire_vel_to_bevy(WireVel::new(t.vx as f32, t.vy as f32));
                     ball_writer.write(SpawnBallMessage {
@@ -121,8 +194,31 @@ fn network_event_system(
                         in_launcher: false,
                         self_owned: false,
                         color: t.color,
+                        ball_id: Some(t.ball_id).filter(|&id| id != 0),
+                    });
+                    // The ball is ours now; otherwise the server re-injects it
+                    transport.send_transfer_ack(t.seq);
+                    if t.owner_id != state.self_id {
+                        net.record_ball_from(t.owner_id, *recv_time_secs);
+                    }
+                }
+                ServerMsg::ServerGoingAway(g) => {
+                    // The close follows; the usual reconnect loop takes over
+                    info!("Server going away: {}", g.reason);
+                    net.connection_label = "server restarting".to_string();
+                }
+                ServerMsg::Pong(p) => {
+                    state.record_pong(p.client_time, p.server_time, *recv_time_secs);
+                }
+                ServerMsg::Emote(e) => {
+                    emote_writer.write(EmoteMessage {
+                        player_id: e.player_id,
+                        kind: e.kind,
                     });
                 }
+                ServerMsg::Leaderboard(lb) => {
+                    net.leaderboard = Some(lb.clone());
+                }
             },
         }
     }
@@ -141,6 +237,7 @@ fn decode_space_balls(wire_balls: &[pinball_shared::protocol::BallWire]) -> Vec<
             pos: crate::shared::vec3::Vec3::new(wire.pos[0], wire.pos[1], wire.pos[2]),
             axis: crate::shared::vec3::Vec3::new(wire.axis[0], wire.axis[1], wire.axis[2]),
             omega: wire.omega,
+            hops: wire.hops,
         });
     }
     balls
@@ -167,6 +264,32 @@ fn update_self_color(
     }
 }
 
+/// Ping the server for clock sync: a short burst after connecting, then
+/// every `PING_INTERVAL`. Timestamps use `now_mono_secs` so they share a
+/// clock with the receive times stamped by the network thread.
+fn ping_system(mut net: ResMut<NetworkState>, state: Res<NetState>, transport: Res<NetTransport>) {
+    if !matches!(
+        state.state,
+        crate::shared::types::ConnectionState::Connected
+    ) {
+        return;
+    }
+
+    let now = now_mono_secs();
+    let interval = if net.pings_sent < PING_BURST_COUNT {
+        PING_BURST_INTERVAL
+    } else {
+        PING_INTERVAL
+    };
+    if net.pings_sent > 0 && now - net.last_ping_sent_time < interval {
+        return;
+    }
+
+    transport.send_ping(now);
+    net.pings_sent += 1;
+    net.last_ping_sent_time = now;
+}
+
 fn activity_heartbeat_system(
     input: Res<InputState>,
     mut net: ResMut<NetworkState>,
@@ -196,6 +319,35 @@ fn activity_heartbeat_system(
     }
 }
 
+/// Report new bumper hits to the server for scoring, batched every
+/// `HITS_REPORT_INTERVAL`. Hits made while disconnected go out with the
+/// next report.
+fn bumper_hits_report_system(
+    hits: Option<Res<HitCounter>>,
+    mut net: ResMut<NetworkState>,
+    state: Res<NetState>,
+    transport: Res<NetTransport>,
+    time: Res<Time>,
+) {
+    let Some(hits) = hits else { return };
+    let now = time.elapsed_secs_f64();
+    if now - net.last_hits_report_time < HITS_REPORT_INTERVAL
+        || !matches!(
+            state.state,
+            crate::shared::types::ConnectionState::Connected
+        )
+    {
+        return;
+    }
+
+    let new_hits = hits.count.saturating_sub(net.hits_reported);
+    if new_hits > 0 {
+        transport.send_bumper_hits(new_hits);
+        net.hits_reported = hits.count;
+    }
+    net.last_hits_report_time = now;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -206,7 +358,8 @@ mod tests {
     use crate::shared::net_state::NetState;
     use pinball_shared::config::DeepSpaceConfig;
     use pinball_shared::protocol::{
-        BallWire, PlayerWire, ServerMsg, SpaceStateMsg, WelcomeMsg, PROTOCOL_VERSION,
+        BallWire, EmoteMsg, LeaderboardEntry, LeaderboardMsg, PlayerWire, PongMsg, ServerMsg,
+        SpaceStateMsg, TransferInMsg, WelcomeMsg, PROTOCOL_VERSION,
     };
 
     fn assert_color_close(a: Color, e: Color) {
@@ -237,6 +390,7 @@ mod tests {
             paused: false,
             balls_produced: 0,
             balls_in_flight: 0,
+            name: String::new(),
         }
     }
 
@@ -248,6 +402,7 @@ mod tests {
                 BallState {
                     in_launcher: true,
                     self_owned,
+                    ball_id: None,
                 },
                 ShapeBuilder::with(&shapes::Circle {
                     radius: 10.0,
@@ -272,6 +427,7 @@ mod tests {
 
         app.add_systems(Update, network_event_system);
         app.add_message::<SpawnBallMessage>();
+        app.add_message::<EmoteMessage>();
 
         (app, event_tx)
     }
@@ -291,6 +447,8 @@ mod tests {
                     self_id: 42,
                     players: vec![make_player_wire(42, real_color)],
                     config: DeepSpaceConfig::default(),
+                    resume_token: String::new(),
+                    spectator: false,
                 }),
                 recv_time_secs: 0.0,
             })
@@ -322,6 +480,8 @@ mod tests {
                     self_id: 42,
                     players: vec![make_player_wire(42, 0xFF8800)],
                     config: DeepSpaceConfig::default(),
+                    resume_token: String::new(),
+                    spectator: false,
                 }),
                 recv_time_secs: 0.0,
             })
@@ -344,13 +504,18 @@ mod tests {
             .send(NetEvent::Message {
                 msg: ServerMsg::SpaceState(SpaceStateMsg {
                     server_time: recv_time_secs,
+                    seq: 1,
+                    keyframe: true,
                     balls: vec![BallWire {
                         id: 1,
                         owner_id: 99,
                         pos: [1.0, 0.0, 0.0],
                         axis: [0.0, 0.0, 1.0],
                         omega: 2.0,
+                        hops: 4,
                     }],
+                    unchanged: vec![],
+                    removed: vec![],
                 }),
                 recv_time_secs,
             })
@@ -364,5 +529,197 @@ mod tests {
         assert!(p.x.is_finite() && p.y.is_finite() && p.z.is_finite());
         // With ~100ms extrapolation and omega=2 rad/s, y should be clearly positive.
         assert!(p.y > 0.05, "expected extrapolated y > 0.05, got {}", p.y);
+        assert_eq!(state.interpolated_balls[0].hops, 4);
+    }
+
+    #[test]
+    fn space_state_delta_advances_unchanged_ball_from_keyframe() {
+        let (mut app, event_tx) = make_test_app_with_events();
+        let t0 = now_mono_secs();
+        let ball = BallWire {
+            id: 1,
+            owner_id: 99,
+            pos: [1.0, 0.0, 0.0],
+            axis: [0.0, 0.0, 1.0],
+            omega: 1.0,
+            hops: 0,
+        };
+
+        // A delta before any keyframe is dropped
+        let delta = |seq: u32, server_time: f64| SpaceStateMsg {
+            server_time,
+            seq,
+            keyframe: false,
+            balls: vec![],
+            unchanged: vec![1],
+            removed: vec![],
+        };
+        event_tx
+            .send(NetEvent::Message {
+                msg: ServerMsg::SpaceState(delta(4, t0)),
+                recv_time_secs: t0,
+            })
+            .unwrap();
+        app.update();
+        assert!(app
+            .world()
+            .resource::<NetState>()
+            .interpolated_balls
+            .is_empty());
+
+        for (msg, at) in [
+            (
+                SpaceStateMsg {
+                    server_time: t0,
+                    seq: 5,
+                    keyframe: true,
+                    balls: vec![ball],
+                    unchanged: vec![],
+                    removed: vec![],
+                },
+                t0,
+            ),
+            (delta(6, t0 + 0.1), t0 + 0.1),
+        ] {
+            event_tx
+                .send(NetEvent::Message {
+                    msg: ServerMsg::SpaceState(msg),
+                    recv_time_secs: at,
+                })
+                .unwrap();
+        }
+        app.update();
+
+        let state = app.world().resource::<NetState>();
+        assert_eq!(state.interpolated_balls.len(), 1);
+        assert_eq!(state.interpolated_balls[0].id, 1);
+    }
+
+    #[test]
+    fn pings_start_on_connect_and_pongs_feed_rtt() {
+        let (mut app, event_tx) = make_test_app_with_events();
+        app.add_systems(Update, ping_system.after(network_event_system));
+
+        // Not connected yet: no pings
+        app.world_mut().resource_mut::<NetState>().state =
+            crate::shared::types::ConnectionState::Connecting;
+        app.update();
+        assert_eq!(app.world().resource::<NetworkState>().pings_sent, 0);
+
+        event_tx.send(NetEvent::Connected).unwrap();
+        app.update();
+        app.update();
+        // First ping goes out at once; the next waits for the burst interval
+        assert_eq!(app.world().resource::<NetworkState>().pings_sent, 1);
+
+        let t0 = now_mono_secs();
+        event_tx
+            .send(NetEvent::Message {
+                msg: ServerMsg::Pong(PongMsg {
+                    client_time: t0,
+                    server_time: 5.0,
+                }),
+                recv_time_secs: t0 + 0.05,
+            })
+            .unwrap();
+        app.update();
+
+        let rtt = app.world().resource::<NetState>().rtt().unwrap();
+        assert!((rtt - 0.05).abs() < 1e-9, "rtt {}", rtt);
+    }
+
+    #[test]
+    fn transfer_in_records_sender_and_emotes_are_forwarded() {
+        let (mut app, event_tx) = make_test_app_with_events();
+        app.world_mut().resource_mut::<NetState>().self_id = 1;
+
+        let now = now_mono_secs();
+        event_tx
+            .send(NetEvent::Message {
+                msg: ServerMsg::TransferIn(TransferInMsg {
+                    vx: 0.5,
+                    vy: 1.0,
+                    owner_id: 7,
+                    color: 0xffffff,
+                    seq: 1,
+                    ball_id: 40,
+                    origin_owner_id: 3,
+                    hops: 2,
+                }),
+                recv_time_secs: now,
+            })
+            .unwrap();
+        event_tx
+            .send(NetEvent::Message {
+                msg: ServerMsg::Emote(EmoteMsg {
+                    player_id: 7,
+                    kind: EmoteKind::Cheer,
+                }),
+                recv_time_secs: now,
+            })
+            .unwrap();
+        app.update();
+
+        let net = app.world().resource::<NetworkState>();
+        assert!(net.got_ball_recently_from(7, now + 1.0));
+        assert!(!net.got_ball_recently_from(7, now + RECENT_BALL_SECS + 1.0));
+        assert!(!net.got_ball_recently_from(8, now));
+
+        let emotes = app.world().resource::<Messages<EmoteMessage>>();
+        let mut cursor = emotes.get_cursor();
+        let received: Vec<_> = cursor.read(emotes).map(|e| e.player_id).collect();
+        assert_eq!(received, vec![7]);
+        let spawns = app.world().resource::<Messages<SpawnBallMessage>>();
+        let mut cursor = spawns.get_cursor();
+        let ids: Vec<_> = cursor.read(spawns).map(|m| m.ball_id).collect();
+        assert_eq!(ids, vec![Some(40)], "the ball keeps its deep-space id");
+    }
+
+    #[test]
+    fn leaderboard_is_kept_until_disconnect() {
+        let (mut app, event_tx) = make_test_app_with_events();
+        event_tx
+            .send(NetEvent::Message {
+                msg: ServerMsg::Leaderboard(LeaderboardMsg {
+                    entries: vec![LeaderboardEntry {
+                        player_id: 3,
+                        score: 40,
+                    }],
+                    total: 1,
+                    resets_in: Some(600.0),
+                }),
+                recv_time_secs: now_mono_secs(),
+            })
+            .unwrap();
+        app.update();
+        let net = app.world().resource::<NetworkState>();
+        assert_eq!(net.leaderboard.as_ref().unwrap().entries[0].score, 40);
+
+        event_tx.send(NetEvent::Disconnected).unwrap();
+        app.update();
+        assert!(app.world().resource::<NetworkState>().leaderboard.is_none());
+    }
+
+    #[test]
+    fn new_bumper_hits_are_reported_once() {
+        let (mut app, _event_tx) = make_test_app_with_events();
+        app.add_systems(Update, bumper_hits_report_system);
+        app.insert_resource(bevy::time::TimeUpdateStrategy::ManualDuration(
+            std::time::Duration::from_millis(200),
+        ));
+        app.insert_resource(HitCounter { count: 5 });
+        app.world_mut().resource_mut::<NetState>().state =
+            crate::shared::types::ConnectionState::Connected;
+
+        for _ in 0..8 {
+            app.update();
+        }
+        assert_eq!(app.world().resource::<NetworkState>().hits_reported, 5);
+
+        app.world_mut().resource_mut::<HitCounter>().count = 7;
+        for _ in 0..8 {
+            app.update();
+        }
+        assert_eq!(app.world().resource::<NetworkState>().hits_reported, 7);
     }
 }
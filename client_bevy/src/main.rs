@@ -19,6 +19,7 @@ use game::{
 
 fn main() {
     let ws_url = ws_url_from_env_or_location();
+    let player_name = player_name_from_env_or_location();
     let primary_window = default_window();
 
     let mut app = App::new();
@@ -37,7 +38,10 @@ fn main() {
     );
     app.add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(PPM).in_fixed_schedule());
     app.add_plugins(ShapePlugin)
-        .add_plugins(CorePlugin { ws_url })
+        .add_plugins(CorePlugin {
+            ws_url,
+            player_name,
+        })
         .add_plugins(WallsPlugin)
         .add_plugins(FlippersPlugin)
         .add_plugins(LauncherPlugin)
@@ -79,7 +83,15 @@ fn default_window() -> Window {
 
 #[cfg(not(target_arch = "wasm32"))]
 fn ws_url_from_env_or_location() -> String {
-    std::env::var("PINBALL_WS_URL").unwrap_or_else(|_| "ws://127.0.0.1:9001/ws".to_string())
+    let url =
+        std::env::var("PINBALL_WS_URL").unwrap_or_else(|_| "ws://127.0.0.1:9001/ws".to_string());
+    let spectate = std::env::var("PINBALL_SPECTATE").is_ok_and(|v| v == "1" || v == "true");
+    with_join_params(
+        &url,
+        std::env::var("PINBALL_ROOM").ok().as_deref(),
+        std::env::var("PINBALL_TOKEN").ok().as_deref(),
+        spectate,
+    )
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -100,7 +112,59 @@ fn ws_url_from_env_or_location() -> String {
         "ws"
     };
 
-    format!("{ws_scheme}://{}/ws", wasm_ws_host_override(&host))
+    let url = format!("{ws_scheme}://{}/ws", wasm_ws_host_override(&host));
+    // Forward `?room=<name>`, `?token=<join token>` and `?spectate=1` from
+    // the page URL
+    let spectate = matches!(page_query_param("spectate").as_deref(), Some("1" | "true"));
+    with_join_params(
+        &url,
+        page_query_param("room").as_deref(),
+        page_query_param("token").as_deref(),
+        spectate,
+    )
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn player_name_from_env_or_location() -> Option<String> {
+    std::env::var("PINBALL_NAME").ok().filter(|n| !n.is_empty())
+}
+
+/// Display name from `?name=<name>` on the page URL.
+#[cfg(target_arch = "wasm32")]
+fn player_name_from_env_or_location() -> Option<String> {
+    page_query_param("name").filter(|n| !n.is_empty())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn page_query_param(key: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    url::form_urlencoded::parse(search.trim_start_matches('?').as_bytes())
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.into_owned())
+}
+
+/// Join a named room (`?room=<name>`) instead of the public one, pass a
+/// join token (`?token=`) for servers that require one, and watch as a
+/// spectator (`?spectate=1`) instead of taking a portal.
+fn with_join_params(url: &str, room: Option<&str>, token: Option<&str>, spectate: bool) -> String {
+    let params: Vec<_> = [
+        ("room", room),
+        ("token", token),
+        ("spectate", spectate.then_some("1")),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key, value.filter(|v| !v.is_empty())?)))
+    .collect();
+    if params.is_empty() {
+        return url.to_string();
+    }
+    match url::Url::parse(url) {
+        Ok(mut parsed) => {
+            parsed.query_pairs_mut().extend_pairs(params);
+            parsed.into()
+        }
+        Err(_) => url.to_string(),
+    }
 }
 
 #[cfg(any(target_arch = "wasm32", test))]
@@ -114,7 +178,52 @@ fn wasm_ws_host_override(host: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::wasm_ws_host_override;
+    use super::{wasm_ws_host_override, with_join_params};
+
+    #[test]
+    fn room_is_appended_to_url() {
+        assert_eq!(
+            with_join_params("ws://127.0.0.1:9001/ws", Some("office"), None, false),
+            "ws://127.0.0.1:9001/ws?room=office"
+        );
+        assert_eq!(
+            with_join_params("ws://127.0.0.1:9001/ws", Some(""), None, false),
+            "ws://127.0.0.1:9001/ws"
+        );
+        assert_eq!(
+            with_join_params("ws://127.0.0.1:9001/ws", None, None, false),
+            "ws://127.0.0.1:9001/ws"
+        );
+    }
+
+    #[test]
+    fn spectate_is_appended_to_url() {
+        assert_eq!(
+            with_join_params("ws://127.0.0.1:9001/ws", None, None, true),
+            "ws://127.0.0.1:9001/ws?spectate=1"
+        );
+        assert_eq!(
+            with_join_params("ws://127.0.0.1:9001/ws", Some("lobby"), None, true),
+            "ws://127.0.0.1:9001/ws?room=lobby&spectate=1"
+        );
+    }
+
+    #[test]
+    fn join_token_is_appended_to_url() {
+        assert_eq!(
+            with_join_params(
+                "ws://127.0.0.1:9001/ws",
+                Some("office"),
+                Some("abc.def"),
+                false
+            ),
+            "ws://127.0.0.1:9001/ws?room=office&token=abc.def"
+        );
+        assert_eq!(
+            with_join_params("ws://127.0.0.1:9001/ws", None, Some("abc.def"), false),
+            "ws://127.0.0.1:9001/ws?token=abc.def"
+        );
+    }
 
     #[test]
     fn wasm_localhost_trunk_port_maps_to_server_port() {
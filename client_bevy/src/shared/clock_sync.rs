@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+
+/// Number of recent ping/pong samples the estimate is drawn from.
+const MAX_SAMPLES: usize = 8;
+/// Round trips longer than this are discarded (stale or wildly delayed pong).
+const MAX_RTT_SECS: f64 = 5.0;
+/// EWMA weight of a new RTT sample (same as TCP's SRTT).
+const RTT_SMOOTH_ALPHA: f64 = 0.125;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    rtt: f64,
+    offset: f64,
+}
+
+/// NTP-style clock estimator fed by `ping`/`pong` round trips.
+///
+/// Each sample gives `rtt = t3 - t0` and `offset = (t0 + t3) / 2 - server_time`
+/// (local clock minus server clock). Queueing delay only ever inflates a
+/// round trip, so the offset is taken from the lowest-RTT sample in the
+/// window, where the symmetric-delay assumption holds best.
+#[derive(Debug, Default)]
+pub struct ClockSync {
+    samples: VecDeque<Sample>,
+    smoothed_rtt: Option<f64>,
+}
+
+impl ClockSync {
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.smoothed_rtt = None;
+    }
+
+    /// Record a pong. `client_send` is the echoed `clientTime`, `client_recv`
+    /// the local time it arrived; both on the `now_mono_secs` clock.
+    pub fn add_sample(&mut self, client_send: f64, server_time: f64, client_recv: f64) {
+        let rtt = client_recv - client_send;
+        if !rtt.is_finite() || !server_time.is_finite() || !(0.0..=MAX_RTT_SECS).contains(&rtt) {
+            return;
+        }
+
+        let offset = (client_send + client_recv) * 0.5 - server_time;
+        self.samples.push_back(Sample { rtt, offset });
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        self.smoothed_rtt = Some(match self.smoothed_rtt {
+            Some(srtt) => srtt + (rtt - srtt) * RTT_SMOOTH_ALPHA,
+            None => rtt,
+        });
+    }
+
+    /// Smoothed round-trip time in seconds, for display.
+    pub fn rtt(&self) -> Option<f64> {
+        self.smoothed_rtt
+    }
+
+    /// Local arrival time minus server send time (`offset + rtt/2` of the
+    /// best sample): what `space_state` arrival times measure, minus their
+    /// jitter.
+    pub fn arrival_offset(&self) -> Option<f64> {
+        self.best().map(|s| s.offset + s.rtt * 0.5)
+    }
+
+    fn best(&self) -> Option<&Sample> {
+        self.samples.iter().min_by(|a, b| a.rtt.total_cmp(&b.rtt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetric_round_trip_recovers_offset() {
+        let mut clock = ClockSync::default();
+        // Local clock 100s ahead of server, 20ms each way
+        clock.add_sample(100.0, 0.02, 100.04);
+
+        assert!((clock.rtt().unwrap() - 0.04).abs() < 1e-9);
+        assert!((clock.arrival_offset().unwrap() - 100.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lowest_rtt_sample_wins_over_delayed_ones() {
+        let mut clock = ClockSync::default();
+        // Fast sample, then one whose reply was stuck in a queue for 300ms
+        clock.add_sample(100.0, 0.02, 100.04);
+        clock.add_sample(101.0, 1.02, 101.34);
+
+        assert!((clock.arrival_offset().unwrap() - 100.02).abs() < 1e-9);
+        let rtt = clock.rtt().unwrap();
+        assert!(rtt > 0.04 && rtt < 0.34, "smoothed rtt {}", rtt);
+    }
+
+    #[test]
+    fn old_samples_age_out_of_the_window() {
+        let mut clock = ClockSync::default();
+        clock.add_sample(0.0, 0.0, 0.01);
+        for i in 1..=MAX_SAMPLES {
+            let t = i as f64;
+            clock.add_sample(t, t - 50.0 + 0.05, t + 0.1);
+        }
+
+        assert!((clock.arrival_offset().unwrap() - 50.05).abs() < 1e-9);
+    }
+
+    #[test]
+    fn invalid_samples_are_ignored() {
+        let mut clock = ClockSync::default();
+        clock.add_sample(10.0, 1.0, 9.0);
+        clock.add_sample(10.0, 1.0, 10.0 + MAX_RTT_SECS + 1.0);
+        clock.add_sample(10.0, f64::NAN, 10.1);
+        assert!(clock.arrival_offset().is_none());
+        assert!(clock.rtt().is_none());
+
+        clock.add_sample(10.0, 1.0, 10.1);
+        clock.reset();
+        assert!(clock.arrival_offset().is_none());
+    }
+}
@@ -12,8 +12,9 @@ use std::time::Duration;
 use std::time::Instant;
 
 use bevy::prelude::Resource;
+use pinball_shared::wire;
 
-use super::protocol::{ClientMsg, ServerMsg};
+use super::protocol::{ClientMsg, EmoteKind, ServerMsg};
 use super::types::CLIENT_PROTOCOL_VERSION;
 
 #[derive(Debug, Clone)]
@@ -44,6 +45,7 @@ pub struct NetTransport {
 impl NetTransport {
     pub fn new(url: String) -> Self {
         let (event_tx, event_rx) = mpsc::channel::<NetEvent>();
+        let url = with_packed_wire(&url);
 
         #[cfg(not(target_arch = "wasm32"))]
         let cmd_tx = Some(spawn_native_network_thread(url.clone(), event_tx));
@@ -94,10 +96,18 @@ impl NetTransport {
         self.event_buf = buf;
     }
 
-    pub fn send_ball_escaped(&self, vx: f32, vy: f32) {
+    pub fn send_ball_escaped(
+        &self,
+        vx: f32,
+        vy: f32,
+        target_player_id: Option<u32>,
+        ball_id: Option<u32>,
+    ) {
         self.send(ClientMsg::BallEscaped {
             vx: vx as f64,
             vy: vy as f64,
+            target_player_id,
+            ball_id,
         });
     }
 
@@ -109,6 +119,32 @@ impl NetTransport {
         self.send(ClientMsg::Activity);
     }
 
+    pub fn send_request_keyframe(&self) {
+        self.send(ClientMsg::RequestKeyframe);
+    }
+
+    pub fn send_transfer_ack(&self, seq: u32) {
+        self.send(ClientMsg::TransferAck { seq });
+    }
+
+    pub fn send_set_name(&self, name: &str) {
+        self.send(ClientMsg::SetName {
+            name: name.to_string(),
+        });
+    }
+
+    pub fn send_emote(&self, kind: EmoteKind) {
+        self.send(ClientMsg::Emote { kind });
+    }
+
+    pub fn send_bumper_hits(&self, count: u32) {
+        self.send(ClientMsg::BumperHits { count });
+    }
+
+    pub fn send_ping(&self, client_time: f64) {
+        self.send(ClientMsg::Ping { client_time });
+    }
+
     fn send(&self, msg: ClientMsg) {
         #[cfg(not(target_arch = "wasm32"))]
         {
@@ -126,6 +162,25 @@ impl NetTransport {
     }
 }
 
+/// Opt into the packed binary space_state encoding (`?wire=packed`).
+/// Servers that don't know the parameter ignore it and keep sending JSON.
+fn with_packed_wire(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) => {
+            parsed
+                .query_pairs_mut()
+                .append_pair("wire", wire::WIRE_PACKED);
+            parsed.into()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Decode a binary frame from the server (packed space_state).
+fn decode_binary_msg(bytes: &[u8]) -> Option<ServerMsg> {
+    wire::decode_space_state(bytes).map(ServerMsg::SpaceState)
+}
+
 pub(crate) fn now_mono_secs() -> f64 {
     #[cfg(target_arch = "wasm32")]
     {
@@ -139,7 +194,7 @@ pub(crate) fn now_mono_secs() -> f64 {
     {
         use std::sync::OnceLock;
         static START: OnceLock<Instant> = OnceLock::new();
-        return START.get_or_init(Instant::now).elapsed().as_secs_f64();
+        START.get_or_init(Instant::now).elapsed().as_secs_f64()
     }
 }
 
@@ -157,8 +212,9 @@ fn next_reconnect_delay_ms(current_ms: u32) -> u32 {
 fn spawn_wasm_network_runtime(url: String, event_tx: Sender<NetEvent>) -> WasmCmdSender {
     let (cmd_tx, cmd_rx) = mpsc::channel::<ClientMsg>();
     let cmd_rx = Arc::new(Mutex::new(cmd_rx));
+    let resume_token = Rc::new(RefCell::new(None));
 
-    connect_wasm_socket(url, event_tx, cmd_rx, RECONNECT_MIN_DELAY_MS);
+    connect_wasm_socket(url, event_tx, cmd_rx, resume_token, RECONNECT_MIN_DELAY_MS);
     cmd_tx
 }
 
@@ -167,12 +223,13 @@ fn connect_wasm_socket(
     url: String,
     event_tx: Sender<NetEvent>,
     cmd_rx: Arc<Mutex<Receiver<ClientMsg>>>,
+    resume_token: Rc<RefCell<Option<String>>>,
     reconnect_delay_ms: u32,
 ) {
     use gloo_timers::callback::{Interval, Timeout};
     use wasm_bindgen::closure::Closure;
     use wasm_bindgen::JsCast;
-    use web_sys::{Event, MessageEvent, WebSocket};
+    use web_sys::{BinaryType, Event, MessageEvent, WebSocket};
 
     let _ = event_tx.send(NetEvent::Disconnected);
     let send_pump: Rc<RefCell<Option<Interval>>> = Rc::new(RefCell::new(None));
@@ -184,20 +241,38 @@ fn connect_wasm_socket(
             let url_retry = url;
             let event_tx_retry = event_tx;
             let cmd_rx_retry = cmd_rx;
+            let resume_token_retry = resume_token;
             Timeout::new(reconnect_delay_ms, move || {
-                connect_wasm_socket(url_retry, event_tx_retry, cmd_rx_retry, next_delay_ms);
+                connect_wasm_socket(
+                    url_retry,
+                    event_tx_retry,
+                    cmd_rx_retry,
+                    resume_token_retry,
+                    next_delay_ms,
+                );
             })
             .forget();
             return;
         }
     };
 
+    ws.set_binary_type(BinaryType::Arraybuffer);
+
     let ws_on_open = ws.clone();
     let cmd_rx_on_open = cmd_rx.clone();
     let event_tx_on_open = event_tx.clone();
     let send_pump_on_open = send_pump.clone();
+    let resume_token_on_open = resume_token.clone();
     let onopen = Closure::<dyn FnMut(Event)>::new(move |_| {
         let _ = event_tx_on_open.send(NetEvent::Connected);
+        // Identify first so the server can restore our portal on reconnect
+        let hello = ClientMsg::Hello {
+            resume_token: resume_token_on_open.borrow().clone(),
+            name: None,
+        };
+        if let Ok(text) = serde_json::to_string(&hello) {
+            let _ = ws_on_open.send_with_str(&text);
+        }
         *send_pump_on_open.borrow_mut() = Some(Interval::new(16, {
             let ws_send = ws_on_open.clone();
             let cmd_rx_send = cmd_rx_on_open.clone();
@@ -222,11 +297,17 @@ fn connect_wasm_socket(
 
     let ws_on_message = ws.clone();
     let event_tx_on_message = event_tx.clone();
+    let resume_token_on_message = resume_token.clone();
     let onmessage = Closure::<dyn FnMut(MessageEvent)>::new(move |evt: MessageEvent| {
-        let Some(txt) = evt.data().as_string() else {
-            return;
+        let data = evt.data();
+        let server_msg = if let Some(txt) = data.as_string() {
+            serde_json::from_str::<ServerMsg>(&txt).ok()
+        } else if let Some(buf) = data.dyn_ref::<js_sys::ArrayBuffer>() {
+            decode_binary_msg(&js_sys::Uint8Array::new(buf).to_vec())
+        } else {
+            None
         };
-        let Ok(server_msg) = serde_json::from_str::<ServerMsg>(&txt) else {
+        let Some(server_msg) = server_msg else {
             return;
         };
 
@@ -239,6 +320,9 @@ fn connect_wasm_socket(
                 let _ = ws_on_message.close();
                 return;
             }
+            if !w.resume_token.is_empty() {
+                *resume_token_on_message.borrow_mut() = Some(w.resume_token.clone());
+            }
         }
 
         let _ = event_tx_on_message.send(NetEvent::Message {
@@ -259,6 +343,7 @@ fn connect_wasm_socket(
     let url_on_close = url;
     let event_tx_on_close = event_tx;
     let cmd_rx_on_close = cmd_rx;
+    let resume_token_on_close = resume_token;
     let send_pump_on_close = send_pump;
     let onclose = Closure::<dyn FnMut(Event)>::new(move |_| {
         *send_pump_on_close.borrow_mut() = None;
@@ -267,8 +352,15 @@ fn connect_wasm_socket(
         let url_retry = url_on_close.clone();
         let event_tx_retry = event_tx_on_close.clone();
         let cmd_rx_retry = cmd_rx_on_close.clone();
+        let resume_token_retry = resume_token_on_close.clone();
         Timeout::new(reconnect_delay_ms, move || {
-            connect_wasm_socket(url_retry, event_tx_retry, cmd_rx_retry, next_delay_ms);
+            connect_wasm_socket(
+                url_retry,
+                event_tx_retry,
+                cmd_rx_retry,
+                resume_token_retry,
+                next_delay_ms,
+            );
         })
         .forget();
     });
@@ -293,6 +385,7 @@ fn spawn_native_network_thread(url: String, event_tx: Sender<NetEvent>) -> Nativ
         rt.block_on(async move {
             let mut reconnect_delay = Duration::from_millis(1000);
             let max_delay = Duration::from_millis(30_000);
+            let mut resume_token: Option<String> = None;
 
             loop {
                 let _ = event_tx.send(NetEvent::Disconnected);
@@ -313,6 +406,19 @@ fn spawn_native_network_thread(url: String, event_tx: Sender<NetEvent>) -> Nativ
 
                 let (mut write, mut read) = ws_stream.split();
 
+                // Identify first so the server can restore our portal on reconnect
+                let hello = ClientMsg::Hello {
+                    resume_token: resume_token.clone(),
+                    name: None,
+                };
+                if let Ok(text) = serde_json::to_string(&hello) {
+                    if write.send(Message::Text(text.into())).await.is_err() {
+                        tokio::time::sleep(reconnect_delay).await;
+                        reconnect_delay = (reconnect_delay.mul_f32(1.5)).min(max_delay);
+                        continue;
+                    }
+                }
+
                 loop {
                     tokio::select! {
                         biased;
@@ -338,6 +444,9 @@ fn spawn_native_network_thread(url: String, event_tx: Sender<NetEvent>) -> Nativ
                                                 let _ = write.close().await;
                                                 break;
                                             }
+                                            if !w.resume_token.is_empty() {
+                                                resume_token = Some(w.resume_token.clone());
+                                            }
                                         }
                                         let _ = event_tx.send(NetEvent::Message {
                                             msg: server_msg,
@@ -345,6 +454,14 @@ fn spawn_native_network_thread(url: String, event_tx: Sender<NetEvent>) -> Nativ
                                         });
                                     }
                                 }
+                                Some(Ok(Message::Binary(bytes))) => {
+                                    if let Some(server_msg) = decode_binary_msg(&bytes) {
+                                        let _ = event_tx.send(NetEvent::Message {
+                                            msg: server_msg,
+                                            recv_time_secs: now_mono_secs(),
+                                        });
+                                    }
+                                }
                                 Some(Ok(Message::Close(_))) => {
                                     break;
                                 }
@@ -369,3 +486,50 @@ fn spawn_native_network_thread(url: String, event_tx: Sender<NetEvent>) -> Nativ
 
     cmd_tx
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::protocol::{BallWire, SpaceStateMsg};
+
+    #[test]
+    fn packed_wire_is_appended_to_url() {
+        assert_eq!(
+            with_packed_wire("ws://127.0.0.1:9001/ws"),
+            "ws://127.0.0.1:9001/ws?wire=packed"
+        );
+        assert_eq!(
+            with_packed_wire("wss://example.com/ws?room=a"),
+            "wss://example.com/ws?room=a&wire=packed"
+        );
+        assert_eq!(with_packed_wire("not a url"), "not a url");
+    }
+
+    #[test]
+    fn binary_frame_decodes_to_space_state() {
+        let msg = SpaceStateMsg {
+            server_time: 2.5,
+            seq: 1,
+            keyframe: true,
+            balls: vec![BallWire {
+                id: 3,
+                owner_id: 4,
+                pos: [1.0, 0.0, 0.0],
+                axis: [0.0, 0.0, 1.0],
+                omega: 1.0,
+                hops: 0,
+            }],
+            unchanged: vec![],
+            removed: vec![],
+        };
+        let bytes = wire::encode_space_state(&msg);
+        match decode_binary_msg(&bytes) {
+            Some(ServerMsg::SpaceState(s)) => {
+                assert_eq!(s.server_time, 2.5);
+                assert_eq!(s.balls[0].owner_id, 4);
+            }
+            other => panic!("expected space_state, got {:?}", other),
+        }
+        assert!(decode_binary_msg(&[0xff]).is_none());
+    }
+}
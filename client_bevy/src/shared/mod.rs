@@ -1,3 +1,4 @@
+pub mod clock_sync;
 pub mod connection;
 pub mod net_state;
 pub mod protocol;
@@ -1,7 +1,10 @@
 use std::collections::{HashMap, VecDeque};
 
 use bevy::prelude::Resource;
+use pinball_shared::delta::{DeltaDecoder, DeltaError};
+use pinball_shared::protocol::{BallWire, SpaceStateMsg};
 
+use super::clock_sync::ClockSync;
 use super::types::{ConnectionState, Player, SpaceBall3D};
 use super::vec3::{rotate_normalize_in_place, slerp};
 
@@ -32,6 +35,8 @@ pub struct NetState {
     snapshots: VecDeque<Snapshot>,
     has_server_time_offset: bool,
     server_time_offset: f64,
+    space_delta: DeltaDecoder,
+    clock: ClockSync,
 }
 
 impl Default for NetState {
@@ -46,16 +51,50 @@ impl Default for NetState {
             snapshots: VecDeque::new(),
             has_server_time_offset: false,
             server_time_offset: 0.0,
+            space_delta: DeltaDecoder::default(),
+            clock: ClockSync::default(),
         }
     }
 }
 
 impl NetState {
     pub fn reset_interpolation(&mut self) {
+        self.reset_timeline();
+        self.space_delta.reset();
+    }
+
+    fn reset_timeline(&mut self) {
         self.snapshots.clear();
         self.interpolated_balls.clear();
         self.has_server_time_offset = false;
         self.server_time_offset = 0.0;
+        self.clock.reset();
+    }
+
+    /// Feed a `pong` into the clock estimator (all times in `now_mono_secs`
+    /// except `server_time`).
+    pub fn record_pong(&mut self, client_time: f64, server_time: f64, recv_time: f64) {
+        self.clock.add_sample(client_time, server_time, recv_time);
+    }
+
+    /// Smoothed ping round-trip time in seconds, once a pong has arrived.
+    pub fn rtt(&self) -> Option<f64> {
+        self.clock.rtt()
+    }
+
+    /// Local time minus server time of arrival, used to place the render
+    /// clock. Prefers the ping/pong estimate; falls back to the
+    /// `space_state`-arrival estimate until the first pong.
+    fn arrival_offset(&self) -> Option<f64> {
+        self.clock.arrival_offset().or(self
+            .has_server_time_offset
+            .then_some(self.server_time_offset))
+    }
+
+    /// Rebuild the full ball set from a space_state keyframe or delta.
+    /// On error the delta chain is broken and the caller should request a keyframe.
+    pub fn apply_space_state(&mut self, msg: &SpaceStateMsg) -> Result<Vec<BallWire>, DeltaError> {
+        self.space_delta.apply(msg)
     }
 
     pub fn push_snapshot(&mut self, server_time: f64, recv_time: f64, balls: Vec<SpaceBall3D>) {
@@ -66,7 +105,7 @@ impl NetState {
         if let Some(last) = self.snapshots.back() {
             if server_time < last.server_time - SNAPSHOT_EPSILON_SECS {
                 // Server timeline moved backwards (e.g. reconnect/server restart).
-                self.reset_interpolation();
+                self.reset_timeline();
             } else if (server_time - last.server_time).abs() <= SNAPSHOT_EPSILON_SECS {
                 // Duplicate timestamp: keep only the latest payload for this time point.
                 self.snapshots.pop_back();
@@ -118,6 +157,7 @@ impl NetState {
             return;
         }
 
+        let arrival_offset = self.arrival_offset();
         let snapshots = &self.snapshots;
         let interpolated = &mut self.interpolated_balls;
 
@@ -129,10 +169,9 @@ impl NetState {
         }
 
         let latest = snapshots.back().expect("len checked");
-        let mut render_server_time = if self.has_server_time_offset {
-            now - self.server_time_offset - INTERPOLATION_DELAY_SECS
-        } else {
-            latest.server_time - INTERPOLATION_DELAY_SECS
+        let mut render_server_time = match arrival_offset {
+            Some(offset) => now - offset - INTERPOLATION_DELAY_SECS,
+            None => latest.server_time - INTERPOLATION_DELAY_SECS,
         };
 
         let first = snapshots.front().expect("len checked");
@@ -195,6 +234,7 @@ fn fill_from_snapshot(
         dst.pos = base.pos;
         dst.axis = base.axis;
         dst.omega = base.omega;
+        dst.hops = base.hops;
 
         if extrap > 0.0 {
             rotate_normalize_in_place(&mut dst.pos, dst.axis, dst.omega * extrap);
@@ -210,6 +250,7 @@ fn fill_between(interpolated: &mut Vec<SpaceBall3D>, older: &Snapshot, newer: &S
         dst.owner_id = curr.owner_id;
         dst.axis = curr.axis;
         dst.omega = curr.omega;
+        dst.hops = curr.hops;
 
         if let Some(&older_idx) = older.id_to_index.get(&curr.id) {
             let prev = &older.balls[older_idx];
@@ -238,6 +279,7 @@ mod tests {
                 pos: Vec3::new(1.0, 0.0, 0.0),
                 axis: Vec3::new(0.0, 0.0, 1.0),
                 omega: 1.0,
+                hops: 0,
             }],
         );
 
@@ -263,6 +305,7 @@ mod tests {
                 pos: Vec3::new(1.0, 0.0, 0.0),
                 axis: Vec3::new(0.0, 0.0, 1.0),
                 omega: 1.0,
+                hops: 0,
             }],
         );
         state.push_snapshot(
@@ -274,6 +317,7 @@ mod tests {
                 pos: Vec3::new(0.0, 1.0, 0.0),
                 axis: Vec3::new(0.0, 0.0, 1.0),
                 omega: 1.0,
+                hops: 0,
             }],
         );
 
@@ -307,6 +351,7 @@ mod tests {
                 pos: Vec3::new(0.0, 0.0, 1.0),
                 axis: Vec3::new(1.0, 0.0, 0.0),
                 omega: 0.5,
+                hops: 0,
             }],
         );
 
@@ -334,6 +379,7 @@ mod tests {
                     pos: Vec3::new(a.cos(), a.sin(), 0.0),
                     axis: Vec3::new(0.0, 0.0, 1.0),
                     omega,
+                    hops: 0,
                 }],
             );
 
@@ -363,6 +409,7 @@ mod tests {
                 pos: Vec3::new(1.0, 0.0, 0.0),
                 axis: Vec3::new(0.0, 0.0, 1.0),
                 omega: 0.0,
+                hops: 0,
             }],
         );
         state.push_snapshot(
@@ -374,6 +421,7 @@ mod tests {
                 pos: Vec3::new(0.0, 1.0, 0.0),
                 axis: Vec3::new(0.0, 0.0, 1.0),
                 omega: 0.0,
+                hops: 0,
             }],
         );
 
@@ -396,6 +444,7 @@ mod tests {
                 pos: Vec3::new(1.0, 0.0, 0.0),
                 axis: Vec3::new(0.0, 0.0, 1.0),
                 omega: 0.0,
+                hops: 0,
             }],
         );
         state.push_snapshot(
@@ -407,6 +456,7 @@ mod tests {
                 pos: Vec3::new(0.0, 1.0, 0.0),
                 axis: Vec3::new(0.0, 0.0, 1.0),
                 omega: 0.0,
+                hops: 0,
             }],
         );
         state.push_snapshot(
@@ -418,6 +468,7 @@ mod tests {
                 pos: Vec3::new(0.0, 0.0, 1.0),
                 axis: Vec3::new(0.0, 0.0, 1.0),
                 omega: 0.0,
+                hops: 0,
             }],
         );
 
@@ -426,6 +477,36 @@ mod tests {
         assert!((last.server_time - 0.9).abs() < 1e-9);
     }
 
+    #[test]
+    fn pong_estimate_replaces_space_state_arrival_offset() {
+        let mut state = NetState::default();
+        let ball = |pos| SpaceBall3D {
+            id: 42,
+            owner_id: 1,
+            pos,
+            axis: Vec3::new(0.0, 0.0, 1.0),
+            omega: 1.0,
+            hops: 0,
+        };
+        // Both snapshots were held up 300ms on the way in
+        state.push_snapshot(1.0, 1.3, vec![ball(Vec3::new(1.0, 0.0, 0.0))]);
+        state.push_snapshot(1.1, 1.4, vec![ball(Vec3::new(0.0, 1.0, 0.0))]);
+
+        // Arrival-based offset (0.3) puts the render clock before both snapshots
+        state.update_interpolation(1.26);
+        assert!((state.interpolated_balls[0].pos.x - 1.0).abs() < 1e-9);
+
+        // A 20ms ping says the clocks agree: render at 1.26 - 0.01 - 0.2 = 1.05
+        state.record_pong(1.0, 1.01, 1.02);
+        assert!((state.rtt().unwrap() - 0.02).abs() < 1e-9);
+        state.update_interpolation(1.26);
+        let p = state.interpolated_balls[0].pos;
+        assert!(p.x > 0.1 && p.y > 0.1, "expected halfway, got {:?}", p);
+
+        state.reset_interpolation();
+        assert!(state.rtt().is_none());
+    }
+
     #[test]
     fn snapshot_buffer_is_capped() {
         let mut state = NetState::default();
@@ -440,6 +521,7 @@ mod tests {
                     pos: Vec3::new(1.0, 0.0, 0.0),
                     axis: Vec3::new(0.0, 0.0, 1.0),
                     omega: 0.0,
+                    hops: 0,
                 }],
             );
         }
@@ -12,6 +12,27 @@ pub struct Player {
     pub paused: bool,
     pub balls_produced: u32,
     pub balls_in_flight: u32,
+    /// Display name; empty if the player hasn't set one
+    pub name: String,
+}
+
+impl Player {
+    /// Display name cut to `max_chars` (with an ellipsis), or `None` if unset.
+    pub fn short_name(&self, max_chars: usize) -> Option<String> {
+        if self.name.is_empty() {
+            return None;
+        }
+        if self.name.chars().count() <= max_chars {
+            return Some(self.name.clone());
+        }
+        let mut short: String = self
+            .name
+            .chars()
+            .take(max_chars.saturating_sub(1))
+            .collect();
+        short.push('…');
+        Some(short)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -21,6 +42,8 @@ pub struct SpaceBall3D {
     pub pos: Vec3,
     pub axis: Vec3,
     pub omega: f64,
+    /// Boards the ball has passed through
+    pub hops: u32,
 }
 
 impl Default for SpaceBall3D {
@@ -31,6 +54,7 @@ impl Default for SpaceBall3D {
             pos: Vec3::new(1.0, 0.0, 0.0),
             axis: Vec3::new(0.0, 0.0, 1.0),
             omega: 0.0,
+            hops: 0,
         }
     }
 }
@@ -44,6 +68,7 @@ pub fn wire_to_player(w: &PlayerWire) -> Player {
         paused: w.paused,
         balls_produced: w.balls_produced,
         balls_in_flight: w.balls_in_flight,
+        name: w.name.clone(),
     }
 }
 
@@ -53,3 +78,29 @@ pub enum ConnectionState {
     Connected,
     Disconnected,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(name: &str) -> Player {
+        Player {
+            id: 1,
+            cell_index: 0,
+            portal_pos: Vec3::new(1.0, 0.0, 0.0),
+            color: 0,
+            paused: false,
+            balls_produced: 0,
+            balls_in_flight: 0,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn short_name_truncates_by_characters() {
+        assert_eq!(named("").short_name(5), None);
+        assert_eq!(named("Ada").short_name(5).as_deref(), Some("Ada"));
+        assert_eq!(named("Lovelace").short_name(5).as_deref(), Some("Love…"));
+        assert_eq!(named("ééééééé").short_name(5).as_deref(), Some("éééé…"));
+    }
+}
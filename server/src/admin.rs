@@ -0,0 +1,335 @@
+//! Authenticated HTTP API for inspecting and controlling live rooms.
+//!
+//! Every route requires `Authorization: Bearer <admin_token>`; the API is
+//! only mounted when `ServerConfig::admin_token` is set. Requests are turned
+//! into `GameCommand`s with a oneshot reply, so all reads and writes happen
+//! on the room's game loop like any other command.
+//!
+//! ```text
+//! GET    /admin/rooms                             list rooms
+//! GET    /admin/rooms/{room}/players              list players
+//! POST   /admin/rooms/{room}/players/{id}/kick    disconnect a player
+//! POST   /admin/rooms/{room}/bots                 add a bot
+//! DELETE /admin/rooms/{room}/bots/{id}            remove a bot
+//! GET    /admin/rooms/{room}/balls                dump deep-space balls
+//! DELETE /admin/rooms/{room}/balls                clear deep space
+//! ```
+
+use std::sync::Arc;
+
+use axum::extract::{Path, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::oneshot;
+
+use crate::game_loop::GameCommand;
+use crate::room::{Room, RoomRegistry, RoomSummary};
+use crate::state::GameState;
+
+/// A player as seen by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminPlayer {
+    pub id: u32,
+    pub name: String,
+    pub is_bot: bool,
+    pub paused: bool,
+    pub cell_index: u32,
+    pub color: u32,
+    /// Server time (seconds) of the last activity heartbeat; `None` if never active
+    pub last_activity: Option<f64>,
+    pub balls_produced: u32,
+    pub balls_in_flight: u32,
+    /// Points this scoring period
+    pub score: u32,
+}
+
+/// A deep-space ball as seen by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminBall {
+    pub id: u32,
+    pub owner_id: u32,
+    /// Player who first launched the ball
+    pub origin_id: u32,
+    /// Boards the ball has passed through
+    pub hops: u32,
+    pub pos: [f64; 3],
+    pub axis: [f64; 3],
+    pub omega: f64,
+    /// Seconds since the ball entered deep space
+    pub age: f64,
+    /// Player the ball was gifted to
+    pub target_id: Option<u32>,
+}
+
+/// Snapshot of every player in `state`, sorted by id.
+pub fn players_snapshot(state: &GameState) -> Vec<AdminPlayer> {
+    let in_flight: std::collections::HashMap<u32, u32> = state
+        .get_players_state()
+        .players
+        .into_iter()
+        .map(|p| (p.id, p.balls_in_flight))
+        .collect();
+    let mut players: Vec<AdminPlayer> = state
+        .players
+        .values()
+        .map(|p| AdminPlayer {
+            id: p.id,
+            name: p.name.clone(),
+            is_bot: p.is_bot,
+            paused: p.paused,
+            cell_index: p.cell_index,
+            color: p.color,
+            last_activity: (p.last_activity > 0.0).then_some(p.last_activity),
+            balls_produced: p.balls_produced,
+            balls_in_flight: in_flight.get(&p.id).copied().unwrap_or(0),
+            score: state.scores.get(p.id).points(),
+        })
+        .collect();
+    players.sort_by_key(|p| p.id);
+    players
+}
+
+/// Snapshot of every ball in deep space, sorted by id.
+pub fn balls_snapshot(state: &GameState) -> Vec<AdminBall> {
+    let mut balls: Vec<AdminBall> = state
+        .deep_space
+        .get_ball_iter()
+        .map(|b| AdminBall {
+            id: b.id,
+            owner_id: b.owner_id,
+            origin_id: b.origin_id,
+            hops: b.hops,
+            pos: [b.pos.x, b.pos.y, b.pos.z],
+            axis: [b.axis.x, b.axis.y, b.axis.z],
+            omega: b.omega,
+            age: b.age,
+            target_id: b.target_id,
+        })
+        .collect();
+    balls.sort_by_key(|b| b.id);
+    balls
+}
+
+#[derive(Clone)]
+struct AdminState {
+    rooms: Arc<RoomRegistry>,
+    token: Arc<str>,
+}
+
+/// Admin routes, guarded by `token`.
+pub fn router(rooms: Arc<RoomRegistry>, token: String) -> Router {
+    let state = AdminState {
+        rooms,
+        token: token.into(),
+    };
+    Router::new()
+        .route("/admin/rooms", get(list_rooms))
+        .route("/admin/rooms/{room}/players", get(list_players))
+        .route("/admin/rooms/{room}/players/{id}/kick", post(kick_player))
+        .route("/admin/rooms/{room}/bots", post(add_bot))
+        .route("/admin/rooms/{room}/bots/{id}", delete(remove_bot))
+        .route(
+            "/admin/rooms/{room}/balls",
+            get(list_balls).delete(clear_balls),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+async fn require_token(State(state): State<AdminState>, req: Request, next: Next) -> Response {
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match presented {
+        Some(t) if token_matches(t, &state.token) => next.run(req).await,
+        _ => AdminError::Unauthorized.into_response(),
+    }
+}
+
+/// Compare without short-circuiting on the first differing byte.
+fn token_matches(presented: &str, expected: &str) -> bool {
+    let (a, b) = (presented.as_bytes(), expected.as_bytes());
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Debug)]
+enum AdminError {
+    Unauthorized,
+    NotFound(String),
+    Conflict(String),
+    /// The room's game loop has stopped
+    Unavailable,
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        let (status, msg) = match self {
+            AdminError::Unauthorized => (StatusCode::UNAUTHORIZED, "unauthorized".to_string()),
+            AdminError::NotFound(m) => (StatusCode::NOT_FOUND, m),
+            AdminError::Conflict(m) => (StatusCode::CONFLICT, m),
+            AdminError::Unavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "room is shutting down".to_string(),
+            ),
+        };
+        (status, Json(json!({ "error": msg }))).into_response()
+    }
+}
+
+fn room(state: &AdminState, name: &str) -> Result<Arc<Room>, AdminError> {
+    state
+        .rooms
+        .get(name)
+        .ok_or_else(|| AdminError::NotFound(format!("no room '{}'", name)))
+}
+
+/// Send a command to the room's game loop and wait for its reply.
+async fn ask<T>(
+    room: &Room,
+    command: impl FnOnce(oneshot::Sender<T>) -> GameCommand,
+) -> Result<T, AdminError> {
+    let (tx, rx) = oneshot::channel();
+    room.game_tx
+        .send(command(tx))
+        .await
+        .map_err(|_| AdminError::Unavailable)?;
+    rx.await.map_err(|_| AdminError::Unavailable)
+}
+
+async fn list_rooms(State(state): State<AdminState>) -> Json<Vec<RoomSummary>> {
+    Json(state.rooms.list())
+}
+
+async fn list_players(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<AdminPlayer>>, AdminError> {
+    let room = room(&state, &name)?;
+    let players = ask(&room, |response| GameCommand::AdminListPlayers { response }).await?;
+    Ok(Json(players))
+}
+
+async fn kick_player(
+    State(state): State<AdminState>,
+    Path((name, id)): Path<(String, u32)>,
+) -> Result<StatusCode, AdminError> {
+    let room = room(&state, &name)?;
+    let kicked = ask(&room, |response| GameCommand::AdminKick {
+        player_id: id,
+        response,
+    })
+    .await?;
+    if !kicked {
+        return Err(AdminError::NotFound(format!("no connected player {}", id)));
+    }
+    tracing::info!("Admin kicked player {} from room '{}'", id, name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn add_bot(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, AdminError> {
+    let room = room(&state, &name)?;
+    let id = ask(&room, |response| GameCommand::AdminAddBot { response })
+        .await?
+        .ok_or_else(|| AdminError::Conflict("no free portal cell".to_string()))?;
+    tracing::info!("Admin added bot {} to room '{}'", id, name);
+    Ok((StatusCode::CREATED, Json(json!({ "id": id }))))
+}
+
+async fn remove_bot(
+    State(state): State<AdminState>,
+    Path((name, id)): Path<(String, u32)>,
+) -> Result<StatusCode, AdminError> {
+    let room = room(&state, &name)?;
+    let removed = ask(&room, |response| GameCommand::AdminRemoveBot {
+        player_id: id,
+        response,
+    })
+    .await?;
+    if !removed {
+        return Err(AdminError::NotFound(format!("no bot {}", id)));
+    }
+    tracing::info!("Admin removed bot {} from room '{}'", id, name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_balls(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<AdminBall>>, AdminError> {
+    let room = room(&state, &name)?;
+    let balls = ask(&room, |response| GameCommand::AdminListBalls { response }).await?;
+    Ok(Json(balls))
+}
+
+async fn clear_balls(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, AdminError> {
+    let room = room(&state, &name)?;
+    let cleared = ask(&room, |response| GameCommand::AdminClearBalls { response }).await?;
+    tracing::info!("Admin cleared {} balls in room '{}'", cleared, name);
+    Ok(Json(json!({ "cleared": cleared })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DeepSpaceConfig, ServerConfig};
+
+    fn test_state() -> GameState {
+        let config = ServerConfig {
+            cell_count: 100,
+            rng_seed: 12345,
+            bot_count: 1,
+            ..Default::default()
+        };
+        GameState::new(&config, DeepSpaceConfig::default(), 1.5)
+    }
+
+    #[test]
+    fn token_comparison() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret"));
+        assert!(!token_matches("secret2", "secret"));
+        assert!(!token_matches("", "secret"));
+    }
+
+    #[test]
+    fn players_snapshot_reports_bots_and_balls_in_flight() {
+        let mut state = test_state();
+        let (id, _) = state.add_player().unwrap();
+        state.player_activity(id);
+        state.ball_escaped(id, 0.1, -1.0);
+
+        let players = players_snapshot(&state);
+        assert_eq!(players.len(), 2);
+        assert!(players.iter().any(|p| p.is_bot));
+        let me = players.iter().find(|p| p.id == id).unwrap();
+        assert!(!me.is_bot);
+        assert_eq!(me.balls_in_flight, 1);
+        assert_eq!(me.balls_produced, 1);
+    }
+
+    #[test]
+    fn balls_snapshot_lists_every_ball() {
+        let mut state = test_state();
+        let (id, _) = state.add_player().unwrap();
+        state.ball_escaped(id, 0.1, -1.0);
+        state.ball_escaped(id, -0.1, -1.0);
+
+        let balls = balls_snapshot(&state);
+        assert_eq!(balls.len(), 2);
+        assert!(balls.iter().all(|b| b.owner_id == id));
+        assert!(balls[0].id < balls[1].id);
+    }
+}
@@ -0,0 +1,480 @@
+//! Plausibility checks on a player's `ball_escaped` stream.
+//!
+//! `validate_ball_escaped` looks at one message at a time, so a script can
+//! still send a perfectly aimed ball for every rate-limit token. Each player
+//! connection keeps an `EscapeTracker` comparing its stream with what a real
+//! board can do:
+//!
+//! - **cadence**: every escape needs a ball, either one received with
+//!   `transfer_in` or one of the board's own, which it only respawns once
+//!   it is empty;
+//! - **physics**: the ball leaves upward through the slot in the top wall,
+//!   no faster than a flipper or the launcher could have sent it;
+//! - **entropy**: escapes come out of a physics simulation, so their
+//!   velocities vary; the same few over and over are scripted;
+//! - **paused / inactive**: own balls have to be launched, which a paused
+//!   or idle player isn't doing (received balls can bounce out unattended).
+//!
+//! An escape failing any check is a suspicion, and suspicions are forgiven
+//! over time. A player who accumulates `flag_after` of them is flagged for
+//! the rest of the connection and gets the configured `CheatPenalty`. These
+//! are heuristics: a player launching full power from an empty board again
+//! and again escapes the same way every time. So the default penalty only
+//! reports, and an operator can turn on throttling or shadow-dropping.
+//!
+//! The state lives with the connection, not the player, so reconnecting
+//! (with or without a resume token) starts over unflagged. Keeping it per
+//! player wouldn't help much: joining without a token makes a new player.
+//!
+//! Time is passed in, like in `rate_limit.rs`.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::config::{AntiCheat, CheatPenalty};
+use crate::rate_limit::TokenBucket;
+
+/// Velocity resolution (m/s) of the entropy check; escapes closer than
+/// this in both components count as the same.
+const ENTROPY_BIN: f64 = 0.05;
+
+/// Full-power launch speed in wire units (m/s at the clients' 500 px/m),
+/// mirrored from `client_bevy/src/board/launcher_logic.rs`. The launcher
+/// gives each of n stacked balls n² times this.
+const MAX_LAUNCH_SPEED: f64 = 1.8;
+
+/// Fastest a single ball can leave the escape slot after a flipper hit,
+/// rounded up: the tip moves at 14 rad/s × 82 px (`flipper_logic.rs`,
+/// `geometry.rs`), a ball dropping onto it bounces off at up to 1.5 times
+/// that plus half its own speed, and it climbs back to the top wall
+/// against gravity.
+const MAX_FLIPPER_SPEED: f64 = 4.0;
+
+/// A check an escape can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// No received or own ball left to escape
+    Cadence,
+    /// Not upward, or faster than the board could send a ball
+    Physics,
+    /// Too little variety in recent escape velocities
+    Entropy,
+    /// Own ball escaped while paused, past the grace period
+    Paused,
+    /// Own ball escaped long after the last `activity`
+    Inactive,
+}
+
+impl Check {
+    pub const ALL: [Check; 5] = [
+        Check::Cadence,
+        Check::Physics,
+        Check::Entropy,
+        Check::Paused,
+        Check::Inactive,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Check::Cadence => "cadence",
+            Check::Physics => "physics",
+            Check::Entropy => "entropy",
+            Check::Paused => "paused",
+            Check::Inactive => "inactive",
+        }
+    }
+}
+
+/// What the tracker made of one escape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscapeVerdict {
+    /// Checks the escape failed
+    pub failed: Vec<Check>,
+    /// This escape got the player flagged
+    pub newly_flagged: bool,
+    /// Penalty that stops this escape reaching deep space, if any
+    pub dropped_by: Option<CheatPenalty>,
+}
+
+/// Per-connection anti-cheat state for one player.
+#[derive(Debug)]
+pub struct EscapeTracker {
+    config: AntiCheat,
+    /// When each ball still assumed on the board was received
+    received: VecDeque<Instant>,
+    /// Own balls the board could have produced
+    own_balls: TokenBucket,
+    /// Binned velocities of the last `entropy_window` escapes
+    recent: VecDeque<(i64, i64)>,
+    paused_since: Option<Instant>,
+    last_activity: Instant,
+    suspicion: f64,
+    last_suspicion_update: Instant,
+    flagged: bool,
+    throttle: TokenBucket,
+}
+
+impl EscapeTracker {
+    pub fn new(config: AntiCheat, now: Instant) -> Self {
+        Self {
+            received: VecDeque::new(),
+            own_balls: TokenBucket::new(config.own_balls_per_sec, config.own_ball_burst, now),
+            recent: VecDeque::with_capacity(config.entropy_window as usize),
+            paused_since: None,
+            last_activity: now,
+            suspicion: 0.0,
+            last_suspicion_update: now,
+            flagged: false,
+            throttle: TokenBucket::new(config.throttle_per_sec, 1, now),
+            config,
+        }
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        self.flagged
+    }
+
+    /// A `transfer_in` was sent to the player.
+    pub fn ball_received(&mut self, now: Instant) {
+        self.expire_received(now);
+        self.received.push_back(now);
+    }
+
+    /// Forget received balls that have drained by now.
+    fn expire_received(&mut self, now: Instant) {
+        let ttl = Duration::from_secs_f64(self.config.received_ball_secs);
+        while self
+            .received
+            .front()
+            .is_some_and(|&at| now.saturating_duration_since(at) > ttl)
+        {
+            self.received.pop_front();
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool, now: Instant) {
+        match (paused, self.paused_since) {
+            (true, None) => self.paused_since = Some(now),
+            (false, Some(_)) => self.paused_since = None,
+            _ => {}
+        }
+    }
+
+    pub fn activity(&mut self, now: Instant) {
+        self.last_activity = now;
+    }
+
+    /// Check a valid `ball_escaped` and decide whether it goes through.
+    pub fn escape(&mut self, vx: f64, vy: f64, now: Instant) -> EscapeVerdict {
+        let mut failed = Vec::new();
+        if self.config.flag_after == 0 {
+            return EscapeVerdict {
+                failed,
+                newly_flagged: false,
+                dropped_by: None,
+            };
+        }
+
+        self.expire_received(now);
+        // Every received ball plus an own one could be stacked in the
+        // launcher, each getting n² times a full launch
+        let stacked = (self.received.len() + 1) as f64;
+        let max_speed = MAX_FLIPPER_SPEED.max(MAX_LAUNCH_SPEED * stacked * stacked);
+        if vy >= 0.0 || vx.hypot(vy) > max_speed {
+            failed.push(Check::Physics);
+        }
+
+        // Pay for the ball: a received one first, then an own one
+        if self.received.pop_front().is_none() {
+            if !self.own_balls.try_take(now) {
+                failed.push(Check::Cadence);
+            }
+            let paused_for = self
+                .paused_since
+                .map_or(Duration::ZERO, |at| now.saturating_duration_since(at));
+            if paused_for.as_secs_f64() > self.config.paused_grace_secs {
+                failed.push(Check::Paused);
+            }
+            let idle_for = now.saturating_duration_since(self.last_activity);
+            if idle_for.as_secs_f64() > self.config.inactive_secs {
+                failed.push(Check::Inactive);
+            }
+        }
+
+        if self.recent.len() == self.config.entropy_window as usize {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((
+            (vx / ENTROPY_BIN).round() as i64,
+            (vy / ENTROPY_BIN).round() as i64,
+        ));
+        if self.recent.len() == self.config.entropy_window as usize
+            && entropy_bits(&self.recent) < self.config.min_entropy_bits
+        {
+            failed.push(Check::Entropy);
+        }
+
+        let elapsed = now.saturating_duration_since(self.last_suspicion_update);
+        self.last_suspicion_update = self.last_suspicion_update.max(now);
+        self.suspicion =
+            (self.suspicion - elapsed.as_secs_f64() * self.config.forgive_per_sec).max(0.0);
+        if !failed.is_empty() {
+            self.suspicion += 1.0;
+        }
+        let newly_flagged = !self.flagged && self.suspicion >= self.config.flag_after as f64;
+        self.flagged |= newly_flagged;
+
+        let dropped_by = if !self.flagged {
+            None
+        } else {
+            match self.config.penalty {
+                CheatPenalty::Report => None,
+                CheatPenalty::Throttle => {
+                    (!self.throttle.try_take(now)).then_some(CheatPenalty::Throttle)
+                }
+                CheatPenalty::ShadowDrop => Some(CheatPenalty::ShadowDrop),
+            }
+        };
+        EscapeVerdict {
+            failed,
+            newly_flagged,
+            dropped_by,
+        }
+    }
+}
+
+/// Shannon entropy, in bits, of the values in `samples`.
+fn entropy_bits(samples: &VecDeque<(i64, i64)>) -> f64 {
+    let mut counts: HashMap<(i64, i64), u32> = HashMap::new();
+    for &sample in samples {
+        *counts.entry(sample).or_default() += 1;
+    }
+    let total = samples.len() as f64;
+    counts
+        .values()
+        .map(|&n| {
+            let p = n as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    /// Velocity of the `i`th of a series of physically varied escapes
+    fn varied(i: u32) -> (f64, f64) {
+        (-1.0 + 0.13 * (i % 16) as f64, -2.0 - 0.07 * (i % 11) as f64)
+    }
+
+    #[test]
+    fn received_and_own_balls_pay_for_escapes() {
+        let t0 = Instant::now();
+        let mut tracker = EscapeTracker::new(AntiCheat::default(), t0);
+        for _ in 0..3 {
+            tracker.ball_received(t0);
+        }
+        // Three received balls plus a burst of two own ones
+        for i in 0..5 {
+            let (vx, vy) = varied(i);
+            assert_eq!(tracker.escape(vx, vy, ms(t0, 100)).failed, vec![]);
+        }
+        let (vx, vy) = varied(5);
+        assert_eq!(
+            tracker.escape(vx, vy, ms(t0, 100)).failed,
+            vec![Check::Cadence]
+        );
+        // The board respawns its own ball about once a second
+        let (vx, vy) = varied(6);
+        assert_eq!(tracker.escape(vx, vy, ms(t0, 1100)).failed, vec![]);
+    }
+
+    #[test]
+    fn received_balls_expire() {
+        let t0 = Instant::now();
+        let config = AntiCheat {
+            own_ball_burst: 1,
+            ..AntiCheat::default()
+        };
+        let mut tracker = EscapeTracker::new(config, t0);
+        tracker.ball_received(t0);
+        tracker.ball_received(t0);
+        tracker.activity(ms(t0, 60_000));
+        // Both drained long ago; only the own ball is left
+        assert_eq!(tracker.escape(0.1, -2.0, ms(t0, 61_000)).failed, vec![]);
+        assert_eq!(
+            tracker.escape(0.2, -2.0, ms(t0, 61_000)).failed,
+            vec![Check::Cadence]
+        );
+    }
+
+    #[test]
+    fn received_balls_are_forgotten_without_escapes() {
+        let t0 = Instant::now();
+        let mut tracker = EscapeTracker::new(AntiCheat::default(), t0);
+        for s in 0..600 {
+            tracker.ball_received(ms(t0, s * 1_000));
+        }
+        // Only the last received_ball_secs' worth is kept
+        assert_eq!(tracker.received.len(), 61);
+    }
+
+    #[test]
+    fn escapes_the_board_cant_produce_fail_physics() {
+        let t0 = Instant::now();
+        let config = AntiCheat {
+            own_ball_burst: 10,
+            ..AntiCheat::default()
+        };
+        let mut tracker = EscapeTracker::new(config, t0);
+        // A flipper shot and a full launch are fine
+        assert_eq!(tracker.escape(2.0, -3.0, t0).failed, vec![]);
+        assert_eq!(tracker.escape(0.0, -1.8, t0).failed, vec![]);
+        // Faster than any single ball goes, or not through the top slot
+        assert_eq!(tracker.escape(3.0, -3.0, t0).failed, vec![Check::Physics]);
+        assert_eq!(tracker.escape(0.5, 0.0, t0).failed, vec![Check::Physics]);
+        // Two balls stacked in the launcher leave at four times full power
+        tracker.ball_received(t0);
+        assert_eq!(tracker.escape(0.0, -7.0, t0).failed, vec![]);
+        assert_eq!(tracker.escape(0.0, -7.0, t0).failed, vec![Check::Physics]);
+    }
+
+    #[test]
+    fn repeated_velocities_fail_entropy() {
+        let t0 = Instant::now();
+        let mut tracker = EscapeTracker::new(AntiCheat::default(), t0);
+        for i in 0..100 {
+            tracker.ball_received(t0);
+            let (vx, vy) = varied(i);
+            assert_eq!(tracker.escape(vx, vy, t0).failed, vec![], "{}", i);
+        }
+        // Identical escapes push entropy down as they fill the window
+        let failed: Vec<_> = (0..16)
+            .map(|_| {
+                tracker.ball_received(t0);
+                tracker.escape(0.5, -3.0, t0).failed
+            })
+            .collect();
+        assert_eq!(failed[0], vec![]);
+        assert_eq!(failed[15], vec![Check::Entropy]);
+        assert_eq!(entropy_bits(&tracker.recent), 0.0);
+    }
+
+    #[test]
+    fn own_balls_while_paused_or_idle_are_suspicious() {
+        let t0 = Instant::now();
+        let config = AntiCheat {
+            own_balls_per_sec: 100.0,
+            ..AntiCheat::default()
+        };
+        let mut tracker = EscapeTracker::new(config, t0);
+        tracker.set_paused(true, t0);
+        // Still in play when the tab was hidden
+        assert_eq!(tracker.escape(0.1, -2.0, ms(t0, 5_000)).failed, vec![]);
+        assert_eq!(
+            tracker.escape(0.2, -2.0, ms(t0, 11_000)).failed,
+            vec![Check::Paused]
+        );
+        // A ball received before pausing can still bounce out
+        tracker.ball_received(t0);
+        assert_eq!(tracker.escape(0.3, -2.0, ms(t0, 12_000)).failed, vec![]);
+        tracker.set_paused(false, ms(t0, 13_000));
+        assert_eq!(
+            tracker.escape(0.4, -2.0, ms(t0, 61_000)).failed,
+            vec![Check::Inactive]
+        );
+        tracker.activity(ms(t0, 62_000));
+        assert_eq!(tracker.escape(0.5, -2.0, ms(t0, 63_000)).failed, vec![]);
+    }
+
+    #[test]
+    fn flagging_is_forgiven_slowly_and_then_sticks() {
+        let t0 = Instant::now();
+        let config = AntiCheat {
+            flag_after: 3,
+            forgive_per_sec: 1.0,
+            own_ball_burst: 1,
+            ..AntiCheat::default()
+        };
+        let mut tracker = EscapeTracker::new(config, t0);
+        tracker.escape(0.1, -2.0, t0);
+        // Two suspicions, forgiven before the third
+        tracker.escape(0.2, -2.0, t0);
+        tracker.escape(0.3, -2.0, t0);
+        assert!(!tracker.escape(0.4, -2.0, ms(t0, 2_000)).newly_flagged);
+        assert!(!tracker.is_flagged());
+
+        let flags: Vec<_> = (0..3)
+            .map(|i| {
+                tracker
+                    .escape(0.5 + i as f64, -2.0, ms(t0, 2_000))
+                    .newly_flagged
+            })
+            .collect();
+        assert_eq!(flags, vec![false, false, true]);
+        assert!(tracker.is_flagged());
+        // Flagged for good, even once forgiven
+        tracker.ball_received(ms(t0, 60_000));
+        tracker.activity(ms(t0, 60_000));
+        let verdict = tracker.escape(3.0, -2.0, ms(t0, 60_000));
+        assert_eq!(verdict.failed, vec![]);
+        assert!(tracker.is_flagged());
+        // The default penalty only reports
+        assert_eq!(verdict.dropped_by, None);
+    }
+
+    #[test]
+    fn flag_after_zero_turns_checks_off() {
+        let t0 = Instant::now();
+        let config = AntiCheat {
+            flag_after: 0,
+            penalty: CheatPenalty::ShadowDrop,
+            ..AntiCheat::default()
+        };
+        let mut tracker = EscapeTracker::new(config, t0);
+        for _ in 0..100 {
+            let verdict = tracker.escape(0.5, -3.0, t0);
+            assert_eq!(verdict.failed, vec![]);
+            assert_eq!(verdict.dropped_by, None);
+        }
+        assert!(!tracker.is_flagged());
+    }
+
+    #[test]
+    fn penalties_drop_flagged_escapes() {
+        let t0 = Instant::now();
+        let flag_now = |penalty| AntiCheat {
+            flag_after: 1,
+            own_ball_burst: 1,
+            penalty,
+            throttle_per_sec: 1.0,
+            ..AntiCheat::default()
+        };
+
+        let mut tracker = EscapeTracker::new(flag_now(CheatPenalty::ShadowDrop), t0);
+        assert_eq!(tracker.escape(0.1, -2.0, t0).dropped_by, None);
+        let verdict = tracker.escape(0.2, -2.0, t0);
+        assert!(verdict.newly_flagged);
+        assert_eq!(verdict.dropped_by, Some(CheatPenalty::ShadowDrop));
+
+        let mut tracker = EscapeTracker::new(flag_now(CheatPenalty::Throttle), t0);
+        tracker.escape(0.1, -2.0, t0);
+        let dropped: Vec<_> = (0..3)
+            .map(|i| tracker.escape(0.2 + i as f64, -2.0, t0).dropped_by)
+            .collect();
+        assert_eq!(
+            dropped,
+            vec![
+                None,
+                Some(CheatPenalty::Throttle),
+                Some(CheatPenalty::Throttle)
+            ]
+        );
+        assert_eq!(tracker.escape(4.0, -2.0, ms(t0, 1_000)).dropped_by, None);
+    }
+}
@@ -45,7 +45,7 @@ enum ServerMsg {
     #[serde(rename = "space_state")]
     SpaceState { balls: Vec<serde_json::Value> },
     #[serde(rename = "transfer_in")]
-    TransferIn { vx: f64, vy: f64 },
+    TransferIn { vx: f64, vy: f64, seq: u32 },
 }
 
 // === Metrics ===
@@ -114,6 +114,16 @@ async fn run_client(
     metrics.latency_count.fetch_add(1, Ordering::Relaxed);
     metrics.connected.fetch_add(1, Ordering::Relaxed);
 
+    // Identify up front so the server doesn't wait out its hello timeout
+    if ws
+        .send(Message::Text(r#"{"type":"hello"}"#.into()))
+        .await
+        .is_err()
+    {
+        metrics.errors.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+
     if client_id < 3 {
         eprintln!("Client {} waiting for welcome...", client_id);
     }
@@ -234,8 +244,14 @@ async fn run_client(
                                     metrics.space_states_received.fetch_add(1, Ordering::Relaxed);
                                     metrics.total_balls_seen.fetch_add(balls.len() as u64, Ordering::Relaxed);
                                 }
-                                ServerMsg::TransferIn { .. } => {
+                                ServerMsg::TransferIn { seq, .. } => {
                                     metrics.transfer_ins_received.fetch_add(1, Ordering::Relaxed);
+                                    // Unacked balls are re-injected and the client dropped
+                                    let ack = format!(r#"{{"type":"transfer_ack","seq":{}}}"#, seq);
+                                    if ws.send(Message::Text(ack.into())).await.is_err() {
+                                        metrics.errors.fetch_add(1, Ordering::Relaxed);
+                                        break;
+                                    }
                                 }
                                 _ => {}
                             }
@@ -364,11 +380,7 @@ async fn main() {
             let escapes = metrics_clone.ball_escapes_sent.load(Ordering::Relaxed);
             let errors = metrics_clone.errors.load(Ordering::Relaxed);
             let balls = metrics_clone.total_balls_seen.load(Ordering::Relaxed);
-            let avg_balls = if space_states > 0 {
-                balls / space_states
-            } else {
-                0
-            };
+            let avg_balls = balls.checked_div(space_states).unwrap_or(0);
 
             println!(
                 "[{:3}s] connected={}, msgs={}, space_states={}, transfer_ins={}, escapes={}, errors={}, avg_balls={}",
@@ -403,15 +415,11 @@ async fn main() {
     println!("Total errors: {}", errors);
     println!(
         "Average balls in deep-space: {}",
-        if space_states > 0 {
-            balls / space_states
-        } else {
-            0
-        }
+        balls.checked_div(space_states).unwrap_or(0)
     );
 
-    if latency_count > 0 {
-        println!("Average connect latency: {}ms", latency_sum / latency_count);
+    if let Some(avg_latency) = latency_sum.checked_div(latency_count) {
+        println!("Average connect latency: {}ms", avg_latency);
     }
 
     let msgs_per_sec = msgs as f64 / duration_secs as f64;
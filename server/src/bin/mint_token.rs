@@ -0,0 +1,64 @@
+//! Mint a signed join token for a server with `join_token_secret` set.
+//!
+//! Usage: JOIN_TOKEN_SECRET=<secret> cargo run --bin mint-token -- \
+//!            [--ttl <seconds>] [--room <name>] [--name <display name>]
+//!
+//! The secret is read from the environment, like the server's, so it
+//! doesn't end up in shell history or `ps`. Prints the token; clients pass
+//! it as `/ws?token=<token>` or as a `pinball-token.<token>` subprotocol.
+
+use std::process::ExitCode;
+
+use pinball_server::join_token::{self, JoinClaims};
+
+const USAGE: &str = "Usage: mint-token [--ttl <seconds>] [--room <name>] [--name <display name>]\n\
+                     Reads the secret from JOIN_TOKEN_SECRET.";
+/// Default token lifetime (seconds)
+const DEFAULT_TTL_SECS: u64 = 24 * 60 * 60;
+
+fn main() -> ExitCode {
+    let Ok(secret) = std::env::var("JOIN_TOKEN_SECRET") else {
+        eprintln!("JOIN_TOKEN_SECRET is not set\n{}", USAGE);
+        return ExitCode::from(2);
+    };
+
+    let mut ttl = DEFAULT_TTL_SECS;
+    let mut room = None;
+    let mut name = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let Some(value) = args.next() else {
+            eprintln!("{}: missing value\n{}", flag, USAGE);
+            return ExitCode::from(2);
+        };
+        match flag.as_str() {
+            "--ttl" => match value.parse() {
+                Ok(secs) => ttl = secs,
+                Err(_) => {
+                    eprintln!("--ttl: expected seconds, got {:?}", value);
+                    return ExitCode::from(2);
+                }
+            },
+            "--room" => {
+                if let Err(e) = pinball_server::room::validate_room_name(&value) {
+                    eprintln!("--room: {}", e);
+                    return ExitCode::from(2);
+                }
+                room = Some(value);
+            }
+            "--name" => name = Some(value),
+            _ => {
+                eprintln!("{}: unknown flag\n{}", flag, USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let Some(exp) = join_token::unix_now().checked_add(ttl) else {
+        eprintln!("--ttl: {} seconds is too far in the future\n{}", ttl, USAGE);
+        return ExitCode::from(2);
+    };
+    let claims = JoinClaims { exp, room, name };
+    println!("{}", join_token::mint(&secret, &claims));
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,42 @@
+//! Replay a room recording and check it is deterministic.
+//!
+//! Re-runs the recorded commands on a fresh game state built from the
+//! recording's header and compares the deep-space checksum after every
+//! tick. Exits 1 at the first divergence (or unreadable recording).
+//!
+//! Usage: cargo run --bin replay -- <recording.jsonl>
+//!
+//! Recordings are written by the server with `record_dir` set.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let (Some(path), None) = (args.next(), args.next()) else {
+        eprintln!("Usage: replay <recording.jsonl>");
+        return ExitCode::from(2);
+    };
+
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match pinball_server::record::replay(BufReader::new(file)) {
+        Ok(summary) => {
+            println!(
+                "{}: {} ticks, {} commands, no divergence",
+                path, summary.ticks, summary.commands
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -5,6 +5,7 @@
 //! - Decide when to send them back based on personality
 //! - Return escape velocities via `tick()`
 
+use crate::deep_space::BallLineage;
 use crate::player::Player;
 use rand::Rng;
 
@@ -62,6 +63,8 @@ struct PendingBall {
     vy: f64,
     /// Time remaining before sending
     delay: f64,
+    /// Identity of the captured ball, kept when it is sent back out
+    lineage: BallLineage,
 }
 
 /// A bot player that automatically plays the game
@@ -105,12 +108,18 @@ impl BotPlayer {
     }
 
     /// Called when a ball is captured by this bot's portal
-    pub fn receive_ball(&mut self, vx: f64, vy: f64, rng: &mut impl Rng) {
+    pub fn receive_ball(&mut self, vx: f64, vy: f64, lineage: BallLineage, rng: &mut impl Rng) {
         let delay = self.personality.random_delay(rng);
-        self.pending_balls.push(PendingBall { vx, vy, delay });
+        self.pending_balls.push(PendingBall {
+            vx,
+            vy,
+            delay,
+            lineage,
+        });
     }
 
-    /// Tick the bot. Returns Some((vx, vy)) if the bot wants to send a ball.
+    /// Tick the bot. Returns Some((vx, vy, lineage)) if the bot wants to
+    /// send a ball; the lineage is None for a newly launched ball.
     /// When `active_players` is false, all timers are frozen and no balls are produced.
     pub fn tick(
         &mut self,
@@ -118,7 +127,7 @@ impl BotPlayer {
         rng: &mut impl Rng,
         real_player_count: usize,
         active_players: bool,
-    ) -> Option<(f64, f64)> {
+    ) -> Option<(f64, f64, Option<BallLineage>)> {
         // Freeze when no active players — don't tick timers, don't produce balls
         if !active_players {
             return None;
@@ -132,7 +141,7 @@ impl BotPlayer {
                 // Send a ball with random velocity
                 let vx = rng.gen_range(-2.0..2.0);
                 let vy = rng.gen_range(1.0..3.0);
-                return Some((vx, vy));
+                return Some((vx, vy, None));
             }
         }
 
@@ -142,7 +151,7 @@ impl BotPlayer {
             self.spontaneous_timer = Self::random_spontaneous_delay(rng, real_player_count);
             let vx = rng.gen_range(-2.0..2.0);
             let vy = rng.gen_range(1.0..3.0);
-            return Some((vx, vy));
+            return Some((vx, vy, None));
         }
 
         // Check pending balls
@@ -169,7 +178,7 @@ impl BotPlayer {
             };
 
             // Ensure vy is positive (ball goes into deep space)
-            return Some((vx, vy.abs().max(0.5)));
+            return Some((vx, vy.abs().max(0.5), Some(ball.lineage)));
         }
 
         None
@@ -216,9 +225,16 @@ impl BotManager {
     }
 
     /// Called when a ball is captured. Routes to the appropriate bot if target is a bot.
-    pub fn handle_capture(&mut self, player_id: u32, vx: f64, vy: f64, rng: &mut impl Rng) {
+    pub fn handle_capture(
+        &mut self,
+        player_id: u32,
+        vx: f64,
+        vy: f64,
+        lineage: BallLineage,
+        rng: &mut impl Rng,
+    ) {
         if let Some(bot) = self.bots.iter_mut().find(|b| b.player_id == player_id) {
-            bot.receive_ball(vx, vy, rng);
+            bot.receive_ball(vx, vy, lineage, rng);
         }
     }
 
@@ -227,18 +243,18 @@ impl BotManager {
         self.bots.iter().any(|b| b.player_id == player_id)
     }
 
-    /// Tick all bots. Returns list of (player_id, vx, vy) for balls to send.
+    /// Tick all bots. Returns list of (player_id, vx, vy, lineage) for balls to send.
     pub fn tick(
         &mut self,
         dt: f64,
         rng: &mut impl Rng,
         real_player_count: usize,
         active_players: bool,
-    ) -> Vec<(u32, f64, f64)> {
+    ) -> Vec<(u32, f64, f64, Option<BallLineage>)> {
         let mut results = Vec::new();
         for bot in &mut self.bots {
-            if let Some((vx, vy)) = bot.tick(dt, rng, real_player_count, active_players) {
-                results.push((bot.player_id, vx, vy));
+            if let Some((vx, vy, lineage)) = bot.tick(dt, rng, real_player_count, active_players) {
+                results.push((bot.player_id, vx, vy, lineage));
             }
         }
         results
@@ -278,6 +294,14 @@ mod tests {
         ChaCha8Rng::seed_from_u64(42)
     }
 
+    fn lineage() -> BallLineage {
+        BallLineage {
+            id: 40,
+            origin_id: 2,
+            hops: 1,
+        }
+    }
+
     #[test]
     fn bot_receives_and_sends_ball() {
         let mut rng = test_rng();
@@ -287,18 +311,19 @@ mod tests {
         bot.initial_ball_delay = None;
 
         // Receive a ball
-        bot.receive_ball(1.0, 2.0, &mut rng);
+        bot.receive_ball(1.0, 2.0, lineage(), &mut rng);
         assert_eq!(bot.pending_count(), 1);
 
         // Tick until ball is sent (eager bots are fast)
-        let mut sent = false;
+        let mut sent = None;
         for _ in 0..100 {
-            if bot.tick(0.1, &mut rng, 1, true).is_some() {
-                sent = true;
+            if let Some((_, _, lineage)) = bot.tick(0.1, &mut rng, 1, true) {
+                sent = Some(lineage);
                 break;
             }
         }
-        assert!(sent, "Bot should send ball within 10 seconds");
+        let sent = sent.expect("Bot should send ball within 10 seconds");
+        assert_eq!(sent, Some(lineage()), "the ball keeps its identity");
         assert_eq!(bot.pending_count(), 0);
     }
 
@@ -308,7 +333,7 @@ mod tests {
         let mut bot = BotPlayer::new(1, BotPersonality::Eager, &mut rng);
         bot.initial_ball_delay = None;
 
-        bot.receive_ball(1.0, 2.0, &mut rng);
+        bot.receive_ball(1.0, 2.0, lineage(), &mut rng);
 
         // Tick for 1 second in small steps
         let mut ticks = 0;
@@ -325,7 +350,7 @@ mod tests {
         let mut bot = BotPlayer::new(1, BotPersonality::Relaxed, &mut rng);
         bot.initial_ball_delay = None;
 
-        bot.receive_ball(1.0, 2.0, &mut rng);
+        bot.receive_ball(1.0, 2.0, lineage(), &mut rng);
 
         // Should not send within first second
         for _ in 0..10 {
@@ -363,6 +388,7 @@ mod tests {
             paused: false,
             balls_produced: 0,
             is_bot: true,
+            name: String::new(),
             last_activity: 0.0,
         };
 
@@ -373,7 +399,7 @@ mod tests {
         // Disable initial ball
         manager.bots[0].initial_ball_delay = None;
 
-        manager.handle_capture(1, 1.0, 2.0, &mut rng);
+        manager.handle_capture(1, 1.0, 2.0, lineage(), &mut rng);
         assert_eq!(manager.bots[0].pending_count(), 1);
     }
 
@@ -390,12 +416,13 @@ mod tests {
             paused: false,
             balls_produced: 0,
             is_bot: true,
+            name: String::new(),
             last_activity: 0.0,
         };
 
         manager.add_bot(&player, &mut rng);
         manager.bots[0].initial_ball_delay = None;
-        manager.handle_capture(1, 1.0, 2.0, &mut rng);
+        manager.handle_capture(1, 1.0, 2.0, lineage(), &mut rng);
 
         // Tick until ball is returned
         let mut results = Vec::new();
@@ -421,7 +448,7 @@ mod tests {
         ] {
             let mut bot = BotPlayer::new(1, personality, &mut rng);
             bot.initial_ball_delay = None;
-            bot.receive_ball(1.0, 2.0, &mut rng);
+            bot.receive_ball(1.0, 2.0, lineage(), &mut rng);
 
             // Tick until sent
             let mut velocity = None;
@@ -432,7 +459,7 @@ mod tests {
                 }
             }
 
-            let (vx, vy) = velocity.expect("Should send ball");
+            let (vx, vy, _) = velocity.expect("Should send ball");
             assert!(!vx.is_nan(), "vx is NaN for {:?}", personality);
             assert!(!vy.is_nan(), "vy is NaN for {:?}", personality);
             assert!(vy >= 0.5, "vy should be positive for {:?}", personality);
@@ -448,7 +475,7 @@ mod tests {
             let mut trial_rng = ChaCha8Rng::seed_from_u64(seed);
             let mut bot = BotPlayer::new(1, BotPersonality::Chaotic, &mut trial_rng);
             bot.initial_ball_delay = None;
-            bot.receive_ball(1.0, 2.0, &mut trial_rng);
+            bot.receive_ball(1.0, 2.0, lineage(), &mut trial_rng);
 
             let mut ticks = 0;
             while bot.tick(0.1, &mut trial_rng, 1, true).is_none() && ticks < 100 {
@@ -475,11 +502,11 @@ mod tests {
         // Send multiple balls and collect velocities
         let mut velocities = Vec::new();
         for _ in 0..5 {
-            bot.receive_ball(1.0, 2.0, &mut rng);
+            bot.receive_ball(1.0, 2.0, lineage(), &mut rng);
 
             // Tick until sent
             for _ in 0..100 {
-                if let Some((vx, vy)) = bot.tick(0.1, &mut rng, 1, true) {
+                if let Some((vx, vy, _)) = bot.tick(0.1, &mut rng, 1, true) {
                     velocities.push((vx, vy));
                     break;
                 }
@@ -510,6 +537,7 @@ mod tests {
             paused: false,
             balls_produced: 0,
             is_bot: true,
+            name: String::new(),
             last_activity: 0.0,
         };
         let player2 = Player {
@@ -520,6 +548,7 @@ mod tests {
             paused: false,
             balls_produced: 0,
             is_bot: true,
+            name: String::new(),
             last_activity: 0.0,
         };
 
@@ -560,9 +589,9 @@ mod tests {
         bot.initial_ball_delay = None;
 
         // Queue multiple balls
-        bot.receive_ball(1.0, 2.0, &mut rng);
-        bot.receive_ball(2.0, 3.0, &mut rng);
-        bot.receive_ball(3.0, 4.0, &mut rng);
+        bot.receive_ball(1.0, 2.0, lineage(), &mut rng);
+        bot.receive_ball(2.0, 3.0, lineage(), &mut rng);
+        bot.receive_ball(3.0, 4.0, lineage(), &mut rng);
 
         assert_eq!(bot.pending_count(), 3);
 
@@ -590,7 +619,7 @@ mod tests {
         assert!(bot.initial_ball_delay.is_some());
         let delay = bot.initial_ball_delay.unwrap();
         assert!(
-            delay >= 2.0 && delay <= 8.0,
+            (2.0..=8.0).contains(&delay),
             "Initial delay should be 2-8s, got {}",
             delay
         );
@@ -659,7 +688,7 @@ mod tests {
         bot.initial_ball_delay = None;
 
         // Receive a ball
-        bot.receive_ball(1.0, 2.0, &mut rng);
+        bot.receive_ball(1.0, 2.0, lineage(), &mut rng);
 
         // Tick with no active players — ball should never be sent
         for _ in 0..200 {
@@ -682,6 +711,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: true,
+                name: String::new(),
                 last_activity: 0.0,
             };
             manager.add_bot(&player, &mut rng);
@@ -711,6 +741,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: true,
+                name: String::new(),
                 last_activity: 0.0,
             };
             manager.add_bot(&player, &mut rng);
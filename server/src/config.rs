@@ -1,7 +1,29 @@
 pub use pinball_shared::config::DeepSpaceConfig;
 
-/// Server configuration
-#[derive(Debug, Clone)]
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Shortest `join_token_secret` accepted.
+const MIN_JOIN_TOKEN_SECRET_LEN: usize = 16;
+
+/// Per-room overrides of the server-wide settings (see `room.rs`).
+/// Unset fields fall back to the server config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoomConfig {
+    pub cell_count: Option<usize>,
+    pub bot_count: Option<usize>,
+    /// Maximum concurrent connections in this room
+    pub max_connections: Option<usize>,
+    #[serde(with = "opt_deep_space")]
+    pub deep_space: Option<DeepSpaceConfig>,
+}
+
+/// Server configuration. Field names double as config file keys, env var
+/// names (upper-cased) and CLI flags (see `settings.rs`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ServerConfig {
     pub listen_addr: String,
     pub tick_rate_hz: u32,
@@ -10,16 +32,58 @@ pub struct ServerConfig {
     pub rng_seed: u64,
     /// Maximum velocity component magnitude for ball_escaped (m/s)
     pub max_velocity: f64,
-    /// Maximum ball_escaped messages per second per client
-    pub max_ball_escaped_per_sec: u32,
     /// Maximum concurrent connections
     pub max_connections: usize,
+    /// Maximum concurrent spectator connections, counted apart from
+    /// `max_connections` (0 disables spectating)
+    pub max_spectators: usize,
     /// Global maximum balls in deep space (prevents memory exhaustion)
     pub max_balls_global: usize,
     /// Allowed origins for WebSocket connections (empty = allow all)
     pub allowed_origins: Vec<String>,
     /// Number of bot players to spawn on server start
     pub bot_count: usize,
+    /// Area-of-interest radius (radians) around each player's portal.
+    /// space_state only carries balls within this radius (plus a margin);
+    /// PI or more sends every ball.
+    pub aoi_radius: f64,
+    /// Maximum rooms alive at once, including the default room
+    pub max_rooms: usize,
+    /// Maximum concurrent connections per room (unless overridden)
+    pub max_connections_per_room: usize,
+    /// Overrides for named rooms; other rooms use the defaults above
+    pub rooms: HashMap<String, RoomConfig>,
+    /// Bearer token for the `/admin` API. `None` disables the API.
+    pub admin_token: Option<String>,
+    /// Shared secret for signed join tokens (see `join_token.rs`). When
+    /// set, `/ws` refuses connections without a valid token.
+    pub join_token_secret: Option<String>,
+    /// Seconds to wait for clients to disconnect after SIGTERM/SIGINT
+    pub shutdown_grace_secs: u64,
+    /// Directory for deep-space snapshots of the default room and the
+    /// rooms in `rooms` (see `persist.rs`). `None` disables persistence.
+    pub snapshot_dir: Option<String>,
+    /// Also snapshot every this many seconds while running (0 = only when
+    /// a room stops)
+    pub snapshot_interval_secs: u64,
+    /// Directory for per-room command recordings (see `record.rs`).
+    /// `None` disables recording.
+    pub record_dir: Option<String>,
+    /// Maximum display name length in characters, after normalisation
+    pub max_name_len: usize,
+    /// Words not allowed anywhere in a display name. Matched ignoring case,
+    /// spaces and punctuation.
+    pub name_blocklist: Vec<String>,
+    /// Scores reset to zero every this many seconds (0 = never)
+    pub score_reset_secs: u64,
+    /// Per-message-type limits on what clients send (see `rate_limit.rs`)
+    pub rate_limits: RateLimits,
+    /// Plausibility checks on each player's ball_escaped stream (see
+    /// `anti_cheat.rs`)
+    pub anti_cheat: AntiCheat,
+    /// Deep-space simulation for every room without its own override
+    #[serde(with = "DeepSpaceConfigDef")]
+    pub deep_space: DeepSpaceConfig,
 }
 
 impl Default for ServerConfig {
@@ -31,37 +95,404 @@ impl Default for ServerConfig {
             cell_count: 2048,
             rng_seed: 42,
             max_velocity: 10.0,
-            max_ball_escaped_per_sec: 30,
             max_connections: 1000,
+            max_spectators: 100,
             max_balls_global: 1000,
             allowed_origins: vec![],
             bot_count: 3,
+            // Matches the clients' THETA_MAX view radius
+            aoi_radius: 0.8,
+            max_rooms: 16,
+            max_connections_per_room: 1000,
+            rooms: HashMap::new(),
+            admin_token: None,
+            join_token_secret: None,
+            shutdown_grace_secs: 10,
+            snapshot_dir: None,
+            snapshot_interval_secs: 0,
+            record_dir: None,
+            max_name_len: 20,
+            name_blocklist: vec![],
+            score_reset_secs: 3600,
+            rate_limits: RateLimits::default(),
+            anti_cheat: AntiCheat::default(),
+            deep_space: DeepSpaceConfig::default(),
         }
     }
 }
 
 impl ServerConfig {
-    /// Validate configuration. Returns Err with description if invalid.
-    pub fn validate(&self) -> Result<(), String> {
-        if self.tick_rate_hz == 0 {
-            return Err("tick_rate_hz must be > 0".to_string());
+    /// Validate configuration. Returns every problem found, each prefixed
+    /// with the path of the offending field.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, msg: &str| {
+            if !ok {
+                errors.push(msg.to_string());
+            }
+        };
+        check(self.tick_rate_hz > 0, "tick_rate_hz: must be > 0");
+        check(self.broadcast_rate_hz > 0, "broadcast_rate_hz: must be > 0");
+        check(self.cell_count > 0, "cell_count: must be > 0");
+        // TOML integers are i64, so a larger seed couldn't be written back
+        // out by `--print-config`
+        check(
+            i64::try_from(self.rng_seed).is_ok(),
+            "rng_seed: must be <= 9223372036854775807",
+        );
+        check(
+            self.max_velocity.is_finite() && self.max_velocity > 0.0,
+            "max_velocity: must be finite and > 0",
+        );
+        check(self.max_connections > 0, "max_connections: must be > 0");
+        check(self.max_balls_global > 0, "max_balls_global: must be > 0");
+        check(
+            self.aoi_radius.is_finite() && self.aoi_radius > 0.0,
+            "aoi_radius: must be finite and > 0",
+        );
+        check(self.max_rooms > 0, "max_rooms: must be > 0");
+        check(
+            self.max_connections_per_room > 0,
+            "max_connections_per_room: must be > 0",
+        );
+        check(
+            !self
+                .admin_token
+                .as_deref()
+                .is_some_and(|t| t.trim().is_empty()),
+            "admin_token: must not be empty",
+        );
+        check(
+            self.join_token_secret
+                .as_deref()
+                .is_none_or(|s| s.trim().len() >= MIN_JOIN_TOKEN_SECRET_LEN),
+            "join_token_secret: must be at least 16 characters",
+        );
+        check(self.max_name_len > 0, "max_name_len: must be > 0");
+        check(
+            !self
+                .snapshot_dir
+                .as_deref()
+                .is_some_and(|d| d.trim().is_empty()),
+            "snapshot_dir: must not be empty",
+        );
+        check(
+            !self
+                .record_dir
+                .as_deref()
+                .is_some_and(|d| d.trim().is_empty()),
+            "record_dir: must not be empty",
+        );
+        errors.extend(self.rate_limits.validate());
+        errors.extend(self.anti_cheat.validate());
+        if let Err(e) = self.deep_space.validate() {
+            errors.extend(e.into_iter().map(|e| format!("deep_space.{}", e)));
+        }
+
+        let mut rooms: Vec<_> = self.rooms.iter().collect();
+        rooms.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, room) in rooms {
+            if let Err(e) = crate::room::validate_room_name(name) {
+                errors.push(format!("rooms.{}: {}", name, e));
+            }
+            if room.cell_count == Some(0) {
+                errors.push(format!("rooms.{}.cell_count: must be > 0", name));
+            }
+            if room.max_connections == Some(0) {
+                errors.push(format!("rooms.{}.max_connections: must be > 0", name));
+            }
+            if let Some(Err(e)) = room.deep_space.map(|ds| ds.validate()) {
+                errors.extend(
+                    e.into_iter()
+                        .map(|e| format!("rooms.{}.deep_space.{}", name, e)),
+                );
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Effective server and deep-space config for a room, with its overrides applied.
+    pub fn for_room(&self, name: &str) -> (ServerConfig, DeepSpaceConfig) {
+        let mut config = self.clone();
+        let mut deep_space = self.deep_space;
+        config.max_connections = self.max_connections_per_room;
+        if let Some(room) = self.rooms.get(name) {
+            config.cell_count = room.cell_count.unwrap_or(config.cell_count);
+            config.bot_count = room.bot_count.unwrap_or(config.bot_count);
+            config.max_connections = room.max_connections.unwrap_or(config.max_connections);
+            deep_space = room.deep_space.unwrap_or(deep_space);
+        }
+        (config, deep_space)
+    }
+}
+
+/// What happens to a client message over its rate limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitPolicy {
+    /// Discard it silently
+    Drop,
+    /// Discard it and log that the client is over the limit
+    Ignore,
+    /// Close the connection
+    Disconnect,
+}
+
+/// Token bucket for one client message type: `burst` messages at once,
+/// refilled at `per_sec`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub per_sec: f64,
+    pub burst: u32,
+    pub policy: RateLimitPolicy,
+}
+
+impl RateLimit {
+    pub const fn new(per_sec: f64, burst: u32, policy: RateLimitPolicy) -> Self {
+        Self {
+            per_sec,
+            burst,
+            policy,
+        }
+    }
+}
+
+/// Rate limits for every client message type that has one. `hello` and
+/// `transfer_ack` have none: only the first `hello` counts, and only acks
+/// for a `transfer_in` the client was sent get through.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    /// Spawns balls in deep space, so abuse disconnects
+    pub ball_escaped: RateLimit,
+    pub set_paused: RateLimit,
+    /// Heartbeat with no game effect
+    pub activity: RateLimit,
+    /// The next periodic keyframe is less than a second away anyway
+    pub request_keyframe: RateLimit,
+    pub ping: RateLimit,
+    /// Broadcast to the whole room
+    pub emote: RateLimit,
+    /// Reports; the hits in them are capped separately
+    pub bumper_hits: RateLimit,
+    /// Every change is broadcast to the room
+    pub set_name: RateLimit,
+    /// Also limit each client IP, across all its connections, to this many
+    /// connections' worth of every limit above (0 = per connection only).
+    /// Behind a reverse proxy every client shares the proxy's address.
+    pub per_ip_connections: u32,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        use RateLimitPolicy::*;
+        Self {
+            ball_escaped: RateLimit::new(30.0, 30, Disconnect),
+            set_paused: RateLimit::new(10.0, 10, Ignore),
+            activity: RateLimit::new(1.0, 1, Drop),
+            request_keyframe: RateLimit::new(1.0, 1, Drop),
+            ping: RateLimit::new(5.0, 5, Drop),
+            emote: RateLimit::new(2.0, 2, Ignore),
+            bumper_hits: RateLimit::new(4.0, 4, Ignore),
+            set_name: RateLimit::new(0.1, 5, Ignore),
+            per_ip_connections: 0,
+        }
+    }
+}
+
+impl RateLimits {
+    fn validate(&self) -> Vec<String> {
+        let limits = [
+            ("ball_escaped", &self.ball_escaped),
+            ("set_paused", &self.set_paused),
+            ("activity", &self.activity),
+            ("request_keyframe", &self.request_keyframe),
+            ("ping", &self.ping),
+            ("emote", &self.emote),
+            ("bumper_hits", &self.bumper_hits),
+            ("set_name", &self.set_name),
+        ];
+        let mut errors = Vec::new();
+        for (name, limit) in limits {
+            if !(limit.per_sec.is_finite() && limit.per_sec > 0.0) {
+                errors.push(format!(
+                    "rate_limits.{}.per_sec: must be finite and > 0",
+                    name
+                ));
+            }
+            if limit.burst == 0 {
+                errors.push(format!("rate_limits.{}.burst: must be > 0", name));
+            }
+        }
+        errors
+    }
+}
+
+/// What a player flagged by the anti-cheat gets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheatPenalty {
+    /// Nothing beyond the log line and metrics
+    Report,
+    /// Only `throttle_per_sec` of their escapes reach deep space
+    Throttle,
+    /// None of their escapes reach deep space; the client isn't told
+    ShadowDrop,
+}
+
+impl CheatPenalty {
+    pub const ALL: [CheatPenalty; 3] = [
+        CheatPenalty::Report,
+        CheatPenalty::Throttle,
+        CheatPenalty::ShadowDrop,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CheatPenalty::Report => "report",
+            CheatPenalty::Throttle => "throttle",
+            CheatPenalty::ShadowDrop => "shadow_drop",
         }
-        if self.broadcast_rate_hz == 0 {
-            return Err("broadcast_rate_hz must be > 0".to_string());
+    }
+}
+
+/// Heuristics for scripted `ball_escaped` streams. Every escape that fails
+/// a check is a suspicion; a player with `flag_after` of them (net of
+/// `forgive_per_sec`) is flagged for the rest of the connection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AntiCheat {
+    /// Suspicious escapes that flag a player (0 = checks off)
+    pub flag_after: u32,
+    /// Suspicions forgiven per second
+    pub forgive_per_sec: f64,
+    pub penalty: CheatPenalty,
+    /// Escapes per second a throttled player keeps
+    pub throttle_per_sec: f64,
+    /// Balls a board can launch of its own per second. It only respawns one
+    /// once it is empty, after 0.5 s, and then it has to be launched.
+    pub own_balls_per_sec: f64,
+    /// Own balls that can be banked while received ones are in play
+    pub own_ball_burst: u32,
+    /// A received ball that hasn't escaped after this long has drained
+    pub received_ball_secs: f64,
+    /// Escapes whose velocities are compared for the entropy check
+    pub entropy_window: u32,
+    /// Least entropy, in bits, of the last `entropy_window` escape velocities
+    pub min_entropy_bits: f64,
+    /// Own balls can still escape this long after pausing
+    pub paused_grace_secs: f64,
+    /// Own balls escaping after this long without `activity` are suspicious
+    pub inactive_secs: f64,
+}
+
+impl Default for AntiCheat {
+    fn default() -> Self {
+        Self {
+            flag_after: 20,
+            forgive_per_sec: 0.05,
+            penalty: CheatPenalty::Report,
+            throttle_per_sec: 0.5,
+            own_balls_per_sec: 1.0,
+            own_ball_burst: 2,
+            received_ball_secs: 60.0,
+            entropy_window: 16,
+            min_entropy_bits: 1.0,
+            paused_grace_secs: 10.0,
+            inactive_secs: 60.0,
+        }
+    }
+}
+
+impl AntiCheat {
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let non_negative = [
+            ("forgive_per_sec", self.forgive_per_sec),
+            ("received_ball_secs", self.received_ball_secs),
+            ("min_entropy_bits", self.min_entropy_bits),
+            ("paused_grace_secs", self.paused_grace_secs),
+            ("inactive_secs", self.inactive_secs),
+        ];
+        for (name, value) in non_negative {
+            if !(value.is_finite() && value >= 0.0) {
+                errors.push(format!("anti_cheat.{}: must be finite and >= 0", name));
+            }
         }
-        if self.cell_count == 0 {
-            return Err("cell_count must be > 0".to_string());
+        let positive = [
+            ("throttle_per_sec", self.throttle_per_sec),
+            ("own_balls_per_sec", self.own_balls_per_sec),
+        ];
+        for (name, value) in positive {
+            if !(value.is_finite() && value > 0.0) {
+                errors.push(format!("anti_cheat.{}: must be finite and > 0", name));
+            }
         }
-        if !self.max_velocity.is_finite() || self.max_velocity <= 0.0 {
-            return Err("max_velocity must be finite and > 0".to_string());
+        if self.own_ball_burst == 0 {
+            errors.push("anti_cheat.own_ball_burst: must be > 0".to_string());
         }
-        if self.max_connections == 0 {
-            return Err("max_connections must be > 0".to_string());
+        if self.entropy_window < 2 {
+            errors.push("anti_cheat.entropy_window: must be >= 2".to_string());
         }
-        if self.max_balls_global == 0 {
-            return Err("max_balls_global must be > 0".to_string());
+        errors
+    }
+}
+
+/// Config-file shape of `DeepSpaceConfig`: snake_case keys like the rest of
+/// the file (the wire format is camelCase), missing keys take defaults.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "DeepSpaceConfig", default, deny_unknown_fields)]
+struct DeepSpaceConfigDef {
+    portal_alpha: f64,
+    omega_min: f64,
+    omega_max: f64,
+    reroute_after: f64,
+    reroute_cooldown: f64,
+    min_age_for_capture: f64,
+    min_age_for_reroute: f64,
+    reroute_arrival_time_min: f64,
+    reroute_arrival_time_max: f64,
+}
+
+impl Default for DeepSpaceConfigDef {
+    fn default() -> Self {
+        let d = DeepSpaceConfig::default();
+        Self {
+            portal_alpha: d.portal_alpha,
+            omega_min: d.omega_min,
+            omega_max: d.omega_max,
+            reroute_after: d.reroute_after,
+            reroute_cooldown: d.reroute_cooldown,
+            min_age_for_capture: d.min_age_for_capture,
+            min_age_for_reroute: d.min_age_for_reroute,
+            reroute_arrival_time_min: d.reroute_arrival_time_min,
+            reroute_arrival_time_max: d.reroute_arrival_time_max,
         }
-        Ok(())
+    }
+}
+
+/// `Option<DeepSpaceConfig>` in config-file shape.
+mod opt_deep_space {
+    use super::{DeepSpaceConfig, DeepSpaceConfigDef};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrap(#[serde(with = "DeepSpaceConfigDef")] DeepSpaceConfig);
+
+    pub fn serialize<S: Serializer>(v: &Option<DeepSpaceConfig>, s: S) -> Result<S::Ok, S::Error> {
+        v.map(Wrap).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<DeepSpaceConfig>, D::Error> {
+        Ok(Option::<Wrap>::deserialize(d)?.map(|w| w.0))
     }
 }
 
@@ -77,36 +508,163 @@ mod tests {
 
     #[test]
     fn server_config_zero_tick_rate_invalid() {
-        let mut config = ServerConfig::default();
-        config.tick_rate_hz = 0;
+        let config = ServerConfig {
+            tick_rate_hz: 0,
+            ..Default::default()
+        };
         assert!(config.validate().is_err());
     }
 
     #[test]
     fn server_config_zero_broadcast_rate_invalid() {
-        let mut config = ServerConfig::default();
-        config.broadcast_rate_hz = 0;
+        let config = ServerConfig {
+            broadcast_rate_hz: 0,
+            ..Default::default()
+        };
         assert!(config.validate().is_err());
     }
 
     #[test]
     fn server_config_zero_cell_count_invalid() {
-        let mut config = ServerConfig::default();
-        config.cell_count = 0;
+        let config = ServerConfig {
+            cell_count: 0,
+            ..Default::default()
+        };
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn server_config_rng_seed_beyond_toml_range_invalid() {
+        let config = ServerConfig {
+            rng_seed: u64::MAX,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate().unwrap_err(),
+            vec!["rng_seed: must be <= 9223372036854775807"]
+        );
+        let config = ServerConfig {
+            rng_seed: i64::MAX as u64,
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+    }
+
     #[test]
     fn server_config_nan_max_velocity_invalid() {
-        let mut config = ServerConfig::default();
-        config.max_velocity = f64::NAN;
+        let config = ServerConfig {
+            max_velocity: f64::NAN,
+            ..Default::default()
+        };
         assert!(config.validate().is_err());
     }
 
     #[test]
     fn server_config_negative_max_velocity_invalid() {
+        let config = ServerConfig {
+            max_velocity: -1.0,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn server_config_non_positive_aoi_radius_invalid() {
+        for aoi_radius in [0.0, -0.5, f64::INFINITY] {
+            let config = ServerConfig {
+                aoi_radius,
+                ..Default::default()
+            };
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn server_config_empty_admin_token_invalid() {
+        let config = ServerConfig {
+            admin_token: Some("  ".to_string()),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn server_config_short_join_token_secret_invalid() {
+        let config = ServerConfig {
+            join_token_secret: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn server_config_bad_rate_limit_invalid() {
+        let mut config = ServerConfig::default();
+        config.rate_limits.ping.burst = 0;
+        config.rate_limits.emote.per_sec = f64::NAN;
+        assert_eq!(
+            config.validate().unwrap_err(),
+            vec![
+                "rate_limits.ping.burst: must be > 0",
+                "rate_limits.emote.per_sec: must be finite and > 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn server_config_bad_anti_cheat_invalid() {
+        let mut config = ServerConfig::default();
+        config.anti_cheat.forgive_per_sec = -1.0;
+        config.anti_cheat.throttle_per_sec = 0.0;
+        config.anti_cheat.entropy_window = 1;
+        assert_eq!(
+            config.validate().unwrap_err(),
+            vec![
+                "anti_cheat.forgive_per_sec: must be finite and >= 0",
+                "anti_cheat.throttle_per_sec: must be finite and > 0",
+                "anti_cheat.entropy_window: must be >= 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn room_overrides_apply_only_to_their_room() {
+        let mut config = ServerConfig::default();
+        config.rooms.insert(
+            "office".to_string(),
+            RoomConfig {
+                bot_count: Some(0),
+                max_connections: Some(12),
+                ..Default::default()
+            },
+        );
+        let (office, _) = config.for_room("office");
+        assert_eq!(office.bot_count, 0);
+        assert_eq!(office.max_connections, 12);
+        assert_eq!(office.cell_count, config.cell_count);
+
+        let (other, _) = config.for_room("other");
+        assert_eq!(other.bot_count, config.bot_count);
+        assert_eq!(other.max_connections, config.max_connections_per_room);
+    }
+
+    #[test]
+    fn invalid_room_override_is_reported_with_room_name() {
+        let mut config = ServerConfig::default();
+        config.rooms.insert(
+            "office".to_string(),
+            RoomConfig {
+                cell_count: Some(0),
+                ..Default::default()
+            },
+        );
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors, vec!["rooms.office.cell_count: must be > 0"]);
+
         let mut config = ServerConfig::default();
-        config.max_velocity = -1.0;
+        config
+            .rooms
+            .insert("bad name!".to_string(), RoomConfig::default());
         assert!(config.validate().is_err());
     }
 }
@@ -1,5 +1,7 @@
 use crate::config::DeepSpaceConfig;
+use crate::id_map::IdMap;
 use crate::player::Player;
+use crate::portal_index::PortalIndex;
 use crate::vec3::{
     angular_distance, arbitrary_orthogonal, build_tangent_basis, cross, dot,
     get_velocity_direction, length, map_2d_to_tangent, map_tangent_to_2d, normalize,
@@ -20,7 +22,15 @@ const DEFAULT_BALL_COLOR: u32 = 0x4da6a6;
 #[serde(rename_all = "camelCase")]
 pub struct SpaceBall3D {
     pub id: u32,
+    /// Player who sent the ball into deep space most recently
     pub owner_id: u32,
+    /// Player who first launched the ball. 0 in snapshots from before
+    /// lineage was tracked; `restore` fills in `owner_id`.
+    #[serde(default)]
+    pub origin_id: u32,
+    /// Boards the ball has passed through since it was first launched
+    #[serde(default, skip_serializing_if = "is_zero_hops")]
+    pub hops: u32,
     /// Current position on the unit sphere (always normalized)
     pub pos: Vec3,
     /// Rotation axis (unit vector perpendicular to the great circle)
@@ -34,14 +44,18 @@ pub struct SpaceBall3D {
     /// Seconds remaining before a new reroute can start
     pub reroute_cooldown: f64,
     /// Target axis for smooth reroute (None = no transition in progress)
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reroute_target_axis: Option<Vec3>,
     /// Progress of reroute transition (0.0 to 1.0)
-    #[serde(skip_serializing_if = "is_zero")]
+    #[serde(default, skip_serializing_if = "is_zero")]
     pub reroute_progress: f64,
     /// Target omega for smooth reroute
-    #[serde(skip_serializing_if = "is_zero")]
+    #[serde(default, skip_serializing_if = "is_zero")]
     pub reroute_target_omega: f64,
+    /// Player the owner gifted this ball to. Reroutes head for their portal
+    /// while they're eligible; cleared once they leave.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<u32>,
 }
 
 /// Serde skip predicate. Uses exact comparison because these fields are
@@ -50,14 +64,71 @@ fn is_zero(v: &f64) -> bool {
     *v == 0.0
 }
 
+fn is_zero_hops(v: &u32) -> bool {
+    *v == 0
+}
+
+/// Identity a ball keeps across boards: captured by a player and escaping
+/// their board again, it comes back with the same id and origin and one
+/// more hop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BallLineage {
+    pub id: u32,
+    pub origin_id: u32,
+    pub hops: u32,
+}
+
+impl BallLineage {
+    /// The same ball after passing through one more board.
+    pub fn next_hop(self) -> Self {
+        Self {
+            hops: self.hops.saturating_add(1),
+            ..self
+        }
+    }
+}
+
+/// 64-bit FNV-1a, for `SphereDeepSpace::checksum`. Hand-rolled because
+/// std's hashers are not guaranteed stable across releases.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write_u64(&mut self, v: u64) {
+        for byte in v.to_le_bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_f64(&mut self, v: f64) {
+        self.write_u64(v.to_bits());
+    }
+
+    fn write_vec3(&mut self, v: Vec3) {
+        self.write_f64(v.x);
+        self.write_f64(v.y);
+        self.write_f64(v.z);
+    }
+}
+
 /// Event when a ball enters a portal.
 /// Contains only the essential data: player ID and computed 2D velocity.
 #[derive(Debug, Clone)]
 pub struct CaptureEvent {
     pub ball_id: u32,
     pub player_id: u32,
-    /// Original owner of the ball (for color)
+    /// Player who last sent the ball out (for color)
     pub ball_owner_id: u32,
+    /// Player who first launched the ball
+    pub origin_id: u32,
+    /// Boards the ball had passed through before this capture
+    pub hops: u32,
     /// Color of the ball (from original owner)
     pub ball_color: u32,
     /// 2D velocity for TransferIn (pre-computed, no need for ball/player clones)
@@ -65,12 +136,42 @@ pub struct CaptureEvent {
     pub vy: f64,
 }
 
+impl CaptureEvent {
+    pub fn lineage(&self) -> BallLineage {
+        BallLineage {
+            id: self.ball_id,
+            origin_id: self.origin_id,
+            hops: self.hops,
+        }
+    }
+}
+
+/// Everything in `SphereDeepSpace` that outlives a restart. Players are
+/// not included: they reconnect and are re-synced with `set_players`.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeepSpaceSnapshot {
+    pub balls: Vec<SpaceBall3D>,
+    pub next_ball_id: u32,
+    /// Colors of every owner seen, so balls of players who never return
+    /// keep their color
+    pub owner_colors: HashMap<u32, u32>,
+}
+
 /// Sphere deep space simulation.
 pub struct SphereDeepSpace {
     config: DeepSpaceConfig,
     cos_portal_alpha: f64,
-    balls: HashMap<u32, SpaceBall3D>,
+    /// Iterated while drawing from the RNG, so the order must not
+    /// depend on anything but the ids inserted and removed
+    balls: IdMap<SpaceBall3D>,
     players: Vec<Player>,
+    /// Rebuilt with `players`: their portals, for capture checks
+    portal_index: PortalIndex,
+    /// Index into `players` by player id
+    player_slots: HashMap<u32, usize>,
+    /// Indices into `players` of those not paused, ascending
+    unpaused: Vec<usize>,
     owner_colors: HashMap<u32, u32>,
     next_ball_id: u32,
     capture_buffer: Vec<CaptureEvent>,
@@ -84,8 +185,11 @@ impl SphereDeepSpace {
         Self {
             config,
             cos_portal_alpha,
-            balls: HashMap::new(),
+            balls: IdMap::new(),
             players: Vec::new(),
+            portal_index: PortalIndex::new(&[], config.portal_alpha),
+            player_slots: HashMap::new(),
+            unpaused: Vec::new(),
             owner_colors: HashMap::new(),
             next_ball_id: 1,
             capture_buffer: Vec::new(),
@@ -97,6 +201,10 @@ impl SphereDeepSpace {
     pub fn set_players(&mut self, players: Vec<Player>) {
         self.owner_colors
             .extend(players.iter().map(|p| (p.id, p.color)));
+        let portals: Vec<Vec3> = players.iter().map(|p| p.portal_pos).collect();
+        self.portal_index = PortalIndex::new(&portals, self.config.portal_alpha);
+        self.player_slots = players.iter().enumerate().map(|(i, p)| (p.id, i)).collect();
+        self.unpaused = (0..players.len()).filter(|&i| !players[i].paused).collect();
         self.players = players;
     }
 
@@ -109,8 +217,33 @@ impl SphereDeepSpace {
         vy: f64,
         rng: &mut impl Rng,
     ) -> u32 {
-        let id = self.next_ball_id;
-        self.next_ball_id = self.next_ball_id.wrapping_add(1);
+        self.add_ball_with_lineage(owner_id, portal_pos, vx, vy, None, rng)
+    }
+
+    /// Like `add_ball`, but a ball with `lineage` keeps its id, origin and
+    /// hop count. Without one it is a new ball launched by `owner_id`. A
+    /// lineage id already in space gets a fresh id instead.
+    pub fn add_ball_with_lineage(
+        &mut self,
+        owner_id: u32,
+        portal_pos: Vec3,
+        vx: f64,
+        vy: f64,
+        lineage: Option<BallLineage>,
+        rng: &mut impl Rng,
+    ) -> u32 {
+        let lineage = lineage.unwrap_or(BallLineage {
+            id: 0,
+            origin_id: owner_id,
+            hops: 0,
+        });
+        let id = if lineage.id != 0 && !self.balls.contains_key(&lineage.id) {
+            lineage.id
+        } else {
+            let id = self.next_ball_id;
+            self.next_ball_id = self.next_ball_id.wrapping_add(1);
+            id
+        };
 
         let (e1, e2) = build_tangent_basis(portal_pos);
         let tangent = map_2d_to_tangent(vx, vy, e1, e2);
@@ -136,6 +269,8 @@ impl SphereDeepSpace {
         let ball = SpaceBall3D {
             id,
             owner_id,
+            origin_id: lineage.origin_id,
+            hops: lineage.hops,
             pos,
             axis,
             omega,
@@ -145,6 +280,7 @@ impl SphereDeepSpace {
             reroute_target_axis: None,
             reroute_progress: 0.0,
             reroute_target_omega: 0.0,
+            target_id: None,
         };
 
         self.balls.insert(id, ball);
@@ -167,6 +303,18 @@ impl SphereDeepSpace {
         self.balls.get(&id)
     }
 
+    /// Gift a ball to `target_id` (or un-gift it with `None`). Returns false
+    /// if the ball doesn't exist.
+    pub fn set_ball_target(&mut self, ball_id: u32, target_id: Option<u32>) -> bool {
+        match self.balls.get_mut(&ball_id) {
+            Some(ball) => {
+                ball.target_id = target_id;
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Get a mutable reference to a specific ball (test-only).
     #[cfg(test)]
     pub fn get_ball_mut(&mut self, id: u32) -> Option<&mut SpaceBall3D> {
@@ -190,6 +338,9 @@ impl SphereDeepSpace {
         let arrival_time_max = self.config.reroute_arrival_time_max;
         let capture_speed = self.capture_speed;
         let players = &self.players;
+        let portal_index = &self.portal_index;
+        let player_slots = &self.player_slots;
+        let unpaused = &self.unpaused;
 
         for ball in self.balls.values_mut() {
             // Update position in-place
@@ -203,10 +354,15 @@ impl SphereDeepSpace {
             // Check portal hits (only if old enough)
             // Select portal with highest dot product to avoid bias toward first player
             // Skip paused players - they don't capture balls
+            // Only portals near the ball can reach it; the index lists them in player order
             let mut captured = false;
             if ball.age >= min_age {
                 let mut best_match: Option<(&Player, f64)> = None;
-                for player in players {
+                for player in portal_index
+                    .candidates(ball.pos)
+                    .iter()
+                    .map(|&i| &players[i])
+                {
                     // Skip paused players
                     if player.paused {
                         continue;
@@ -217,10 +373,8 @@ impl SphereDeepSpace {
                     }
                     let p = player.portal_pos;
                     let d = ball.pos.x * p.x + ball.pos.y * p.y + ball.pos.z * p.z;
-                    if d >= cos_portal_alpha {
-                        if best_match.map_or(true, |(_, best_d)| d > best_d) {
-                            best_match = Some((player, d));
-                        }
+                    if d >= cos_portal_alpha && best_match.is_none_or(|(_, best_d)| d > best_d) {
+                        best_match = Some((player, d));
                     }
                 }
                 if let Some((player, _)) = best_match {
@@ -238,10 +392,9 @@ impl SphereDeepSpace {
                     };
 
                     // Find original owner's color
-                    let ball_color = players
-                        .iter()
-                        .find(|p| p.id == ball.owner_id)
-                        .map(|p| p.color)
+                    let ball_color = player_slots
+                        .get(&ball.owner_id)
+                        .map(|&i| players[i].color)
                         .or_else(|| self.owner_colors.get(&ball.owner_id).copied())
                         .unwrap_or(DEFAULT_BALL_COLOR);
 
@@ -249,6 +402,8 @@ impl SphereDeepSpace {
                         ball_id: ball.id,
                         player_id: player.id,
                         ball_owner_id: ball.owner_id,
+                        origin_id: ball.origin_id,
+                        hops: ball.hops,
                         ball_color,
                         vx,
                         vy,
@@ -293,19 +448,32 @@ impl SphereDeepSpace {
                 && ball.reroute_cooldown <= 0.0
                 && !players.is_empty()
             {
-                // Reservoir-sample one eligible target (no temporary Vec allocation).
+                // Pick one eligible target uniformly from the unpaused players.
                 // Rules match capture: skip paused players and bots targeting own balls.
-                let mut target_idx: Option<usize> = None;
-                let mut eligible_count: usize = 0;
-                for (idx, player) in players.iter().enumerate() {
-                    if player.paused || (player.is_bot && player.id == ball.owner_id) {
-                        continue;
-                    }
-                    eligible_count += 1;
-                    if rng.gen_range(0..eligible_count) == 0 {
-                        target_idx = Some(idx);
-                    }
+                // A gifted ball heads for its recipient instead while they're
+                // eligible; if they've left, the gift is dropped.
+                let owner_slot = player_slots
+                    .get(&ball.owner_id)
+                    .copied()
+                    .filter(|&i| players[i].is_bot && !players[i].paused);
+                let eligible = |i: usize| !players[i].paused && Some(i) != owner_slot;
+                let gift_slot = ball.target_id.and_then(|id| player_slots.get(&id).copied());
+                if gift_slot.is_none() {
+                    ball.target_id = None;
                 }
+                let target_idx = gift_slot.filter(|&i| eligible(i)).or_else(|| {
+                    // The excluded bot owner, if any, is skipped over
+                    let skipped = owner_slot.and_then(|o| unpaused.binary_search(&o).ok());
+                    let eligible_count = unpaused.len() - skipped.is_some() as usize;
+                    (eligible_count > 0).then(|| {
+                        let k = rng.gen_range(0..eligible_count);
+                        unpaused[if skipped.is_some_and(|s| k >= s) {
+                            k + 1
+                        } else {
+                            k
+                        }]
+                    })
+                });
                 let Some(target_idx) = target_idx else {
                     ball.reroute_cooldown = reroute_cd;
                     continue;
@@ -360,6 +528,90 @@ impl SphereDeepSpace {
     pub fn ball_count(&self) -> usize {
         self.balls.len()
     }
+
+    /// Remove every ball. Returns how many were removed.
+    pub fn clear_balls(&mut self) -> usize {
+        let count = self.balls.len();
+        self.balls.clear();
+        count
+    }
+
+    /// Capture balls, ball ids and owner colors for persisting.
+    pub fn snapshot(&self) -> DeepSpaceSnapshot {
+        let mut balls: Vec<SpaceBall3D> = self.balls.values().cloned().collect();
+        balls.sort_by_key(|b| b.id);
+        DeepSpaceSnapshot {
+            balls,
+            next_ball_id: self.next_ball_id,
+            owner_colors: self.owner_colors.clone(),
+        }
+    }
+
+    /// FNV-1a hash of every ball's full state, in id order, plus the next
+    /// ball id. Equal across runs exactly when the simulations agree
+    /// bit for bit (see `record.rs`).
+    pub fn checksum(&self) -> u64 {
+        let mut balls: Vec<&SpaceBall3D> = self.balls.values().collect();
+        balls.sort_unstable_by_key(|b| b.id);
+
+        let mut hash = Fnv1a::default();
+        hash.write_u64(self.next_ball_id as u64);
+        for b in balls {
+            hash.write_u64(b.id as u64);
+            hash.write_u64(b.owner_id as u64);
+            hash.write_u64(b.origin_id as u64);
+            hash.write_u64(b.hops as u64);
+            for v in [b.pos, b.axis] {
+                hash.write_vec3(v);
+            }
+            for f in [b.omega, b.age, b.time_since_hit, b.reroute_cooldown] {
+                hash.write_f64(f);
+            }
+            match b.reroute_target_axis {
+                Some(axis) => hash.write_vec3(axis),
+                None => hash.write_u64(u64::MAX),
+            }
+            hash.write_f64(b.reroute_progress);
+            hash.write_f64(b.reroute_target_omega);
+            hash.write_u64(b.target_id.map_or(u64::MAX, u64::from));
+        }
+        hash.0
+    }
+
+    /// Replace all balls with those in `snapshot`, keeping at most
+    /// `max_balls`. Balls with a non-finite or degenerate position or axis
+    /// are dropped. Owner colors are merged; current players keep theirs.
+    /// Returns how many balls were restored.
+    pub fn restore(&mut self, snapshot: DeepSpaceSnapshot, max_balls: usize) -> usize {
+        self.balls.clear();
+        for mut ball in snapshot.balls {
+            if self.balls.len() >= max_balls {
+                break;
+            }
+            if !is_unit(ball.pos) || !is_unit(ball.axis) || !ball.omega.is_finite() {
+                continue;
+            }
+            if ball.origin_id == 0 {
+                ball.origin_id = ball.owner_id;
+            }
+            self.balls.insert(ball.id, ball);
+        }
+        let next_free = self.balls.keys().max().map_or(1, |id| id.wrapping_add(1));
+        self.next_ball_id = snapshot.next_ball_id.max(next_free);
+        for (owner, color) in snapshot.owner_colors {
+            self.owner_colors.entry(owner).or_insert(color);
+        }
+        for player in &self.players {
+            self.owner_colors.insert(player.id, player.color);
+        }
+        self.balls.len()
+    }
+}
+
+/// Whether `v` is finite and (roughly) on the unit sphere.
+fn is_unit(v: Vec3) -> bool {
+    let len = length(v);
+    len.is_finite() && (len - 1.0).abs() < 1e-3
 }
 
 #[cfg(test)]
@@ -368,6 +620,7 @@ mod tests {
     use crate::vec3::vec3;
     use rand::SeedableRng;
     use rand_chacha::ChaCha8Rng;
+    use std::collections::HashSet;
 
     fn test_config() -> DeepSpaceConfig {
         DeepSpaceConfig {
@@ -397,6 +650,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: false,
+                name: String::new(),
                 last_activity: 0.0,
             },
             Player {
@@ -407,6 +661,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: false,
+                name: String::new(),
                 last_activity: 0.0,
             },
             Player {
@@ -417,6 +672,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: false,
+                name: String::new(),
                 last_activity: 0.0,
             },
             Player {
@@ -427,6 +683,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: false,
+                name: String::new(),
                 last_activity: 0.0,
             },
         ]
@@ -449,6 +706,32 @@ mod tests {
         assert_eq!(ds.get_ball(id).unwrap().owner_id, 1);
     }
 
+    #[test]
+    fn add_ball_with_lineage_keeps_identity() {
+        let (mut ds, mut rng) = setup();
+        let fresh = ds.add_ball(1, vec3(1.0, 0.0, 0.0), 1.0, 0.0, &mut rng);
+        let ball = ds.get_ball(fresh).unwrap();
+        assert_eq!((ball.origin_id, ball.hops), (1, 0));
+
+        let lineage = BallLineage {
+            id: 40,
+            origin_id: 3,
+            hops: 2,
+        };
+        let id =
+            ds.add_ball_with_lineage(1, vec3(1.0, 0.0, 0.0), 1.0, 0.0, Some(lineage), &mut rng);
+        assert_eq!(id, 40);
+        let ball = ds.get_ball(id).unwrap();
+        assert_eq!((ball.owner_id, ball.origin_id, ball.hops), (1, 3, 2));
+
+        // An id already in space is never reused
+        let dup =
+            ds.add_ball_with_lineage(1, vec3(1.0, 0.0, 0.0), 1.0, 0.0, Some(lineage), &mut rng);
+        assert_ne!(dup, 40);
+        assert_eq!(ds.get_ball(dup).unwrap().origin_id, 3);
+        assert_eq!(ds.ball_count(), 3);
+    }
+
     #[test]
     fn add_ball_pos_is_unit() {
         let (mut ds, mut rng) = setup();
@@ -585,6 +868,14 @@ mod tests {
         assert_eq!(captures[0].ball_id, id);
         assert_eq!(captures[0].player_id, 3);
         assert_eq!(captures[0].ball_color, 0xff0000);
+        assert_eq!(
+            captures[0].lineage(),
+            BallLineage {
+                id,
+                origin_id: 1,
+                hops: 0
+            }
+        );
     }
 
     #[test]
@@ -686,6 +977,34 @@ mod tests {
         assert!(ball.reroute_progress > 0.0, "Progress should have advanced");
     }
 
+    #[test]
+    fn reroute_skips_owning_bot_and_paused_players() {
+        let mut ds = SphereDeepSpace::new(test_config(), TEST_CAPTURE_SPEED);
+        let mut players = create_test_players();
+        players[0].is_bot = true; // owns the balls, at x=1
+        players[2].paused = true; // at z=1
+        ds.set_players(players);
+        let mut rng = test_rng();
+
+        let mut targets = HashSet::new();
+        for _ in 0..50 {
+            let id = ds.add_ball(1, vec3(1.0, 0.0, 0.0), 1.0, 0.0, &mut rng);
+            let ball = ds.get_ball_mut(id).unwrap();
+            ball.age = test_config().reroute_after + 1.0;
+            ball.time_since_hit = test_config().reroute_after + 1.0;
+            ball.reroute_cooldown = 0.0;
+            ball.pos = normalize(vec3(0.0, -1.0, -1.0));
+            ds.tick(0.001, &mut rng);
+            let axis = ds.get_ball(id).unwrap().reroute_target_axis.unwrap();
+            // Heading for player 2 (y=1) or player 4 (x=-1); the owner
+            // would give -y and the paused player -x
+            let target = if axis.x > 0.9 { 2 } else { 4 };
+            assert!(axis.x > 0.9 || axis.y > 0.6, "{:?}", axis);
+            targets.insert(target);
+        }
+        assert_eq!(targets, HashSet::from([2, 4]));
+    }
+
     #[test]
     fn reroute_sets_cooldown() {
         let (mut ds, mut rng) = setup();
@@ -716,6 +1035,75 @@ mod tests {
         assert!(ds.get_ball(id).unwrap().time_since_hit < 1.0);
     }
 
+    fn ready_to_reroute(ds: &mut SphereDeepSpace, id: u32, target_id: Option<u32>) {
+        assert!(ds.set_ball_target(id, target_id));
+        let ball = ds.get_ball_mut(id).unwrap();
+        ball.pos = normalize(vec3(1.0, 1.0, 1.0));
+        ball.age = test_config().reroute_after + 1.0;
+        ball.time_since_hit = test_config().reroute_after + 1.0;
+        ball.reroute_cooldown = 0.0;
+    }
+
+    #[test]
+    fn gifted_ball_reroutes_toward_target() {
+        let expected = normalize(cross(normalize(vec3(1.0, 1.0, 1.0)), vec3(-1.0, 0.0, 0.0)));
+        for seed in 0..20 {
+            let mut ds = SphereDeepSpace::new(test_config(), TEST_CAPTURE_SPEED);
+            ds.set_players(create_test_players());
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let id = ds.add_ball(1, vec3(1.0, 0.0, 0.0), 1.0, 0.0, &mut rng);
+            ready_to_reroute(&mut ds, id, Some(4));
+
+            ds.tick(0.01, &mut rng);
+            let axis = ds.get_ball(id).unwrap().reroute_target_axis.unwrap();
+            assert!(
+                dot(axis, expected) > 0.999,
+                "seed {} picked another portal",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn gift_to_paused_player_falls_back_but_is_kept() {
+        let mut ds = SphereDeepSpace::new(test_config(), TEST_CAPTURE_SPEED);
+        let mut players = create_test_players();
+        players[3].paused = true;
+        ds.set_players(players);
+        let mut rng = test_rng();
+        let id = ds.add_ball(1, vec3(1.0, 0.0, 0.0), 1.0, 0.0, &mut rng);
+        ready_to_reroute(&mut ds, id, Some(4));
+
+        ds.tick(0.01, &mut rng);
+        let ball = ds.get_ball(id).unwrap();
+        assert!(
+            ball.reroute_target_axis.is_some(),
+            "normal reroute still happens"
+        );
+        assert_eq!(ball.target_id, Some(4), "gift survives a pause");
+    }
+
+    #[test]
+    fn gift_is_dropped_when_target_leaves() {
+        let (mut ds, mut rng) = setup();
+        let id = ds.add_ball(1, vec3(1.0, 0.0, 0.0), 1.0, 0.0, &mut rng);
+        let mut players = create_test_players();
+        players.pop();
+        ds.set_players(players);
+        ready_to_reroute(&mut ds, id, Some(4));
+
+        ds.tick(0.01, &mut rng);
+        let ball = ds.get_ball(id).unwrap();
+        assert!(ball.reroute_target_axis.is_some());
+        assert_eq!(ball.target_id, None);
+    }
+
+    #[test]
+    fn set_ball_target_on_missing_ball() {
+        let (mut ds, _) = setup();
+        assert!(!ds.set_ball_target(99, Some(2)));
+    }
+
     // --- getBalls ---
 
     #[test]
@@ -775,6 +1163,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: false,
+                name: String::new(),
                 last_activity: 0.0,
             },
             Player {
@@ -785,6 +1174,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: false,
+                name: String::new(),
                 last_activity: 0.0,
             },
         ]);
@@ -962,6 +1352,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: false,
+                name: String::new(),
                 last_activity: 0.0,
             },
             Player {
@@ -972,6 +1363,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: false,
+                name: String::new(),
                 last_activity: 0.0,
             },
         ]);
@@ -1028,6 +1420,7 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 is_bot: false,
+                name: String::new(),
                 last_activity: 0.0,
             });
         }
@@ -1071,4 +1464,162 @@ mod tests {
         assert_eq!(ds.ball_count() + total_captures, 200);
         assert!(elapsed.as_millis() < 5000, "Took too long: {:?}", elapsed);
     }
+
+    #[test]
+    fn indexed_capture_matches_full_scan() {
+        let config = DeepSpaceConfig {
+            portal_alpha: 0.3,
+            min_age_for_capture: 0.0,
+            ..test_config()
+        };
+        let mut ds = SphereDeepSpace::new(config, TEST_CAPTURE_SPEED);
+        let mut rng = test_rng();
+
+        // Overlapping caps, some portals shared (exact ties), paused players and bots
+        let mut portals = crate::sphere::fibonacci_sphere(200);
+        portals.extend_from_slice(&portals.clone()[..20]);
+        let players: Vec<Player> = portals
+            .iter()
+            .enumerate()
+            .map(|(i, &portal_pos)| Player {
+                id: i as u32 + 1,
+                cell_index: i as u32,
+                portal_pos,
+                color: 0xffffff,
+                paused: i % 7 == 3,
+                balls_produced: 0,
+                is_bot: i % 5 == 0,
+                name: String::new(),
+                last_activity: 0.0,
+            })
+            .collect();
+        ds.set_players(players.clone());
+
+        for (k, pos) in crate::sphere::fibonacci_sphere(3000)
+            .into_iter()
+            .enumerate()
+        {
+            let owner = &players[k % players.len()];
+            let id = ds.add_ball(owner.id, owner.portal_pos, 0.3, 1.0, &mut rng);
+            ds.get_ball_mut(id).unwrap().pos = pos;
+        }
+
+        // What the scan over every player picked before the index existed
+        let dt = 0.01;
+        let expected: HashMap<u32, u32> = ds
+            .get_ball_iter()
+            .filter_map(|ball| {
+                let mut pos = ball.pos;
+                rotate_normalize_in_place(&mut pos, ball.axis, ball.omega * dt);
+                let mut best: Option<(u32, f64)> = None;
+                for p in &players {
+                    if p.paused || (p.is_bot && p.id == ball.owner_id) {
+                        continue;
+                    }
+                    let d = dot(pos, p.portal_pos);
+                    if d >= config.portal_alpha.cos() && best.is_none_or(|(_, bd)| d > bd) {
+                        best = Some((p.id, d));
+                    }
+                }
+                best.map(|(id, _)| (ball.id, id))
+            })
+            .collect();
+
+        let captured: HashMap<u32, u32> = ds
+            .tick(dt, &mut rng)
+            .into_iter()
+            .map(|cap| (cap.ball_id, cap.player_id))
+            .collect();
+        assert!(expected.len() > 1000);
+        assert_eq!(captured, expected);
+    }
+
+    #[test]
+    fn snapshot_restore_round_trip() {
+        let mut rng = test_rng();
+        let mut ds = SphereDeepSpace::new(test_config(), 1.5);
+        ds.set_players(create_test_players());
+        for _ in 0..5 {
+            ds.add_ball(1, vec3(1.0, 0.0, 0.0), 0.3, 1.0, &mut rng);
+        }
+        ds.add_ball(2, vec3(0.0, 1.0, 0.0), -0.5, 1.0, &mut rng);
+        for _ in 0..30 {
+            ds.tick(1.0 / 60.0, &mut rng);
+        }
+
+        let json = serde_json::to_string(&ds.snapshot()).unwrap();
+        let snapshot: DeepSpaceSnapshot = serde_json::from_str(&json).unwrap();
+
+        // Restored into a world where nobody has joined yet
+        let mut restored = SphereDeepSpace::new(test_config(), 1.5);
+        assert_eq!(restored.restore(snapshot, 100), 6);
+        for ball in ds.get_ball_iter() {
+            let other = restored.get_ball(ball.id).unwrap();
+            assert_eq!(other.pos, ball.pos);
+            assert_eq!(other.axis, ball.axis);
+            assert_eq!(other.age, ball.age);
+        }
+        let id = restored.add_ball(3, vec3(0.0, 0.0, 1.0), 0.0, 1.0, &mut rng);
+        assert_eq!(id, 7);
+
+        // Departed owner's color survives until the ball is captured
+        restored.set_players(vec![create_test_players().remove(2)]);
+        let ball = restored.get_ball_mut(1).unwrap();
+        ball.pos = vec3(0.0, 0.0, 1.0);
+        ball.age = 100.0;
+        let captures = restored.tick(0.0, &mut rng);
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0].ball_color, 0xff0000);
+    }
+
+    #[test]
+    fn restore_drops_invalid_balls_and_caps_count() {
+        let mut rng = test_rng();
+        let mut ds = SphereDeepSpace::new(test_config(), 1.5);
+        for _ in 0..4 {
+            ds.add_ball(1, vec3(1.0, 0.0, 0.0), 0.3, 1.0, &mut rng);
+        }
+        let mut snapshot = ds.snapshot();
+        snapshot.balls[0].pos = vec3(f64::NAN, 0.0, 0.0);
+        snapshot.balls[1].axis = vec3(0.0, 0.0, 0.0);
+
+        let mut restored = SphereDeepSpace::new(test_config(), 1.5);
+        assert_eq!(restored.restore(snapshot.clone(), 100), 2);
+        assert_eq!(restored.restore(snapshot, 1), 1);
+        assert_eq!(restored.get_ball(3).map(|b| b.id), Some(3));
+    }
+
+    #[test]
+    fn restore_fills_origin_of_old_snapshots() {
+        let (mut ds, mut rng) = setup();
+        let id = ds.add_ball(2, vec3(1.0, 0.0, 0.0), 0.3, 1.0, &mut rng);
+        let mut json = serde_json::to_value(ds.snapshot()).unwrap();
+        json["balls"][0].as_object_mut().unwrap().remove("originId");
+        let snapshot: DeepSpaceSnapshot = serde_json::from_value(json).unwrap();
+
+        let mut restored = SphereDeepSpace::new(test_config(), 1.5);
+        restored.restore(snapshot, 100);
+        let ball = restored.get_ball(id).unwrap();
+        assert_eq!((ball.origin_id, ball.hops), (2, 0));
+    }
+
+    #[test]
+    fn checksum_tracks_ball_state_not_insertion_order() {
+        let (mut a, mut rng) = setup();
+        let mut b = SphereDeepSpace::new(test_config(), 1.5);
+        assert_eq!(a.checksum(), b.checksum());
+
+        a.add_ball(1, vec3(1.0, 0.0, 0.0), 1.0, 0.0, &mut rng);
+        a.add_ball(2, vec3(0.0, 1.0, 0.0), 0.0, 1.0, &mut rng);
+        assert_ne!(a.checksum(), b.checksum());
+
+        // Same balls restored in the opposite order hash the same
+        let mut snapshot = a.snapshot();
+        snapshot.balls.reverse();
+        b.restore(snapshot, 100);
+        assert_eq!(a.checksum(), b.checksum());
+
+        a.tick(0.1, &mut rng);
+        assert_ne!(a.checksum(), b.checksum());
+    }
 }
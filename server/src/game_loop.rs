@@ -1,21 +1,47 @@
+use crate::admin::{self, AdminBall, AdminPlayer};
 use crate::config::{DeepSpaceConfig, ServerConfig};
-use crate::protocol::{ServerMsg, WelcomeMsg, PROTOCOL_VERSION};
+use crate::interest::{
+    BucketGrid, Interest, SpaceStateFragments, INTEREST_BUCKETS, INTEREST_MARGIN,
+};
+use crate::lifecycle::Heartbeat;
+use crate::metrics::RoomMetrics;
+use crate::persist::SnapshotFile;
+use crate::protocol::{EmoteKind, EmoteMsg, WelcomeMsg, PROTOCOL_VERSION};
+use crate::record::{self, Command, Recorder};
+use crate::serializer::BroadcastSerializer;
 use crate::state::GameState;
 use axum::extract::ws::Utf8Bytes;
 use std::collections::HashMap;
-use std::time::Duration;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{broadcast, mpsc, oneshot};
 
 /// Speed at which captured balls enter the board (m/s).
 /// This is passed to SphereDeepSpace so it can compute vx/vy at capture time.
 const CAPTURE_SPEED: f64 = 1.5;
+/// Seconds between `leaderboard` broadcasts
+const LEADERBOARD_INTERVAL_SECS: u32 = 2;
 
 /// Commands from client connections to the game loop
 pub enum GameCommand {
     PlayerJoin {
-        response: oneshot::Sender<Result<(u32, WelcomeMsg), String>>,
+        /// Player id, welcome message and the player's space_state area of interest
+        response: oneshot::Sender<Result<(u32, WelcomeMsg, Interest), String>>,
         /// Channel for reliable per-client messages (e.g., TransferIn)
         client_tx: mpsc::Sender<ClientEvent>,
+        /// Token from the client's `hello`, if any
+        resume_token: Option<String>,
+        /// Display name from the client's `hello`, if any
+        name: Option<String>,
+    },
+    /// A spectator joined. Replies with self id 0, a welcome and the
+    /// whole sphere as its area of interest; spectators never leave
+    /// explicitly since the game loop keeps no state for them.
+    SpectatorJoin {
+        response: oneshot::Sender<Result<(u32, WelcomeMsg, Interest), String>>,
     },
     PlayerLeave {
         id: u32,
@@ -24,6 +50,10 @@ pub enum GameCommand {
         owner_id: u32,
         vx: f64,
         vy: f64,
+        /// Player the ball is gifted to, if any
+        target_id: Option<u32>,
+        /// Id from the `transfer_in` this ball arrived with, if any
+        ball_id: Option<u32>,
     },
     SetPaused {
         player_id: u32,
@@ -32,10 +62,64 @@ pub enum GameCommand {
     Activity {
         player_id: u32,
     },
+    /// Relay an emote to everyone in the room
+    Emote {
+        player_id: u32,
+        kind: EmoteKind,
+    },
+    /// Bumper hits to credit, already capped per second by the connection
+    BumperHits {
+        player_id: u32,
+        count: u32,
+    },
+    /// Unvalidated display name from `set_name`
+    SetName {
+        player_id: u32,
+        name: String,
+    },
+    /// A client has the ball from its `transfer_in` with this `seq`
+    TransferAck {
+        player_id: u32,
+        seq: u32,
+    },
+    /// A client lost track of the space_state delta chain
+    RequestKeyframe,
+    /// Admin: snapshot of all players
+    AdminListPlayers {
+        response: oneshot::Sender<Vec<AdminPlayer>>,
+    },
+    /// Admin: disconnect a connected player. Replies false if not connected.
+    AdminKick {
+        player_id: u32,
+        response: oneshot::Sender<bool>,
+    },
+    /// Admin: add a bot. Replies with its id, or None if no cell is free.
+    AdminAddBot {
+        response: oneshot::Sender<Option<u32>>,
+    },
+    /// Admin: remove a bot. Replies false if `player_id` is not a bot.
+    AdminRemoveBot {
+        player_id: u32,
+        response: oneshot::Sender<bool>,
+    },
+    /// Admin: snapshot of all deep-space balls
+    AdminListBalls {
+        response: oneshot::Sender<Vec<AdminBall>>,
+    },
+    /// Admin: remove every ball from deep space. Replies with the count.
+    AdminClearBalls {
+        response: oneshot::Sender<usize>,
+    },
+    /// Write the room's snapshot now (shutdown). Replies once it is on
+    /// disk, or immediately if persistence is disabled.
+    SaveSnapshot {
+        response: oneshot::Sender<()>,
+    },
 }
 
 /// Per-client events sent via dedicated mpsc channel.
-/// If a client's channel is full, the client is marked dead and removed.
+/// If a client's channel is full, the client is marked dead and removed
+/// (its unacked transfers go back to deep space).
 #[derive(Debug, Clone)]
 pub enum ClientEvent {
     TransferIn {
@@ -43,6 +127,11 @@ pub enum ClientEvent {
         vy: f64,
         owner_id: u32,
         color: u32,
+        /// Transfer sequence id the client acks with `transfer_ack`
+        seq: u32,
+        ball_id: u32,
+        origin_owner_id: u32,
+        hops: u32,
     },
     /// Server-initiated disconnect (client will receive this and close)
     Disconnect,
@@ -51,12 +140,21 @@ pub enum ClientEvent {
 /// Broadcasts from game loop to all clients (lossy - ok to drop on lag)
 /// Uses Utf8Bytes for pre-serialized JSON - O(1) clone, no allocation per client
 /// UTF-8 validation happens once in game_loop, not per client
+/// space_state is pre-serialized per bucket; each client assembles the
+/// buckets in its area of interest, in the encoding it negotiated
 #[derive(Debug, Clone)]
 pub enum GameBroadcast {
-    /// Pre-serialized JSON for space_state
-    SpaceState(Utf8Bytes),
+    /// Per-bucket JSON and packed fragments for space_state
+    SpaceState(Arc<SpaceStateFragments>),
+    /// Keyframe for clients that need a baseline. Sent just before the
+    /// shared space_state with the same seq, which those clients skip.
+    SpaceStateKeyframe(Arc<SpaceStateFragments>),
     /// Pre-serialized JSON for players_state
     PlayersState(Utf8Bytes),
+    /// Pre-serialized JSON for an emote
+    Emote(Utf8Bytes),
+    /// Pre-serialized JSON for leaderboard
+    Leaderboard(Utf8Bytes),
 }
 
 /// Run the main game loop. Owns all game state.
@@ -70,18 +168,45 @@ pub async fn run_game_loop(
         broadcast_tx,
         server_config,
         DeepSpaceConfig::default(),
+        Arc::new(RoomMetrics::default()),
+        Arc::new(Heartbeat::default()),
+        None,
+        None,
     )
     .await;
 }
 
-/// Game loop with custom deep space config (used by integration tests).
+/// Game loop with custom deep space config, recording into `metrics` and
+/// beating `heartbeat` (and publishing the game clock to it) every tick.
+/// With a `snapshot` file, deep space is restored from it on start and
+/// written back on stop (see `persist.rs`). With a `record_path`, every
+/// state-changing command and tick is recorded there (see `record.rs`).
+#[allow(clippy::too_many_arguments)]
 pub async fn run_game_loop_with_config(
     mut cmd_rx: mpsc::Receiver<GameCommand>,
     broadcast_tx: broadcast::Sender<GameBroadcast>,
     server_config: ServerConfig,
     deep_space_config: DeepSpaceConfig,
+    metrics: Arc<RoomMetrics>,
+    heartbeat: Arc<Heartbeat>,
+    snapshot: Option<Arc<SnapshotFile>>,
+    record_path: Option<PathBuf>,
 ) {
     let mut state = GameState::new(&server_config, deep_space_config, CAPTURE_SPEED);
+    // Held until the final write, so a reopened room restores what we save
+    let _snapshot_claim = match &snapshot {
+        Some(file) => {
+            let claim = file.claim().await;
+            restore_snapshot(&mut state, file);
+            Some(claim)
+        }
+        None => None,
+    };
+    let mut recorder = match &record_path {
+        Some(path) => start_recording(&state, &server_config, path),
+        None => Recorder::off(),
+    };
+    let snapshot_every_n = server_config.snapshot_interval_secs * server_config.tick_rate_hz as u64;
 
     // Per-client channels for reliable messages (TransferIn)
     let mut client_channels: HashMap<u32, mpsc::Sender<ClientEvent>> = HashMap::new();
@@ -90,9 +215,16 @@ pub async fn run_game_loop_with_config(
     let broadcast_every_n = (server_config.tick_rate_hz / server_config.broadcast_rate_hz).max(1);
     // Players state broadcasts at 2 Hz for stats updates (much lower than space_state)
     let players_broadcast_every_n = (server_config.tick_rate_hz / 2).max(1);
+    let leaderboard_every_n = (server_config.tick_rate_hz * LEADERBOARD_INTERVAL_SECS).max(1);
     let mut tick_count: u64 = 0;
     // Dirty flag for immediate players_state broadcast on join/leave/pause
     let mut players_dirty = false;
+    // Balls are bucketed so each client only gets those near its portal
+    let interest_grid = BucketGrid::new(INTEREST_BUCKETS);
+    // Broadcasts are encoded and serialized off the game loop
+    let mut serializer =
+        BroadcastSerializer::spawn(broadcast_tx.clone(), interest_grid.clone(), metrics.clone());
+    let interest_radius = server_config.aoi_radius + INTEREST_MARGIN;
 
     let mut tick_interval = tokio::time::interval(tick_duration);
     // Skip missed ticks rather than bursting to catch up. Under load the
@@ -100,113 +232,310 @@ pub async fn run_game_loop_with_config(
     // a burst of catch-up ticks. This keeps frame timing smooth at the cost
     // of briefly running slower than real-time.
     tick_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    // Skipped ticks show up as gaps between scheduled tick instants
+    let mut last_scheduled: Option<tokio::time::Instant> = None;
 
     loop {
         tokio::select! {
-            _ = tick_interval.tick() => {
+            scheduled = tick_interval.tick() => {
+                let tick_start = Instant::now();
+                heartbeat.beat();
+                if let Some(last) = last_scheduled {
+                    let periods = (scheduled - last).as_secs_f64() / tick_duration.as_secs_f64();
+                    let missed = periods.round() as u64;
+                    if missed > 1 {
+                        metrics.missed_ticks.add(missed - 1);
+                    }
+                }
+                last_scheduled = Some(scheduled);
+
                 let dt = 1.0 / server_config.tick_rate_hz as f64;
                 let captures = state.tick(dt);
+                tick_count += 1;
+                heartbeat.set_server_time(state.elapsed());
 
                 // Send transfer_in for each capture via dedicated client channel
                 // vx/vy are pre-computed in deep_space - no cloning needed
-                // If channel is full, mark client as dead (will be cleaned up)
+                // Each transfer stays pending until acked; if the channel is full
+                // the client is marked dead and the ball returns to deep space
                 let mut dead_clients: Vec<u32> = Vec::new();
                 for cap in &captures {
-                    if let Some(client_tx) = client_channels.get(&cap.player_id) {
-                        if client_tx.try_send(ClientEvent::TransferIn {
+                    let seq = state.begin_transfer(cap);
+                    let sent = client_channels.get(&cap.player_id).is_some_and(|client_tx| {
+                        client_tx.try_send(ClientEvent::TransferIn {
                             vx: cap.vx,
                             vy: cap.vy,
                             owner_id: cap.ball_owner_id,
                             color: cap.ball_color,
-                        }).is_err() {
-                            tracing::warn!("Player {} channel full, marking as dead", cap.player_id);
-                            dead_clients.push(cap.player_id);
-                        }
+                            seq,
+                            ball_id: cap.ball_id,
+                            origin_owner_id: cap.origin_id,
+                            hops: cap.hops,
+                        }).is_ok()
+                    });
+                    if !sent {
+                        tracing::warn!("Player {} channel full or closed, marking as dead", cap.player_id);
+                        dead_clients.push(cap.player_id);
                     }
                 }
+                recorder.tick(tick_count, state.deep_space.checksum());
+                // Clients sitting on an unacked transfer are presumed dead too
+                for id in state.overdue_transfers() {
+                    tracing::warn!("Player {} did not ack transfer_in in time, marking as dead", id);
+                    dead_clients.push(id);
+                }
                 // Remove dead clients (mark players_dirty for broadcast)
+                dead_clients.sort_unstable();
+                dead_clients.dedup();
                 for id in dead_clients {
-                    client_channels.remove(&id);
-                    state.remove_player(id);
+                    if let Some(client_tx) = client_channels.remove(&id) {
+                        let _ = client_tx.try_send(ClientEvent::Disconnect);
+                    }
+                    metrics.transfers_reinjected.add(state.remove_player(id) as u64);
+                    recorder.command(tick_count, Command::Leave { id });
                     players_dirty = true;
                 }
 
                 // Broadcast space_state at 10 Hz
-                tick_count += 1;
-                if tick_count % broadcast_every_n as u64 == 0 {
-                    let msg = state.get_space_state();
-                    let ball_count = msg.balls.len();
-                    match serde_json::to_string(&ServerMsg::SpaceState(msg)) {
-                        Ok(json) => { let _ = broadcast_tx.send(GameBroadcast::SpaceState(json.into())); }
-                        Err(e) => tracing::error!("Failed to serialize SpaceState: {}", e),
-                    }
+                if tick_count.is_multiple_of(broadcast_every_n as u64) {
+                    let full = state.space_state_into(serializer.ball_buffer());
+                    let ball_count = full.balls.len();
+                    serializer.space_state(full);
 
-                    if ball_count > 0 && tick_count % (broadcast_every_n as u64 * 15) == 0 {
+                    if ball_count > 0 && tick_count.is_multiple_of(broadcast_every_n as u64 * 15) {
                         tracing::debug!("Broadcasting space_state with {} balls", ball_count);
                     }
                 }
 
                 // Broadcast players_state only when dirty OR at low rate (2 Hz) for stats
-                if players_dirty || tick_count % players_broadcast_every_n as u64 == 0 {
-                    let players_msg = state.get_players_state();
-                    match serde_json::to_string(&ServerMsg::PlayersState(players_msg)) {
-                        Ok(json) => { let _ = broadcast_tx.send(GameBroadcast::PlayersState(json.into())); }
-                        Err(e) => tracing::error!("Failed to serialize PlayersState: {}", e),
-                    }
+                if players_dirty || tick_count.is_multiple_of(players_broadcast_every_n as u64) {
+                    serializer.players_state(state.get_players_state());
                     players_dirty = false;
                 }
+
+                if tick_count.is_multiple_of(leaderboard_every_n as u64) {
+                    serializer.leaderboard(state.get_leaderboard());
+                }
+
+                // Periodic snapshot; the file write happens off the game loop
+                if let Some(file) = &snapshot {
+                    if snapshot_every_n > 0 && tick_count.is_multiple_of(snapshot_every_n) {
+                        let encoded = file.encode(&state.snapshot());
+                        let file = file.clone();
+                        tokio::task::spawn_blocking(move || {
+                            if let Err(e) = file.write(encoded) {
+                                tracing::error!("Failed to write snapshot {}: {}", file.path().display(), e);
+                            }
+                        });
+                    }
+                }
+
+                metrics.deep_space_balls.set(state.deep_space_ball_count() as u64);
+                metrics.connected_players.set(client_channels.len() as u64);
+                metrics.bot_players.set(state.players.values().filter(|p| p.is_bot).count() as u64);
+                metrics.tick_seconds.observe(tick_start.elapsed().as_secs_f64());
             }
 
-            Some(cmd) = cmd_rx.recv() => {
+            cmd = cmd_rx.recv() => {
+                // All senders gone (room closed or server shutting down)
+                let Some(cmd) = cmd else { break };
+                let command_start = Instant::now();
                 match cmd {
-                    GameCommand::PlayerJoin { response, client_tx } => {
-                        match state.add_player() {
-                            Some((player_id, _player)) => {
+                    GameCommand::PlayerJoin { response, client_tx, resume_token, name } => {
+                        let resumed = resume_token.as_deref().and_then(|t| state.retained_id(t));
+                        let joined = state.join_player(resume_token.as_deref());
+                        recorder.command(tick_count, Command::Join {
+                            id: joined.as_ref().map(|(id, _)| *id),
+                            resumed,
+                            name: name.clone(),
+                        });
+                        match joined {
+                            Some((player_id, player)) => {
                                 // Store client channel for reliable messaging
                                 client_channels.insert(player_id, client_tx);
+                                if let Some(Err(e)) = name.map(|n| state.set_player_name(player_id, &n)) {
+                                    tracing::debug!("Player {} joined with rejected name: {}", player_id, e);
+                                }
 
-                                let welcome = WelcomeMsg {
-                                    protocol_version: PROTOCOL_VERSION,
-                                    server_version: env!("CARGO_PKG_VERSION").to_string(),
-                                    self_id: player_id,
-                                    players: state.get_players_state().players,
-                                    config: state.config.clone(),
-                                };
-                                let _ = response.send(Ok((player_id, welcome)));
+                                let welcome = welcome_msg(&state, player_id);
+                                let interest = interest_grid.interest(player.portal_pos, interest_radius);
+                                let _ = response.send(Ok((player_id, welcome, interest)));
                                 // Broadcast immediately so other players see the new player
                                 players_dirty = true;
+                                // Give the newcomer a baseline for space_state deltas
+                                serializer.request_keyframe();
                             }
                             None => {
                                 let _ = response.send(Err("Server full".to_string()));
                             }
                         }
                     }
+                    GameCommand::SpectatorJoin { response } => {
+                        let _ = response.send(Ok((0, welcome_msg(&state, 0), Interest::All)));
+                        serializer.request_keyframe();
+                    }
                     GameCommand::PlayerLeave { id } => {
                         client_channels.remove(&id);
-                        state.remove_player(id);
+                        metrics.transfers_reinjected.add(state.remove_player(id) as u64);
+                        recorder.command(tick_count, Command::Leave { id });
                         players_dirty = true;
                         tracing::info!("Player {} left", id);
                     }
-                    GameCommand::BallEscaped { owner_id, vx, vy } => {
-                        if state.ball_escaped(owner_id, vx, vy).is_none() {
+                    GameCommand::BallEscaped { owner_id, vx, vy, target_id, ball_id } => {
+                        recorder.command(tick_count, Command::BallEscaped { owner_id, vx, vy, target_id, ball_id });
+                        if state.ball_escaped_to(owner_id, vx, vy, target_id, ball_id).is_none() {
                             tracing::warn!("ball_escaped failed for player {} (player not found?)", owner_id);
                         }
                     }
                     GameCommand::SetPaused { player_id, paused } => {
+                        recorder.command(tick_count, Command::SetPaused { player_id, paused });
                         if state.set_player_paused(player_id, paused) {
                             tracing::debug!("Player {} paused={}", player_id, paused);
                             players_dirty = true;
                         }
                     }
                     GameCommand::Activity { player_id } => {
+                        recorder.command(tick_count, Command::Activity { player_id });
                         state.player_activity(player_id);
                     }
+                    GameCommand::Emote { player_id, kind } => {
+                        // Late emotes from a player who already left are dropped
+                        if state.players.contains_key(&player_id) {
+                            serializer.emote(EmoteMsg { player_id, kind });
+                        }
+                    }
+                    GameCommand::BumperHits { player_id, count } => {
+                        recorder.command(tick_count, Command::BumperHits { player_id, count });
+                        state.add_bumper_hits(player_id, count);
+                    }
+                    GameCommand::SetName { player_id, name } => {
+                        recorder.command(tick_count, Command::SetName { player_id, name: name.clone() });
+                        match state.set_player_name(player_id, &name) {
+                            Ok(true) => players_dirty = true,
+                            Ok(false) => {}
+                            Err(e) => tracing::debug!("Player {} set_name rejected: {}", player_id, e),
+                        }
+                    }
+                    GameCommand::TransferAck { player_id, seq } => {
+                        recorder.command(tick_count, Command::TransferAck { player_id, seq });
+                        if !state.ack_transfer(player_id, seq) {
+                            tracing::debug!("Player {} acked transfer {} after it was re-injected", player_id, seq);
+                        }
+                    }
+                    GameCommand::RequestKeyframe => {
+                        serializer.request_keyframe();
+                    }
+                    GameCommand::AdminListPlayers { response } => {
+                        let _ = response.send(admin::players_snapshot(&state));
+                    }
+                    GameCommand::AdminKick { player_id, response } => {
+                        // The connection sends PlayerLeave once it sees Disconnect
+                        // (or its channel closing, if Disconnect didn't fit)
+                        let kicked = match client_channels.remove(&player_id) {
+                            Some(client_tx) => {
+                                let _ = client_tx.try_send(ClientEvent::Disconnect);
+                                true
+                            }
+                            None => false,
+                        };
+                        let _ = response.send(kicked);
+                    }
+                    GameCommand::AdminAddBot { response } => {
+                        let id = state.add_bot();
+                        recorder.command(tick_count, Command::AddBot);
+                        players_dirty |= id.is_some();
+                        let _ = response.send(id);
+                    }
+                    GameCommand::AdminRemoveBot { player_id, response } => {
+                        let removed = state.remove_bot(player_id);
+                        recorder.command(tick_count, Command::RemoveBot { player_id });
+                        players_dirty |= removed;
+                        let _ = response.send(removed);
+                    }
+                    GameCommand::AdminListBalls { response } => {
+                        let _ = response.send(admin::balls_snapshot(&state));
+                    }
+                    GameCommand::AdminClearBalls { response } => {
+                        recorder.command(tick_count, Command::ClearBalls);
+                        let _ = response.send(state.clear_balls());
+                        players_dirty = true;
+                    }
+                    GameCommand::SaveSnapshot { response } => {
+                        if let Some(file) = &snapshot {
+                            save_snapshot(&state, file);
+                        }
+                        let _ = response.send(());
+                    }
                 }
+                metrics.command_seconds.observe(command_start.elapsed().as_secs_f64());
             }
-
-            else => break,
         }
     }
 
+    if let Some(file) = &snapshot {
+        save_snapshot(&state, file);
+    }
     tracing::info!("Game loop ended");
 }
+
+/// Welcome for player `self_id`, or for a spectator if it is 0.
+fn welcome_msg(state: &GameState, self_id: u32) -> WelcomeMsg {
+    WelcomeMsg {
+        protocol_version: PROTOCOL_VERSION,
+        server_version: env!("CARGO_PKG_VERSION").to_string(),
+        self_id,
+        players: state.get_players_state().players,
+        config: state.config,
+        resume_token: state.resume_token(self_id).unwrap_or_default().to_string(),
+        spectator: self_id == 0,
+    }
+}
+
+/// Continue from the snapshot in `file`, if any. A missing, unreadable or
+/// incompatible snapshot leaves the fresh world in place.
+fn restore_snapshot(state: &mut GameState, file: &SnapshotFile) {
+    let path = file.path();
+    let restored = match file.load() {
+        Ok(Some(snapshot)) => state.restore(snapshot),
+        Ok(None) => return,
+        Err(e) => Err(e.to_string()),
+    };
+    match restored {
+        Ok(balls) => tracing::info!("Restored {} balls from {}", balls, path.display()),
+        Err(e) => tracing::warn!("Ignoring snapshot {}: {}", path.display(), e),
+    }
+}
+
+/// Open a new recording at `path` starting from the current state. A
+/// recording that can't be created is logged and skipped.
+fn start_recording(
+    state: &GameState,
+    server_config: &ServerConfig,
+    path: &Path,
+) -> Recorder<BufWriter<File>> {
+    let header = record::Header::new(state, server_config, CAPTURE_SPEED);
+    match Recorder::create(path, header) {
+        Ok(recorder) => {
+            tracing::info!("Recording commands to {}", path.display());
+            recorder
+        }
+        Err(e) => {
+            tracing::error!("Failed to start recording {}: {}", path.display(), e);
+            Recorder::off()
+        }
+    }
+}
+
+fn save_snapshot(state: &GameState, file: &SnapshotFile) {
+    let path = file.path();
+    match file.save(&state.snapshot()) {
+        Ok(true) => tracing::info!(
+            "Saved {} balls to {}",
+            state.deep_space_ball_count(),
+            path.display()
+        ),
+        // A newer snapshot is on disk already
+        Ok(false) => {}
+        Err(e) => tracing::error!("Failed to write snapshot {}: {}", path.display(), e),
+    }
+}
@@ -0,0 +1,145 @@
+//! Map keyed by id that iterates in a reproducible order.
+//!
+//! `HashMap` iteration order depends on a per-process random seed, so a
+//! simulation that draws from the seeded RNG while iterating one diverges
+//! between runs. `IdMap` keeps its values in a dense `Vec` (removal swaps
+//! the last entry into the hole) with a `HashMap` from id to slot for O(1)
+//! lookups. Iteration follows the slots, so it depends only on the
+//! sequence of inserts and removes. The index is never iterated.
+//!
+//! The method names match the `HashMap` methods it replaces.
+
+use std::collections::HashMap;
+use std::ops::Index;
+
+#[derive(Debug, Clone)]
+pub struct IdMap<V> {
+    entries: Vec<(u32, V)>,
+    slots: HashMap<u32, usize>,
+}
+
+impl<V> Default for IdMap<V> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            slots: HashMap::new(),
+        }
+    }
+}
+
+impl<V> IdMap<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, id: &u32) -> bool {
+        self.slots.contains_key(id)
+    }
+
+    pub fn get(&self, id: &u32) -> Option<&V> {
+        self.slots.get(id).map(|&slot| &self.entries[slot].1)
+    }
+
+    pub fn get_mut(&mut self, id: &u32) -> Option<&mut V> {
+        self.slots.get(id).map(|&slot| &mut self.entries[slot].1)
+    }
+
+    /// Insert or replace. A new id goes last; a replaced value keeps its
+    /// place. Returns the replaced value.
+    pub fn insert(&mut self, id: u32, value: V) -> Option<V> {
+        match self.slots.get(&id) {
+            Some(&slot) => Some(std::mem::replace(&mut self.entries[slot].1, value)),
+            None => {
+                self.slots.insert(id, self.entries.len());
+                self.entries.push((id, value));
+                None
+            }
+        }
+    }
+
+    /// Remove `id`. The last entry moves into its place.
+    pub fn remove(&mut self, id: &u32) -> Option<V> {
+        let slot = self.slots.remove(id)?;
+        let (_, value) = self.entries.swap_remove(slot);
+        if let Some((moved, _)) = self.entries.get(slot) {
+            self.slots.insert(*moved, slot);
+        }
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.slots.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&u32, &V)> {
+        self.entries.iter().map(|(id, v)| (id, v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &u32> {
+        self.entries.iter().map(|(id, _)| id)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.entries.iter_mut().map(|(_, v)| v)
+    }
+}
+
+impl<V> Index<&u32> for IdMap<V> {
+    type Output = V;
+
+    fn index(&self, id: &u32) -> &V {
+        self.get(id).expect("no entry for id")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iterates_in_insertion_order_with_swap_on_remove() {
+        let mut map = IdMap::new();
+        for id in [7, 3, 9, 1] {
+            map.insert(id, id * 10);
+        }
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), [7, 3, 9, 1]);
+
+        assert_eq!(map.remove(&3), Some(30));
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), [7, 1, 9]);
+        assert_eq!(map.get(&1), Some(&10));
+        assert_eq!(map.remove(&3), None);
+
+        // Replacing keeps the slot
+        assert_eq!(map.insert(7, 71), Some(70));
+        assert_eq!(map.values().copied().collect::<Vec<_>>(), [71, 10, 90]);
+        assert_eq!(map[&9], 90);
+    }
+
+    #[test]
+    fn removing_the_last_entry_and_clearing() {
+        let mut map = IdMap::new();
+        map.insert(1, "a");
+        map.insert(2, "b");
+        assert_eq!(map.remove(&2), Some("b"));
+        assert_eq!(map.get(&1), Some(&"a"));
+        assert!(!map.contains_key(&2));
+
+        map.clear();
+        assert!(map.is_empty());
+        map.insert(2, "c");
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&2, &"c")]);
+    }
+}
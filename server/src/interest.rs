@@ -0,0 +1,383 @@
+//! Per-client area of interest for `space_state`.
+//!
+//! Clients only draw deep-space balls within `THETA_MAX` of their own portal,
+//! so sending every ball to every client wastes most of the bandwidth once
+//! space gets busy. Balls are bucketed by the nearest point of a coarse
+//! Fibonacci lattice, each bucket is serialized once per broadcast, and each
+//! connection concatenates the buckets that overlap its interest cap.
+//!
+//! Buckets are a conservative cover: a client may receive a few balls just
+//! outside its radius, never miss one inside it.
+
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+use axum::body::Bytes;
+use axum::extract::ws::Utf8Bytes;
+use pinball_shared::delta::BucketedSpaceState;
+use pinball_shared::wire::{self, PackedParts};
+
+use crate::sphere::fibonacci_sphere;
+use crate::vec3::{angular_distance, dot, Vec3};
+
+/// Number of buckets space is split into (~0.31 rad across each).
+pub const INTEREST_BUCKETS: usize = 128;
+
+/// Extra radius (radians) added to the configured area of interest so balls
+/// are already known before they reach the visible edge.
+pub const INTEREST_MARGIN: f64 = 0.2;
+
+/// Samples per bucket used to estimate the bucket radius.
+const RADIUS_SAMPLES_PER_BUCKET: usize = 32;
+
+/// Which buckets a client receives.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Interest {
+    /// Everything (radius covers the whole sphere)
+    All,
+    /// Sorted bucket indices
+    Buckets(Vec<usize>),
+}
+
+/// Fixed partition of the sphere into nearest-center buckets.
+#[derive(Debug, Clone)]
+pub struct BucketGrid {
+    centers: Vec<Vec3>,
+    /// Upper bound on the angular distance from a center to any point of its bucket
+    bucket_radius: f64,
+}
+
+impl BucketGrid {
+    pub fn new(count: usize) -> Self {
+        let centers = fibonacci_sphere(count.max(1));
+        let mut grid = Self {
+            centers,
+            bucket_radius: 0.0,
+        };
+        let bucket_radius = fibonacci_sphere(grid.len() * RADIUS_SAMPLES_PER_BUCKET)
+            .into_iter()
+            .map(|p| angular_distance(p, grid.centers[grid.bucket_of(p)]))
+            .fold(0.0, f64::max);
+        // Sampling can miss the far corner of a bucket; pad generously
+        grid.bucket_radius = bucket_radius * 1.25;
+        grid
+    }
+
+    pub fn len(&self) -> usize {
+        self.centers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centers.is_empty()
+    }
+
+    /// Bucket containing `pos` (nearest center).
+    pub fn bucket_of(&self, pos: Vec3) -> usize {
+        let mut best = 0;
+        let mut best_dot = f64::NEG_INFINITY;
+        for (i, c) in self.centers.iter().enumerate() {
+            let d = dot(pos, *c);
+            if d > best_dot {
+                best_dot = d;
+                best = i;
+            }
+        }
+        best
+    }
+
+    /// Buckets that may hold a point within `radius` of `center`.
+    pub fn interest(&self, center: Vec3, radius: f64) -> Interest {
+        let reach = radius + self.bucket_radius;
+        if reach >= PI {
+            return Interest::All;
+        }
+        Interest::Buckets(
+            self.centers
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| angular_distance(center, **c) <= reach)
+                .map(|(i, _)| i)
+                .collect(),
+        )
+    }
+}
+
+/// JSON pieces of one bucket: comma-separated list items without brackets.
+#[derive(Debug, Default)]
+struct JsonBucket {
+    balls: String,
+    unchanged: String,
+    removed: String,
+}
+
+/// One `space_state` broadcast, pre-serialized per bucket in both wire
+/// formats. Shared by all connections; each assembles its own frame.
+#[derive(Debug)]
+pub struct SpaceStateFragments {
+    server_time: f64,
+    seq: u32,
+    keyframe: bool,
+    json: Vec<JsonBucket>,
+    packed: Vec<PackedParts>,
+    all_json: OnceLock<Utf8Bytes>,
+    all_packed: OnceLock<Bytes>,
+}
+
+impl SpaceStateFragments {
+    pub fn new(state: &BucketedSpaceState) -> Result<Self, serde_json::Error> {
+        let mut json = Vec::with_capacity(state.buckets.len());
+        let mut packed = Vec::with_capacity(state.buckets.len());
+        for bucket in &state.buckets {
+            let balls = bucket
+                .balls
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<Result<Vec<_>, _>>()?;
+            json.push(JsonBucket {
+                balls: balls.join(","),
+                unchanged: join_ids(&bucket.unchanged),
+                removed: join_ids(&bucket.removed),
+            });
+            packed.push(PackedParts::new(
+                &bucket.balls,
+                &bucket.unchanged,
+                &bucket.removed,
+            ));
+        }
+        Ok(Self {
+            server_time: state.server_time,
+            seq: state.seq,
+            keyframe: state.keyframe,
+            json,
+            packed,
+            all_json: OnceLock::new(),
+            all_packed: OnceLock::new(),
+        })
+    }
+
+    /// `space_state` JSON text for a client with the given interest.
+    pub fn json(&self, interest: &Interest) -> Utf8Bytes {
+        match interest {
+            Interest::All => self
+                .all_json
+                .get_or_init(|| self.assemble_json(0..self.json.len()).into())
+                .clone(),
+            Interest::Buckets(b) => self.assemble_json(b.iter().copied()).into(),
+        }
+    }
+
+    /// Packed binary `space_state` for a client with the given interest.
+    pub fn packed(&self, interest: &Interest) -> Bytes {
+        match interest {
+            Interest::All => self
+                .all_packed
+                .get_or_init(|| self.assemble_packed(0..self.packed.len()).into())
+                .clone(),
+            Interest::Buckets(b) => self.assemble_packed(b.iter().copied()).into(),
+        }
+    }
+
+    fn assemble_json(&self, buckets: impl Iterator<Item = usize> + Clone) -> String {
+        let buckets = buckets.filter_map(|i| self.json.get(i));
+        let list = |field: fn(&JsonBucket) -> &String| {
+            buckets
+                .clone()
+                .map(field)
+                .filter(|s| !s.is_empty())
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let balls = list(|b| &b.balls);
+        let unchanged = list(|b| &b.unchanged);
+        let removed = list(|b| &b.removed);
+
+        // Same shape serde produces for ServerMsg::SpaceState
+        let mut out = String::with_capacity(96 + balls.len() + unchanged.len() + removed.len());
+        out.push_str(r#"{"type":"space_state","serverTime":"#);
+        out.push_str(&serde_json::Value::from(self.server_time).to_string());
+        out.push_str(&format!(
+            r#","seq":{},"keyframe":{},"balls":["#,
+            self.seq, self.keyframe
+        ));
+        out.push_str(&balls);
+        out.push(']');
+        if !unchanged.is_empty() {
+            out.push_str(r#","unchanged":["#);
+            out.push_str(&unchanged);
+            out.push(']');
+        }
+        if !removed.is_empty() {
+            out.push_str(r#","removed":["#);
+            out.push_str(&removed);
+            out.push(']');
+        }
+        out.push('}');
+        out
+    }
+
+    fn assemble_packed(&self, buckets: impl Iterator<Item = usize>) -> Vec<u8> {
+        let parts: Vec<&PackedParts> = buckets
+            .filter_map(|i| self.packed.get(i))
+            .filter(|p| !p.is_empty())
+            .collect();
+        wire::assemble_space_state(self.server_time, self.seq, self.keyframe, &parts)
+    }
+}
+
+fn join_ids(ids: &[u32]) -> String {
+    ids.iter().map(u32::to_string).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{BallWire, ServerMsg, SpaceStateMsg};
+    use crate::vec3::vec3;
+    use pinball_shared::delta::DeltaEncoder;
+
+    fn ball_at(id: u32, pos: Vec3) -> BallWire {
+        BallWire {
+            id,
+            owner_id: 7,
+            pos: [pos.x, pos.y, pos.z],
+            axis: [0.0, 0.0, 1.0],
+            omega: 0.5,
+            hops: 0,
+        }
+    }
+
+    fn encode(grid: &BucketGrid, balls: Vec<BallWire>) -> BucketedSpaceState {
+        let full = SpaceStateMsg {
+            server_time: 1.5,
+            seq: 0,
+            keyframe: false,
+            balls,
+            unchanged: vec![],
+            removed: vec![],
+        };
+        DeltaEncoder::default().encode_bucketed(&full, grid.len(), |b| {
+            grid.bucket_of(vec3(b.pos[0], b.pos[1], b.pos[2]))
+        })
+    }
+
+    fn parse(json: &str) -> SpaceStateMsg {
+        match serde_json::from_str::<ServerMsg>(json).unwrap() {
+            ServerMsg::SpaceState(s) => s,
+            other => panic!("expected space_state, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bucket_of_center_is_itself() {
+        let grid = BucketGrid::new(INTEREST_BUCKETS);
+        for (i, c) in fibonacci_sphere(INTEREST_BUCKETS).into_iter().enumerate() {
+            assert_eq!(grid.bucket_of(c), i);
+        }
+    }
+
+    #[test]
+    fn interest_covers_every_point_within_radius() {
+        let grid = BucketGrid::new(INTEREST_BUCKETS);
+        let portal = vec3(0.0, 0.6, 0.8);
+        let radius = 0.8;
+        let Interest::Buckets(buckets) = grid.interest(portal, radius) else {
+            panic!("expected a bucket subset");
+        };
+        assert!(buckets.len() < grid.len() / 2);
+        for p in fibonacci_sphere(5000) {
+            if angular_distance(p, portal) <= radius {
+                assert!(buckets.contains(&grid.bucket_of(p)));
+            }
+        }
+        // Antipode is never included
+        let antipode = vec3(0.0, -0.6, -0.8);
+        assert!(!buckets.contains(&grid.bucket_of(antipode)));
+    }
+
+    #[test]
+    fn whole_sphere_radius_is_all() {
+        let grid = BucketGrid::new(INTEREST_BUCKETS);
+        assert_eq!(grid.interest(vec3(1.0, 0.0, 0.0), PI), Interest::All);
+    }
+
+    #[test]
+    fn all_interest_matches_serde_json() {
+        let grid = BucketGrid::new(INTEREST_BUCKETS);
+        let state = encode(
+            &grid,
+            vec![
+                ball_at(1, vec3(1.0, 0.0, 0.0)),
+                ball_at(2, vec3(-1.0, 0.0, 0.0)),
+            ],
+        );
+        let frags = SpaceStateFragments::new(&state).unwrap();
+
+        let msg = parse(&frags.json(&Interest::All));
+        assert_eq!(msg.seq, state.seq);
+        assert!(msg.keyframe);
+        assert_eq!(msg.server_time, 1.5);
+        assert_eq!(msg.balls.len(), 2);
+
+        let packed = wire::decode_space_state(&frags.packed(&Interest::All)).unwrap();
+        assert_eq!(packed.balls.len(), 2);
+        assert_eq!(packed.seq, state.seq);
+    }
+
+    #[test]
+    fn bucket_interest_only_includes_nearby_balls() {
+        let grid = BucketGrid::new(INTEREST_BUCKETS);
+        let portal = vec3(1.0, 0.0, 0.0);
+        let state = encode(
+            &grid,
+            vec![
+                ball_at(1, vec3(0.99, 0.141, 0.0)),
+                ball_at(2, vec3(-1.0, 0.0, 0.0)),
+            ],
+        );
+        let frags = SpaceStateFragments::new(&state).unwrap();
+        let interest = grid.interest(portal, 0.8);
+
+        let msg = parse(&frags.json(&interest));
+        let ids: Vec<u32> = msg.balls.iter().map(|b| b.id).collect();
+        assert_eq!(ids, vec![1]);
+
+        let packed = wire::decode_space_state(&frags.packed(&interest)).unwrap();
+        let ids: Vec<u32> = packed.balls.iter().map(|b| b.id).collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[test]
+    fn json_includes_id_lists_only_when_present() {
+        let grid = BucketGrid::new(8);
+        let mut enc = DeltaEncoder::new(100);
+        let bucket_of = |b: &BallWire| grid.bucket_of(vec3(b.pos[0], b.pos[1], b.pos[2]));
+        let full = |balls| SpaceStateMsg {
+            server_time: 0.0,
+            seq: 0,
+            keyframe: false,
+            balls,
+            unchanged: vec![],
+            removed: vec![],
+        };
+        let p = vec3(0.0, 0.0, 1.0);
+        enc.encode_bucketed(
+            &full(vec![ball_at(1, p), ball_at(2, p)]),
+            grid.len(),
+            bucket_of,
+        );
+        let delta = enc.encode_bucketed(&full(vec![ball_at(1, p)]), grid.len(), bucket_of);
+        let frags = SpaceStateFragments::new(&delta).unwrap();
+
+        let json = frags.json(&Interest::All);
+        let msg = parse(&json);
+        assert!(!msg.keyframe);
+        assert!(msg.balls.is_empty());
+        assert_eq!(msg.unchanged, vec![1]);
+        assert_eq!(msg.removed, vec![2]);
+
+        let empty = frags.json(&Interest::Buckets(vec![]));
+        assert!(!empty.contains("unchanged") && !empty.contains("removed"));
+        assert!(parse(&empty).balls.is_empty());
+    }
+}
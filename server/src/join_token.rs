@@ -0,0 +1,204 @@
+//! Signed join tokens for private servers.
+//!
+//! With `join_token_secret` set, `/ws` only upgrades connections that
+//! present a token signed with it, either as `?token=<token>` or as a
+//! `pinball-token.<token>` WebSocket subprotocol (browsers can't set
+//! headers on WebSocket requests, and query strings end up in access logs).
+//!
+//! A token is `<claims>.<signature>`: the JSON `JoinClaims`, then the
+//! HMAC-SHA256 of that first part, both base64url without padding. Tokens
+//! are minted with the `mint-token` binary and can't be revoked short of
+//! changing the secret, so keep their expiry short.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Subprotocol prefix for passing a token in `Sec-WebSocket-Protocol`.
+pub const SUBPROTOCOL_PREFIX: &str = "pinball-token.";
+
+/// What a token allows.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinClaims {
+    /// Unix time (seconds) after which the token is refused
+    pub exp: u64,
+    /// The only room the token joins; `None` allows any room
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+    /// Display name the player joins with, instead of any the client picks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl JoinClaims {
+    /// The room to join, given the one the client asked for (`None` for
+    /// the default room). A token for one room joins it even without
+    /// `?room=`, and refuses any other.
+    pub fn room<'a>(&'a self, requested: Option<&'a str>) -> Result<Option<&'a str>, TokenError> {
+        match (self.room.as_deref(), requested) {
+            (Some(allowed), Some(requested)) if allowed != requested => Err(TokenError::WrongRoom),
+            (Some(allowed), _) => Ok(Some(allowed)),
+            (None, requested) => Ok(requested),
+        }
+    }
+}
+
+/// Why a connection's token was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenError {
+    Missing,
+    Malformed,
+    BadSignature,
+    Expired,
+    WrongRoom,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TokenError::Missing => "join token required",
+            TokenError::Malformed => "malformed join token",
+            TokenError::BadSignature => "invalid join token signature",
+            TokenError::Expired => "join token expired",
+            TokenError::WrongRoom => "join token is for another room",
+        })
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+fn mac(secret: &str) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length")
+}
+
+/// Sign `claims` with `secret`.
+pub fn mint(secret: &str, claims: &JoinClaims) -> String {
+    let payload =
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).expect("JoinClaims serializes"));
+    let mut mac = mac(secret);
+    mac.update(payload.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+    format!("{}.{}", payload, signature)
+}
+
+/// Check `token`'s signature and expiry at `now` (unix seconds).
+pub fn verify(secret: &str, token: &str, now: u64) -> Result<JoinClaims, TokenError> {
+    let (payload, signature) = token.split_once('.').ok_or(TokenError::Malformed)?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| TokenError::Malformed)?;
+    let mut mac = mac(secret);
+    mac.update(payload.as_bytes());
+    // Constant-time comparison
+    mac.verify_slice(&signature)
+        .map_err(|_| TokenError::BadSignature)?;
+
+    let json = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| TokenError::Malformed)?;
+    let claims: JoinClaims = serde_json::from_slice(&json).map_err(|_| TokenError::Malformed)?;
+    if now >= claims.exp {
+        return Err(TokenError::Expired);
+    }
+    Ok(claims)
+}
+
+/// The token offered as a subprotocol in a `Sec-WebSocket-Protocol` value,
+/// with the full subprotocol the server has to accept.
+pub fn from_subprotocols(header: &str) -> Option<(&str, &str)> {
+    header.split(',').map(str::trim).find_map(|protocol| {
+        protocol
+            .strip_prefix(SUBPROTOCOL_PREFIX)
+            .map(|token| (token, protocol))
+    })
+}
+
+/// Current unix time in seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "correct horse battery staple";
+
+    fn claims(room: Option<&str>, name: Option<&str>) -> JoinClaims {
+        JoinClaims {
+            exp: 1_000,
+            room: room.map(str::to_string),
+            name: name.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn minted_tokens_verify_until_they_expire() {
+        let claims = claims(Some("office"), Some("Ada"));
+        let token = mint(SECRET, &claims);
+        assert_eq!(verify(SECRET, &token, 999), Ok(claims));
+        assert_eq!(verify(SECRET, &token, 1_000), Err(TokenError::Expired));
+        // Usable as a query value and as a subprotocol token
+        assert!(token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)));
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_refused() {
+        let token = mint(SECRET, &claims(None, None));
+        assert_eq!(
+            verify("another secret", &token, 0),
+            Err(TokenError::BadSignature)
+        );
+
+        // Extend the expiry but keep the old signature
+        let (_, signature) = token.split_once('.').unwrap();
+        let forged = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(br#"{"exp":99999999999}"#),
+            signature
+        );
+        assert_eq!(verify(SECRET, &forged, 0), Err(TokenError::BadSignature));
+
+        for malformed in ["", "abc", "abc.!!", "."] {
+            assert!(verify(SECRET, malformed, 0).is_err(), "{:?}", malformed);
+        }
+        // Signed garbage is still refused
+        let mut mac = mac(SECRET);
+        mac.update(b"bm90IGpzb24");
+        let garbage = format!(
+            "bm90IGpzb24.{}",
+            URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+        );
+        assert_eq!(verify(SECRET, &garbage, 0), Err(TokenError::Malformed));
+    }
+
+    #[test]
+    fn room_claim_picks_or_restricts_the_room() {
+        let any = claims(None, None);
+        assert_eq!(any.room(None), Ok(None));
+        assert_eq!(any.room(Some("a")), Ok(Some("a")));
+
+        let office = claims(Some("office"), None);
+        assert_eq!(office.room(None), Ok(Some("office")));
+        assert_eq!(office.room(Some("office")), Ok(Some("office")));
+        assert_eq!(office.room(Some("other")), Err(TokenError::WrongRoom));
+    }
+
+    #[test]
+    fn token_is_found_among_subprotocols() {
+        assert_eq!(
+            from_subprotocols("chat, pinball-token.abc.def"),
+            Some(("abc.def", "pinball-token.abc.def"))
+        );
+        assert_eq!(from_subprotocols("chat"), None);
+    }
+}
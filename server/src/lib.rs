@@ -9,27 +9,75 @@
 //!
 //! - **`ws`** — WebSocket handler: one connection per player, validates
 //!   input, enforces rate limits and connection caps.
-//! - **`game_loop`** — Single async task that owns all mutable game state
-//!   and runs the simulation tick at a fixed rate.
+//! - **`rate_limit`** — Token buckets per client message type, per
+//!   connection and optionally per IP, with a configured policy for excess.
+//! - **`anti_cheat`** — Per-player plausibility checks on `ball_escaped`
+//!   streams (cadence, velocity entropy, paused/idle), with soft penalties.
+//! - **`join_token`** — Optional HMAC-signed join tokens: `/ws` only
+//!   upgrades with a valid token, which may pin the room and name.
+//! - **`room`** — Room registry: routes `/ws?room=` to per-room game loops,
+//!   created on first join and torn down when empty.
+//! - **`game_loop`** — Single async task per room that owns all mutable game
+//!   state and runs the simulation tick at a fixed rate.
+//! - **`serializer`** — Per-room thread that delta-encodes and serializes
+//!   the game loop's broadcast snapshots, reusing ball buffers.
 //! - **`state`** — `GameState`: player registry, bot management, ball
 //!   production tracking, and the deep-space simulation.
 //! - **`deep_space`** — `SphereDeepSpace`: the core simulation. Balls
 //!   rotate on great circles (Rodrigues rotation), get rerouted toward
 //!   portals via smooth slerp transitions, and are captured when they
 //!   enter a portal's angular threshold.
+//! - **`id_map`** — `IdMap`: id-keyed storage with O(1) lookups and a
+//!   reproducible iteration order, so seeded runs replay bit for bit.
+//! - **`portal_index`** — `PortalIndex`: latitude/longitude grid so
+//!   captures only test the portals near each ball.
+//! - **`interest`** — Per-client area of interest: buckets deep space so
+//!   each connection only receives balls near its own portal.
 //! - **`sphere`** — `PortalPlacement`: distributes player portals evenly
 //!   on the sphere using a Fibonacci lattice.
 //! - **`bot`** — AI players with personalities (Eager, Relaxed, Chaotic)
 //!   that receive captured balls and send them back after a delay.
+//! - **`admin`** — Token-protected `/admin` HTTP API: list/kick players,
+//!   add/remove bots, dump/clear deep-space balls.
+//! - **`lifecycle`** — `/healthz`, `/readyz` and graceful shutdown:
+//!   drain connections with a `server_going_away` notice on SIGTERM.
+//! - **`metrics`** — Prometheus text-format `/metrics`: tick timing,
+//!   command latency, broadcast cost, player/ball gauges, disconnect and rejection counters.
+//! - **`names`** — Display-name normalisation (NFKC, invisible
+//!   characters, whitespace), length limit and blocklist.
+//! - **`score`** — Per-player scores from deliveries, receipts and
+//!   bumper hits, periodic resets, and the `leaderboard` broadcast.
+//! - **`persist`** — Per-room deep-space snapshots written on shutdown
+//!   (and optionally periodically) and restored on start.
+//! - **`record`** — Optional per-room recording of every state-changing
+//!   command with per-tick deep-space checksums, and its `replay`.
+//! - **`settings`** — Layered configuration for the binary: defaults, TOML
+//!   file, environment and CLI flags, plus `--print-config`.
 //! - **`vec3`** / **`player`** / **`protocol`** / **`config`** — shared
 //!   types, serialization, and configuration.
 
+pub mod admin;
+pub mod anti_cheat;
 pub mod bot;
 pub mod config;
 pub mod deep_space;
 pub mod game_loop;
+pub mod id_map;
+pub mod interest;
+pub mod join_token;
+pub mod lifecycle;
+pub mod metrics;
+pub mod names;
+pub mod persist;
 pub mod player;
+pub mod portal_index;
 pub mod protocol;
+pub mod rate_limit;
+pub mod record;
+pub mod room;
+pub mod score;
+pub mod serializer;
+pub mod settings;
 pub mod sphere;
 pub mod state;
 pub mod vec3;
@@ -0,0 +1,294 @@
+//! Process lifecycle: health, readiness and graceful shutdown.
+//!
+//! - `/healthz` fails when any room's game loop has stopped ticking.
+//! - `/readyz` fails once shutdown has begun, so load balancers stop
+//!   routing new players here while existing ones are drained.
+//!
+//! On SIGTERM/SIGINT the server stops accepting joins, every connection
+//! sends `server_going_away` and a close frame, and the process waits up to
+//! `shutdown_grace_secs` for connections to finish before exiting.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde_json::json;
+use tokio::sync::{watch, Semaphore};
+
+use crate::ws::AppState;
+
+/// A game loop that hasn't ticked for this long is considered stuck.
+pub const STALL_THRESHOLD: Duration = Duration::from_secs(2);
+
+/// Close reason sent to clients on shutdown.
+pub const GOING_AWAY_REASON: &str = "server shutting down";
+
+/// Last time a game loop ticked, and the game clock as of that tick.
+#[derive(Debug)]
+pub struct Heartbeat {
+    epoch: Instant,
+    /// Milliseconds since `epoch`
+    last_ms: AtomicU64,
+    /// Wall-clock instant and game `elapsed` published by the last tick
+    clock: Mutex<(Instant, f64)>,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        let epoch = Instant::now();
+        Self {
+            epoch,
+            last_ms: AtomicU64::new(0),
+            clock: Mutex::new((epoch, 0.0)),
+        }
+    }
+}
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        self.last_ms
+            .store(self.epoch.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Time since the last beat (or since creation).
+    pub fn age(&self) -> Duration {
+        let last = Duration::from_millis(self.last_ms.load(Ordering::Relaxed));
+        self.epoch.elapsed().saturating_sub(last)
+    }
+
+    /// Publish the game clock (`space_state.serverTime`) after a tick.
+    pub fn set_server_time(&self, server_time: f64) {
+        *self.clock.lock().unwrap() = (Instant::now(), server_time);
+    }
+
+    /// Current game clock, extrapolated from the last tick so `pong` can be
+    /// answered off the game loop without tick-sized steps.
+    pub fn server_time(&self) -> f64 {
+        let (at, server_time) = *self.clock.lock().unwrap();
+        server_time + at.elapsed().as_secs_f64()
+    }
+}
+
+/// Shared "are we shutting down" flag.
+#[derive(Debug)]
+pub struct Lifecycle {
+    draining: watch::Sender<bool>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            draining: watch::Sender::new(false),
+        }
+    }
+}
+
+impl Lifecycle {
+    /// Stop accepting joins and tell connections to close.
+    pub fn begin_drain(&self) {
+        self.draining.send_replace(true);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow()
+    }
+
+    /// Watch for draining; `wait_for(|d| *d)` resolves once it begins
+    /// (immediately if it already has).
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.draining.subscribe()
+    }
+
+    /// Begin draining, then wait until every permit of each
+    /// `(semaphore, size)` pool (players, spectators) is back or `grace`
+    /// runs out.
+    pub async fn drain(&self, pools: &[(&Semaphore, usize)], grace: Duration) {
+        self.begin_drain();
+        let open = || -> usize {
+            pools
+                .iter()
+                .map(|(pool, size)| size - pool.available_permits())
+                .sum()
+        };
+        tracing::info!(
+            "Shutting down: draining {} connection(s) for up to {}s",
+            open(),
+            grace.as_secs()
+        );
+        let all_closed = async {
+            // Permits are held until every pool is drained
+            let mut permits = Vec::with_capacity(pools.len());
+            for (pool, size) in pools {
+                let all = u32::try_from(*size).unwrap_or(u32::MAX);
+                permits.push(pool.acquire_many(all).await);
+            }
+            permits
+        };
+        match tokio::time::timeout(grace, all_closed).await {
+            Ok(_) => tracing::info!("All connections closed"),
+            Err(_) => tracing::warn!("Grace period over with {} connection(s) still open", open()),
+        }
+    }
+}
+
+/// Resolves on SIGINT (Ctrl+C) or, on Unix, SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// `GET /healthz`: 200 while every room's game loop is ticking.
+pub async fn healthz_handler(State(app_state): State<AppState>) -> impl IntoResponse {
+    let rooms: Vec<_> = app_state
+        .rooms
+        .rooms()
+        .iter()
+        .map(|r| {
+            let age = r.heartbeat.age();
+            json!({
+                "name": r.name,
+                "last_tick_ms": age.as_millis() as u64,
+                "ticking": age < STALL_THRESHOLD,
+            })
+        })
+        .collect();
+    let healthy = rooms.iter().all(|r| r["ticking"] == true);
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(json!({
+            "status": if healthy { "ok" } else { "stalled" },
+            "rooms": rooms,
+        })),
+    )
+}
+
+/// `GET /readyz`: 200 until shutdown begins.
+pub async fn readyz_handler(State(app_state): State<AppState>) -> impl IntoResponse {
+    if app_state.lifecycle.is_draining() {
+        (StatusCode::SERVICE_UNAVAILABLE, "draining")
+    } else {
+        (StatusCode::OK, "ready")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn heartbeat_age_resets_on_beat() {
+        let hb = Heartbeat::default();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(hb.age() >= Duration::from_millis(20));
+        hb.beat();
+        assert!(hb.age() < Duration::from_millis(20));
+    }
+
+    #[test]
+    fn heartbeat_server_time_extrapolates_from_last_tick() {
+        let hb = Heartbeat::default();
+        hb.set_server_time(100.0);
+        std::thread::sleep(Duration::from_millis(20));
+        let t = hb.server_time();
+        assert!((100.02..101.0).contains(&t), "{}", t);
+        hb.set_server_time(50.0);
+        assert!(hb.server_time() < 50.1);
+    }
+
+    #[tokio::test]
+    async fn drain_flips_flag_and_wakes_waiters() {
+        let lifecycle = Arc::new(Lifecycle::default());
+        assert!(!lifecycle.is_draining());
+        let mut rx = lifecycle.subscribe();
+        let waiter = tokio::spawn(async move { rx.wait_for(|d| *d).await.is_ok() });
+
+        let connections = Semaphore::new(4);
+        lifecycle
+            .drain(&[(&connections, 4)], Duration::from_secs(1))
+            .await;
+        assert!(lifecycle.is_draining());
+        assert!(waiter.await.unwrap());
+        // Late subscribers resolve immediately
+        assert!(lifecycle.subscribe().wait_for(|d| *d).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn drain_waits_for_connections_up_to_grace() {
+        let lifecycle = Lifecycle::default();
+        let connections = Arc::new(Semaphore::new(2));
+        let permit = connections.clone().try_acquire_owned().unwrap();
+
+        // Released well within the grace period
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(permit);
+        });
+        let start = Instant::now();
+        lifecycle
+            .drain(&[(&connections, 2)], Duration::from_secs(5))
+            .await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(50) && elapsed < Duration::from_secs(2));
+
+        // A connection that never closes holds shutdown for the full grace
+        let _stuck = connections.clone().try_acquire_owned().unwrap();
+        let start = Instant::now();
+        lifecycle
+            .drain(&[(&connections, 2)], Duration::from_millis(100))
+            .await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn drain_waits_for_every_pool() {
+        let lifecycle = Lifecycle::default();
+        let players = Semaphore::new(2);
+        let spectators = Arc::new(Semaphore::new(1));
+        let spectator = spectators.clone().try_acquire_owned().unwrap();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(spectator);
+        });
+        let start = Instant::now();
+        lifecycle
+            .drain(&[(&players, 2), (&spectators, 1)], Duration::from_secs(5))
+            .await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(50) && elapsed < Duration::from_secs(2));
+    }
+}
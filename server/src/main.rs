@@ -1,78 +1,109 @@
 use axum::routing::get;
 use axum::Router;
-use pinball_server::config::ServerConfig;
-use pinball_server::game_loop::{run_game_loop, GameBroadcast, GameCommand};
+use pinball_server::admin;
+use pinball_server::lifecycle::{self, healthz_handler, readyz_handler, Lifecycle};
+use pinball_server::metrics::{metrics_handler, ServerMetrics};
+use pinball_server::rate_limit::RateLimiter;
+use pinball_server::room::RoomRegistry;
+use pinball_server::settings::{self, Settings};
 use pinball_server::ws::{ws_handler, AppState};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio::sync::Semaphore;
 use tower_http::cors::CorsLayer;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
 
-    let mut config = ServerConfig::default();
-
-    // Allow overriding bot count via environment variable
-    if let Ok(val) = std::env::var("BOT_COUNT") {
-        if let Ok(count) = val.parse::<usize>() {
-            config.bot_count = count;
+    // Defaults < config file < environment < CLI flags; see `settings`
+    let settings = match Settings::from_env() {
+        Ok(settings) => settings,
+        Err(errors) => {
+            eprintln!("Invalid server configuration:");
+            for e in errors {
+                eprintln!("  {}", e);
+            }
+            std::process::exit(1);
         }
+    };
+    if settings.help {
+        print!("{}", settings::usage());
+        return Ok(());
     }
-
-    // Allow overriding allowed origins via environment variable.
-    // Comma-separated list, e.g. "https://pinball.vatnar.no,https://pinballbevy.vatnar.no"
-    // If not set, all origins are allowed (open server mode).
-    if let Ok(val) = std::env::var("ALLOWED_ORIGINS") {
-        config.allowed_origins = val
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
-    }
-
-    // Validate configuration before starting
-    if let Err(e) = config.validate() {
-        eprintln!("Invalid server configuration: {}", e);
-        std::process::exit(1);
+    if settings.print_config {
+        print!("{}", settings::to_toml(&settings.config));
+        return Ok(());
     }
+    let config = settings.config;
 
     let listen_addr = config.listen_addr.clone();
     let max_velocity = config.max_velocity;
-    let max_ball_escaped_per_sec = config.max_ball_escaped_per_sec;
+    let rate_limiter = Arc::new(RateLimiter::new(config.rate_limits.clone()));
+    let anti_cheat = config.anti_cheat.clone();
     let max_connections = config.max_connections;
+    let max_spectators = config.max_spectators;
     let allowed_origins = config.allowed_origins.clone();
+    let admin_token = config.admin_token.clone();
+    let join_token_secret = config.join_token_secret.clone();
+    let shutdown_grace = std::time::Duration::from_secs(config.shutdown_grace_secs);
 
-    let (game_tx, game_rx) = mpsc::channel::<GameCommand>(256);
-    let (broadcast_tx, _) = broadcast::channel::<GameBroadcast>(64);
-
-    // Spawn game loop
-    let bc_tx = broadcast_tx.clone();
-    tokio::spawn(async move {
-        run_game_loop(game_rx, bc_tx, config).await;
-    });
+    // Starts the default room's game loop; other rooms start on first join
+    let rooms = RoomRegistry::new(config);
 
     // Connection semaphore for limiting concurrent connections
     let connection_semaphore = Arc::new(Semaphore::new(max_connections));
+    let spectator_semaphore = Arc::new(Semaphore::new(max_spectators));
+    let lifecycle = Arc::new(Lifecycle::default());
 
     // Axum app
     let app_state = AppState {
-        game_tx,
-        broadcast_tx,
+        rooms: rooms.clone(),
         max_velocity,
-        max_ball_escaped_per_sec,
-        connection_semaphore,
+        rate_limiter,
+        anti_cheat,
+        connection_semaphore: connection_semaphore.clone(),
+        spectator_semaphore: spectator_semaphore.clone(),
         allowed_origins,
+        join_token_secret,
+        metrics: Arc::new(ServerMetrics::default()),
+        lifecycle: lifecycle.clone(),
     };
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/ws", get(ws_handler))
-        .layer(CorsLayer::permissive())
+        .route("/metrics", get(metrics_handler))
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler))
         .with_state(app_state);
+    if let Some(token) = admin_token {
+        app = app.merge(admin::router(rooms.clone(), token));
+        tracing::info!("Admin API enabled at /admin");
+    }
+    let app = app.layer(CorsLayer::permissive());
 
     tracing::info!("Starting pinball server on {}", listen_addr);
     println!("Pinball server listening on {}", listen_addr);
 
     let listener = tokio::net::TcpListener::bind(&listen_addr).await?;
-    axum::serve(listener, app).await?;
+    // Client addresses feed the per-IP rate limits
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        lifecycle::shutdown_signal().await;
+        lifecycle
+            .drain(
+                &[
+                    (&connection_semaphore, max_connections),
+                    (&spectator_semaphore, max_spectators),
+                ],
+                shutdown_grace,
+            )
+            .await;
+    })
+    .await?;
+    rooms.save_snapshots().await;
+    tracing::info!("Server stopped");
     Ok(())
 }
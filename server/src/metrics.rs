@@ -0,0 +1,700 @@
+//! Prometheus text-format metrics, served on `/metrics`.
+//!
+//! Everything is plain atomics so the game loop and connection tasks can
+//! record without locks. Per-room metrics live on the `Room` and disappear
+//! with it; server-wide metrics live in `ServerMetrics` on `AppState`.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+
+use crate::anti_cheat::Check;
+use crate::config::CheatPenalty;
+use crate::rate_limit::MessageKind;
+use crate::ws::{AppState, BallEscapedValidation};
+
+/// Bucket bounds (seconds) for tick, command and serialization timings.
+const TIMING_BUCKETS: &[f64] = &[
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1,
+];
+
+/// Monotonic counter.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Value that can go up and down.
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    pub fn set(&self, v: u64) {
+        self.0.store(v, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Fixed-bucket histogram.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Per-bucket (non-cumulative) counts; the last one is `+Inf`
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    /// f64 bits
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+
+    pub fn observe(&self, v: f64) {
+        let i = self
+            .bounds
+            .iter()
+            .position(|b| v <= *b)
+            .unwrap_or(self.bounds.len());
+        self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + v).to_bits())
+            });
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn sum(&self) -> f64 {
+        f64::from_bits(self.sum.load(Ordering::Relaxed))
+    }
+}
+
+/// Broadcast message kinds, as the `message` label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastKind {
+    SpaceState,
+    PlayersState,
+    Emote,
+    Leaderboard,
+}
+
+impl BroadcastKind {
+    const ALL: [BroadcastKind; 4] = [
+        BroadcastKind::SpaceState,
+        BroadcastKind::PlayersState,
+        BroadcastKind::Emote,
+        BroadcastKind::Leaderboard,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            BroadcastKind::SpaceState => "space_state",
+            BroadcastKind::PlayersState => "players_state",
+            BroadcastKind::Emote => "emote",
+            BroadcastKind::Leaderboard => "leaderboard",
+        }
+    }
+}
+
+/// Metrics for one room's game loop and its connections.
+#[derive(Debug)]
+pub struct RoomMetrics {
+    /// Simulation and snapshotting; serialization runs on its own thread
+    pub tick_seconds: Histogram,
+    /// Game loop time handling one `GameCommand`
+    pub command_seconds: Histogram,
+    /// Ticks skipped because the loop fell behind (`MissedTickBehavior::Skip`)
+    pub missed_ticks: Counter,
+    pub deep_space_balls: Gauge,
+    pub connected_players: Gauge,
+    pub bot_players: Gauge,
+    serialize_seconds: [Histogram; 4],
+    dropped_broadcasts: [Counter; 4],
+    sent_bytes: [Counter; 4],
+    /// Times a connection fell behind the broadcast channel
+    pub lagged_receivers: Counter,
+    /// Broadcasts those connections skipped as a result
+    pub lagged_messages: Counter,
+    /// Unacked `transfer_in` balls returned to deep space
+    pub transfers_reinjected: Counter,
+}
+
+impl Default for RoomMetrics {
+    fn default() -> Self {
+        Self {
+            tick_seconds: Histogram::new(TIMING_BUCKETS),
+            command_seconds: Histogram::new(TIMING_BUCKETS),
+            missed_ticks: Counter::default(),
+            deep_space_balls: Gauge::default(),
+            connected_players: Gauge::default(),
+            bot_players: Gauge::default(),
+            serialize_seconds: [
+                Histogram::new(TIMING_BUCKETS),
+                Histogram::new(TIMING_BUCKETS),
+                Histogram::new(TIMING_BUCKETS),
+                Histogram::new(TIMING_BUCKETS),
+            ],
+            dropped_broadcasts: Default::default(),
+            sent_bytes: Default::default(),
+            lagged_receivers: Counter::default(),
+            lagged_messages: Counter::default(),
+            transfers_reinjected: Counter::default(),
+        }
+    }
+}
+
+impl RoomMetrics {
+    /// Time spent serializing one broadcast of `kind`.
+    pub fn serialize_seconds(&self, kind: BroadcastKind) -> &Histogram {
+        &self.serialize_seconds[kind as usize]
+    }
+
+    /// `kind` broadcasts dropped because the serializer fell behind.
+    pub fn dropped_broadcasts(&self, kind: BroadcastKind) -> &Counter {
+        &self.dropped_broadcasts[kind as usize]
+    }
+
+    /// Payload bytes written to clients for `kind` broadcasts.
+    pub fn sent_bytes(&self, kind: BroadcastKind) -> &Counter {
+        &self.sent_bytes[kind as usize]
+    }
+}
+
+/// Why the server closed a connection on its own initiative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// Over a rate limit with the `disconnect` policy
+    RateLimited,
+    /// Text message over the size limit
+    OversizedMessage,
+    /// Too many consecutive unparseable messages
+    ParseErrors,
+    /// Unexpected binary frame
+    BinaryFrame,
+    /// Nothing received for the idle timeout
+    IdleTimeout,
+    /// Send to the client timed out or failed
+    SlowConsumer,
+}
+
+impl DisconnectReason {
+    const ALL: [DisconnectReason; 6] = [
+        DisconnectReason::RateLimited,
+        DisconnectReason::OversizedMessage,
+        DisconnectReason::ParseErrors,
+        DisconnectReason::BinaryFrame,
+        DisconnectReason::IdleTimeout,
+        DisconnectReason::SlowConsumer,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            DisconnectReason::RateLimited => "rate_limited",
+            DisconnectReason::OversizedMessage => "oversized_message",
+            DisconnectReason::ParseErrors => "parse_errors",
+            DisconnectReason::BinaryFrame => "binary_frame",
+            DisconnectReason::IdleTimeout => "idle_timeout",
+            DisconnectReason::SlowConsumer => "slow_consumer",
+        }
+    }
+}
+
+/// `BallEscapedValidation` rejection variants, as the `reason` label.
+const REJECTION_LABELS: [&str; 3] = ["non_finite", "vy_positive", "too_slow"];
+
+/// Server-wide metrics recorded by connection handlers.
+#[derive(Debug, Default)]
+pub struct ServerMetrics {
+    disconnects: [Counter; 6],
+    ball_escaped_rejections: [Counter; 3],
+    rate_limited: [Counter; MessageKind::ALL.len()],
+    escape_suspicions: [Counter; Check::ALL.len()],
+    players_flagged: [Counter; CheatPenalty::ALL.len()],
+    escapes_penalized: [Counter; CheatPenalty::ALL.len()],
+}
+
+impl ServerMetrics {
+    pub fn disconnect(&self, reason: DisconnectReason) {
+        self.disconnects[reason as usize].inc();
+    }
+
+    pub fn disconnects(&self, reason: DisconnectReason) -> u64 {
+        self.disconnects[reason as usize].get()
+    }
+
+    /// Count a message over its rate limit, whatever the policy.
+    pub fn rate_limited(&self, kind: MessageKind) {
+        self.rate_limited[kind as usize].inc();
+    }
+
+    pub fn rate_limited_messages(&self, kind: MessageKind) -> u64 {
+        self.rate_limited[kind as usize].get()
+    }
+
+    /// Count an escape that failed an anti-cheat check.
+    pub fn escape_suspicion(&self, check: Check) {
+        self.escape_suspicions[check as usize].inc();
+    }
+
+    pub fn escape_suspicions(&self, check: Check) -> u64 {
+        self.escape_suspicions[check as usize].get()
+    }
+
+    /// Count a player flagged by the anti-cheat, under the penalty they get.
+    pub fn player_flagged(&self, penalty: CheatPenalty) {
+        self.players_flagged[penalty as usize].inc();
+    }
+
+    pub fn players_flagged(&self, penalty: CheatPenalty) -> u64 {
+        self.players_flagged[penalty as usize].get()
+    }
+
+    /// Count a flagged player's escape kept out of deep space.
+    pub fn escape_penalized(&self, penalty: CheatPenalty) {
+        self.escapes_penalized[penalty as usize].inc();
+    }
+
+    pub fn escapes_penalized(&self, penalty: CheatPenalty) -> u64 {
+        self.escapes_penalized[penalty as usize].get()
+    }
+
+    /// Count a rejected `ball_escaped`. `Valid` is ignored.
+    pub fn ball_escaped_rejected(&self, validation: &BallEscapedValidation) {
+        if let Some(i) = rejection_index(validation) {
+            self.ball_escaped_rejections[i].inc();
+        }
+    }
+
+    pub fn ball_escaped_rejections(&self, validation: &BallEscapedValidation) -> u64 {
+        rejection_index(validation).map_or(0, |i| self.ball_escaped_rejections[i].get())
+    }
+}
+
+fn rejection_index(validation: &BallEscapedValidation) -> Option<usize> {
+    match validation {
+        BallEscapedValidation::Valid { .. } => None,
+        BallEscapedValidation::InvalidNonFinite => Some(0),
+        BallEscapedValidation::InvalidVyPositive => Some(1),
+        BallEscapedValidation::InvalidTooSlow => Some(2),
+    }
+}
+
+/// Render all metrics in Prometheus text exposition format (0.0.4).
+/// `rooms` are (room name, metrics) pairs.
+pub fn render(server: &ServerMetrics, rooms: &[(String, &RoomMetrics)]) -> String {
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "pinball_tick_duration_seconds",
+        "histogram",
+        "Time spent in one simulation tick.",
+    );
+    for (room, m) in rooms {
+        histogram(
+            &mut out,
+            "pinball_tick_duration_seconds",
+            &format!("room=\"{}\"", room),
+            &m.tick_seconds,
+        );
+    }
+    header(
+        &mut out,
+        "pinball_command_duration_seconds",
+        "histogram",
+        "Time the game loop spent handling one command.",
+    );
+    for (room, m) in rooms {
+        histogram(
+            &mut out,
+            "pinball_command_duration_seconds",
+            &format!("room=\"{}\"", room),
+            &m.command_seconds,
+        );
+    }
+    header(
+        &mut out,
+        "pinball_missed_ticks_total",
+        "counter",
+        "Ticks skipped because the game loop fell behind.",
+    );
+    for (room, m) in rooms {
+        sample(
+            &mut out,
+            "pinball_missed_ticks_total",
+            &format!("room=\"{}\"", room),
+            m.missed_ticks.get(),
+        );
+    }
+
+    header(
+        &mut out,
+        "pinball_deep_space_balls",
+        "gauge",
+        "Balls currently in deep space.",
+    );
+    for (room, m) in rooms {
+        sample(
+            &mut out,
+            "pinball_deep_space_balls",
+            &format!("room=\"{}\"", room),
+            m.deep_space_balls.get(),
+        );
+    }
+    header(
+        &mut out,
+        "pinball_connected_players",
+        "gauge",
+        "Players with an open connection.",
+    );
+    for (room, m) in rooms {
+        sample(
+            &mut out,
+            "pinball_connected_players",
+            &format!("room=\"{}\"", room),
+            m.connected_players.get(),
+        );
+    }
+    header(
+        &mut out,
+        "pinball_transfers_reinjected_total",
+        "counter",
+        "Captured balls returned to deep space because the client never acked them.",
+    );
+    for (room, m) in rooms {
+        sample(
+            &mut out,
+            "pinball_transfers_reinjected_total",
+            &format!("room=\"{}\"", room),
+            m.transfers_reinjected.get(),
+        );
+    }
+    header(&mut out, "pinball_bot_players", "gauge", "Bot players.");
+    for (room, m) in rooms {
+        sample(
+            &mut out,
+            "pinball_bot_players",
+            &format!("room=\"{}\"", room),
+            m.bot_players.get(),
+        );
+    }
+
+    header(
+        &mut out,
+        "pinball_broadcast_serialize_seconds",
+        "histogram",
+        "Time spent serializing one broadcast.",
+    );
+    for (room, m) in rooms {
+        for kind in BroadcastKind::ALL {
+            let labels = format!("room=\"{}\",message=\"{}\"", room, kind.label());
+            histogram(
+                &mut out,
+                "pinball_broadcast_serialize_seconds",
+                &labels,
+                m.serialize_seconds(kind),
+            );
+        }
+    }
+    header(
+        &mut out,
+        "pinball_broadcasts_dropped_total",
+        "counter",
+        "Broadcasts dropped because the serializer fell behind.",
+    );
+    for (room, m) in rooms {
+        for kind in BroadcastKind::ALL {
+            let labels = format!("room=\"{}\",message=\"{}\"", room, kind.label());
+            sample(
+                &mut out,
+                "pinball_broadcasts_dropped_total",
+                &labels,
+                m.dropped_broadcasts(kind).get(),
+            );
+        }
+    }
+    header(
+        &mut out,
+        "pinball_broadcast_sent_bytes_total",
+        "counter",
+        "Broadcast payload bytes sent to clients.",
+    );
+    for (room, m) in rooms {
+        for kind in BroadcastKind::ALL {
+            let labels = format!("room=\"{}\",message=\"{}\"", room, kind.label());
+            sample(
+                &mut out,
+                "pinball_broadcast_sent_bytes_total",
+                &labels,
+                m.sent_bytes(kind).get(),
+            );
+        }
+    }
+    header(
+        &mut out,
+        "pinball_broadcast_lagged_receivers_total",
+        "counter",
+        "Times a connection fell behind the broadcast channel.",
+    );
+    for (room, m) in rooms {
+        sample(
+            &mut out,
+            "pinball_broadcast_lagged_receivers_total",
+            &format!("room=\"{}\"", room),
+            m.lagged_receivers.get(),
+        );
+    }
+    header(
+        &mut out,
+        "pinball_broadcast_lagged_messages_total",
+        "counter",
+        "Broadcasts skipped by lagging connections.",
+    );
+    for (room, m) in rooms {
+        sample(
+            &mut out,
+            "pinball_broadcast_lagged_messages_total",
+            &format!("room=\"{}\"", room),
+            m.lagged_messages.get(),
+        );
+    }
+
+    header(
+        &mut out,
+        "pinball_disconnects_total",
+        "counter",
+        "Connections closed by the server, by reason.",
+    );
+    for reason in DisconnectReason::ALL {
+        sample(
+            &mut out,
+            "pinball_disconnects_total",
+            &format!("reason=\"{}\"", reason.label()),
+            server.disconnects(reason),
+        );
+    }
+    header(
+        &mut out,
+        "pinball_ball_escaped_rejected_total",
+        "counter",
+        "Rejected ball_escaped messages, by validation result.",
+    );
+    for (label, counter) in REJECTION_LABELS.iter().zip(&server.ball_escaped_rejections) {
+        sample(
+            &mut out,
+            "pinball_ball_escaped_rejected_total",
+            &format!("reason=\"{}\"", label),
+            counter.get(),
+        );
+    }
+    header(
+        &mut out,
+        "pinball_rate_limited_total",
+        "counter",
+        "Client messages over their rate limit, by message type.",
+    );
+    for kind in MessageKind::ALL {
+        sample(
+            &mut out,
+            "pinball_rate_limited_total",
+            &format!("message=\"{}\"", kind.label()),
+            server.rate_limited_messages(kind),
+        );
+    }
+    header(
+        &mut out,
+        "pinball_escape_suspicions_total",
+        "counter",
+        "Valid ball_escaped messages that failed an anti-cheat check, by check.",
+    );
+    for check in Check::ALL {
+        sample(
+            &mut out,
+            "pinball_escape_suspicions_total",
+            &format!("check=\"{}\"", check.label()),
+            server.escape_suspicions(check),
+        );
+    }
+    header(
+        &mut out,
+        "pinball_players_flagged_total",
+        "counter",
+        "Players flagged by the anti-cheat, by the penalty they get.",
+    );
+    for penalty in CheatPenalty::ALL {
+        sample(
+            &mut out,
+            "pinball_players_flagged_total",
+            &format!("penalty=\"{}\"", penalty.label()),
+            server.players_flagged(penalty),
+        );
+    }
+    header(
+        &mut out,
+        "pinball_escapes_penalized_total",
+        "counter",
+        "Flagged players' escapes kept out of deep space, by penalty.",
+    );
+    for penalty in CheatPenalty::ALL {
+        sample(
+            &mut out,
+            "pinball_escapes_penalized_total",
+            &format!("penalty=\"{}\"", penalty.label()),
+            server.escapes_penalized(penalty),
+        );
+    }
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &str, value: impl std::fmt::Display) {
+    let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+}
+
+fn histogram(out: &mut String, name: &str, labels: &str, h: &Histogram) {
+    let mut cumulative = 0;
+    for (i, bucket) in h.buckets.iter().enumerate() {
+        cumulative += bucket.load(Ordering::Relaxed);
+        let le = h
+            .bounds
+            .get(i)
+            .map_or("+Inf".to_string(), |b| b.to_string());
+        sample(
+            out,
+            &format!("{}_bucket", name),
+            &format!("{},le=\"{}\"", labels, le),
+            cumulative,
+        );
+    }
+    sample(out, &format!("{}_sum", name), labels, h.sum());
+    sample(out, &format!("{}_count", name), labels, h.count());
+}
+
+/// `GET /metrics`
+pub async fn metrics_handler(State(app_state): State<AppState>) -> impl IntoResponse {
+    let rooms = app_state.rooms.rooms();
+    let rooms: Vec<(String, &RoomMetrics)> = rooms
+        .iter()
+        .map(|r| (r.name.clone(), r.metrics.as_ref()))
+        .collect();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render(&app_state.metrics, &rooms),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let h = Histogram::new(&[1.0, 2.0]);
+        h.observe(0.5);
+        h.observe(1.5);
+        h.observe(5.0);
+        let mut out = String::new();
+        histogram(&mut out, "x", "room=\"a\"", &h);
+        assert!(out.contains("x_bucket{room=\"a\",le=\"1\"} 1\n"));
+        assert!(out.contains("x_bucket{room=\"a\",le=\"2\"} 2\n"));
+        assert!(out.contains("x_bucket{room=\"a\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("x_sum{room=\"a\"} 7\n"));
+        assert!(out.contains("x_count{room=\"a\"} 3\n"));
+    }
+
+    #[test]
+    fn rejections_counted_per_variant() {
+        let m = ServerMetrics::default();
+        m.ball_escaped_rejected(&BallEscapedValidation::InvalidTooSlow);
+        m.ball_escaped_rejected(&BallEscapedValidation::InvalidTooSlow);
+        m.ball_escaped_rejected(&BallEscapedValidation::Valid { vx: 0.0, vy: -1.0 });
+        assert_eq!(
+            m.ball_escaped_rejections(&BallEscapedValidation::InvalidTooSlow),
+            2
+        );
+        assert_eq!(
+            m.ball_escaped_rejections(&BallEscapedValidation::InvalidNonFinite),
+            0
+        );
+
+        let out = render(&m, &[]);
+        assert!(out.contains("pinball_ball_escaped_rejected_total{reason=\"too_slow\"} 2\n"));
+        assert!(out.contains("pinball_ball_escaped_rejected_total{reason=\"non_finite\"} 0\n"));
+    }
+
+    #[test]
+    fn render_labels_room_metrics() {
+        let server = ServerMetrics::default();
+        server.disconnect(DisconnectReason::IdleTimeout);
+        server.rate_limited(MessageKind::Emote);
+        server.escape_suspicion(Check::Entropy);
+        server.player_flagged(CheatPenalty::ShadowDrop);
+        server.escape_penalized(CheatPenalty::ShadowDrop);
+        let room = RoomMetrics::default();
+        room.deep_space_balls.set(4);
+        room.missed_ticks.add(2);
+        room.sent_bytes(BroadcastKind::PlayersState).add(100);
+        room.dropped_broadcasts(BroadcastKind::SpaceState).inc();
+        room.command_seconds.observe(0.0002);
+
+        let out = render(&server, &[("public".to_string(), &room)]);
+        assert!(out.contains("# TYPE pinball_deep_space_balls gauge\n"));
+        assert!(out.contains("pinball_deep_space_balls{room=\"public\"} 4\n"));
+        assert!(out.contains("pinball_missed_ticks_total{room=\"public\"} 2\n"));
+        assert!(out.contains(
+            "pinball_broadcast_sent_bytes_total{room=\"public\",message=\"players_state\"} 100\n"
+        ));
+        assert!(out.contains(
+            "pinball_broadcasts_dropped_total{room=\"public\",message=\"space_state\"} 1\n"
+        ));
+        assert!(out.contains("pinball_command_duration_seconds_count{room=\"public\"} 1\n"));
+        assert!(out.contains("pinball_disconnects_total{reason=\"idle_timeout\"} 1\n"));
+        assert!(out.contains("pinball_rate_limited_total{message=\"emote\"} 1\n"));
+        assert!(out.contains("pinball_escape_suspicions_total{check=\"entropy\"} 1\n"));
+        assert!(out.contains("pinball_players_flagged_total{penalty=\"shadow_drop\"} 1\n"));
+        assert!(out.contains("pinball_escapes_penalized_total{penalty=\"throttle\"} 0\n"));
+        // Every sample line belongs to a declared family
+        for line in out.lines().filter(|l| !l.starts_with('#')) {
+            let name = line.split(['{', ' ']).next().unwrap();
+            let family = name
+                .trim_end_matches("_bucket")
+                .trim_end_matches("_sum")
+                .trim_end_matches("_count");
+            assert!(out.contains(&format!("# TYPE {} ", family)), "{}", line);
+        }
+    }
+}
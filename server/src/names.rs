@@ -0,0 +1,154 @@
+//! Player display names: normalisation and validation of client-supplied
+//! names before they go out to everyone in `players_state`.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Why a display name was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameError {
+    /// Longer than `max_name_len` characters after normalisation
+    TooLong,
+    /// Contains a blocklisted word
+    Blocked,
+}
+
+impl std::fmt::Display for NameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameError::TooLong => write!(f, "name too long"),
+            NameError::Blocked => write!(f, "name not allowed"),
+        }
+    }
+}
+
+/// Length limit and blocklist applied to every display name.
+#[derive(Debug, Clone)]
+pub struct NamePolicy {
+    max_len: usize,
+    /// Blocklist entries, folded the same way as candidate names
+    blocklist: Vec<String>,
+}
+
+impl NamePolicy {
+    pub fn new(max_len: usize, blocklist: &[String]) -> Self {
+        Self {
+            max_len,
+            blocklist: blocklist
+                .iter()
+                .map(|w| fold(w))
+                .filter(|w| !w.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Normalise a raw name and check it against the policy. Returns the
+    /// name to store; an empty string clears the name.
+    pub fn validate(&self, raw: &str) -> Result<String, NameError> {
+        let name = normalize(raw);
+        if name.chars().count() > self.max_len {
+            return Err(NameError::TooLong);
+        }
+        let folded = fold(&name);
+        if self.blocklist.iter().any(|w| folded.contains(w.as_str())) {
+            return Err(NameError::Blocked);
+        }
+        Ok(name)
+    }
+}
+
+/// NFKC-normalise, drop control and invisible formatting characters, and
+/// collapse whitespace runs to a single space.
+fn normalize(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut pending_space = false;
+    for c in raw.nfkc() {
+        if c.is_whitespace() {
+            pending_space = !out.is_empty();
+        } else if !c.is_control() && !is_invisible(c) {
+            if pending_space {
+                out.push(' ');
+                pending_space = false;
+            }
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Zero-width and bidi override characters, which can hide or reorder text.
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}' | '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}' | '\u{FEFF}'
+    )
+}
+
+/// Lowercase alphanumerics only, so "B a.D" still matches "bad".
+fn fold(s: &str) -> String {
+    normalize(s)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> NamePolicy {
+        NamePolicy::new(12, &["badword".to_string()])
+    }
+
+    #[test]
+    fn trims_and_collapses_whitespace() {
+        assert_eq!(
+            policy().validate("  Ada \t Lovelace ").unwrap(),
+            "Ada Lovelace"
+        );
+    }
+
+    #[test]
+    fn applies_nfkc_and_strips_invisible_characters() {
+        // Fullwidth letters fold to ASCII; zero-width space and RTL override vanish
+        assert_eq!(
+            policy().validate("\u{FF21}da\u{200B}\u{202E}!").unwrap(),
+            "Ada!"
+        );
+        assert_eq!(policy().validate("a\u{0007}b\nc").unwrap(), "ab c");
+    }
+
+    #[test]
+    fn empty_or_blank_clears_the_name() {
+        assert_eq!(policy().validate("").unwrap(), "");
+        assert_eq!(policy().validate(" \u{200B} ").unwrap(), "");
+    }
+
+    #[test]
+    fn length_is_counted_in_characters() {
+        assert_eq!(
+            policy().validate("éééééééééééé").unwrap().chars().count(),
+            12
+        );
+        assert_eq!(policy().validate("ééééééééééééé"), Err(NameError::TooLong));
+    }
+
+    #[test]
+    fn blocklist_ignores_case_spacing_and_punctuation() {
+        assert_eq!(policy().validate("xBadWordx"), Err(NameError::Blocked));
+        assert_eq!(
+            policy().validate("B.a d-w\u{200B}ord"),
+            Err(NameError::Blocked)
+        );
+        assert_eq!(policy().validate("\u{FF42}adword"), Err(NameError::Blocked));
+        assert!(policy().validate("bad words").is_err());
+        assert!(policy().validate("good word").is_ok());
+    }
+
+    #[test]
+    fn empty_blocklist_entries_are_ignored() {
+        let policy = NamePolicy::new(12, &["".to_string(), " ".to_string()]);
+        assert_eq!(policy.validate("Ada").unwrap(), "Ada");
+    }
+}
@@ -0,0 +1,221 @@
+//! Deep-space snapshots on disk.
+//!
+//! With `snapshot_dir` set, the game loop of the default room and of each
+//! room in `ServerConfig::rooms` restores `<snapshot_dir>/<room>.json`
+//! when it starts and writes it back when it stops (room closed or server
+//! shut down), and every `snapshot_interval_secs` if that is non-zero.
+//! Other rooms can be made up by any client, so they aren't kept. Writes
+//! go to a temporary file that is renamed into place, so a crash mid-write
+//! leaves the previous snapshot intact.
+//!
+//! A room's writes all go through its `SnapshotFile`, shared by every game
+//! loop the room has had. Periodic writes finish off the game loop and can
+//! overlap the final one, so writes are serialized and a snapshot older
+//! than the one on disk is dropped. A reopened room's new loop waits for
+//! the old one's final write before it restores.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::OwnedMutexGuard;
+
+use crate::state::GameSnapshot;
+
+/// Snapshot file of `room` under `dir`. Room names are validated to be
+/// plain identifiers (see `room.rs`), so they are safe as file names.
+pub fn snapshot_path(dir: &Path, room: &str) -> PathBuf {
+    dir.join(format!("{}.json", room))
+}
+
+/// Serialize a snapshot (done on the game loop; the write can happen elsewhere).
+pub fn encode(snapshot: &GameSnapshot) -> Vec<u8> {
+    serde_json::to_vec(snapshot).expect("GameSnapshot serializes")
+}
+
+/// Atomically replace `path` with `bytes`, creating its directory if needed.
+pub fn write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Write `snapshot` to `path`.
+pub fn save(path: &Path, snapshot: &GameSnapshot) -> io::Result<()> {
+    write(path, &encode(snapshot))
+}
+
+/// One room's snapshot file.
+#[derive(Debug)]
+pub struct SnapshotFile {
+    path: PathBuf,
+    /// Held by the game loop using the file, from restore to final write
+    owner: Arc<tokio::sync::Mutex<()>>,
+    /// Generation of the last snapshot encoded
+    encoded: AtomicU64,
+    /// Generation of the snapshot on disk; held while writing
+    written: Mutex<u64>,
+}
+
+/// A snapshot encoded for a `SnapshotFile`, numbered in the order taken.
+#[derive(Debug)]
+pub struct EncodedSnapshot {
+    generation: u64,
+    bytes: Vec<u8>,
+}
+
+impl SnapshotFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            owner: Arc::new(tokio::sync::Mutex::new(())),
+            encoded: AtomicU64::new(0),
+            written: Mutex::new(0),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Wait until no other game loop uses the file, and keep it until the
+    /// guard is dropped.
+    pub async fn claim(&self) -> OwnedMutexGuard<()> {
+        self.owner.clone().lock_owned().await
+    }
+
+    /// Serialize a snapshot (done on the game loop; the write can happen elsewhere).
+    pub fn encode(&self, snapshot: &GameSnapshot) -> EncodedSnapshot {
+        EncodedSnapshot {
+            generation: self.encoded.fetch_add(1, Ordering::Relaxed) + 1,
+            bytes: encode(snapshot),
+        }
+    }
+
+    /// Write `snapshot` unless a newer one is already on disk. Returns
+    /// whether it was written.
+    pub fn write(&self, snapshot: EncodedSnapshot) -> io::Result<bool> {
+        let mut written = self.written.lock().unwrap_or_else(|e| e.into_inner());
+        if snapshot.generation <= *written {
+            return Ok(false);
+        }
+        write(&self.path, &snapshot.bytes)?;
+        *written = snapshot.generation;
+        Ok(true)
+    }
+
+    /// Encode and write `snapshot` now.
+    pub fn save(&self, snapshot: &GameSnapshot) -> io::Result<bool> {
+        self.write(self.encode(snapshot))
+    }
+
+    pub fn load(&self) -> io::Result<Option<GameSnapshot>> {
+        load(&self.path)
+    }
+}
+
+/// Read the snapshot at `path`. `Ok(None)` if there is none yet.
+pub fn load(path: &Path) -> io::Result<Option<GameSnapshot>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_slice(&bytes)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DeepSpaceConfig, ServerConfig};
+    use crate::state::GameState;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pinball-persist-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn state_with_balls() -> GameState {
+        let config = ServerConfig {
+            bot_count: 0,
+            ..Default::default()
+        };
+        let mut state = GameState::new(&config, DeepSpaceConfig::default(), 1.5);
+        let (id, _) = state.add_player().unwrap();
+        state.ball_escaped(id, 0.5, 1.0);
+        state.ball_escaped(id, -0.5, 1.0);
+        state
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let dir = temp_dir("roundtrip");
+        let path = snapshot_path(&dir, "public");
+        assert!(load(&path).unwrap().is_none());
+
+        let state = state_with_balls();
+        save(&path, &state.snapshot()).unwrap();
+        let loaded = load(&path).unwrap().unwrap();
+        assert_eq!(loaded.deep_space.balls.len(), 2);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        // Overwrites in place
+        let mut state = state;
+        state.clear_balls();
+        save(&path, &state.snapshot()).unwrap();
+        assert!(load(&path).unwrap().unwrap().deep_space.balls.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn older_snapshots_never_replace_newer_ones() {
+        let dir = temp_dir("generations");
+        let file = SnapshotFile::new(snapshot_path(&dir, "public"));
+
+        let mut state = state_with_balls();
+        let periodic = file.encode(&state.snapshot());
+        state.clear_balls();
+        assert!(file.save(&state.snapshot()).unwrap());
+        // The periodic write finishing late is dropped
+        assert!(!file.write(periodic).unwrap());
+        assert!(file.load().unwrap().unwrap().deep_space.balls.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn claim_waits_for_the_previous_owner() {
+        let file = Arc::new(SnapshotFile::new(snapshot_path(
+            &temp_dir("claim"),
+            "public",
+        )));
+        let first = file.claim().await;
+        let waiting = tokio::spawn({
+            let file = file.clone();
+            async move {
+                let _second = file.claim().await;
+            }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+        drop(first);
+        waiting.await.unwrap();
+    }
+
+    #[test]
+    fn corrupt_snapshot_is_an_error() {
+        let dir = temp_dir("corrupt");
+        let path = snapshot_path(&dir, "public");
+        write(&path, b"{ not json").unwrap();
+        let err = load(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
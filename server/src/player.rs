@@ -17,6 +17,9 @@ pub struct Player {
     /// Whether this player is a bot. Bots don't capture their own balls.
     #[serde(default)]
     pub is_bot: bool,
+    /// Validated display name (see `names.rs`). Empty = no name.
+    #[serde(default)]
+    pub name: String,
     /// Last activity timestamp (server elapsed seconds). 0.0 = never active.
     #[serde(skip)]
     pub last_activity: f64,
@@ -25,7 +28,7 @@ pub struct Player {
 /// Generate a color from player ID using golden angle hue distribution.
 pub fn color_from_id(id: u32) -> u32 {
     // 137° ≈ golden angle — maximizes hue separation between consecutive IDs
-    let hue = ((id as u32).wrapping_mul(137)) % 360;
+    let hue = (id.wrapping_mul(137)) % 360;
     hsv_to_rgb(hue as f64, 0.55, 0.95)
 }
 
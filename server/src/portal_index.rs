@@ -0,0 +1,201 @@
+//! Spatial index of player portals for `SphereDeepSpace::tick`.
+//!
+//! Space is cut into latitude bands of equal angular height, each split
+//! into equal longitude sectors, so finding the cell of a point is O(1).
+//! Every cell lists the portals whose capture cap (`portal_alpha` around
+//! the portal) reaches into it, in player order. A ball only needs testing
+//! against the portals listed for its cell; since the lists keep player
+//! order and are a superset of the portals that can capture it, scanning
+//! them picks exactly the portal a scan of every player would.
+
+use std::f64::consts::{PI, TAU};
+
+use crate::vec3::Vec3;
+
+/// Smallest cell size (radians), so tiny capture radii don't explode the grid.
+const MIN_CELL_SIZE: f64 = 0.05;
+
+/// Added to the capture radius when filling cells, so rounding in
+/// `acos`/`atan2` can't drop a portal right at a cell border.
+const RADIUS_PAD: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy)]
+struct Band {
+    first_cell: usize,
+    sectors: usize,
+}
+
+/// Cell -> portal lists, stored flat: the portals of cell `c` are
+/// `portals[starts[c]..starts[c + 1]]`.
+#[derive(Debug, Clone)]
+pub struct PortalIndex {
+    band_height: f64,
+    bands: Vec<Band>,
+    starts: Vec<usize>,
+    portals: Vec<usize>,
+}
+
+impl PortalIndex {
+    /// Index `portals` (positions in player order) for captures within
+    /// `radius` radians.
+    pub fn new(portals: &[Vec3], radius: f64) -> Self {
+        let cell_size = radius.max(MIN_CELL_SIZE);
+        let band_count = ((PI / cell_size).ceil() as usize).max(1);
+        let band_height = PI / band_count as f64;
+
+        let mut bands = Vec::with_capacity(band_count);
+        let mut cell_count = 0;
+        for b in 0..band_count {
+            let (top, bottom) = (b as f64 * band_height, (b + 1) as f64 * band_height);
+            // Widest circle of latitude in the band
+            let widest = if top <= PI / 2.0 && bottom >= PI / 2.0 {
+                1.0
+            } else {
+                top.sin().max(bottom.sin())
+            };
+            let sectors = ((TAU * widest / cell_size).ceil() as usize).max(1);
+            bands.push(Band {
+                first_cell: cell_count,
+                sectors,
+            });
+            cell_count += sectors;
+        }
+
+        let mut index = Self {
+            band_height,
+            bands,
+            starts: Vec::new(),
+            portals: Vec::new(),
+        };
+        let mut cells: Vec<Vec<usize>> = vec![Vec::new(); cell_count];
+        for (i, &pos) in portals.iter().enumerate() {
+            index.for_each_cell_near(pos, radius + RADIUS_PAD, |cell| cells[cell].push(i));
+        }
+
+        index.starts.reserve(cell_count + 1);
+        index.starts.push(0);
+        for cell in cells {
+            index.portals.extend(cell);
+            index.starts.push(index.portals.len());
+        }
+        index
+    }
+
+    /// Portals (player indices, ascending) that may be within the radius of `pos`.
+    pub fn candidates(&self, pos: Vec3) -> &[usize] {
+        let cell = self.cell_of(pos);
+        &self.portals[self.starts[cell]..self.starts[cell + 1]]
+    }
+
+    fn cell_of(&self, pos: Vec3) -> usize {
+        let (theta, phi) = polar(pos);
+        let band = ((theta / self.band_height) as usize).min(self.bands.len() - 1);
+        let Band {
+            first_cell,
+            sectors,
+        } = self.bands[band];
+        first_cell + ((phi / TAU * sectors as f64) as usize).min(sectors - 1)
+    }
+
+    /// Call `f` once for every cell the cap of `radius` around `pos` may
+    /// touch: the cells overlapping the cap's latitude/longitude bounds.
+    fn for_each_cell_near(&self, pos: Vec3, radius: f64, mut f: impl FnMut(usize)) {
+        let (theta, phi) = polar(pos);
+        let (top, bottom) = (theta - radius, theta + radius);
+        // A cap over a pole spans every longitude
+        let half_width = if top <= 0.0 || bottom >= PI {
+            None
+        } else {
+            Some((radius.sin() / theta.sin()).min(1.0).asin())
+        };
+
+        let last_band = self.bands.len() - 1;
+        let first = ((top.max(0.0) / self.band_height) as usize).min(last_band);
+        let last = ((bottom.min(PI) / self.band_height) as usize).min(last_band);
+        for band in &self.bands[first..=last] {
+            let sectors = band.sectors as i64;
+            let span = half_width.map(|w| {
+                let sector = |a: f64| (a / TAU * sectors as f64).floor() as i64;
+                (sector(phi - w), sector(phi + w))
+            });
+            match span {
+                Some((lo, hi)) if hi - lo + 1 < sectors => {
+                    for s in lo..=hi {
+                        f(band.first_cell + s.rem_euclid(sectors) as usize);
+                    }
+                }
+                _ => (0..band.sectors).for_each(|s| f(band.first_cell + s)),
+            }
+        }
+    }
+}
+
+/// Polar angle from +y in [0, PI] and longitude in [0, TAU].
+fn polar(pos: Vec3) -> (f64, f64) {
+    (pos.y.clamp(-1.0, 1.0).acos(), pos.z.atan2(pos.x) + PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::fibonacci_sphere;
+    use crate::vec3::{dot, normalize};
+
+    /// Every portal within `radius` of a probe must be a candidate.
+    fn assert_covers(portals: &[Vec3], radius: f64) {
+        let index = PortalIndex::new(portals, radius);
+        let cos_radius = radius.cos();
+        for probe in fibonacci_sphere(1500) {
+            let candidates = index.candidates(probe);
+            assert!(candidates.windows(2).all(|w| w[0] < w[1]));
+            for (i, &p) in portals.iter().enumerate() {
+                if dot(probe, p) >= cos_radius {
+                    assert!(
+                        candidates.binary_search(&i).is_ok(),
+                        "portal {} missing near {:?} (radius {})",
+                        i,
+                        probe,
+                        radius
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn candidates_cover_every_portal_in_range() {
+        let portals = fibonacci_sphere(150);
+        for radius in [0.01, 0.15, 0.4, 1.5, 3.0, PI] {
+            assert_covers(&portals, radius);
+        }
+    }
+
+    #[test]
+    fn covers_portals_at_the_poles_and_the_seam() {
+        let portals = [
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            normalize(Vec3::new(-0.999, 0.0, -0.0447)),
+        ];
+        assert_covers(&portals, 0.15);
+    }
+
+    #[test]
+    fn small_radius_prunes_far_portals() {
+        let portals = fibonacci_sphere(1000);
+        let index = PortalIndex::new(&portals, 0.15);
+        let worst = fibonacci_sphere(500)
+            .into_iter()
+            .map(|p| index.candidates(p).len())
+            .max()
+            .unwrap();
+        assert!(worst < 100, "{} candidates", worst);
+    }
+
+    #[test]
+    fn empty_index_has_no_candidates() {
+        let index = PortalIndex::new(&[], 0.15);
+        assert!(index.candidates(Vec3::new(1.0, 0.0, 0.0)).is_empty());
+    }
+}
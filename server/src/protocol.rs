@@ -11,6 +11,7 @@ pub fn ball_to_wire(ball: &crate::deep_space::SpaceBall3D) -> BallWire {
             round4(ball.axis.z),
         ],
         omega: round4(ball.omega),
+        hops: ball.hops,
     }
 }
 
@@ -27,5 +28,6 @@ pub fn player_to_wire(player: &crate::player::Player, balls_in_flight: u32) -> P
         paused: player.paused,
         balls_produced: player.balls_produced,
         balls_in_flight,
+        name: player.name.clone(),
     }
 }
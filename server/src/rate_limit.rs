@@ -0,0 +1,372 @@
+//! Token-bucket rate limiting of client messages.
+//!
+//! Every rate-limited message type is a `MessageKind` with a `RateLimit`
+//! in `ServerConfig::rate_limits`: a bucket of `burst` tokens refilled at
+//! `per_sec`, and the `RateLimitPolicy` applied to messages that find it
+//! empty. Each connection gets a `ConnectionLimiter` with its own buckets;
+//! with `per_ip_connections` set, it also draws from buckets shared by every
+//! connection from the same IP, which hold that many connections' worth.
+//!
+//! Time is passed in, so limits can be tested without sockets or sleeping.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::config::{RateLimit, RateLimitPolicy, RateLimits};
+use crate::protocol::ClientMsg;
+
+/// Refilling bucket of tokens; each allowed message takes one.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    per_sec: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket of `capacity` tokens refilled at `per_sec`.
+    pub fn new(per_sec: f64, capacity: u32, now: Instant) -> Self {
+        Self {
+            per_sec,
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            last: now,
+        }
+    }
+
+    /// Take a token if there is one.
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        self.take_up_to(1, now) == 1
+    }
+
+    /// Take up to `n` whole tokens; returns how many were taken.
+    pub fn take_up_to(&mut self, n: u32, now: Instant) -> u32 {
+        self.refill(now);
+        let taken = (self.tokens.floor() as u32).min(n);
+        self.tokens -= taken as f64;
+        taken
+    }
+
+    fn has_token(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= 1.0
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.capacity);
+        self.last = self.last.max(now);
+    }
+}
+
+/// Client message types that are rate limited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    BallEscaped,
+    SetPaused,
+    Activity,
+    RequestKeyframe,
+    Ping,
+    Emote,
+    BumperHits,
+    SetName,
+}
+
+impl MessageKind {
+    pub const ALL: [MessageKind; 8] = [
+        MessageKind::BallEscaped,
+        MessageKind::SetPaused,
+        MessageKind::Activity,
+        MessageKind::RequestKeyframe,
+        MessageKind::Ping,
+        MessageKind::Emote,
+        MessageKind::BumperHits,
+        MessageKind::SetName,
+    ];
+
+    /// The kind of `msg`, or `None` if it isn't rate limited.
+    pub fn of(msg: &ClientMsg) -> Option<MessageKind> {
+        match msg {
+            ClientMsg::Hello { .. } | ClientMsg::TransferAck { .. } => None,
+            ClientMsg::BallEscaped { .. } => Some(MessageKind::BallEscaped),
+            ClientMsg::SetPaused { .. } => Some(MessageKind::SetPaused),
+            ClientMsg::Activity => Some(MessageKind::Activity),
+            ClientMsg::RequestKeyframe => Some(MessageKind::RequestKeyframe),
+            ClientMsg::Ping { .. } => Some(MessageKind::Ping),
+            ClientMsg::Emote { .. } => Some(MessageKind::Emote),
+            ClientMsg::BumperHits { .. } => Some(MessageKind::BumperHits),
+            ClientMsg::SetName { .. } => Some(MessageKind::SetName),
+        }
+    }
+
+    /// Wire name of the message, as the `message` metrics label.
+    pub fn label(self) -> &'static str {
+        match self {
+            MessageKind::BallEscaped => "ball_escaped",
+            MessageKind::SetPaused => "set_paused",
+            MessageKind::Activity => "activity",
+            MessageKind::RequestKeyframe => "request_keyframe",
+            MessageKind::Ping => "ping",
+            MessageKind::Emote => "emote",
+            MessageKind::BumperHits => "bumper_hits",
+            MessageKind::SetName => "set_name",
+        }
+    }
+
+    fn limit(self, limits: &RateLimits) -> &RateLimit {
+        match self {
+            MessageKind::BallEscaped => &limits.ball_escaped,
+            MessageKind::SetPaused => &limits.set_paused,
+            MessageKind::Activity => &limits.activity,
+            MessageKind::RequestKeyframe => &limits.request_keyframe,
+            MessageKind::Ping => &limits.ping,
+            MessageKind::Emote => &limits.emote,
+            MessageKind::BumperHits => &limits.bumper_hits,
+            MessageKind::SetName => &limits.set_name,
+        }
+    }
+}
+
+/// One bucket per `MessageKind`, each `scale` times the configured limit.
+#[derive(Debug)]
+struct Buckets([TokenBucket; MessageKind::ALL.len()]);
+
+impl Buckets {
+    fn new(limits: &RateLimits, scale: u32, now: Instant) -> Self {
+        Self(MessageKind::ALL.map(|kind| {
+            let limit = kind.limit(limits);
+            TokenBucket::new(limit.per_sec * scale as f64, limit.burst * scale, now)
+        }))
+    }
+
+    fn get(&mut self, kind: MessageKind) -> &mut TokenBucket {
+        &mut self.0[kind as usize]
+    }
+}
+
+/// A message over its limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limited {
+    pub policy: RateLimitPolicy,
+    /// The IP's shared limit was hit rather than the connection's own
+    pub per_ip: bool,
+}
+
+/// Server-wide limiter, shared by all connections (see `AppState`).
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    /// Shared buckets of every IP with an open connection
+    ips: Mutex<HashMap<IpAddr, Arc<Mutex<Buckets>>>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            ips: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Limiter for a new connection from `ip`.
+    pub fn connection(self: &Arc<Self>, ip: IpAddr, now: Instant) -> ConnectionLimiter {
+        let shared = (self.limits.per_ip_connections > 0).then(|| {
+            let mut ips = self.ips.lock().unwrap();
+            let buckets = ips.entry(ip).or_insert_with(|| {
+                Arc::new(Mutex::new(Buckets::new(
+                    &self.limits,
+                    self.limits.per_ip_connections,
+                    now,
+                )))
+            });
+            (ip, buckets.clone())
+        });
+        ConnectionLimiter {
+            limiter: self.clone(),
+            own: Buckets::new(&self.limits, 1, now),
+            shared,
+        }
+    }
+
+    /// IPs with shared buckets, i.e. with open connections.
+    pub fn tracked_ips(&self) -> usize {
+        self.ips.lock().unwrap().len()
+    }
+}
+
+/// Rate limits of one connection. Dropping the last connection from an IP
+/// forgets that IP's shared buckets.
+#[derive(Debug)]
+pub struct ConnectionLimiter {
+    limiter: Arc<RateLimiter>,
+    own: Buckets,
+    shared: Option<(IpAddr, Arc<Mutex<Buckets>>)>,
+}
+
+impl ConnectionLimiter {
+    /// Take a token for a `kind` message received at `now`. Messages over
+    /// either the connection's or the IP's limit take nothing and get the
+    /// policy for `kind`.
+    pub fn check(&mut self, kind: MessageKind, now: Instant) -> Result<(), Limited> {
+        let policy = kind.limit(&self.limiter.limits).policy;
+        let own = self.own.get(kind);
+        if !own.has_token(now) {
+            return Err(Limited {
+                policy,
+                per_ip: false,
+            });
+        }
+        if let Some((_, shared)) = &self.shared {
+            if !shared.lock().unwrap().get(kind).try_take(now) {
+                return Err(Limited {
+                    policy,
+                    per_ip: true,
+                });
+            }
+        }
+        own.try_take(now);
+        Ok(())
+    }
+}
+
+impl Drop for ConnectionLimiter {
+    fn drop(&mut self) {
+        if let Some((ip, shared)) = self.shared.take() {
+            let mut ips = self.limiter.ips.lock().unwrap();
+            // The map holds the other reference
+            if Arc::strong_count(&shared) == 2 {
+                ips.remove(&ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    fn ms(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn bucket_allows_burst_then_refills() {
+        let t0 = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, 3, t0);
+        assert!((0..3).all(|_| bucket.try_take(t0)));
+        assert!(!bucket.try_take(t0));
+        // Half a second refills one token
+        assert!(!bucket.try_take(ms(t0, 400)));
+        assert!(bucket.try_take(ms(t0, 500)));
+        assert!(!bucket.try_take(ms(t0, 500)));
+        // Never more than the burst after a long pause
+        assert_eq!(bucket.take_up_to(10, ms(t0, 60_000)), 3);
+    }
+
+    #[test]
+    fn take_up_to_takes_whole_tokens() {
+        let t0 = Instant::now();
+        let mut bucket = TokenBucket::new(20.0, 20, t0);
+        assert_eq!(bucket.take_up_to(15, t0), 15);
+        assert_eq!(bucket.take_up_to(15, t0), 5);
+        assert_eq!(bucket.take_up_to(15, ms(t0, 120)), 2);
+    }
+
+    #[test]
+    fn connection_limit_applies_the_message_policy() {
+        let t0 = Instant::now();
+        let limiter = Arc::new(RateLimiter::new(RateLimits::default()));
+        let mut conn = limiter.connection(ip(1), t0);
+        for _ in 0..30 {
+            assert_eq!(conn.check(MessageKind::BallEscaped, t0), Ok(()));
+        }
+        assert_eq!(
+            conn.check(MessageKind::BallEscaped, t0),
+            Err(Limited {
+                policy: RateLimitPolicy::Disconnect,
+                per_ip: false
+            })
+        );
+        // Other kinds have their own buckets
+        assert_eq!(conn.check(MessageKind::Activity, t0), Ok(()));
+        assert_eq!(
+            conn.check(MessageKind::Activity, t0).unwrap_err().policy,
+            RateLimitPolicy::Drop
+        );
+        assert_eq!(conn.check(MessageKind::Activity, ms(t0, 1000)), Ok(()));
+        // Per-IP limiting is off by default
+        assert_eq!(limiter.tracked_ips(), 0);
+    }
+
+    #[test]
+    fn ip_limit_is_shared_across_connections() {
+        let t0 = Instant::now();
+        let limits = RateLimits {
+            set_paused: RateLimit::new(1.0, 2, RateLimitPolicy::Ignore),
+            per_ip_connections: 2,
+            ..Default::default()
+        };
+        let limiter = Arc::new(RateLimiter::new(limits));
+        let mut conns: Vec<_> = (0..3).map(|_| limiter.connection(ip(1), t0)).collect();
+        let mut other_ip = limiter.connection(ip(2), t0);
+        assert_eq!(limiter.tracked_ips(), 2);
+
+        // The IP gets 4 messages at once, whichever connections send them
+        for conn in &mut conns[..2] {
+            assert_eq!(conn.check(MessageKind::SetPaused, t0), Ok(()));
+            assert_eq!(conn.check(MessageKind::SetPaused, t0), Ok(()));
+        }
+        assert_eq!(
+            conns[2].check(MessageKind::SetPaused, t0),
+            Err(Limited {
+                policy: RateLimitPolicy::Ignore,
+                per_ip: true
+            })
+        );
+        assert_eq!(other_ip.check(MessageKind::SetPaused, t0), Ok(()));
+
+        // A connection over its own limit doesn't use up the IP's tokens
+        assert!(
+            !conns[0]
+                .check(MessageKind::SetPaused, ms(t0, 500))
+                .unwrap_err()
+                .per_ip
+        );
+        assert_eq!(conns[2].check(MessageKind::SetPaused, ms(t0, 500)), Ok(()));
+
+        drop(conns);
+        assert_eq!(limiter.tracked_ips(), 1);
+        drop(other_ip);
+        assert_eq!(limiter.tracked_ips(), 0);
+    }
+
+    #[test]
+    fn every_client_message_is_classified() {
+        let msgs = [
+            r#"{"type":"ball_escaped","vx":1,"vy":-1}"#,
+            r#"{"type":"set_paused","paused":true}"#,
+            r#"{"type":"activity"}"#,
+            r#"{"type":"request_keyframe"}"#,
+            r#"{"type":"ping","clientTime":1}"#,
+            r#"{"type":"emote","kind":"wave"}"#,
+            r#"{"type":"bumper_hits","count":1}"#,
+            r#"{"type":"set_name","name":"a"}"#,
+        ];
+        for (json, kind) in msgs.iter().zip(MessageKind::ALL) {
+            let msg: ClientMsg = serde_json::from_str(json).unwrap();
+            assert_eq!(MessageKind::of(&msg), Some(kind));
+            assert_eq!(json.split('"').nth(3), Some(kind.label()));
+        }
+        let ack: ClientMsg = serde_json::from_str(r#"{"type":"transfer_ack","seq":1}"#).unwrap();
+        assert_eq!(MessageKind::of(&ack), None);
+    }
+}
@@ -0,0 +1,564 @@
+//! Command recordings and deterministic replay.
+//!
+//! With `record_dir` set, each room's game loop writes
+//! `<record_dir>/<room>-<unix millis>.jsonl`: a header with the room's
+//! config and the state it started from (seed and RNG position included),
+//! then every command that changed its `GameState` tagged with the tick it
+//! was applied after, and a checksum of deep space after every tick.
+//! `replay` (and the `replay` binary) feeds the same commands to a fresh
+//! `GameState` and stops at the first tick whose checksum differs.
+//!
+//! Connection-side decisions are recorded as their outcome: a client the
+//! game loop drops mid-tick shows up as a `leave` after that tick.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{DeepSpaceConfig, ServerConfig};
+use crate::state::{GameSnapshot, GameState};
+
+/// Bumped when the recording format, the meaning of a command, or the
+/// seeded simulation changes, so older recordings are refused rather than
+/// reported as diverging. 2: reroutes draw one random number per target
+/// pick instead of one per eligible player.
+pub const RECORD_VERSION: u32 = 2;
+
+/// Recording file of a room started at `unix_millis` under `dir`. Room
+/// names are plain identifiers (see `room.rs`), so they are safe here.
+pub fn recording_path(dir: &Path, room: &str, unix_millis: u128) -> PathBuf {
+    dir.join(format!("{}-{}.jsonl", room, unix_millis))
+}
+
+/// First line of a recording: everything needed to rebuild the room's
+/// `GameState` as it was before the first recorded tick.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    /// The room's effective config, `admin_token` and `join_token_secret`
+    /// removed
+    pub server_config: ServerConfig,
+    pub deep_space: DeepSpaceConfig,
+    pub capture_speed: f64,
+    /// State after any snapshot was restored
+    pub start: GameSnapshot,
+}
+
+impl Header {
+    pub fn new(state: &GameState, server_config: &ServerConfig, capture_speed: f64) -> Self {
+        Self {
+            version: RECORD_VERSION,
+            server_config: ServerConfig {
+                admin_token: None,
+                join_token_secret: None,
+                ..server_config.clone()
+            },
+            deep_space: state.config,
+            capture_speed,
+            start: state.snapshot(),
+        }
+    }
+}
+
+/// One line of a recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entry {
+    Header(Box<Header>),
+    /// `command` was applied after `tick` ticks had run
+    Command {
+        tick: u64,
+        command: Command,
+    },
+    /// Tick number `tick` ran, leaving deep space with `checksum`
+    Tick {
+        tick: u64,
+        checksum: u64,
+    },
+}
+
+/// A state-changing game loop command, as applied.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Command {
+    /// A player joined as `id` (None: the room was full), resuming the
+    /// departed player `resumed` if their identity was still retained
+    Join {
+        id: Option<u32>,
+        resumed: Option<u32>,
+        name: Option<String>,
+    },
+    Leave {
+        id: u32,
+    },
+    BallEscaped {
+        owner_id: u32,
+        vx: f64,
+        vy: f64,
+        target_id: Option<u32>,
+        ball_id: Option<u32>,
+    },
+    SetPaused {
+        player_id: u32,
+        paused: bool,
+    },
+    Activity {
+        player_id: u32,
+    },
+    BumperHits {
+        player_id: u32,
+        count: u32,
+    },
+    SetName {
+        player_id: u32,
+        name: String,
+    },
+    TransferAck {
+        player_id: u32,
+        seq: u32,
+    },
+    AddBot,
+    RemoveBot {
+        player_id: u32,
+    },
+    ClearBalls,
+}
+
+/// Appends a room's entries to a recording. The first write error is
+/// logged and turns the recorder off; the game carries on unrecorded.
+pub struct Recorder<W: Write> {
+    out: Option<W>,
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Start a new recording at `path`. Never overwrites an existing file.
+    pub fn create(path: &Path, header: Header) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        Self::new(BufWriter::new(file), header)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(out: W, header: Header) -> io::Result<Self> {
+        let mut out = out;
+        write_entry(&mut out, &Entry::Header(Box::new(header)))?;
+        out.flush()?;
+        Ok(Self { out: Some(out) })
+    }
+
+    /// A recorder that drops everything.
+    pub fn off() -> Self {
+        Self { out: None }
+    }
+
+    pub fn command(&mut self, tick: u64, command: Command) {
+        self.write(&Entry::Command { tick, command }, false);
+    }
+
+    /// Record a finished tick and flush, so a crash loses at most the
+    /// commands of the tick in progress.
+    pub fn tick(&mut self, tick: u64, checksum: u64) {
+        self.write(&Entry::Tick { tick, checksum }, true);
+    }
+
+    /// The underlying writer, unless a write failed.
+    pub fn into_inner(self) -> Option<W> {
+        self.out
+    }
+
+    fn write(&mut self, entry: &Entry, flush: bool) {
+        let Some(out) = &mut self.out else { return };
+        let written =
+            write_entry(out, entry).and_then(|()| if flush { out.flush() } else { Ok(()) });
+        if let Err(e) = written {
+            tracing::error!("Failed to write recording, recording stopped: {}", e);
+            self.out = None;
+        }
+    }
+}
+
+fn write_entry(out: &mut impl Write, entry: &Entry) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, entry)?;
+    out.write_all(b"\n")
+}
+
+/// What a replay got through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplaySummary {
+    pub ticks: u64,
+    pub commands: u64,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// Line `line` (1-based) is not a valid entry
+    Parse {
+        line: usize,
+        error: String,
+    },
+    MissingHeader,
+    UnsupportedVersion(u32),
+    /// The header's start state could not be restored
+    BadStart(String),
+    /// Line `line` claims tick `tick`, but `expected` ticks had run
+    OutOfOrder {
+        line: usize,
+        tick: u64,
+        expected: u64,
+    },
+    /// Deep space after tick `tick` does not match the recording
+    Diverged {
+        tick: u64,
+        expected: u64,
+        actual: u64,
+    },
+    /// A join after tick `tick` got a different id than recorded
+    JoinMismatch {
+        tick: u64,
+        expected: Option<u32>,
+        actual: Option<u32>,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "read failed: {}", e),
+            ReplayError::Parse { line, error } => write!(f, "line {}: {}", line, error),
+            ReplayError::MissingHeader => write!(f, "recording does not start with a header"),
+            ReplayError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported recording version {} (expected {})",
+                v, RECORD_VERSION
+            ),
+            ReplayError::BadStart(e) => write!(f, "cannot restore start state: {}", e),
+            ReplayError::OutOfOrder {
+                line,
+                tick,
+                expected,
+            } => write!(
+                f,
+                "line {}: entry for tick {} after {} ticks",
+                line, tick, expected
+            ),
+            ReplayError::Diverged {
+                tick,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "diverged at tick {}: checksum {:016x}, recorded {:016x}",
+                tick, actual, expected
+            ),
+            ReplayError::JoinMismatch {
+                tick,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "diverged after tick {}: join got {:?}, recorded {:?}",
+                tick, actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+/// Re-run a recording and check every tick's checksum.
+pub fn replay(input: impl BufRead) -> Result<ReplaySummary, ReplayError> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.as_ref().is_ok_and(|l| l.trim().is_empty()));
+
+    let header = match lines.next() {
+        Some((line, text)) => match parse(line, &text?)? {
+            Entry::Header(header) => *header,
+            _ => return Err(ReplayError::MissingHeader),
+        },
+        None => return Err(ReplayError::MissingHeader),
+    };
+    if header.version != RECORD_VERSION {
+        return Err(ReplayError::UnsupportedVersion(header.version));
+    }
+
+    let mut state = GameState::new(
+        &header.server_config,
+        header.deep_space,
+        header.capture_speed,
+    );
+    state.restore(header.start).map_err(ReplayError::BadStart)?;
+    let dt = 1.0 / header.server_config.tick_rate_hz as f64;
+
+    let mut summary = ReplaySummary {
+        ticks: 0,
+        commands: 0,
+    };
+    for (line, text) in lines {
+        match parse(line, &text?)? {
+            Entry::Header(_) => {
+                return Err(ReplayError::Parse {
+                    line,
+                    error: "second header".to_string(),
+                })
+            }
+            Entry::Tick { tick, checksum } => {
+                if tick != summary.ticks + 1 {
+                    return Err(ReplayError::OutOfOrder {
+                        line,
+                        tick,
+                        expected: summary.ticks,
+                    });
+                }
+                for cap in state.tick(dt) {
+                    state.begin_transfer(&cap);
+                }
+                summary.ticks = tick;
+                let actual = state.deep_space.checksum();
+                if actual != checksum {
+                    return Err(ReplayError::Diverged {
+                        tick,
+                        expected: checksum,
+                        actual,
+                    });
+                }
+            }
+            Entry::Command { tick, command } => {
+                if tick != summary.ticks {
+                    return Err(ReplayError::OutOfOrder {
+                        line,
+                        tick,
+                        expected: summary.ticks,
+                    });
+                }
+                apply(&mut state, tick, command)?;
+                summary.commands += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn parse(line: usize, text: &str) -> Result<Entry, ReplayError> {
+    serde_json::from_str(text).map_err(|e| ReplayError::Parse {
+        line,
+        error: e.to_string(),
+    })
+}
+
+/// Apply `command` the way `game_loop` did when it was recorded.
+fn apply(state: &mut GameState, tick: u64, command: Command) -> Result<(), ReplayError> {
+    match command {
+        Command::Join { id, resumed, name } => {
+            let token = resumed.and_then(|r| state.retained_token(r).map(str::to_string));
+            let actual = state.join_player(token.as_deref()).map(|(id, _)| id);
+            if actual != id {
+                return Err(ReplayError::JoinMismatch {
+                    tick,
+                    expected: id,
+                    actual,
+                });
+            }
+            if let (Some(id), Some(name)) = (actual, name) {
+                let _ = state.set_player_name(id, &name);
+            }
+        }
+        Command::Leave { id } => {
+            state.remove_player(id);
+        }
+        Command::BallEscaped {
+            owner_id,
+            vx,
+            vy,
+            target_id,
+            ball_id,
+        } => {
+            state.ball_escaped_to(owner_id, vx, vy, target_id, ball_id);
+        }
+        Command::SetPaused { player_id, paused } => {
+            state.set_player_paused(player_id, paused);
+        }
+        Command::Activity { player_id } => state.player_activity(player_id),
+        Command::BumperHits { player_id, count } => state.add_bumper_hits(player_id, count),
+        Command::SetName { player_id, name } => {
+            let _ = state.set_player_name(player_id, &name);
+        }
+        Command::TransferAck { player_id, seq } => {
+            state.ack_transfer(player_id, seq);
+        }
+        Command::AddBot => {
+            state.add_bot();
+        }
+        Command::RemoveBot { player_id } => {
+            state.remove_bot(player_id);
+        }
+        Command::ClearBalls => {
+            state.clear_balls();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> ServerConfig {
+        ServerConfig {
+            tick_rate_hz: 10,
+            cell_count: 16,
+            bot_count: 0,
+            rng_seed: 7,
+            admin_token: Some("secret".to_string()),
+            join_token_secret: Some("a long enough secret".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Record a short session with one player and one ball, the way the
+    /// game loop would. Returns the recording.
+    fn record_session(config: &ServerConfig) -> Vec<u8> {
+        let mut state = GameState::new(config, DeepSpaceConfig::default(), 1.5);
+        let header = Header::new(&state, config, 1.5);
+        let mut recorder = Recorder::new(Vec::new(), header).unwrap();
+
+        let (id, _) = state.join_player(None).unwrap();
+        recorder.command(
+            0,
+            Command::Join {
+                id: Some(id),
+                resumed: None,
+                name: None,
+            },
+        );
+        for tick in 1..=30 {
+            if tick == 5 {
+                state.ball_escaped_to(id, 0.4, 1.2, None, None).unwrap();
+                recorder.command(
+                    tick - 1,
+                    Command::BallEscaped {
+                        owner_id: id,
+                        vx: 0.4,
+                        vy: 1.2,
+                        target_id: None,
+                        ball_id: None,
+                    },
+                );
+            }
+            for cap in state.tick(0.1) {
+                state.begin_transfer(&cap);
+            }
+            recorder.tick(tick, state.deep_space.checksum());
+        }
+        recorder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn recording_replays_to_the_same_checksums() {
+        let recording = record_session(&test_config());
+        let summary = replay(recording.as_slice()).unwrap();
+        assert_eq!(
+            summary,
+            ReplaySummary {
+                ticks: 30,
+                commands: 2
+            }
+        );
+    }
+
+    #[test]
+    fn header_leaves_out_secrets() {
+        let recording = record_session(&test_config());
+        let text = String::from_utf8(recording).unwrap();
+        assert!(!text.contains("\"secret\""));
+        assert!(!text.contains("a long enough secret"));
+        assert!(text.starts_with("{\"type\":\"header\""));
+    }
+
+    #[test]
+    fn divergence_is_reported_at_its_tick() {
+        let recording = String::from_utf8(record_session(&test_config())).unwrap();
+        let tampered: String = recording
+            .lines()
+            .map(|line| match serde_json::from_str(line).unwrap() {
+                Entry::Tick { tick: 12, checksum } => serde_json::to_string(&Entry::Tick {
+                    tick: 12,
+                    checksum: checksum ^ 1,
+                })
+                .unwrap(),
+                _ => line.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        match replay(tampered.as_bytes()) {
+            Err(ReplayError::Diverged { tick, .. }) => assert_eq!(tick, 12),
+            other => panic!("expected divergence, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn different_seed_diverges() {
+        let recording = record_session(&test_config());
+        let mut lines: Vec<String> = String::from_utf8(recording)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let Entry::Header(mut header) = serde_json::from_str(&lines[0]).unwrap() else {
+            panic!("no header");
+        };
+        header.start.rng.stream += 1;
+        lines[0] = serde_json::to_string(&Entry::Header(header)).unwrap();
+
+        // The ball's speed is drawn from the RNG when it is added (tick 4)
+        match replay(lines.join("\n").as_bytes()) {
+            Err(ReplayError::Diverged { tick, .. }) => assert_eq!(tick, 5),
+            other => panic!("expected divergence, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_recordings_without_header_or_out_of_order() {
+        assert!(matches!(replay(&b""[..]), Err(ReplayError::MissingHeader)));
+        assert!(matches!(
+            replay(&b"{\"type\":\"tick\",\"tick\":1,\"checksum\":0}"[..]),
+            Err(ReplayError::MissingHeader)
+        ));
+
+        let recording = String::from_utf8(record_session(&test_config())).unwrap();
+        let mut lines: Vec<&str> = recording.lines().collect();
+        lines.swap(3, 4);
+        assert!(matches!(
+            replay(lines.join("\n").as_bytes()),
+            Err(ReplayError::OutOfOrder { line: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_recordings_from_older_versions() {
+        let recording = String::from_utf8(record_session(&test_config())).unwrap();
+        let current = format!("\"version\":{}", RECORD_VERSION);
+        assert!(recording.lines().next().unwrap().contains(&current));
+        let old = recording.replacen(&current, "\"version\":1", 1);
+        assert!(matches!(
+            replay(old.as_bytes()),
+            Err(ReplayError::UnsupportedVersion(1))
+        ));
+    }
+}
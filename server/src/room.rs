@@ -0,0 +1,573 @@
+//! Independent game worlds on one server.
+//!
+//! Each room is its own game loop with its own `GameState`, sphere and bots.
+//! Clients pick one with `/ws?room=<name>`; without it they join the default
+//! (public) room. Named rooms are created on first join and torn down when
+//! their last connection leaves. The default room lives for the whole
+//! process so its bots keep the public sphere busy.
+//!
+//! Spectators (`/ws?spectate=1`) keep a room open like players do but are
+//! counted separately and never take one of its connection slots.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::sync::{broadcast, mpsc, oneshot};
+
+use crate::config::ServerConfig;
+use crate::game_loop::{run_game_loop_with_config, GameBroadcast, GameCommand};
+use crate::lifecycle::Heartbeat;
+use crate::metrics::RoomMetrics;
+use crate::persist::{self, SnapshotFile};
+use crate::record;
+
+/// Room used when a client doesn't ask for one.
+pub const DEFAULT_ROOM: &str = "public";
+
+/// Maximum length of a room name (bytes).
+pub const MAX_ROOM_NAME_LEN: usize = 32;
+
+/// Room names are short ASCII identifiers: letters, digits, `-` and `_`.
+pub fn validate_room_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_ROOM_NAME_LEN {
+        return Err(format!(
+            "room name must be 1-{} characters",
+            MAX_ROOM_NAME_LEN
+        ));
+    }
+    if !name
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    {
+        return Err("room name may only contain letters, digits, '-' and '_'".to_string());
+    }
+    Ok(())
+}
+
+/// Why a connection could not join a room.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoomError {
+    InvalidName(String),
+    /// `max_rooms` reached and the requested room doesn't exist yet
+    TooManyRooms,
+    /// The room is at its connection cap
+    RoomFull,
+}
+
+impl std::fmt::Display for RoomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoomError::InvalidName(e) => write!(f, "invalid room: {}", e),
+            RoomError::TooManyRooms => write!(f, "too many rooms"),
+            RoomError::RoomFull => write!(f, "room full"),
+        }
+    }
+}
+
+/// Channels into one room's game loop.
+#[derive(Debug)]
+pub struct Room {
+    pub name: String,
+    pub game_tx: mpsc::Sender<GameCommand>,
+    pub broadcast_tx: broadcast::Sender<GameBroadcast>,
+    pub metrics: Arc<RoomMetrics>,
+    /// Updated by the game loop every tick (`/healthz`)
+    pub heartbeat: Arc<Heartbeat>,
+}
+
+/// Room listing entry (admin API).
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RoomSummary {
+    pub name: String,
+    pub connections: usize,
+    pub max_connections: usize,
+    pub spectators: usize,
+}
+
+struct RoomEntry {
+    room: Arc<Room>,
+    connections: usize,
+    max_connections: usize,
+    spectators: usize,
+}
+
+/// All live rooms, keyed by name.
+pub struct RoomRegistry {
+    server_config: ServerConfig,
+    rooms: Mutex<HashMap<String, RoomEntry>>,
+    /// Snapshot file of the default room and each configured one, kept
+    /// across reopenings so their writes stay ordered. Rooms any client
+    /// can make up by name aren't persisted, or they could fill the disk.
+    snapshots: HashMap<String, Arc<SnapshotFile>>,
+}
+
+impl RoomRegistry {
+    /// Create the registry and start the default room. Must be called
+    /// inside a tokio runtime.
+    pub fn new(server_config: ServerConfig) -> Arc<Self> {
+        let snapshots = match server_config.snapshot_dir.as_deref() {
+            Some(dir) => std::iter::once(DEFAULT_ROOM)
+                .chain(server_config.rooms.keys().map(String::as_str))
+                .map(|name| {
+                    let path = persist::snapshot_path(Path::new(dir), name);
+                    (name.to_string(), Arc::new(SnapshotFile::new(path)))
+                })
+                .collect(),
+            None => HashMap::new(),
+        };
+        let registry = Arc::new(Self {
+            server_config,
+            rooms: Mutex::new(HashMap::new()),
+            snapshots,
+        });
+        {
+            let mut rooms = registry.lock_rooms();
+            let entry = registry.spawn_room(DEFAULT_ROOM);
+            rooms.insert(DEFAULT_ROOM.to_string(), entry);
+        }
+        registry
+    }
+
+    /// Join `name` (or the default room if `None`), starting it if needed.
+    /// The connection counts against the room until the guard is dropped.
+    pub fn join(self: &Arc<Self>, name: Option<&str>) -> Result<RoomGuard, RoomError> {
+        self.enter(name, false)
+    }
+
+    /// Watch `name` (or the default room) as a spectator, starting it if
+    /// needed. Spectators don't count against the room's connection cap.
+    pub fn spectate(self: &Arc<Self>, name: Option<&str>) -> Result<RoomGuard, RoomError> {
+        self.enter(name, true)
+    }
+
+    fn enter(
+        self: &Arc<Self>,
+        name: Option<&str>,
+        spectator: bool,
+    ) -> Result<RoomGuard, RoomError> {
+        let name = name.unwrap_or(DEFAULT_ROOM);
+        validate_room_name(name).map_err(RoomError::InvalidName)?;
+
+        let mut rooms = self.lock_rooms();
+        if !rooms.contains_key(name) {
+            if rooms.len() >= self.server_config.max_rooms {
+                return Err(RoomError::TooManyRooms);
+            }
+            let entry = self.spawn_room(name);
+            tracing::info!("Room '{}' created", name);
+            rooms.insert(name.to_string(), entry);
+        }
+        let entry = rooms.get_mut(name).expect("room just inserted");
+        if spectator {
+            entry.spectators += 1;
+        } else {
+            if entry.connections >= entry.max_connections {
+                return Err(RoomError::RoomFull);
+            }
+            entry.connections += 1;
+        }
+        Ok(RoomGuard {
+            registry: self.clone(),
+            room: entry.room.clone(),
+            spectator,
+        })
+    }
+
+    /// Number of live rooms (including the default room).
+    pub fn room_count(&self) -> usize {
+        self.lock_rooms().len()
+    }
+
+    /// A live room by name, without joining it.
+    pub fn get(&self, name: &str) -> Option<Arc<Room>> {
+        self.lock_rooms().get(name).map(|e| e.room.clone())
+    }
+
+    /// Every live room, sorted by name.
+    pub fn list(&self) -> Vec<RoomSummary> {
+        let mut rooms: Vec<RoomSummary> = self
+            .lock_rooms()
+            .iter()
+            .map(|(name, e)| RoomSummary {
+                name: name.clone(),
+                connections: e.connections,
+                max_connections: e.max_connections,
+                spectators: e.spectators,
+            })
+            .collect();
+        rooms.sort_by(|a, b| a.name.cmp(&b.name));
+        rooms
+    }
+
+    /// Every live room, sorted by name.
+    pub fn rooms(&self) -> Vec<Arc<Room>> {
+        let mut rooms: Vec<Arc<Room>> =
+            self.lock_rooms().values().map(|e| e.room.clone()).collect();
+        rooms.sort_by(|a, b| a.name.cmp(&b.name));
+        rooms
+    }
+
+    /// Have every live room write its snapshot and wait until they have.
+    /// Rooms closed earlier already wrote theirs when their loop ended.
+    pub async fn save_snapshots(&self) {
+        if self.server_config.snapshot_dir.is_none() {
+            return;
+        }
+        for room in self.rooms() {
+            let (response, done) = oneshot::channel();
+            if room
+                .game_tx
+                .send(GameCommand::SaveSnapshot { response })
+                .await
+                .is_ok()
+            {
+                let _ = done.await;
+            }
+        }
+    }
+
+    fn leave(&self, name: &str, spectator: bool) {
+        let mut rooms = self.lock_rooms();
+        let Some(entry) = rooms.get_mut(name) else {
+            return;
+        };
+        if spectator {
+            entry.spectators = entry.spectators.saturating_sub(1);
+        } else {
+            entry.connections = entry.connections.saturating_sub(1);
+        }
+        if entry.connections == 0 && entry.spectators == 0 && name != DEFAULT_ROOM {
+            // Dropping the last sender ends the room's game loop
+            rooms.remove(name);
+            tracing::info!("Room '{}' closed", name);
+        }
+    }
+
+    fn spawn_room(&self, name: &str) -> RoomEntry {
+        let (config, deep_space) = self.server_config.for_room(name);
+        let (game_tx, game_rx) = mpsc::channel::<GameCommand>(256);
+        let (broadcast_tx, _) = broadcast::channel::<GameBroadcast>(64);
+        let max_connections = config.max_connections;
+        let metrics = Arc::new(RoomMetrics::default());
+        let heartbeat = Arc::new(Heartbeat::default());
+        let snapshot = self.snapshots.get(name).cloned();
+        let record_path = self.server_config.record_dir.as_deref().map(|dir| {
+            let started = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            record::recording_path(Path::new(dir), name, started.as_millis())
+        });
+        tokio::spawn(run_game_loop_with_config(
+            game_rx,
+            broadcast_tx.clone(),
+            config,
+            deep_space,
+            metrics.clone(),
+            heartbeat.clone(),
+            snapshot,
+            record_path,
+        ));
+        RoomEntry {
+            room: Arc::new(Room {
+                name: name.to_string(),
+                game_tx,
+                broadcast_tx,
+                metrics,
+                heartbeat,
+            }),
+            connections: 0,
+            max_connections,
+            spectators: 0,
+        }
+    }
+
+    fn lock_rooms(&self) -> std::sync::MutexGuard<'_, HashMap<String, RoomEntry>> {
+        // A panic while holding the lock leaves the map itself consistent
+        self.rooms.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A connection's membership in a room. Dropping it releases the slot and
+/// closes the room if it was the last one.
+pub struct RoomGuard {
+    registry: Arc<RoomRegistry>,
+    room: Arc<Room>,
+    spectator: bool,
+}
+
+impl RoomGuard {
+    /// Whether this connection is a spectator rather than a player.
+    pub fn is_spectator(&self) -> bool {
+        self.spectator
+    }
+}
+
+impl std::ops::Deref for RoomGuard {
+    type Target = Room;
+
+    fn deref(&self) -> &Room {
+        &self.room
+    }
+}
+
+impl Drop for RoomGuard {
+    fn drop(&mut self) {
+        self.registry.leave(&self.room.name, self.spectator);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RoomConfig;
+
+    fn registry(config: ServerConfig) -> Arc<RoomRegistry> {
+        RoomRegistry::new(ServerConfig {
+            bot_count: 0,
+            ..config
+        })
+    }
+
+    #[test]
+    fn room_name_validation() {
+        assert!(validate_room_name("office-3_b").is_ok());
+        assert!(validate_room_name("").is_err());
+        assert!(validate_room_name("has space").is_err());
+        assert!(validate_room_name("ünicode").is_err());
+        assert!(validate_room_name(&"x".repeat(MAX_ROOM_NAME_LEN + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn default_room_exists_and_survives_leave() {
+        let reg = registry(ServerConfig::default());
+        assert_eq!(reg.room_count(), 1);
+        let guard = reg.join(None).unwrap();
+        assert_eq!(guard.name, DEFAULT_ROOM);
+        drop(guard);
+        assert_eq!(reg.room_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn named_room_is_shared_and_closed_when_empty() {
+        let reg = registry(ServerConfig::default());
+        let a = reg.join(Some("office")).unwrap();
+        let b = reg.join(Some("office")).unwrap();
+        assert!(a.game_tx.same_channel(&b.game_tx));
+        assert_eq!(reg.room_count(), 2);
+        let names: Vec<String> = reg.list().into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["office", DEFAULT_ROOM]);
+        assert_eq!(reg.get("office").unwrap().name, "office");
+
+        drop(a);
+        assert_eq!(reg.room_count(), 2);
+        drop(b);
+        assert_eq!(reg.room_count(), 1);
+        assert!(reg.get("office").is_none());
+
+        // Rejoining starts a fresh world
+        let c = reg.join(Some("office")).unwrap();
+        assert_eq!(reg.room_count(), 2);
+        drop(c);
+    }
+
+    #[tokio::test]
+    async fn caps_rooms_and_connections_per_room() {
+        let mut config = ServerConfig {
+            max_rooms: 2,
+            max_connections_per_room: 2,
+            ..Default::default()
+        };
+        config.rooms.insert(
+            "solo".to_string(),
+            RoomConfig {
+                max_connections: Some(1),
+                ..Default::default()
+            },
+        );
+        let reg = registry(config);
+
+        let _solo = reg.join(Some("solo")).unwrap();
+        assert!(matches!(reg.join(Some("solo")), Err(RoomError::RoomFull)));
+        assert!(matches!(
+            reg.join(Some("another")),
+            Err(RoomError::TooManyRooms)
+        ));
+
+        let _p1 = reg.join(None).unwrap();
+        let _p2 = reg.join(None).unwrap();
+        assert!(matches!(reg.join(None), Err(RoomError::RoomFull)));
+        assert!(matches!(
+            reg.join(Some("bad name")),
+            Err(RoomError::InvalidName(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn rooms_restore_and_save_snapshots() {
+        use crate::config::DeepSpaceConfig;
+        use crate::state::GameState;
+
+        let dir =
+            std::env::temp_dir().join(format!("pinball-room-snapshots-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = persist::snapshot_path(&dir, "office");
+
+        // A previous run left two balls in "office"
+        let mut previous =
+            GameState::new(&ServerConfig::default(), DeepSpaceConfig::default(), 1.5);
+        let (id, _) = previous.add_player().unwrap();
+        previous.ball_escaped(id, 0.5, 1.0);
+        previous.ball_escaped(id, -0.5, 1.0);
+        persist::save(&path, &previous.snapshot()).unwrap();
+
+        let reg = registry(ServerConfig {
+            snapshot_dir: Some(dir.to_string_lossy().into_owned()),
+            rooms: HashMap::from([("office".to_string(), RoomConfig::default())]),
+            ..Default::default()
+        });
+        let guard = reg.join(Some("office")).unwrap();
+        let (response, balls) = oneshot::channel();
+        guard
+            .game_tx
+            .send(GameCommand::AdminClearBalls { response })
+            .await
+            .unwrap();
+        assert_eq!(balls.await.unwrap(), 2);
+
+        // Rooms made up by clients aren't persisted
+        let scratch = reg.join(Some("scratch")).unwrap();
+
+        reg.save_snapshots().await;
+        let saved = persist::load(&path).unwrap().unwrap();
+        assert!(saved.deep_space.balls.is_empty());
+        assert!(persist::snapshot_path(&dir, DEFAULT_ROOM).exists());
+        drop(scratch);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!persist::snapshot_path(&dir, "scratch").exists());
+        drop(guard);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn reopened_room_restores_what_it_last_saved() {
+        use crate::config::DeepSpaceConfig;
+        use crate::state::GameState;
+
+        let dir = std::env::temp_dir().join(format!("pinball-room-reopen-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = persist::snapshot_path(&dir, "office");
+        let mut previous =
+            GameState::new(&ServerConfig::default(), DeepSpaceConfig::default(), 1.5);
+        let (id, _) = previous.add_player().unwrap();
+        previous.ball_escaped(id, 0.5, 1.0);
+        persist::save(&path, &previous.snapshot()).unwrap();
+
+        let reg = registry(ServerConfig {
+            snapshot_dir: Some(dir.to_string_lossy().into_owned()),
+            rooms: HashMap::from([("office".to_string(), RoomConfig::default())]),
+            ..Default::default()
+        });
+        let clear_balls = |guard: &RoomGuard| {
+            let game_tx = guard.game_tx.clone();
+            async move {
+                let (response, balls) = oneshot::channel();
+                game_tx
+                    .send(GameCommand::AdminClearBalls { response })
+                    .await
+                    .unwrap();
+                balls.await.unwrap()
+            }
+        };
+        let guard = reg.join(Some("office")).unwrap();
+        assert_eq!(clear_balls(&guard).await, 1);
+        drop(guard);
+        // Straight back in: the new loop waits for the old one's final save
+        let guard = reg.join(Some("office")).unwrap();
+        assert_eq!(clear_balls(&guard).await, 0);
+        drop(guard);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn rooms_record_commands_that_replay() {
+        let dir =
+            std::env::temp_dir().join(format!("pinball-room-recordings-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let reg = registry(ServerConfig {
+            record_dir: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+        let guard = reg.join(Some("office")).unwrap();
+        let (client_tx, _client_rx) = mpsc::channel(8);
+        let (response, joined) = oneshot::channel();
+        guard
+            .game_tx
+            .send(GameCommand::PlayerJoin {
+                response,
+                client_tx,
+                resume_token: None,
+                name: Some("Ada".to_string()),
+            })
+            .await
+            .unwrap();
+        let (id, _, _) = joined.await.unwrap().unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        guard
+            .game_tx
+            .send(GameCommand::BallEscaped {
+                owner_id: id,
+                vx: 0.5,
+                vy: 1.0,
+                target_id: None,
+                ball_id: None,
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        // Closing the room ends its loop, which finishes the recording
+        drop(guard);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let recording = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .find(|p| {
+                p.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("office-")
+            })
+            .unwrap();
+        let file = std::fs::File::open(&recording).unwrap();
+        let summary = record::replay(std::io::BufReader::new(file)).unwrap();
+        assert!(summary.ticks > 0);
+        assert!(summary.commands >= 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn spectators_keep_room_open_without_taking_slots() {
+        let reg = registry(ServerConfig {
+            max_connections_per_room: 1,
+            ..Default::default()
+        });
+        let watcher = reg.spectate(Some("lobby")).unwrap();
+        assert!(watcher.is_spectator());
+        let player = reg.join(Some("lobby")).unwrap();
+        assert!(!player.is_spectator());
+        let second_watcher = reg.spectate(Some("lobby")).unwrap();
+        assert!(matches!(reg.join(Some("lobby")), Err(RoomError::RoomFull)));
+
+        let lobby = reg.list().into_iter().find(|r| r.name == "lobby").unwrap();
+        assert_eq!((lobby.connections, lobby.spectators), (1, 2));
+
+        drop(player);
+        drop(watcher);
+        assert!(reg.get("lobby").is_some());
+        drop(second_watcher);
+        assert!(reg.get("lobby").is_none());
+    }
+}
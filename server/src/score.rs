@@ -0,0 +1,201 @@
+//! Per-player scores and the `leaderboard` broadcast.
+//!
+//! Points come from deep-space captures (delivering a ball to someone
+//! else's portal, receiving one, and having a ball you first launched
+//! delivered by someone else) and from client-reported bumper hits,
+//! which `ws` caps per second before they get here. Scores are kept by
+//! player id, so a resumed player keeps theirs, and are wiped every
+//! `score_reset_secs`.
+
+use crate::protocol::{LeaderboardEntry, LeaderboardMsg};
+use std::collections::HashMap;
+
+/// Points for one of your balls captured by another player's portal
+pub const DELIVER_POINTS: u32 = 10;
+/// Points for capturing another player's ball
+pub const RECEIVE_POINTS: u32 = 5;
+/// Points for the player who first launched a ball another player delivered
+pub const ASSIST_POINTS: u32 = 2;
+/// Points per credited bumper hit
+pub const HIT_POINTS: u32 = 1;
+/// Most entries sent in one `leaderboard`
+pub const LEADERBOARD_MAX_ENTRIES: usize = 100;
+
+/// What a player has earned points for this period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Score {
+    pub delivered: u32,
+    pub received: u32,
+    pub assists: u32,
+    pub hits: u32,
+}
+
+impl Score {
+    pub fn points(&self) -> u32 {
+        self.delivered
+            .saturating_mul(DELIVER_POINTS)
+            .saturating_add(self.received.saturating_mul(RECEIVE_POINTS))
+            .saturating_add(self.assists.saturating_mul(ASSIST_POINTS))
+            .saturating_add(self.hits.saturating_mul(HIT_POINTS))
+    }
+}
+
+/// Scores of the current period.
+#[derive(Debug)]
+pub struct ScoreBoard {
+    scores: HashMap<u32, Score>,
+    /// Period length in seconds (0 = never reset)
+    reset_secs: f64,
+    /// Server elapsed time the current period started at
+    period_start: f64,
+}
+
+impl ScoreBoard {
+    pub fn new(reset_secs: u64) -> Self {
+        Self {
+            scores: HashMap::new(),
+            reset_secs: reset_secs as f64,
+            period_start: 0.0,
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Score {
+        self.scores.get(&id).copied().unwrap_or_default()
+    }
+
+    /// `owner`'s ball, first launched by `origin`, was captured by
+    /// `receiver`'s portal. Capturing your own ball scores nothing; the
+    /// origin gets an assist unless they delivered or received it.
+    pub fn record_capture(&mut self, owner: u32, origin: u32, receiver: u32) {
+        if owner == receiver {
+            return;
+        }
+        let delivered = &mut self.scores.entry(owner).or_default().delivered;
+        *delivered = delivered.saturating_add(1);
+        let received = &mut self.scores.entry(receiver).or_default().received;
+        *received = received.saturating_add(1);
+        if origin != owner && origin != receiver {
+            let assists = &mut self.scores.entry(origin).or_default().assists;
+            *assists = assists.saturating_add(1);
+        }
+    }
+
+    /// Credit bumper hits (already capped by the caller).
+    pub fn add_hits(&mut self, id: u32, count: u32) {
+        if count > 0 {
+            let hits = &mut self.scores.entry(id).or_default().hits;
+            *hits = hits.saturating_add(count);
+        }
+    }
+
+    /// Forget a player for good (bots, expired resume identities).
+    pub fn remove(&mut self, id: u32) {
+        self.scores.remove(&id);
+    }
+
+    /// Start a new period if the current one is over. Returns true if the
+    /// scores were reset.
+    pub fn maybe_reset(&mut self, now: f64) -> bool {
+        if self.reset_secs <= 0.0 || now - self.period_start < self.reset_secs {
+            return false;
+        }
+        self.scores.clear();
+        self.period_start = now;
+        true
+    }
+
+    /// Rank `players`, best first. Players without a score rank with 0.
+    pub fn leaderboard(&self, players: impl Iterator<Item = u32>, now: f64) -> LeaderboardMsg {
+        let mut entries: Vec<LeaderboardEntry> = players
+            .map(|player_id| LeaderboardEntry {
+                player_id,
+                score: self.get(player_id).points(),
+            })
+            .collect();
+        entries.sort_by_key(|e| (std::cmp::Reverse(e.score), e.player_id));
+        let total = entries.len() as u32;
+        entries.truncate(LEADERBOARD_MAX_ENTRIES);
+        LeaderboardMsg {
+            entries,
+            total,
+            resets_in: (self.reset_secs > 0.0)
+                .then(|| (self.period_start + self.reset_secs - now).max(0.0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_score_both_sides_but_not_self() {
+        let mut board = ScoreBoard::new(0);
+        board.record_capture(1, 1, 2);
+        board.record_capture(1, 1, 2);
+        board.record_capture(3, 3, 3);
+
+        assert_eq!(board.get(1).points(), 2 * DELIVER_POINTS);
+        assert_eq!(board.get(2).points(), 2 * RECEIVE_POINTS);
+        assert_eq!(board.get(3), Score::default());
+    }
+
+    #[test]
+    fn origin_gets_an_assist_for_relayed_balls() {
+        let mut board = ScoreBoard::new(0);
+        board.record_capture(2, 1, 3);
+        assert_eq!(board.get(1).points(), ASSIST_POINTS);
+        assert_eq!(board.get(2).points(), DELIVER_POINTS);
+
+        // Back to where it started: the origin only scores the receive
+        board.record_capture(3, 1, 1);
+        assert_eq!(board.get(1).points(), ASSIST_POINTS + RECEIVE_POINTS);
+        assert_eq!(board.get(1).assists, 1);
+    }
+
+    #[test]
+    fn leaderboard_ranks_by_score_then_id() {
+        let mut board = ScoreBoard::new(0);
+        board.add_hits(3, 7);
+        board.add_hits(2, 7);
+        board.record_capture(5, 5, 9);
+
+        let lb = board.leaderboard([1, 2, 3, 5].into_iter(), 0.0);
+        let ranked: Vec<(u32, u32)> = lb.entries.iter().map(|e| (e.player_id, e.score)).collect();
+        assert_eq!(ranked, vec![(5, DELIVER_POINTS), (2, 7), (3, 7), (1, 0)]);
+        assert_eq!(lb.total, 4);
+        assert_eq!(lb.resets_in, None);
+    }
+
+    #[test]
+    fn leaderboard_is_cut_to_max_entries() {
+        let board = ScoreBoard::new(0);
+        let lb = board.leaderboard(1..=(LEADERBOARD_MAX_ENTRIES as u32 + 5), 0.0);
+        assert_eq!(lb.entries.len(), LEADERBOARD_MAX_ENTRIES);
+        assert_eq!(lb.total, LEADERBOARD_MAX_ENTRIES as u32 + 5);
+    }
+
+    #[test]
+    fn scores_reset_each_period() {
+        let mut board = ScoreBoard::new(60);
+        board.add_hits(1, 4);
+        assert!(!board.maybe_reset(59.0));
+        assert_eq!(
+            board.leaderboard([1].into_iter(), 45.0).resets_in,
+            Some(15.0)
+        );
+
+        assert!(board.maybe_reset(60.5));
+        assert_eq!(board.get(1), Score::default());
+        assert!(!board.maybe_reset(100.0));
+        assert!(board.maybe_reset(120.5));
+    }
+
+    #[test]
+    fn zero_period_never_resets() {
+        let mut board = ScoreBoard::new(0);
+        board.add_hits(1, 4);
+        assert!(!board.maybe_reset(1e9));
+        assert_eq!(board.get(1).hits, 4);
+    }
+}
@@ -0,0 +1,322 @@
+//! Broadcast serialization off the game loop.
+//!
+//! Each room's game loop hands immutable snapshots (`space_state`,
+//! `players_state`, `leaderboard`, `emote`) to a dedicated serializer
+//! thread, which delta-encodes and serializes them and sends the
+//! `GameBroadcast`s. The tick only pays for copying the snapshot.
+//!
+//! The job queue is bounded. When the thread falls behind, new jobs are
+//! dropped and counted rather than stalling the tick; a dropped
+//! `space_state` is simply never encoded, so the delta chain stays
+//! consistent. A keyframe requested for a joining client is carried to
+//! the next `space_state` that makes it into the queue and sent as a
+//! separate `SpaceStateKeyframe` ahead of that snapshot's delta, so the
+//! shared chain is never reset. Ball buffers go back to the game loop after encoding and
+//! are reused for the next snapshot.
+
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::Instant;
+
+use pinball_shared::delta::DeltaEncoder;
+use tokio::sync::broadcast;
+
+use crate::game_loop::GameBroadcast;
+use crate::interest::{BucketGrid, SpaceStateFragments};
+use crate::metrics::{BroadcastKind, RoomMetrics};
+use crate::protocol::{
+    BallWire, EmoteMsg, LeaderboardMsg, PlayersStateMsg, ServerMsg, SpaceStateMsg,
+};
+use crate::vec3::vec3;
+
+/// Jobs waiting for the serializer thread before new ones are dropped.
+const JOB_QUEUE_LEN: usize = 8;
+/// Ball buffers kept for reuse.
+const SPARE_BUFFERS: usize = 4;
+
+enum Job {
+    /// Full snapshot; `keyframe` also sends a keyframe for it
+    SpaceState {
+        msg: SpaceStateMsg,
+        keyframe: bool,
+    },
+    PlayersState(PlayersStateMsg),
+    Leaderboard(LeaderboardMsg),
+    Emote(EmoteMsg),
+}
+
+impl Job {
+    fn kind(&self) -> BroadcastKind {
+        match self {
+            Job::SpaceState { .. } => BroadcastKind::SpaceState,
+            Job::PlayersState(_) => BroadcastKind::PlayersState,
+            Job::Leaderboard(_) => BroadcastKind::Leaderboard,
+            Job::Emote(_) => BroadcastKind::Emote,
+        }
+    }
+}
+
+/// The game loop's handle on its room's serializer thread. The thread
+/// finishes the queued jobs and exits once this is dropped.
+pub struct BroadcastSerializer {
+    jobs: SyncSender<Job>,
+    spare: Receiver<Vec<BallWire>>,
+    /// A client asked for a keyframe not yet handed to the thread
+    keyframe_pending: bool,
+    metrics: Arc<RoomMetrics>,
+}
+
+impl BroadcastSerializer {
+    /// Start the serializer thread. `grid` buckets space_state for the
+    /// clients' areas of interest.
+    pub fn spawn(
+        broadcast_tx: broadcast::Sender<GameBroadcast>,
+        grid: BucketGrid,
+        metrics: Arc<RoomMetrics>,
+    ) -> Self {
+        let (jobs, job_rx) = mpsc::sync_channel(JOB_QUEUE_LEN);
+        let (spare_tx, spare) = mpsc::sync_channel(SPARE_BUFFERS);
+        let thread_metrics = metrics.clone();
+        std::thread::Builder::new()
+            .name("broadcast-serializer".to_string())
+            .spawn(move || run(job_rx, spare_tx, broadcast_tx, grid, thread_metrics))
+            .expect("failed to spawn serializer thread");
+        Self {
+            jobs,
+            spare,
+            keyframe_pending: false,
+            metrics,
+        }
+    }
+
+    /// Send a keyframe alongside the next space_state, for clients that
+    /// just joined or lost the chain.
+    pub fn request_keyframe(&mut self) {
+        self.keyframe_pending = true;
+    }
+
+    /// An empty buffer to snapshot balls into, reusing a returned one if
+    /// there is any.
+    pub fn ball_buffer(&self) -> Vec<BallWire> {
+        self.spare.try_recv().unwrap_or_default()
+    }
+
+    /// Queue a full space_state snapshot for delta encoding.
+    pub fn space_state(&mut self, msg: SpaceStateMsg) {
+        let keyframe = self.keyframe_pending;
+        if self.submit(Job::SpaceState { msg, keyframe }) {
+            self.keyframe_pending = false;
+        }
+    }
+
+    pub fn players_state(&mut self, msg: PlayersStateMsg) {
+        self.submit(Job::PlayersState(msg));
+    }
+
+    pub fn leaderboard(&mut self, msg: LeaderboardMsg) {
+        self.submit(Job::Leaderboard(msg));
+    }
+
+    pub fn emote(&mut self, msg: EmoteMsg) {
+        self.submit(Job::Emote(msg));
+    }
+
+    /// Returns false if the job was dropped.
+    fn submit(&mut self, job: Job) -> bool {
+        let kind = job.kind();
+        match self.jobs.try_send(job) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.metrics.dropped_broadcasts(kind).inc();
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                tracing::error!("Serializer thread is gone, dropping broadcast");
+                false
+            }
+        }
+    }
+}
+
+fn run(
+    jobs: Receiver<Job>,
+    spare: SyncSender<Vec<BallWire>>,
+    broadcast_tx: broadcast::Sender<GameBroadcast>,
+    grid: BucketGrid,
+    metrics: Arc<RoomMetrics>,
+) {
+    // space_state is sent as keyframes + deltas (all clients share one chain)
+    let mut encoder = DeltaEncoder::default();
+    for job in jobs {
+        let started = Instant::now();
+        let kind = job.kind();
+        let broadcast = match job {
+            Job::SpaceState { mut msg, keyframe } => {
+                let encoded = encoder.encode_bucketed(&msg, grid.len(), |b| {
+                    grid.bucket_of(vec3(b.pos[0], b.pos[1], b.pos[2]))
+                });
+                msg.balls.clear();
+                let _ = spare.try_send(msg.balls);
+                // Goes out first so a waiting client can pick up the
+                // delta that follows
+                if keyframe {
+                    match SpaceStateFragments::new(&encoder.keyframe_bucketed(grid.len())) {
+                        Ok(frags) => {
+                            let _ = broadcast_tx
+                                .send(GameBroadcast::SpaceStateKeyframe(Arc::new(frags)));
+                        }
+                        Err(e) => tracing::error!("Failed to serialize keyframe: {}", e),
+                    }
+                }
+                SpaceStateFragments::new(&encoded)
+                    .map(|frags| GameBroadcast::SpaceState(Arc::new(frags)))
+            }
+            Job::PlayersState(msg) => serde_json::to_string(&ServerMsg::PlayersState(msg))
+                .map(|json| GameBroadcast::PlayersState(json.into())),
+            Job::Leaderboard(msg) => serde_json::to_string(&ServerMsg::Leaderboard(msg))
+                .map(|json| GameBroadcast::Leaderboard(json.into())),
+            Job::Emote(msg) => serde_json::to_string(&ServerMsg::Emote(msg))
+                .map(|json| GameBroadcast::Emote(json.into())),
+        };
+        match broadcast {
+            Ok(broadcast) => {
+                metrics
+                    .serialize_seconds(kind)
+                    .observe(started.elapsed().as_secs_f64());
+                let _ = broadcast_tx.send(broadcast);
+            }
+            Err(e) => tracing::error!("Failed to serialize {:?}: {}", kind, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interest::Interest;
+    use crate::protocol::EmoteKind;
+
+    fn ball(id: u32) -> BallWire {
+        BallWire {
+            id,
+            owner_id: 1,
+            pos: [1.0, 0.0, 0.0],
+            axis: [0.0, 0.0, 1.0],
+            omega: 0.5,
+            hops: 0,
+        }
+    }
+
+    fn snapshot(server_time: f64, balls: Vec<BallWire>) -> SpaceStateMsg {
+        SpaceStateMsg {
+            server_time,
+            seq: 0,
+            keyframe: true,
+            balls,
+            unchanged: vec![],
+            removed: vec![],
+        }
+    }
+
+    fn space_state_json(rx: &mut broadcast::Receiver<GameBroadcast>) -> serde_json::Value {
+        match rx.blocking_recv().unwrap() {
+            GameBroadcast::SpaceState(frags) => {
+                serde_json::from_str(&frags.json(&Interest::All)).unwrap()
+            }
+            other => panic!("expected space_state, got {:?}", other),
+        }
+    }
+
+    fn keyframe_json(rx: &mut broadcast::Receiver<GameBroadcast>) -> serde_json::Value {
+        match rx.blocking_recv().unwrap() {
+            GameBroadcast::SpaceStateKeyframe(frags) => {
+                serde_json::from_str(&frags.json(&Interest::All)).unwrap()
+            }
+            other => panic!("expected a keyframe, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn encodes_space_state_and_returns_the_buffer() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let metrics = Arc::new(RoomMetrics::default());
+        let mut serializer = BroadcastSerializer::spawn(tx, BucketGrid::new(8), metrics.clone());
+
+        serializer.space_state(snapshot(0.1, vec![ball(1), ball(2)]));
+        let first = space_state_json(&mut rx);
+        assert_eq!(first["keyframe"], true);
+        assert_eq!(first["balls"].as_array().unwrap().len(), 2);
+
+        // Encoded snapshots give their ball buffer back
+        let buffer = serializer.ball_buffer();
+        assert!(buffer.is_empty() && buffer.capacity() >= 2);
+
+        serializer.space_state(snapshot(0.2, vec![ball(1)]));
+        let delta = space_state_json(&mut rx);
+        assert_eq!(delta["keyframe"], false);
+        assert_eq!(delta["removed"], serde_json::json!([2]));
+
+        // A requested keyframe comes first, and the chain carries on
+        serializer.request_keyframe();
+        serializer.space_state(snapshot(0.3, vec![ball(1), ball(3)]));
+        let key = keyframe_json(&mut rx);
+        assert_eq!(key["keyframe"], true);
+        assert_eq!(key["seq"], delta["seq"].as_u64().unwrap() + 1);
+        assert_eq!(key["balls"].as_array().unwrap().len(), 2);
+        let next = space_state_json(&mut rx);
+        assert_eq!(next["keyframe"], false);
+        assert_eq!(next["seq"], key["seq"]);
+        assert_eq!(
+            metrics.serialize_seconds(BroadcastKind::SpaceState).count(),
+            3
+        );
+    }
+
+    #[test]
+    fn serializes_json_broadcasts() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let mut serializer =
+            BroadcastSerializer::spawn(tx, BucketGrid::new(8), Arc::new(RoomMetrics::default()));
+        serializer.emote(EmoteMsg {
+            player_id: 3,
+            kind: EmoteKind::Wave,
+        });
+        match rx.blocking_recv().unwrap() {
+            GameBroadcast::Emote(json) => {
+                assert_eq!(
+                    json.as_str(),
+                    r#"{"type":"emote","playerId":3,"kind":"wave"}"#
+                )
+            }
+            other => panic!("expected emote, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn full_queue_drops_and_counts_but_keeps_keyframe_pending() {
+        let metrics = Arc::new(RoomMetrics::default());
+        // A handle whose thread never drains, so the queue fills up
+        let (jobs, job_rx) = mpsc::sync_channel(1);
+        let (_spare_tx, spare) = mpsc::sync_channel(1);
+        let mut serializer = BroadcastSerializer {
+            jobs,
+            spare,
+            keyframe_pending: true,
+            metrics: metrics.clone(),
+        };
+
+        serializer.space_state(snapshot(0.1, vec![]));
+        serializer.space_state(snapshot(0.2, vec![]));
+        serializer.request_keyframe();
+        serializer.space_state(snapshot(0.3, vec![]));
+        assert_eq!(
+            metrics.dropped_broadcasts(BroadcastKind::SpaceState).get(),
+            2
+        );
+        assert!(serializer.keyframe_pending);
+        assert!(matches!(
+            job_rx.try_recv(),
+            Ok(Job::SpaceState { keyframe: true, .. })
+        ));
+    }
+}
@@ -0,0 +1,746 @@
+//! Layered configuration for the server binary.
+//!
+//! Every `ServerConfig` field, including `deep_space.*`, can be set at each
+//! layer; later layers win:
+//!
+//! 1. built-in defaults
+//! 2. a TOML file (`--config <path>`, or the `CONFIG_FILE` env var)
+//! 3. an environment variable named after the field path, upper-cased:
+//!    `TICK_RATE_HZ`, `DEEP_SPACE_PORTAL_ALPHA`
+//! 4. a CLI flag with the same name in kebab case: `--tick-rate-hz 30`,
+//!    `--deep-space-portal-alpha=0.2`
+//!
+//! List fields (`allowed_origins`) take comma-separated values in env and
+//! CLI. Per-room overrides (`[rooms.<name>]`) can only be set in the file;
+//! a room's `deep_space` table is layered over the top-level one.
+//!
+//! Layers are merged as JSON values and deserialized once at the end, so a
+//! type error in any layer is reported with the path of the field. Every bad
+//! field is reported, not just the first, along with any validation problems.
+//!
+//! Keys that were replaced (`DEPRECATED`) are still read from the file and
+//! env, with a warning, and set the fields that replaced them unless the
+//! same layer sets those too.
+
+use std::path::PathBuf;
+
+use serde_json::{Map, Value};
+use serde_path_to_error::Segment;
+
+use crate::config::ServerConfig;
+
+/// Env var naming the config file when `--config` isn't given.
+pub const CONFIG_FILE_ENV: &str = "CONFIG_FILE";
+
+/// Replaced top-level keys and the field paths each one now sets.
+/// `max_ball_escaped_per_sec` capped escapes per one-second window, which
+/// is a bucket refilling at that rate and holding a second's worth.
+const DEPRECATED: &[(&str, &[&str])] = &[(
+    "max_ball_escaped_per_sec",
+    &[
+        "rate_limits.ball_escaped.per_sec",
+        "rate_limits.ball_escaped.burst",
+    ],
+)];
+
+/// Loaded configuration plus what the binary was asked to do with it.
+#[derive(Debug)]
+pub struct Settings {
+    pub config: ServerConfig,
+    /// `--print-config`: dump the effective configuration and exit
+    pub print_config: bool,
+    /// `--help`: print usage and exit
+    pub help: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Number,
+    Bool,
+    String,
+    List,
+}
+
+/// One settable leaf of the config tree.
+#[derive(Debug)]
+struct Field {
+    path: Vec<String>,
+    kind: Kind,
+    default: Value,
+}
+
+impl Field {
+    fn env_name(&self) -> String {
+        self.path.join("_").to_uppercase()
+    }
+
+    fn flag_name(&self) -> String {
+        format!("--{}", self.path.join("-").replace('_', "-"))
+    }
+
+    fn parse(&self, raw: &str) -> Result<Value, String> {
+        let raw = raw.trim();
+        match self.kind {
+            Kind::String => Ok(Value::String(raw.to_string())),
+            Kind::List => Ok(Value::Array(
+                raw.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(|s| Value::String(s.to_string()))
+                    .collect(),
+            )),
+            Kind::Bool => raw
+                .parse::<bool>()
+                .map(Value::Bool)
+                .map_err(|_| format!("expected true or false, got {:?}", raw)),
+            Kind::Number => raw
+                .parse::<u64>()
+                .map(Value::from)
+                .or_else(|_| raw.parse::<i64>().map(Value::from))
+                .ok()
+                .or_else(|| {
+                    raw.parse::<f64>()
+                        .ok()
+                        .and_then(serde_json::Number::from_f64)
+                        .map(Value::Number)
+                })
+                .ok_or_else(|| format!("expected a number, got {:?}", raw)),
+        }
+    }
+}
+
+/// Every leaf field, derived from the defaults so new config fields are
+/// picked up without touching this module.
+fn fields(defaults: &Value) -> Vec<Field> {
+    fn walk(value: &Value, path: &mut Vec<String>, out: &mut Vec<Field>) {
+        let kind = match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    // Keyed by room name; file only
+                    if path.is_empty() && key == "rooms" {
+                        continue;
+                    }
+                    path.push(key.clone());
+                    walk(child, path, out);
+                    path.pop();
+                }
+                return;
+            }
+            Value::Number(_) => Kind::Number,
+            Value::Bool(_) => Kind::Bool,
+            Value::Array(_) => Kind::List,
+            Value::String(_) | Value::Null => Kind::String,
+        };
+        out.push(Field {
+            path: path.clone(),
+            kind,
+            default: value.clone(),
+        });
+    }
+    let mut out = Vec::new();
+    walk(defaults, &mut Vec::new(), &mut out);
+    out
+}
+
+fn defaults() -> Value {
+    serde_json::to_value(ServerConfig::default()).expect("ServerConfig serializes")
+}
+
+/// Recursively merge `over` into `base`; tables merge, everything else replaces.
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+fn set(root: &mut Value, path: &[String], value: Value) {
+    let mut node = root;
+    for key in path {
+        if !node.is_object() {
+            *node = Value::Object(Map::new());
+        }
+        node = node
+            .as_object_mut()
+            .expect("just made an object")
+            .entry(key.clone())
+            .or_insert(Value::Null);
+    }
+    *node = value;
+}
+
+/// Drop the value at `path` so deserializing falls back to its default.
+/// Returns false if there was nothing there to drop.
+fn remove(root: &mut Value, path: &serde_path_to_error::Path) -> bool {
+    let segments: Vec<&Segment> = path.iter().collect();
+    let Some((last, parents)) = segments.split_last() else {
+        return false;
+    };
+    let mut node = root;
+    for segment in parents {
+        let next = match (segment, node) {
+            (Segment::Map { key }, Value::Object(map)) => map.get_mut(key),
+            (Segment::Seq { index }, Value::Array(items)) => items.get_mut(*index),
+            _ => None,
+        };
+        let Some(next) = next else {
+            return false;
+        };
+        node = next;
+    }
+    match (last, node) {
+        (Segment::Map { key }, Value::Object(map)) => map.remove(key).is_some(),
+        (Segment::Seq { index }, Value::Array(items)) if *index < items.len() => {
+            items.remove(*index);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Move deprecated keys in one layer over to their replacements.
+fn apply_deprecated(layer: &mut Value, source: &str) {
+    for (old, replacements) in DEPRECATED {
+        let Some(value) = layer.as_object_mut().and_then(|map| map.remove(*old)) else {
+            continue;
+        };
+        tracing::warn!(
+            "{}: {} is deprecated, use {} instead",
+            source,
+            old,
+            replacements.join(" and ")
+        );
+        for replacement in *replacements {
+            let path: Vec<String> = replacement.split('.').map(str::to_string).collect();
+            if layer.pointer(&format!("/{}", path.join("/"))).is_none() {
+                set(layer, &path, value.clone());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CliArgs {
+    config_path: Option<PathBuf>,
+    print_config: bool,
+    help: bool,
+    /// (index into `fields`, raw value)
+    overrides: Vec<(usize, String)>,
+}
+
+fn parse_args(
+    args: impl IntoIterator<Item = String>,
+    fields: &[Field],
+) -> Result<CliArgs, Vec<String>> {
+    let mut cli = CliArgs::default();
+    let mut errors = Vec::new();
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+        match flag.as_str() {
+            "--help" | "-h" => cli.help = true,
+            "--print-config" => cli.print_config = true,
+            _ => {
+                let target = if flag == "--config" {
+                    None
+                } else {
+                    match fields.iter().position(|f| f.flag_name() == flag) {
+                        Some(i) => Some(i),
+                        None => {
+                            errors.push(format!("{}: unknown flag (see --help)", flag));
+                            // Don't report its value as another unknown flag
+                            if inline.is_none() {
+                                args.next_if(|next| !next.starts_with('-'));
+                            }
+                            continue;
+                        }
+                    }
+                };
+                let Some(value) = inline.or_else(|| args.next()) else {
+                    errors.push(format!("{}: missing value", flag));
+                    continue;
+                };
+                match target {
+                    Some(i) => cli.overrides.push((i, value)),
+                    None => cli.config_path = Some(PathBuf::from(value)),
+                }
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(cli)
+    } else {
+        Err(errors)
+    }
+}
+
+impl Settings {
+    /// Load from the process's arguments and environment.
+    pub fn from_env() -> Result<Settings, Vec<String>> {
+        Self::load(std::env::args().skip(1), |name| std::env::var(name).ok())
+    }
+
+    /// Load from `args` (without the program name) and an environment
+    /// lookup. Returns every problem found, each prefixed with where it
+    /// came from: a file path, env var, flag or config field path.
+    pub fn load(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Settings, Vec<String>> {
+        let mut merged = defaults();
+        let fields = fields(&merged);
+        let cli = parse_args(args, &fields)?;
+        let mut errors = Vec::new();
+
+        // File
+        let config_path = cli
+            .config_path
+            .clone()
+            .or_else(|| env(CONFIG_FILE_ENV).map(PathBuf::from));
+        if let Some(path) = &config_path {
+            match std::fs::read_to_string(path) {
+                Ok(text) => match toml::from_str::<Value>(&text) {
+                    Ok(mut file) => {
+                        apply_deprecated(&mut file, &path.display().to_string());
+                        merge(&mut merged, file);
+                    }
+                    Err(e) => errors.push(format!("{}: {}", path.display(), e.message())),
+                },
+                Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+
+        // Environment
+        for (old, _) in DEPRECATED {
+            let name = old.to_uppercase();
+            let Some(raw) = env(&name) else {
+                continue;
+            };
+            let field = Field {
+                path: vec![old.to_string()],
+                kind: Kind::Number,
+                default: Value::Null,
+            };
+            match field.parse(&raw) {
+                Ok(value) => {
+                    let mut layer = Value::Object(Map::new());
+                    set(&mut layer, &field.path, value);
+                    apply_deprecated(&mut layer, &name);
+                    merge(&mut merged, layer);
+                }
+                Err(e) => errors.push(format!("{}: {}", name, e)),
+            }
+        }
+        for field in &fields {
+            let name = field.env_name();
+            if let Some(raw) = env(&name) {
+                match field.parse(&raw) {
+                    Ok(value) => set(&mut merged, &field.path, value),
+                    Err(e) => errors.push(format!("{}: {}", name, e)),
+                }
+            }
+        }
+
+        // CLI
+        for (i, raw) in &cli.overrides {
+            let field = &fields[*i];
+            match field.parse(raw) {
+                Ok(value) => set(&mut merged, &field.path, value),
+                Err(e) => errors.push(format!("{}: {}", field.flag_name(), e)),
+            }
+        }
+
+        // Room deep_space tables only need the keys they change
+        if let (Some(base), Some(Value::Object(rooms))) =
+            (merged.get("deep_space").cloned(), merged.get_mut("rooms"))
+        {
+            for room in rooms.values_mut() {
+                if let Some(ds) = room.get_mut("deep_space").filter(|ds| ds.is_object()) {
+                    let mut layered = base.clone();
+                    merge(&mut layered, ds.take());
+                    *ds = layered;
+                }
+            }
+        }
+
+        // Deserializing stops at the first bad field; drop it and go again
+        // so every one gets reported
+        let config = loop {
+            match serde_path_to_error::deserialize::<_, ServerConfig>(&merged) {
+                Ok(config) => break Some(config),
+                Err(e) => {
+                    errors.push(format!("{}: {}", e.path(), e.inner()));
+                    if !remove(&mut merged, e.path()) {
+                        break None;
+                    }
+                }
+            }
+        };
+        if let Some(Err(problems)) = config.as_ref().map(ServerConfig::validate) {
+            errors.extend(problems);
+        }
+        let Some(config) = config.filter(|_| errors.is_empty()) else {
+            return Err(errors);
+        };
+        Ok(Settings {
+            config,
+            print_config: cli.print_config,
+            help: cli.help,
+        })
+    }
+}
+
+/// The effective configuration as a TOML file (`--print-config`). The admin
+/// token and join token secret are redacted.
+pub fn to_toml(config: &ServerConfig) -> String {
+    let mut config = config.clone();
+    for secret in [&mut config.admin_token, &mut config.join_token_secret] {
+        if secret.is_some() {
+            *secret = Some("<redacted>".to_string());
+        }
+    }
+    toml::to_string_pretty(&config).expect("ServerConfig serializes to TOML")
+}
+
+/// `--help` text: every flag with its env var and default.
+pub fn usage() -> String {
+    let mut out = String::from(
+        "Usage: pinball-server [--config <file.toml>] [--print-config] [--<field> <value>]...\n\
+         \n\
+         Precedence: defaults < config file < environment < flags.\n\
+         The config file can also be given with CONFIG_FILE.\n\
+         \n\
+         Fields:\n",
+    );
+    for field in fields(&defaults()) {
+        let default = match &field.default {
+            Value::Null => "(unset)".to_string(),
+            Value::Array(items) if items.is_empty() => "(empty)".to_string(),
+            other => other.to_string(),
+        };
+        out.push_str(&format!(
+            "  {:<44} {:<36} {}\n",
+            field.flag_name(),
+            field.env_name(),
+            default
+        ));
+    }
+    out.push_str("\nPer-room overrides ([rooms.<name>]) can only be set in the config file.\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn load(list: &[&str], env: &[(&str, &str)]) -> Result<Settings, Vec<String>> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Settings::load(args(list), |name| env.get(name).cloned())
+    }
+
+    /// Write `contents` to a fresh file under the temp dir.
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "pinball-settings-{}-{}.toml",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn defaults_without_any_layer() {
+        let settings = load(&[], &[]).unwrap();
+        assert_eq!(
+            settings.config.tick_rate_hz,
+            ServerConfig::default().tick_rate_hz
+        );
+        assert!(!settings.print_config && !settings.help);
+    }
+
+    #[test]
+    fn file_then_env_then_cli() {
+        let path = config_file(
+            "precedence",
+            r#"
+                tick_rate_hz = 30
+                bot_count = 1
+                cell_count = 500
+                [deep_space]
+                portal_alpha = 0.2
+            "#,
+        );
+        let path = path.to_str().unwrap();
+        let settings = load(
+            &[
+                "--config",
+                path,
+                "--bot-count",
+                "7",
+                "--deep-space-omega-max=2.5",
+            ],
+            &[
+                ("BOT_COUNT", "5"),
+                ("CELL_COUNT", "600"),
+                ("ALLOWED_ORIGINS", "https://a, https://b"),
+            ],
+        )
+        .unwrap();
+        let c = settings.config;
+        assert_eq!(c.tick_rate_hz, 30); // file
+        assert_eq!(c.cell_count, 600); // env over file
+        assert_eq!(c.bot_count, 7); // CLI over env
+        assert_eq!(c.deep_space.portal_alpha, 0.2);
+        assert_eq!(c.deep_space.omega_max, 2.5);
+        assert_eq!(
+            c.deep_space.omega_min,
+            ServerConfig::default().deep_space.omega_min
+        );
+        assert_eq!(c.allowed_origins, vec!["https://a", "https://b"]);
+    }
+
+    #[test]
+    fn config_file_from_env_and_room_deep_space_layering() {
+        let path = config_file(
+            "rooms",
+            r#"
+                [deep_space]
+                omega_max = 3.0
+                [rooms.office]
+                bot_count = 0
+                [rooms.office.deep_space]
+                portal_alpha = 0.3
+            "#,
+        );
+        let settings = load(&[], &[(CONFIG_FILE_ENV, path.to_str().unwrap())]).unwrap();
+        let office = &settings.config.rooms["office"];
+        assert_eq!(office.bot_count, Some(0));
+        let ds = office.deep_space.unwrap();
+        assert_eq!(ds.portal_alpha, 0.3);
+        // Unset keys come from the top-level [deep_space], not the built-in default
+        assert_eq!(ds.omega_max, 3.0);
+    }
+
+    #[test]
+    fn deprecated_keys_set_their_replacements() {
+        use crate::config::{RateLimit, RateLimitPolicy};
+
+        let path = config_file("deprecated", "max_ball_escaped_per_sec = 12\n");
+        let settings = load(&["--config", path.to_str().unwrap()], &[]).unwrap();
+        assert_eq!(
+            settings.config.rate_limits.ball_escaped,
+            RateLimit::new(12.0, 12, RateLimitPolicy::Disconnect)
+        );
+
+        let settings = load(&[], &[("MAX_BALL_ESCAPED_PER_SEC", "8")]).unwrap();
+        assert_eq!(settings.config.rate_limits.ball_escaped.per_sec, 8.0);
+        assert_eq!(settings.config.rate_limits.ball_escaped.burst, 8);
+
+        // The new key wins when a layer has both
+        let path = config_file(
+            "deprecated-both",
+            r#"
+                max_ball_escaped_per_sec = 12
+                [rate_limits.ball_escaped]
+                per_sec = 5.0
+                burst = 10
+                policy = "ignore"
+            "#,
+        );
+        let settings = load(&["--config", path.to_str().unwrap()], &[]).unwrap();
+        assert_eq!(
+            settings.config.rate_limits.ball_escaped,
+            RateLimit::new(5.0, 10, RateLimitPolicy::Ignore)
+        );
+    }
+
+    #[test]
+    fn errors_name_their_source() {
+        let errors = load(&["--tick-rate", "30", "--bot-count"], &[]).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "--tick-rate: unknown flag (see --help)",
+                "--bot-count: missing value"
+            ]
+        );
+
+        let errors = load(
+            &["--deep-space-portal-alpha", "wide"],
+            &[("TICK_RATE_HZ", "fast")],
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "TICK_RATE_HZ: expected a number, got \"fast\"",
+                "--deep-space-portal-alpha: expected a number, got \"wide\"",
+            ]
+        );
+
+        let path = config_file("typo", "[deep_space]\nomega_maxx = 1.0\n");
+        let errors = load(&["--config", path.to_str().unwrap()], &[]).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(
+            errors[0].starts_with("deep_space.omega_maxx: unknown field `omega_maxx`"),
+            "{:?}",
+            errors
+        );
+
+        let path = config_file("type", "max_rooms = \"many\"\n");
+        let errors = load(&["--config", path.to_str().unwrap()], &[]).unwrap_err();
+        assert!(
+            errors[0].starts_with("max_rooms: invalid type"),
+            "{:?}",
+            errors
+        );
+
+        let errors = load(&["--config", "/nonexistent/pinball.toml"], &[]).unwrap_err();
+        assert!(
+            errors[0].starts_with("/nonexistent/pinball.toml: "),
+            "{:?}",
+            errors
+        );
+    }
+
+    #[test]
+    fn every_bad_field_is_reported_with_validation_problems() {
+        let path = config_file(
+            "several",
+            r#"
+                max_rooms = "many"
+                tick_rate_hz = 0
+                [deep_space]
+                omega_maxx = 1.0
+                [rooms.office]
+                bot_count = -1
+            "#,
+        );
+        let errors = load(
+            &["--config", path.to_str().unwrap()],
+            &[("CELL_COUNT", "lots")],
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        for prefix in [
+            "CELL_COUNT: expected a number",
+            "max_rooms: invalid type",
+            "deep_space.omega_maxx: unknown field",
+            "rooms.office.bot_count: invalid value",
+            "tick_rate_hz: must be > 0",
+        ] {
+            assert!(
+                errors.iter().any(|e| e.starts_with(prefix)),
+                "{} missing from {:?}",
+                prefix,
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn validation_reports_every_problem_with_field_paths() {
+        let path = config_file(
+            "invalid",
+            r#"
+                tick_rate_hz = 0
+                [deep_space]
+                omega_min = 2.0
+                omega_max = 1.0
+                [rooms.office]
+                max_connections = 0
+            "#,
+        );
+        let errors = load(
+            &["--config", path.to_str().unwrap()],
+            &[("AOI_RADIUS", "-1")],
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "tick_rate_hz: must be > 0",
+                "aoi_radius: must be finite and > 0",
+                "deep_space.omega_max: must be finite and >= omega_min",
+                "rooms.office.max_connections: must be > 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn print_config_round_trips_and_redacts_token() {
+        let settings = load(
+            &["--print-config"],
+            &[("ADMIN_TOKEN", "secret"), ("BOT_COUNT", "9")],
+        )
+        .unwrap();
+        assert!(settings.print_config);
+        let text = to_toml(&settings.config);
+        assert!(!text.contains("secret"));
+        assert!(text.contains("[deep_space]"));
+
+        let with_secret = load(&[], &[("JOIN_TOKEN_SECRET", "0123456789abcdef-secret")]).unwrap();
+        assert!(!to_toml(&with_secret.config).contains("0123456789abcdef"));
+
+        let path = config_file("roundtrip", &text);
+        let reloaded = load(&["--config", path.to_str().unwrap()], &[]).unwrap();
+        assert_eq!(reloaded.config.bot_count, 9);
+        assert_eq!(reloaded.config.admin_token.as_deref(), Some("<redacted>"));
+    }
+
+    #[test]
+    fn seeds_toml_cannot_hold_are_rejected_before_printing() {
+        let errors = load(
+            &["--print-config", "--rng-seed", &u64::MAX.to_string()],
+            &[],
+        )
+        .unwrap_err();
+        assert_eq!(errors, vec!["rng_seed: must be <= 9223372036854775807"]);
+        let max = i64::MAX.to_string();
+        let settings = load(&["--print-config", "--rng-seed", &max], &[]).unwrap();
+        assert!(to_toml(&settings.config).contains(&format!("rng_seed = {}", max)));
+    }
+
+    #[test]
+    fn usage_lists_every_field() {
+        let text = usage();
+        for field in fields(&defaults()) {
+            assert!(text.contains(&field.flag_name()), "{}", field.flag_name());
+            assert!(text.contains(&field.env_name()), "{}", field.env_name());
+        }
+        assert!(text.contains("--deep-space-portal-alpha"));
+        assert!(text.contains("ADMIN_TOKEN"));
+        assert!(!text.contains("--rooms"));
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/config.example.toml");
+        let settings = load(&["--config", path], &[]).unwrap();
+        assert_eq!(
+            settings.config.listen_addr,
+            ServerConfig::default().listen_addr
+        );
+    }
+}
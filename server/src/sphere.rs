@@ -1,7 +1,7 @@
 use crate::vec3::{self, Vec3};
 use rand::seq::SliceRandom;
 use rand::Rng;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 /// Golden angle in radians: PI * (3 - sqrt(5)) ≈ 2.39996...
 /// Pre-computed since sqrt is not const fn.
@@ -29,8 +29,9 @@ pub fn fibonacci_sphere(m: usize) -> Vec<Vec3> {
 /// Manages cell allocation for players on the sphere.
 pub struct PortalPlacement {
     pub cell_centers: Vec<Vec3>,
-    /// Stack of free cell indices for O(1) pop
-    free_cells: Vec<usize>,
+    /// Free cell indices. Allocation pops from the back; released cells go to
+    /// the front so they are reused last (gives resume tokens a chance).
+    free_cells: VecDeque<usize>,
     /// O(1) lookup to check if a cell is free
     free_set: HashSet<usize>,
     token_to_cell: HashMap<String, usize>,
@@ -40,9 +41,10 @@ impl PortalPlacement {
     pub fn new(cell_count: usize, rng: &mut impl Rng) -> Self {
         let cell_centers = fibonacci_sphere(cell_count);
 
-        let mut free_cells: Vec<usize> = (0..cell_count).collect();
-        free_cells.shuffle(rng);
-        let free_set: HashSet<usize> = free_cells.iter().copied().collect();
+        let mut shuffled: Vec<usize> = (0..cell_count).collect();
+        shuffled.shuffle(rng);
+        let free_set: HashSet<usize> = shuffled.iter().copied().collect();
+        let free_cells = VecDeque::from(shuffled);
 
         Self {
             cell_centers,
@@ -60,14 +62,14 @@ impl PortalPlacement {
                 if self.free_set.remove(&prev_cell) {
                     // Remove from free_cells vec (O(n) but rare path)
                     if let Some(free_idx) = self.free_cells.iter().position(|&c| c == prev_cell) {
-                        self.free_cells.swap_remove(free_idx);
+                        self.free_cells.remove(free_idx);
                     }
                     return Some(prev_cell);
                 }
             }
         }
 
-        let cell_index = self.free_cells.pop()?;
+        let cell_index = self.free_cells.pop_back()?;
         self.free_set.remove(&cell_index);
 
         if let Some(token) = resume_token {
@@ -80,10 +82,15 @@ impl PortalPlacement {
     /// Release a cell back to the pool. O(1).
     pub fn release(&mut self, cell_index: usize) {
         if self.free_set.insert(cell_index) {
-            self.free_cells.push(cell_index);
+            self.free_cells.push_front(cell_index);
         }
     }
 
+    /// Forget the cell remembered for a resume token.
+    pub fn forget_token(&mut self, token: &str) {
+        self.token_to_cell.remove(token);
+    }
+
     /// Get portal position for a cell.
     pub fn portal_pos(&self, cell_index: usize) -> Vec3 {
         self.cell_centers[cell_index]
@@ -231,6 +238,31 @@ mod tests {
         assert_eq!(idx1, idx2);
     }
 
+    #[test]
+    fn released_cell_is_reused_last() {
+        let mut rng = test_rng();
+        let mut placement = PortalPlacement::new(10, &mut rng);
+        let idx = placement.allocate(Some("player-123")).unwrap();
+        placement.release(idx);
+
+        // Other players joining must not take the released cell while others are free
+        for _ in 0..9 {
+            assert_ne!(placement.allocate(None).unwrap(), idx);
+        }
+        assert_eq!(placement.allocate(None), Some(idx));
+    }
+
+    #[test]
+    fn forgotten_token_does_not_reclaim_cell() {
+        let mut rng = test_rng();
+        let mut placement = PortalPlacement::new(100, &mut rng);
+        let idx1 = placement.allocate(Some("player-123")).unwrap();
+        placement.release(idx1);
+        placement.forget_token("player-123");
+        let idx2 = placement.allocate(Some("player-123")).unwrap();
+        assert_ne!(idx1, idx2);
+    }
+
     #[test]
     fn different_tokens_get_different_indices() {
         let mut rng = test_rng();
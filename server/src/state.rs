@@ -1,20 +1,84 @@
 use crate::bot::BotManager;
 use crate::config::{DeepSpaceConfig, ServerConfig};
-use crate::deep_space::{CaptureEvent, SphereDeepSpace};
+use crate::deep_space::{BallLineage, CaptureEvent, DeepSpaceSnapshot, SphereDeepSpace};
+use crate::id_map::IdMap;
+use crate::names::{NameError, NamePolicy};
 use crate::player::{color_from_id, Player};
-use crate::protocol::{ball_to_wire, player_to_wire, PlayersStateMsg, SpaceStateMsg};
+use crate::protocol::{
+    ball_to_wire, player_to_wire, BallWire, LeaderboardMsg, PlayersStateMsg, SpaceStateMsg,
+};
+use crate::score::ScoreBoard;
 use crate::sphere::PortalPlacement;
+use rand::Rng;
 use rand_chacha::ChaCha8Rng;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 /// How long (seconds) since last activity before a player is considered inactive.
 const ACTIVITY_TIMEOUT: f64 = 30.0;
+/// How long (seconds) a departed player's identity can be resumed with their token.
+const RESUME_RETENTION: f64 = 600.0;
+/// How long (seconds) a client has to ack a `transfer_in` before it is
+/// considered dead and the ball goes back to deep space.
+pub const TRANSFER_ACK_TIMEOUT: f64 = 5.0;
+/// Most received balls remembered per player for when they escape again.
+/// Beyond this the oldest lose their lineage and re-escape as new balls.
+const MAX_HELD_BALLS: usize = 16;
+
+/// A captured ball sent to a client as `transfer_in` but not yet acked.
+/// Until the ack arrives the server still answers for the ball.
+#[derive(Debug, Clone)]
+pub struct PendingTransfer {
+    pub seq: u32,
+    /// Player who last sent the ball out
+    pub ball_owner_id: u32,
+    pub lineage: BallLineage,
+    pub vx: f64,
+    pub vy: f64,
+    /// Server elapsed time when the transfer was sent
+    pub sent_at: f64,
+}
+
+/// Identity kept for a departed player so a reconnect with the same resume
+/// token gets the same id (and therefore color) and ball stats back.
+#[derive(Debug, Clone)]
+struct RetainedPlayer {
+    id: u32,
+    balls_produced: u32,
+    name: String,
+    /// Server elapsed time when the player left
+    left_at: f64,
+}
+
+/// Bumped when `GameSnapshot`'s layout changes incompatibly.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The part of `GameState` persisted across restarts (see `persist.rs`):
+/// deep space and enough RNG state to continue the same random stream.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSnapshot {
+    pub version: u32,
+    pub deep_space: DeepSpaceSnapshot,
+    pub next_player_id: u32,
+    pub rng: RngSnapshot,
+}
+
+/// Position of the game's `ChaCha8Rng` in its stream.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RngSnapshot {
+    pub seed: [u8; 32],
+    pub stream: u64,
+    /// Stored as a string: JSON numbers can't hold a u128 losslessly
+    pub word_pos: String,
+}
 
 /// Central game state owned by the game loop task.
 pub struct GameState {
     pub deep_space: SphereDeepSpace,
     pub placement: PortalPlacement,
-    pub players: HashMap<u32, Player>,
+    /// Passed to deep space in this order (see `id_map.rs`)
+    pub players: IdMap<Player>,
     pub config: DeepSpaceConfig,
     pub rng: ChaCha8Rng,
     pub bots: BotManager,
@@ -25,6 +89,18 @@ pub struct GameState {
     elapsed: f64,
     /// Whether there were active players last tick (used to detect reactivation)
     was_active: bool,
+    /// Resume token of each connected real player
+    resume_tokens: HashMap<u32, String>,
+    /// Departed players by resume token, dropped after `RESUME_RETENTION`
+    retained: HashMap<String, RetainedPlayer>,
+    /// Unacked transfers per receiving player, oldest first
+    pending_transfers: HashMap<u32, VecDeque<PendingTransfer>>,
+    /// Acked balls on each player's board, oldest first, so the lineage
+    /// can be picked up again when the client echoes the id on escape
+    held_balls: HashMap<u32, VecDeque<BallLineage>>,
+    next_transfer_seq: u32,
+    names: NamePolicy,
+    pub scores: ScoreBoard,
 }
 
 impl GameState {
@@ -36,12 +112,12 @@ impl GameState {
         use rand::SeedableRng;
         let mut rng = ChaCha8Rng::seed_from_u64(server_config.rng_seed);
         let placement = PortalPlacement::new(server_config.cell_count, &mut rng);
-        let deep_space = SphereDeepSpace::new(deep_space_config.clone(), capture_speed);
+        let deep_space = SphereDeepSpace::new(deep_space_config, capture_speed);
 
         let mut state = Self {
             deep_space,
             placement,
-            players: HashMap::new(),
+            players: IdMap::new(),
             config: deep_space_config,
             rng,
             bots: BotManager::new(),
@@ -49,6 +125,13 @@ impl GameState {
             max_balls_global: server_config.max_balls_global,
             elapsed: 0.0,
             was_active: false,
+            resume_tokens: HashMap::new(),
+            retained: HashMap::new(),
+            pending_transfers: HashMap::new(),
+            held_balls: HashMap::new(),
+            next_transfer_seq: 1,
+            names: NamePolicy::new(server_config.max_name_len, &server_config.name_blocklist),
+            scores: ScoreBoard::new(server_config.score_reset_secs),
         };
 
         // Spawn bots
@@ -61,45 +144,143 @@ impl GameState {
 
     /// Add a bot player. Returns the player ID if successful.
     pub fn add_bot(&mut self) -> Option<u32> {
-        let (id, _) = self.add_player_internal(true)?;
-        let player = self.players.get(&id)?;
-        self.bots.add_bot(player, &mut self.rng);
+        let cell_index = self.placement.allocate(None)?;
+        let id = self.next_id();
+        let player = self.insert_player(id, cell_index, true, 0, String::new());
+        self.bots.add_bot(&player, &mut self.rng);
         Some(id)
     }
 
+    /// Remove a bot player. Returns false if `id` is not a bot.
+    pub fn remove_bot(&mut self, id: u32) -> bool {
+        if !self.bots.is_bot(id) {
+            return false;
+        }
+        self.bots.remove_bot(id);
+        self.remove_player(id);
+        true
+    }
+
     /// Add a new player, returns (player_id, Player)
     pub fn add_player(&mut self) -> Option<(u32, Player)> {
-        self.add_player_internal(false)
+        self.join_player(None)
     }
 
-    /// Internal: Add a new player with is_bot flag
-    fn add_player_internal(&mut self, is_bot: bool) -> Option<(u32, Player)> {
-        let cell_index = self.placement.allocate(None)?;
+    /// Add a real player, resuming a departed player's identity if
+    /// `resume_token` matches one. Unknown tokens are ignored and a fresh
+    /// token is issued (see `resume_token`).
+    pub fn join_player(&mut self, resume_token: Option<&str>) -> Option<(u32, Player)> {
+        self.prune_retained();
+
+        let (token, retained) = match resume_token.and_then(|t| self.retained.remove_entry(t)) {
+            Some((token, retained)) => (token, Some(retained)),
+            None => (new_resume_token(), None),
+        };
+
+        let Some(cell_index) = self.placement.allocate(Some(&token)) else {
+            if let Some(retained) = retained {
+                self.retained.insert(token, retained);
+            }
+            return None;
+        };
+
+        let (id, balls_produced, name) = match retained {
+            Some(r) => (r.id, r.balls_produced, r.name),
+            None => (self.next_id(), 0, String::new()),
+        };
+        let player = self.insert_player(id, cell_index, false, balls_produced, name);
+        self.resume_tokens.insert(id, token);
+        Some((id, player))
+    }
+
+    /// Resume token issued to a connected player.
+    pub fn resume_token(&self, id: u32) -> Option<&str> {
+        self.resume_tokens.get(&id).map(String::as_str)
+    }
+
+    /// Id of the departed player `token` would resume, if still retained.
+    pub fn retained_id(&self, token: &str) -> Option<u32> {
+        self.retained.get(token).map(|r| r.id)
+    }
+
+    /// Resume token of the departed player `id`, if still retained.
+    pub fn retained_token(&self, id: u32) -> Option<&str> {
+        self.retained
+            .iter()
+            .find(|(_, r)| r.id == id)
+            .map(|(token, _)| token.as_str())
+    }
+
+    fn next_id(&mut self) -> u32 {
         let id = self.next_player_id;
         self.next_player_id += 1;
+        id
+    }
 
+    fn insert_player(
+        &mut self,
+        id: u32,
+        cell_index: usize,
+        is_bot: bool,
+        balls_produced: u32,
+        name: String,
+    ) -> Player {
         let player = Player {
             id,
             cell_index: cell_index as u32,
             portal_pos: self.placement.portal_pos(cell_index),
             color: color_from_id(id),
             paused: false,
-            balls_produced: 0,
+            balls_produced,
             is_bot,
+            name,
             last_activity: 0.0,
         };
 
         self.players.insert(id, player.clone());
         self.sync_players_to_deep_space();
-        Some((id, player))
+        player
     }
 
-    /// Remove a player
-    pub fn remove_player(&mut self, id: u32) {
+    /// Remove a player. Balls they never acked go back into deep space
+    /// from their portal; returns how many.
+    pub fn remove_player(&mut self, id: u32) -> usize {
+        let reinjected = self.reinject_transfers(id);
+        self.held_balls.remove(&id);
         if let Some(player) = self.players.remove(&id) {
             self.placement.release(player.cell_index as usize);
+            // A resumable player keeps their score until the identity expires
+            if let Some(token) = self.resume_tokens.remove(&id) {
+                self.retained.insert(
+                    token,
+                    RetainedPlayer {
+                        id,
+                        balls_produced: player.balls_produced,
+                        name: player.name,
+                        left_at: self.elapsed,
+                    },
+                );
+            } else {
+                self.scores.remove(id);
+            }
             self.sync_players_to_deep_space();
         }
+        reinjected
+    }
+
+    /// Drop retained identities older than `RESUME_RETENTION`.
+    fn prune_retained(&mut self) {
+        let now = self.elapsed;
+        let placement = &mut self.placement;
+        let scores = &mut self.scores;
+        self.retained.retain(|token, r| {
+            let keep = now - r.left_at < RESUME_RETENTION;
+            if !keep {
+                placement.forget_token(token);
+                scores.remove(r.id);
+            }
+            keep
+        });
     }
 
     /// Set a player's paused state. Returns true if player exists and state changed.
@@ -114,6 +295,32 @@ impl GameState {
         false
     }
 
+    /// Validate and set a player's display name (empty clears it). Returns
+    /// Ok(true) if the name changed; unknown players are Ok(false).
+    pub fn set_player_name(&mut self, id: u32, raw: &str) -> Result<bool, NameError> {
+        let name = self.names.validate(raw)?;
+        match self.players.get_mut(&id) {
+            Some(player) if player.name != name => {
+                player.name = name;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Credit bumper hits reported by a real player.
+    pub fn add_bumper_hits(&mut self, id: u32, count: u32) {
+        if self.players.get(&id).is_some_and(|p| !p.is_bot) {
+            self.scores.add_hits(id, count);
+        }
+    }
+
+    /// Ranking of connected real players for the `leaderboard` broadcast.
+    pub fn get_leaderboard(&self) -> LeaderboardMsg {
+        let ids = self.players.values().filter(|p| !p.is_bot).map(|p| p.id);
+        self.scores.leaderboard(ids, self.elapsed)
+    }
+
     /// Record player activity (called when server receives an activity heartbeat).
     pub fn player_activity(&mut self, id: u32) {
         if let Some(player) = self.players.get_mut(&id) {
@@ -134,6 +341,17 @@ impl GameState {
         self.elapsed += dt;
 
         let all_captures = self.deep_space.tick(dt, &mut self.rng);
+        self.scores.maybe_reset(self.elapsed);
+        // Bots take their captures on the spot; a real player's capture
+        // scores in `ack_transfer`, since an unacked ball is re-injected
+        // and could be captured (and scored) again
+        for cap in all_captures
+            .iter()
+            .filter(|c| self.bots.is_bot(c.player_id))
+        {
+            self.scores
+                .record_capture(cap.ball_owner_id, cap.origin_id, cap.player_id);
+        }
 
         // Detect transition from inactive → active: flush stale pending bot balls
         let has_active = self.has_active_players();
@@ -148,8 +366,13 @@ impl GameState {
                 // During inactivity we discard the ball so pending queues don't
                 // accumulate and flood deep-space the moment a player returns.
                 if has_active {
-                    self.bots
-                        .handle_capture(cap.player_id, cap.vx, cap.vy, &mut self.rng);
+                    self.bots.handle_capture(
+                        cap.player_id,
+                        cap.vx,
+                        cap.vy,
+                        cap.lineage(),
+                        &mut self.rng,
+                    );
                 }
             } else {
                 // Real player - return the capture event
@@ -168,16 +391,145 @@ impl GameState {
         let bot_balls = self
             .bots
             .tick(dt, &mut self.rng, real_player_count, has_active);
-        for (bot_id, vx, vy) in bot_balls {
-            self.ball_escaped(bot_id, vx, vy);
+        for (bot_id, vx, vy, lineage) in bot_balls {
+            self.add_escaped_ball(bot_id, vx, vy, lineage.map(BallLineage::next_hop));
         }
 
         real_captures
     }
 
+    /// Record a capture about to be sent to its player as `transfer_in`.
+    /// Returns the sequence id the client must ack.
+    pub fn begin_transfer(&mut self, cap: &CaptureEvent) -> u32 {
+        let seq = self.next_transfer_seq;
+        self.next_transfer_seq = self.next_transfer_seq.wrapping_add(1).max(1);
+        self.pending_transfers
+            .entry(cap.player_id)
+            .or_default()
+            .push_back(PendingTransfer {
+                seq,
+                ball_owner_id: cap.ball_owner_id,
+                lineage: cap.lineage(),
+                vx: cap.vx,
+                vy: cap.vy,
+                sent_at: self.elapsed,
+            });
+        seq
+    }
+
+    /// The client has the ball, so the capture scores now. Returns false
+    /// for unknown or repeated acks.
+    pub fn ack_transfer(&mut self, player_id: u32, seq: u32) -> bool {
+        let Some(pending) = self.pending_transfers.get_mut(&player_id) else {
+            return false;
+        };
+        let Some(i) = pending.iter().position(|t| t.seq == seq) else {
+            return false;
+        };
+        let transfer = pending.remove(i);
+        if pending.is_empty() {
+            self.pending_transfers.remove(&player_id);
+        }
+        if let Some(t) = transfer {
+            self.scores
+                .record_capture(t.ball_owner_id, t.lineage.origin_id, player_id);
+            let held = self.held_balls.entry(player_id).or_default();
+            if held.len() >= MAX_HELD_BALLS {
+                held.pop_front();
+            }
+            held.push_back(t.lineage);
+        }
+        true
+    }
+
+    /// Take the lineage of a ball on `player_id`'s board that escapes
+    /// again, counting the board it just left as one more hop.
+    fn take_held_ball(&mut self, player_id: u32, ball_id: u32) -> Option<BallLineage> {
+        let held = self.held_balls.get_mut(&player_id)?;
+        let i = held.iter().position(|l| l.id == ball_id)?;
+        let lineage = held.remove(i)?;
+        if held.is_empty() {
+            self.held_balls.remove(&player_id);
+        }
+        Some(lineage.next_hop())
+    }
+
+    /// Players with a transfer unacked for longer than `TRANSFER_ACK_TIMEOUT`.
+    pub fn overdue_transfers(&self) -> Vec<u32> {
+        self.pending_transfers
+            .iter()
+            .filter(|(_, pending)| {
+                pending
+                    .front()
+                    .is_some_and(|t| self.elapsed - t.sent_at > TRANSFER_ACK_TIMEOUT)
+            })
+            .map(|(&id, _)| id)
+            .collect()
+    }
+
+    /// Unacked transfers across all players.
+    pub fn pending_transfer_count(&self) -> usize {
+        self.pending_transfers.values().map(VecDeque::len).sum()
+    }
+
+    /// Send a player's unacked balls back out of their portal, as if their
+    /// board had bounced them straight back. Returns how many.
+    fn reinject_transfers(&mut self, player_id: u32) -> usize {
+        let Some(pending) = self.pending_transfers.remove(&player_id) else {
+            return 0;
+        };
+        let Some(portal_pos) = self.players.get(&player_id).map(|p| p.portal_pos) else {
+            return 0;
+        };
+        for t in &pending {
+            // Ignores max_balls_global: these balls were already counted
+            self.deep_space.add_ball_with_lineage(
+                t.ball_owner_id,
+                portal_pos,
+                t.vx,
+                -t.vy,
+                Some(t.lineage),
+                &mut self.rng,
+            );
+        }
+        pending.len()
+    }
+
     /// Add a ball escaped from a player's board.
     /// Returns None if player not found or global ball cap reached.
     pub fn ball_escaped(&mut self, owner_id: u32, vx: f64, vy: f64) -> Option<u32> {
+        self.ball_escaped_to(owner_id, vx, vy, None, None)
+    }
+
+    /// Like `ball_escaped`, but gifts the ball to `target_id` so reroutes
+    /// steer it toward their portal. Targets that aren't connected, or are
+    /// the owner, are ignored and the ball routes normally. A `ball_id`
+    /// from a `transfer_in` this player acked keeps that ball's identity;
+    /// any other id is ignored and the ball is new.
+    pub fn ball_escaped_to(
+        &mut self,
+        owner_id: u32,
+        vx: f64,
+        vy: f64,
+        target_id: Option<u32>,
+        ball_id: Option<u32>,
+    ) -> Option<u32> {
+        let target_id = target_id.filter(|&t| t != owner_id && self.players.contains_key(&t));
+        let lineage = ball_id.and_then(|id| self.take_held_ball(owner_id, id));
+        let ball_id = self.add_escaped_ball(owner_id, vx, vy, lineage)?;
+        if target_id.is_some() {
+            self.deep_space.set_ball_target(ball_id, target_id);
+        }
+        Some(ball_id)
+    }
+
+    fn add_escaped_ball(
+        &mut self,
+        owner_id: u32,
+        vx: f64,
+        vy: f64,
+        lineage: Option<BallLineage>,
+    ) -> Option<u32> {
         // Check global ball cap
         if self.deep_space.ball_count() >= self.max_balls_global {
             return None;
@@ -186,17 +538,33 @@ impl GameState {
         let player = self.players.get_mut(&owner_id)?;
         let portal_pos = player.portal_pos;
         player.balls_produced += 1;
-        Some(
-            self.deep_space
-                .add_ball(owner_id, portal_pos, vx, vy, &mut self.rng),
-        )
+        Some(self.deep_space.add_ball_with_lineage(
+            owner_id,
+            portal_pos,
+            vx,
+            vy,
+            lineage,
+            &mut self.rng,
+        ))
     }
 
     /// Get space state for broadcasting
     pub fn get_space_state(&self) -> SpaceStateMsg {
+        self.space_state_into(Vec::new())
+    }
+
+    /// Like `get_space_state`, but fills `balls` (cleared first), so the
+    /// caller can reuse its allocation.
+    pub fn space_state_into(&self, mut balls: Vec<BallWire>) -> SpaceStateMsg {
+        balls.clear();
+        balls.extend(self.deep_space.get_ball_iter().map(ball_to_wire));
         SpaceStateMsg {
             server_time: self.elapsed,
-            balls: self.deep_space.get_ball_iter().map(ball_to_wire).collect(),
+            seq: 0,
+            keyframe: true,
+            balls,
+            unchanged: Vec::new(),
+            removed: Vec::new(),
         }
     }
 
@@ -226,6 +594,64 @@ impl GameState {
     pub fn deep_space_ball_count(&self) -> usize {
         self.deep_space.ball_count()
     }
+
+    /// Remove every ball from deep space. Returns how many were removed.
+    pub fn clear_balls(&mut self) -> usize {
+        self.deep_space.clear_balls()
+    }
+
+    /// Elapsed server time in seconds
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Capture the persistent part of the state.
+    pub fn snapshot(&self) -> GameSnapshot {
+        GameSnapshot {
+            version: SNAPSHOT_VERSION,
+            deep_space: self.deep_space.snapshot(),
+            next_player_id: self.next_player_id,
+            rng: RngSnapshot {
+                seed: self.rng.get_seed(),
+                stream: self.rng.get_stream(),
+                word_pos: self.rng.get_word_pos().to_string(),
+            },
+        }
+    }
+
+    /// Continue from `snapshot`: its balls replace the current ones and the
+    /// RNG resumes where it was. Player ids keep counting from the larger
+    /// of the two, so new players never reuse the id of a ball's owner.
+    /// Returns how many balls were restored.
+    pub fn restore(&mut self, snapshot: GameSnapshot) -> Result<usize, String> {
+        use rand::SeedableRng;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "unsupported snapshot version {} (expected {})",
+                snapshot.version, SNAPSHOT_VERSION
+            ));
+        }
+        let word_pos: u128 = snapshot
+            .rng
+            .word_pos
+            .parse()
+            .map_err(|_| format!("invalid rng word_pos {:?}", snapshot.rng.word_pos))?;
+
+        let mut rng = ChaCha8Rng::from_seed(snapshot.rng.seed);
+        rng.set_stream(snapshot.rng.stream);
+        rng.set_word_pos(word_pos);
+        self.rng = rng;
+        self.next_player_id = self.next_player_id.max(snapshot.next_player_id);
+        Ok(self
+            .deep_space
+            .restore(snapshot.deep_space, self.max_balls_global))
+    }
+}
+
+/// Mint an unguessable resume token. Uses the thread RNG rather than the
+/// seeded game RNG so issuing tokens doesn't perturb the simulation.
+fn new_resume_token() -> String {
+    format!("{:032x}", rand::thread_rng().gen::<u128>())
 }
 
 #[cfg(test)]
@@ -240,10 +666,12 @@ mod tests {
             bot_count: 0, // No bots in unit tests for predictable behavior
             ..Default::default()
         };
-        let mut deep_space_config = DeepSpaceConfig::default();
         // Use shorter times for faster tests
-        deep_space_config.min_age_for_capture = 2.0;
-        deep_space_config.reroute_after = 5.0;
+        let deep_space_config = DeepSpaceConfig {
+            min_age_for_capture: 2.0,
+            reroute_after: 5.0,
+            ..Default::default()
+        };
         GameState::new(&server_config, deep_space_config, 3.0)
     }
 
@@ -334,6 +762,157 @@ mod tests {
         assert_eq!(p.balls_produced, 1);
     }
 
+    #[test]
+    fn ball_escaped_to_keeps_only_valid_targets() {
+        let mut state = test_state();
+        let (id1, _) = state.add_player().unwrap();
+        let (id2, _) = state.add_player().unwrap();
+
+        let gifted = state
+            .ball_escaped_to(id1, 0.1, -1.0, Some(id2), None)
+            .unwrap();
+        let to_self = state
+            .ball_escaped_to(id1, 0.1, -1.0, Some(id1), None)
+            .unwrap();
+        let to_nobody = state
+            .ball_escaped_to(id1, 0.1, -1.0, Some(999), None)
+            .unwrap();
+
+        let target = |ball| state.deep_space.get_ball(ball).unwrap().target_id;
+        assert_eq!(target(gifted), Some(id2));
+        assert_eq!(target(to_self), None);
+        assert_eq!(target(to_nobody), None);
+        assert_eq!(state.players.get(&id1).unwrap().balls_produced, 3);
+    }
+
+    #[test]
+    fn bumper_hits_score_for_real_players_and_survive_resume() {
+        let mut state = test_state();
+        let bot = state.add_bot().unwrap();
+        let (id, _) = state.join_player(None).unwrap();
+        let token = state.resume_token(id).unwrap().to_string();
+
+        state.add_bumper_hits(id, 3);
+        state.add_bumper_hits(bot, 3);
+        let lb = state.get_leaderboard();
+        assert_eq!(lb.total, 1, "bots aren't ranked");
+        assert_eq!(lb.entries[0].player_id, id);
+        assert_eq!(lb.entries[0].score, 3);
+        assert_eq!(state.scores.get(bot).points(), 0);
+
+        state.remove_player(id);
+        let (resumed, _) = state.join_player(Some(&token)).unwrap();
+        assert_eq!(resumed, id);
+        assert_eq!(state.scores.get(id).points(), 3);
+    }
+
+    // --- Resume token tests ---
+
+    #[test]
+    fn join_issues_resume_token() {
+        let mut state = test_state();
+        let (id, _) = state.join_player(None).unwrap();
+        let token = state.resume_token(id).unwrap();
+        assert_eq!(token.len(), 32);
+    }
+
+    #[test]
+    fn resume_restores_id_cell_color_and_balls_produced() {
+        let mut state = test_state();
+        let (id, original) = state.join_player(None).unwrap();
+        let token = state.resume_token(id).unwrap().to_string();
+        state.ball_escaped(id, 1.0, 2.0);
+        state.ball_escaped(id, 1.0, 2.0);
+        state.remove_player(id);
+
+        // Someone else joins in the meantime
+        state.add_player().unwrap();
+
+        let (resumed_id, resumed) = state.join_player(Some(&token)).unwrap();
+        assert_eq!(resumed_id, id);
+        assert_eq!(resumed.cell_index, original.cell_index);
+        assert_eq!(resumed.color, original.color);
+        assert_eq!(resumed.balls_produced, 2);
+        assert_eq!(state.resume_token(id), Some(token.as_str()));
+    }
+
+    #[test]
+    fn unknown_resume_token_gets_fresh_identity() {
+        let mut state = test_state();
+        let (id, _) = state.join_player(Some("not-a-real-token")).unwrap();
+        assert_ne!(state.resume_token(id), Some("not-a-real-token"));
+    }
+
+    #[test]
+    fn token_of_connected_player_cannot_be_reused() {
+        let mut state = test_state();
+        let (id, _) = state.join_player(None).unwrap();
+        let token = state.resume_token(id).unwrap().to_string();
+
+        let (other_id, _) = state.join_player(Some(&token)).unwrap();
+        assert_ne!(other_id, id);
+        assert_ne!(state.resume_token(other_id), Some(token.as_str()));
+    }
+
+    #[test]
+    fn retained_identity_expires() {
+        let mut state = test_state();
+        let (id, _) = state.join_player(None).unwrap();
+        let token = state.resume_token(id).unwrap().to_string();
+        state.remove_player(id);
+
+        state.tick(RESUME_RETENTION + 1.0);
+
+        let (new_id, player) = state.join_player(Some(&token)).unwrap();
+        assert_ne!(new_id, id);
+        assert_eq!(player.balls_produced, 0);
+    }
+
+    // --- Display name tests ---
+
+    #[test]
+    fn set_player_name_validates_and_reports_changes() {
+        let server_config = ServerConfig {
+            cell_count: 100,
+            bot_count: 0,
+            max_name_len: 8,
+            name_blocklist: vec!["rude".to_string()],
+            ..Default::default()
+        };
+        let mut state = GameState::new(&server_config, DeepSpaceConfig::default(), 1.0);
+        let (id, _) = state.add_player().unwrap();
+
+        assert_eq!(state.set_player_name(id, "  Ada  "), Ok(true));
+        assert_eq!(state.set_player_name(id, "Ada"), Ok(false));
+        assert_eq!(
+            state.set_player_name(id, "Ada Lovelace"),
+            Err(NameError::TooLong)
+        );
+        assert_eq!(
+            state.set_player_name(id, "RUDE dog"),
+            Err(NameError::Blocked)
+        );
+        assert_eq!(state.set_player_name(999, "Bob"), Ok(false));
+
+        let players_state = state.get_players_state();
+        assert_eq!(players_state.players[0].name, "Ada");
+
+        assert_eq!(state.set_player_name(id, ""), Ok(true));
+        assert_eq!(state.players[&id].name, "");
+    }
+
+    #[test]
+    fn resume_restores_name() {
+        let mut state = test_state();
+        let (id, _) = state.join_player(None).unwrap();
+        let token = state.resume_token(id).unwrap().to_string();
+        state.set_player_name(id, "Ada").unwrap();
+        state.remove_player(id);
+
+        let (_, resumed) = state.join_player(Some(&token)).unwrap();
+        assert_eq!(resumed.name, "Ada");
+    }
+
     // --- Bot integration tests ---
 
     fn test_state_with_bots(bot_count: usize) -> GameState {
@@ -343,9 +922,11 @@ mod tests {
             bot_count,
             ..Default::default()
         };
-        let mut deep_space_config = DeepSpaceConfig::default();
-        deep_space_config.min_age_for_capture = 2.0;
-        deep_space_config.reroute_after = 5.0;
+        let deep_space_config = DeepSpaceConfig {
+            min_age_for_capture: 2.0,
+            reroute_after: 5.0,
+            ..Default::default()
+        };
         GameState::new(&server_config, deep_space_config, 3.0)
     }
 
@@ -442,13 +1023,19 @@ mod tests {
         let mut ball_returned = false;
         for _ in 0..100 {
             state.tick(0.1);
-            if state.deep_space_ball_count() > 0 {
+            if state.deep_space.get_ball(1).is_some() {
                 ball_returned = true;
                 break;
             }
         }
 
         assert!(ball_returned, "Bot should return ball to deep space");
+        // Same ball, one board further
+        let ball = state.deep_space.get_ball(1).unwrap();
+        assert_eq!(
+            (ball.owner_id, ball.origin_id, ball.hops),
+            (receiver_bot_id, sender_bot_id, 1)
+        );
     }
 
     #[test]
@@ -678,4 +1265,255 @@ mod tests {
 
         state.remove_player(new_player_id);
     }
+
+    #[test]
+    fn remove_bot_only_removes_bots() {
+        let mut state = test_state_with_bots(2);
+        let (real_id, _) = state.add_player().unwrap();
+        let bot_id = state.bots.bot_ids()[0];
+
+        assert!(!state.remove_bot(real_id));
+        assert!(state.players.contains_key(&real_id));
+
+        assert!(state.remove_bot(bot_id));
+        assert!(!state.players.contains_key(&bot_id));
+        assert_eq!(state.bots.bot_count(), 1);
+        assert!(!state.remove_bot(bot_id));
+    }
+
+    #[test]
+    fn clear_balls_empties_deep_space() {
+        let mut state = test_state();
+        let (id, _) = state.add_player().unwrap();
+        state.ball_escaped(id, 0.1, -1.0);
+        state.ball_escaped(id, 0.2, -1.0);
+
+        assert_eq!(state.clear_balls(), 2);
+        assert_eq!(state.deep_space_ball_count(), 0);
+        assert!(state.get_space_state().balls.is_empty());
+    }
+
+    #[test]
+    fn restore_continues_balls_ids_and_rng() {
+        let mut state = test_state();
+        let (id, _) = state.add_player().unwrap();
+        state.ball_escaped(id, 1.0, 2.0);
+        state.ball_escaped(id, -1.0, 1.5);
+        state.tick(0.5);
+
+        let json = serde_json::to_string(&state.snapshot()).unwrap();
+        let snapshot: GameSnapshot = serde_json::from_str(&json).unwrap();
+
+        let mut restored = test_state();
+        assert_eq!(restored.restore(snapshot), Ok(2));
+        assert_eq!(restored.deep_space_ball_count(), 2);
+        // Same random stream from here on
+        assert_eq!(restored.rng.gen::<u64>(), state.rng.gen::<u64>());
+        // The departed owner's id isn't handed to someone else
+        let (new_id, _) = restored.add_player().unwrap();
+        assert!(new_id > id);
+    }
+
+    #[test]
+    fn restore_rejects_other_versions() {
+        let mut state = test_state();
+        let mut snapshot = state.snapshot();
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        assert!(state.restore(snapshot).is_err());
+    }
+
+    fn capture_for(player_id: u32, ball_owner_id: u32) -> CaptureEvent {
+        CaptureEvent {
+            ball_id: 0,
+            player_id,
+            ball_owner_id,
+            origin_id: ball_owner_id,
+            hops: 0,
+            ball_color: 0,
+            vx: 0.5,
+            vy: 1.0,
+        }
+    }
+
+    #[test]
+    fn re_escaped_ball_keeps_its_lineage() {
+        let mut state = test_state();
+        let (origin, _) = state.add_player().unwrap();
+        let (receiver, _) = state.add_player().unwrap();
+        let (other, _) = state.add_player().unwrap();
+
+        let seq = state.begin_transfer(&CaptureEvent {
+            ball_id: 40,
+            origin_id: origin,
+            hops: 2,
+            ..capture_for(receiver, other)
+        });
+        // Not acked yet: the ball isn't on the board as far as we know
+        let early = state
+            .ball_escaped_to(receiver, 0.1, -1.0, None, Some(40))
+            .unwrap();
+        assert_ne!(early, 40);
+        assert!(state.ack_transfer(receiver, seq));
+
+        // Someone else can't claim it
+        let stolen = state
+            .ball_escaped_to(other, 0.1, -1.0, None, Some(40))
+            .unwrap();
+        assert_ne!(stolen, 40);
+        assert_eq!(state.deep_space.get_ball(stolen).unwrap().hops, 0);
+
+        let id = state
+            .ball_escaped_to(receiver, 0.1, -1.0, None, Some(40))
+            .unwrap();
+        assert_eq!(id, 40);
+        let ball = state.deep_space.get_ball(id).unwrap();
+        assert_eq!(
+            (ball.owner_id, ball.origin_id, ball.hops),
+            (receiver, origin, 3)
+        );
+
+        // Only once
+        state.deep_space.clear_balls();
+        let again = state
+            .ball_escaped_to(receiver, 0.1, -1.0, None, Some(40))
+            .unwrap();
+        assert_eq!(
+            state.deep_space.get_ball(again).unwrap().origin_id,
+            receiver
+        );
+    }
+
+    #[test]
+    fn reinjected_transfer_keeps_its_lineage() {
+        let mut state = test_state();
+        let (origin, _) = state.add_player().unwrap();
+        let (receiver, _) = state.add_player().unwrap();
+        state.begin_transfer(&CaptureEvent {
+            ball_id: 40,
+            origin_id: origin,
+            hops: 5,
+            ..capture_for(receiver, origin)
+        });
+
+        assert_eq!(state.remove_player(receiver), 1);
+        let ball = state.deep_space.get_ball(40).unwrap();
+        assert_eq!((ball.origin_id, ball.hops), (origin, 5));
+    }
+
+    #[test]
+    fn acked_transfer_is_forgotten() {
+        let mut state = test_state();
+        let (id, _) = state.add_player().unwrap();
+
+        let a = state.begin_transfer(&capture_for(id, id));
+        let b = state.begin_transfer(&capture_for(id, id));
+        assert_ne!(a, b);
+        assert_eq!(state.pending_transfer_count(), 2);
+
+        assert!(state.ack_transfer(id, b));
+        assert!(!state.ack_transfer(id, b), "repeated ack");
+        assert!(!state.ack_transfer(id + 1, a), "someone else's transfer");
+        assert!(state.ack_transfer(id, a));
+        assert_eq!(state.pending_transfer_count(), 0);
+
+        // Nothing left to re-inject
+        assert_eq!(state.remove_player(id), 0);
+        assert_eq!(state.deep_space_ball_count(), 0);
+    }
+
+    #[test]
+    fn transfers_score_once_when_acked() {
+        let mut state = test_state();
+        let (origin, _) = state.add_player().unwrap();
+        let (sender, _) = state.add_player().unwrap();
+        let (receiver, _) = state.add_player().unwrap();
+        let cap = CaptureEvent {
+            origin_id: origin,
+            ..capture_for(receiver, sender)
+        };
+
+        // Unacked and re-injected: nobody scores
+        state.begin_transfer(&cap);
+        assert_eq!(state.remove_player(receiver), 1);
+        for id in [origin, sender, receiver] {
+            assert_eq!(state.scores.get(id).points(), 0);
+        }
+
+        let (receiver, _) = state.add_player().unwrap();
+        let seq = state.begin_transfer(&CaptureEvent {
+            player_id: receiver,
+            ..cap
+        });
+        assert!(state.ack_transfer(receiver, seq));
+        assert!(!state.ack_transfer(receiver, seq));
+        assert_eq!(state.scores.get(sender).delivered, 1);
+        assert_eq!(state.scores.get(receiver).received, 1);
+        assert_eq!(state.scores.get(origin).assists, 1);
+    }
+
+    #[test]
+    fn unacked_transfers_return_to_space_when_player_leaves() {
+        let mut state = test_state();
+        let (sender, _) = state.add_player().unwrap();
+        let (receiver, _) = state.add_player().unwrap();
+        let receiver_portal = state.players[&receiver].portal_pos;
+
+        let acked = state.begin_transfer(&capture_for(receiver, sender));
+        state.begin_transfer(&capture_for(receiver, sender));
+        state.begin_transfer(&capture_for(receiver, sender));
+        state.ack_transfer(receiver, acked);
+
+        assert_eq!(state.remove_player(receiver), 2);
+        assert_eq!(state.pending_transfer_count(), 0);
+        assert_eq!(state.deep_space_ball_count(), 2);
+        for ball in state.deep_space.get_ball_iter() {
+            assert_eq!(ball.owner_id, sender);
+            let d = ball.pos.x * receiver_portal.x
+                + ball.pos.y * receiver_portal.y
+                + ball.pos.z * receiver_portal.z;
+            assert!(d > 0.999, "re-injected away from the receiving portal");
+        }
+        // Not counted as produced by anyone
+        assert_eq!(state.players[&sender].balls_produced, 0);
+    }
+
+    #[test]
+    fn transfers_become_overdue_after_timeout() {
+        let mut state = test_state();
+        let (id, _) = state.add_player().unwrap();
+        state.begin_transfer(&capture_for(id, id));
+
+        state.tick(TRANSFER_ACK_TIMEOUT - 0.5);
+        assert!(state.overdue_transfers().is_empty());
+        state.tick(1.0);
+        assert_eq!(state.overdue_transfers(), vec![id]);
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        // Two states in one process still get differently seeded hashers,
+        // so this catches any RNG draw that follows a HashMap's order
+        let run = || {
+            let mut state = test_state();
+            let ids: Vec<u32> = (0..5).map(|_| state.add_player().unwrap().0).collect();
+            for (i, &id) in ids.iter().enumerate() {
+                for j in 0..6 {
+                    let vx = (i as f64 - 2.0) * 0.3 + j as f64 * 0.05;
+                    state.ball_escaped(id, vx, 1.0 + j as f64 * 0.1);
+                }
+            }
+            let mut captures = Vec::new();
+            for _ in 0..400 {
+                for cap in state.tick(0.05) {
+                    captures.push((cap.ball_id, cap.player_id, cap.vx.to_bits()));
+                    state.begin_transfer(&cap);
+                }
+            }
+            (captures, state.deep_space.checksum())
+        };
+
+        let (captures, checksum) = run();
+        assert!(!captures.is_empty());
+        assert_eq!(run(), (captures, checksum));
+    }
 }
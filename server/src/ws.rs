@@ -1,14 +1,25 @@
-use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
-use axum::extract::State;
-use axum::http::HeaderMap;
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, Query, State};
+use axum::http::{header, HeaderMap};
 use axum::response::IntoResponse;
 use futures_util::{SinkExt, StreamExt};
+use pinball_shared::wire::WIRE_PACKED;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{broadcast, mpsc, oneshot, Semaphore};
 
+use crate::anti_cheat::EscapeTracker;
+use crate::config::{AntiCheat, RateLimitPolicy};
 use crate::game_loop::{ClientEvent, GameBroadcast, GameCommand};
-use crate::protocol::{ClientMsg, ServerMsg, TransferInMsg};
+use crate::join_token::{self, TokenError};
+use crate::lifecycle::{Lifecycle, GOING_AWAY_REASON};
+use crate::metrics::{BroadcastKind, DisconnectReason, ServerMetrics};
+use crate::protocol::{ClientMsg, PongMsg, ServerGoingAwayMsg, ServerMsg, TransferInMsg};
+use crate::rate_limit::{MessageKind, RateLimiter, TokenBucket};
+use crate::room::{RoomError, RoomGuard, RoomRegistry};
 
 /// Maximum size of a text message from client (bytes)
 const MAX_TEXT_MSG_BYTES: usize = 1024;
@@ -17,12 +28,14 @@ const MAX_PARSE_ERRORS: u32 = 5;
 /// Timeout for sending messages to client (slow consumer protection)
 const SEND_TIMEOUT: Duration = Duration::from_secs(5);
 /// Idle timeout: disconnect clients that send nothing for this long.
-/// Prevents slow-loris style connection slot exhaustion.
+/// Prevents slow-loris style connection slot exhaustion. Spectators have
+/// nothing to send, so they are exempt; a dead one still trips SEND_TIMEOUT.
 const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
-/// Maximum set_paused messages per second per client
-const MAX_SET_PAUSED_PER_SEC: u32 = 10;
-/// Maximum activity messages per second per client
-const MAX_ACTIVITY_PER_SEC: u32 = 1;
+/// Most bumper hits credited per second per client; a real board can't
+/// do much better, so anything above is dropped
+const MAX_BUMPER_HITS_PER_SEC: u32 = 20;
+/// How long to wait for an optional `hello` before joining without a resume token
+const HELLO_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// Result of validating a ball_escaped message
 #[derive(Debug, Clone, PartialEq)]
@@ -66,16 +79,45 @@ pub fn validate_ball_escaped(vx: f64, vy: f64, max_velocity: f64) -> BallEscaped
 /// Shared app state passed to each WebSocket handler
 #[derive(Clone)]
 pub struct AppState {
-    pub game_tx: mpsc::Sender<GameCommand>,
-    pub broadcast_tx: broadcast::Sender<GameBroadcast>,
+    /// Live rooms; each connection joins one
+    pub rooms: Arc<RoomRegistry>,
     /// Maximum velocity component magnitude for ball_escaped
     pub max_velocity: f64,
-    /// Maximum ball_escaped messages per second per client
-    pub max_ball_escaped_per_sec: u32,
-    /// Semaphore to limit concurrent connections
+    /// Per-message rate limits, per connection and per IP
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Plausibility checks on each player's ball_escaped stream
+    pub anti_cheat: AntiCheat,
+    /// Semaphore to limit concurrent connections (across all rooms)
     pub connection_semaphore: Arc<Semaphore>,
+    /// Separate limit for spectator connections
+    pub spectator_semaphore: Arc<Semaphore>,
     /// Allowed origins for WebSocket connections (empty = allow all)
     pub allowed_origins: Vec<String>,
+    /// Secret join tokens must be signed with; `None` lets anyone join
+    pub join_token_secret: Option<String>,
+    /// Server-wide counters for `/metrics`
+    pub metrics: Arc<ServerMetrics>,
+    /// Set when the server is shutting down
+    pub lifecycle: Arc<Lifecycle>,
+}
+
+/// Query parameters accepted on the WebSocket upgrade
+#[derive(Debug, Default, Deserialize)]
+pub struct WsParams {
+    /// Wire format for space_state: `packed` for binary frames, anything else is JSON
+    pub wire: Option<String>,
+    /// Room to join; empty or missing means the default room
+    pub room: Option<String>,
+    /// `1` or `true` to watch without taking a portal
+    pub spectate: Option<String>,
+    /// Signed join token, when the server requires one (see `join_token.rs`)
+    pub token: Option<String>,
+}
+
+impl WsParams {
+    pub fn is_spectator(&self) -> bool {
+        matches!(self.spectate.as_deref(), Some("1" | "true"))
+    }
 }
 
 /// Check if the Origin header is allowed
@@ -97,10 +139,30 @@ fn is_origin_allowed(headers: &HeaderMap, allowed_origins: &[String]) -> bool {
     allowed_origins.iter().any(|allowed| allowed == origin)
 }
 
+/// The join token presented with an upgrade request, from `?token=` or a
+/// `pinball-token.<token>` subprotocol. For a subprotocol, also returns it:
+/// the upgrade has to accept it or browsers drop the connection.
+fn presented_join_token<'a>(
+    params: &'a WsParams,
+    headers: &'a HeaderMap,
+) -> Option<(&'a str, Option<&'a str>)> {
+    if let Some(token) = params.token.as_deref().filter(|t| !t.is_empty()) {
+        return Some((token, None));
+    }
+    headers
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find_map(join_token::from_subprotocols)
+        .map(|(token, protocol)| (token, Some(protocol)))
+}
+
 /// HTTP handler for WebSocket upgrade
 pub async fn ws_handler(
     headers: HeaderMap,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     ws: WebSocketUpgrade,
+    Query(params): Query<WsParams>,
     State(app_state): State<AppState>,
 ) -> impl IntoResponse {
     // Check Origin header for CSRF protection
@@ -113,11 +175,66 @@ pub async fn ws_handler(
         return (axum::http::StatusCode::FORBIDDEN, "Origin not allowed").into_response();
     }
 
-    // Try to acquire a connection permit
-    let permit = match app_state.connection_semaphore.clone().try_acquire_owned() {
+    // No new players once shutdown has begun
+    if app_state.lifecycle.is_draining() {
+        return (
+            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            "Server shutting down",
+        )
+            .into_response();
+    }
+
+    // Private server: only signed tokens get in, and they may pin the
+    // room and display name. Checked before taking a connection permit.
+    let mut subprotocol = None;
+    let claims = match &app_state.join_token_secret {
+        Some(secret) => {
+            let verified = match presented_join_token(&params, &headers) {
+                Some((token, protocol)) => {
+                    subprotocol = protocol.map(str::to_string);
+                    join_token::verify(secret, token, join_token::unix_now())
+                }
+                None => Err(TokenError::Missing),
+            };
+            match verified {
+                Ok(claims) => Some(claims),
+                Err(e) => {
+                    tracing::warn!("Connection rejected: {}", e);
+                    return (axum::http::StatusCode::UNAUTHORIZED, e.to_string()).into_response();
+                }
+            }
+        }
+        None => None,
+    };
+    let requested_room = params.room.as_deref().filter(|r| !r.is_empty());
+    let room_name = match claims.as_ref().map(|c| c.room(requested_room)) {
+        None => requested_room,
+        Some(Ok(room)) => room,
+        Some(Err(e)) => {
+            tracing::warn!("Connection rejected: {}", e);
+            return (axum::http::StatusCode::FORBIDDEN, e.to_string()).into_response();
+        }
+    };
+    let pinned_name = claims.as_ref().and_then(|c| c.name.clone());
+
+    // Try to acquire a connection permit (spectators have their own pool)
+    let spectator = params.is_spectator();
+    let semaphore = if spectator {
+        &app_state.spectator_semaphore
+    } else {
+        &app_state.connection_semaphore
+    };
+    let permit = match semaphore.clone().try_acquire_owned() {
         Ok(permit) => permit,
         Err(_) => {
-            tracing::warn!("Connection rejected: max connections reached");
+            tracing::warn!(
+                "Connection rejected: max {} reached",
+                if spectator {
+                    "spectators"
+                } else {
+                    "connections"
+                }
+            );
             // Return 503 Service Unavailable instead of upgrading
             // This avoids giving attackers free WebSocket handshake work
             return (
@@ -127,37 +244,103 @@ pub async fn ws_handler(
                 .into_response();
         }
     };
-    ws.on_upgrade(|socket| handle_socket(socket, app_state, permit))
-        .into_response()
+    let joined = if spectator {
+        app_state.rooms.spectate(room_name)
+    } else {
+        app_state.rooms.join(room_name)
+    };
+    let room = match joined {
+        Ok(room) => room,
+        Err(e) => {
+            tracing::warn!("Connection rejected: {}", e);
+            let status = match e {
+                RoomError::InvalidName(_) => axum::http::StatusCode::BAD_REQUEST,
+                RoomError::TooManyRooms | RoomError::RoomFull => {
+                    axum::http::StatusCode::SERVICE_UNAVAILABLE
+                }
+            };
+            return (status, e.to_string()).into_response();
+        }
+    };
+    let packed = params.wire.as_deref() == Some(WIRE_PACKED);
+    let ip = peer.ip();
+    let ws = match subprotocol {
+        Some(protocol) => ws.protocols([protocol]),
+        None => ws,
+    };
+    ws.on_upgrade(move |socket| {
+        handle_socket(socket, app_state, room, permit, packed, ip, pinned_name)
+    })
+    .into_response()
 }
 
 async fn handle_socket(
     socket: WebSocket,
     app_state: AppState,
+    room: RoomGuard,
     _permit: tokio::sync::OwnedSemaphorePermit,
+    packed: bool,
+    ip: IpAddr,
+    pinned_name: Option<String>,
 ) {
-    // _permit is held for the lifetime of this function, automatically released on drop
+    // _permit and room are held for the lifetime of this function and released on drop
     let (mut sink, mut stream) = socket.split();
 
+    // Wait briefly for an optional hello carrying a resume token. Anything
+    // else is put back in front of the stream and handled after joining.
+    let mut resume_token = None;
+    let mut name = None;
+    let mut first_msg = None;
+    match tokio::time::timeout(HELLO_TIMEOUT, stream.next()).await {
+        Ok(Some(Ok(Message::Text(text)))) => match serde_json::from_str::<ClientMsg>(&text) {
+            Ok(ClientMsg::Hello {
+                resume_token: token,
+                name: hello_name,
+            }) => {
+                resume_token = token;
+                name = hello_name;
+            }
+            _ => first_msg = Some(Ok(Message::Text(text))),
+        },
+        Ok(Some(Ok(Message::Close(_)))) | Ok(Some(Err(_))) | Ok(None) => return,
+        Ok(Some(Ok(msg))) => first_msg = Some(Ok(msg)),
+        Err(_) => {} // No hello - join without a token
+    }
+    let mut stream = futures_util::stream::iter(first_msg).chain(stream);
+    // A name from the join token beats the client's
+    let name_pinned = pinned_name.is_some();
+    if name_pinned {
+        name = pinned_name;
+    }
+
     // Create per-client channel for reliable events (TransferIn)
     let (client_tx, mut client_rx) = mpsc::channel::<ClientEvent>(32);
+    let spectator = room.is_spectator();
 
-    // Join the game
+    // Subscribe to broadcasts before joining so the keyframe the join asks
+    // for can't go out before we listen
+    let mut broadcast_rx = room.broadcast_tx.subscribe();
+
+    // Join the game. Spectators get no per-client events; their sender is
+    // kept here so the channel stays open and simply never yields.
     let (resp_tx, resp_rx) = oneshot::channel();
-    if app_state
-        .game_tx
-        .send(GameCommand::PlayerJoin {
+    let _spectator_tx = spectator.then(|| client_tx.clone());
+    let join = if spectator {
+        GameCommand::SpectatorJoin { response: resp_tx }
+    } else {
+        GameCommand::PlayerJoin {
             response: resp_tx,
             client_tx,
-        })
-        .await
-        .is_err()
-    {
-        tracing::error!("Failed to send PlayerJoin command");
+            resume_token,
+            name,
+        }
+    };
+    if room.game_tx.send(join).await.is_err() {
+        tracing::error!("Failed to send join command");
         return;
     }
 
-    let (my_id, welcome) = match resp_rx.await {
+    let (my_id, welcome, interest) = match resp_rx.await {
         Ok(Ok(result)) => result,
         Ok(Err(e)) => {
             tracing::warn!("Join rejected: {}", e);
@@ -169,7 +352,11 @@ async fn handle_socket(
         }
     };
 
-    tracing::info!("Player {} connected", my_id);
+    if spectator {
+        tracing::info!("Spectator connected to room '{}'", room.name);
+    } else {
+        tracing::info!("Player {} connected to room '{}'", my_id, room.name);
+    }
 
     // Send welcome message (with timeout for slow consumer protection)
     let welcome_json = match serde_json::to_string(&ServerMsg::Welcome(welcome)) {
@@ -189,23 +376,30 @@ async fn handle_socket(
         return;
     }
 
-    // Subscribe to broadcasts
-    let mut broadcast_rx = app_state.broadcast_tx.subscribe();
-
-    // Rate limiting per message type.
-    // Consequences differ by severity:
-    //   ball_escaped: disconnect (most exploitable — spawns balls in deep space)
-    //   set_paused:   ignore excess (low risk, just a flag toggle)
-    //   activity:     silently drop (heartbeat, no game effect)
-    let mut ball_escaped_count: u32 = 0;
-    let mut ball_escaped_window_start = Instant::now();
-    let mut set_paused_count: u32 = 0;
-    let mut set_paused_window_start = Instant::now();
-    let mut activity_count: u32 = 0;
-    let mut activity_window_start = Instant::now();
+    // Rate limiting per message type; the policies are configured in
+    // `ServerConfig::rate_limits` (see `rate_limit.rs`). Bumper hits are
+    // capped on top of that, and transfer_acks only get through for a
+    // transfer_in we sent.
+    let mut rate_limits = app_state.rate_limiter.connection(ip, Instant::now());
+    let mut bumper_hit_credit = TokenBucket::new(
+        MAX_BUMPER_HITS_PER_SEC as f64,
+        MAX_BUMPER_HITS_PER_SEC,
+        Instant::now(),
+    );
+    // Checks this player's escapes against what a real board can produce
+    let mut escapes = EscapeTracker::new(app_state.anti_cheat.clone(), Instant::now());
+    // space_state deltas are useless until we have forwarded a keyframe;
+    // the shared space_state right after that keyframe has its seq and is skipped
+    let mut awaiting_keyframe = true;
+    let mut skip_next_space_state = false;
+    // transfer_in seqs sent to this client; only those acks reach the game loop
+    let mut unacked_transfers: HashSet<u32> = HashSet::new();
     let mut parse_error_count: u32 = 0;
     let max_velocity = app_state.max_velocity;
-    let max_per_sec = app_state.max_ball_escaped_per_sec;
+    let metrics = &app_state.metrics;
+
+    // Resolves when the server starts shutting down
+    let mut shutdown = app_state.lifecycle.subscribe();
 
     // Reset on every message received from client
     let mut last_rx = tokio::time::Instant::now();
@@ -227,30 +421,51 @@ async fn handle_socket(
                                 "Player {} sent oversized ws msg: {} bytes (max {}), disconnecting",
                                 my_id, text_str.len(), MAX_TEXT_MSG_BYTES
                             );
+                            metrics.disconnect(DisconnectReason::OversizedMessage);
                             break;
                         }
 
                         match serde_json::from_str::<ClientMsg>(text_str) {
                             Ok(client_msg) => {
                                 parse_error_count = 0; // Reset on successful parse
-                                match client_msg {
-                                    ClientMsg::BallEscaped { vx, vy } => {
-                                        // Rate limiting FIRST (before validation)
-                                        // This prevents attackers from spamming invalid messages
-                                        let now = Instant::now();
-                                        if now.duration_since(ball_escaped_window_start).as_secs_f64() >= 1.0 {
-                                            ball_escaped_window_start = now;
-                                            ball_escaped_count = 0;
-                                        }
-                                        ball_escaped_count += 1;
-                                        if ball_escaped_count > max_per_sec {
-                                            tracing::warn!("Player {} exceeded rate limit ({} ball_escaped/sec), disconnecting", my_id, max_per_sec);
-                                            break;
+                                // Spectators only watch: nothing they send affects the game,
+                                // but any message still counts as a keep-alive
+                                if spectator
+                                    && !matches!(client_msg, ClientMsg::RequestKeyframe | ClientMsg::Ping { .. })
+                                {
+                                    tracing::trace!("Spectator sent {:?}, ignoring", client_msg);
+                                    continue;
+                                }
+                                // Rate limiting FIRST (before validation)
+                                // This prevents attackers from spamming invalid messages
+                                if let Some(kind) = MessageKind::of(&client_msg) {
+                                    if let Err(limited) = rate_limits.check(kind, Instant::now()) {
+                                        metrics.rate_limited(kind);
+                                        let scope = if limited.per_ip { "IP" } else { "connection" };
+                                        match limited.policy {
+                                            RateLimitPolicy::Drop => continue,
+                                            RateLimitPolicy::Ignore => {
+                                                tracing::debug!("Player {} exceeded {} {} rate limit, ignoring", my_id, scope, kind.label());
+                                                continue;
+                                            }
+                                            RateLimitPolicy::Disconnect => {
+                                                tracing::warn!("Player {} exceeded {} {} rate limit, disconnecting", my_id, scope, kind.label());
+                                                metrics.disconnect(DisconnectReason::RateLimited);
+                                                break;
+                                            }
                                         }
-
+                                    }
+                                }
+                                match client_msg {
+                                    ClientMsg::Hello { .. } => {
+                                        tracing::trace!("Player {} sent hello after joining, ignoring", my_id);
+                                    }
+                                    ClientMsg::BallEscaped { vx, vy, target_player_id, ball_id } => {
                                         // Validate and clamp velocity
                                         // Use trace level to avoid log spam from invalid messages
-                                        let (vx, vy) = match validate_ball_escaped(vx, vy, max_velocity) {
+                                        let validation = validate_ball_escaped(vx, vy, max_velocity);
+                                        metrics.ball_escaped_rejected(&validation);
+                                        let (vx, vy) = match validation {
                                             BallEscapedValidation::Valid { vx, vy } => (vx, vy),
                                             BallEscapedValidation::InvalidNonFinite => {
                                                 tracing::trace!("Player {} sent invalid velocity (NaN/Inf), ignoring", my_id);
@@ -266,49 +481,112 @@ async fn handle_socket(
                                             }
                                         };
 
+                                        let verdict = escapes.escape(vx, vy, Instant::now());
+                                        for &check in &verdict.failed {
+                                            metrics.escape_suspicion(check);
+                                            tracing::debug!("Player {} escape failed the {} check", my_id, check.label());
+                                        }
+                                        if verdict.newly_flagged {
+                                            let penalty = app_state.anti_cheat.penalty;
+                                            metrics.player_flagged(penalty);
+                                            tracing::warn!(
+                                                "Player {} flagged for implausible ball_escaped stream (penalty: {})",
+                                                my_id, penalty.label()
+                                            );
+                                        }
+                                        if let Some(penalty) = verdict.dropped_by {
+                                            // Shadow drop: the client already removed the ball, so it can't tell
+                                            metrics.escape_penalized(penalty);
+                                            tracing::trace!("Player {} escape dropped ({})", my_id, penalty.label());
+                                            continue;
+                                        }
+
                                         // Hot path - only log at trace level
                                         tracing::trace!("Player {} ball_escaped", my_id);
-                                        let _ = app_state.game_tx.send(GameCommand::BallEscaped {
+                                        let _ = room.game_tx.send(GameCommand::BallEscaped {
                                             owner_id: my_id,
                                             vx,
                                             vy,
+                                            target_id: target_player_id,
+                                            ball_id,
                                         }).await;
                                     }
                                     ClientMsg::SetPaused { paused } => {
-                                        // Rate limiting for set_paused
-                                        let now = Instant::now();
-                                        if now.duration_since(set_paused_window_start).as_secs_f64() >= 1.0 {
-                                            set_paused_window_start = now;
-                                            set_paused_count = 0;
-                                        }
-                                        set_paused_count += 1;
-                                        if set_paused_count > MAX_SET_PAUSED_PER_SEC {
-                                            tracing::warn!("Player {} exceeded set_paused rate limit, ignoring", my_id);
-                                            continue;
-                                        }
-
                                         tracing::trace!("Player {} set_paused={}", my_id, paused);
-                                        let _ = app_state.game_tx.send(GameCommand::SetPaused {
+                                        escapes.set_paused(paused, Instant::now());
+                                        let _ = room.game_tx.send(GameCommand::SetPaused {
                                             player_id: my_id,
                                             paused,
                                         }).await;
                                     }
                                     ClientMsg::Activity => {
-                                        // Rate limiting for activity
-                                        let now = Instant::now();
-                                        if now.duration_since(activity_window_start).as_secs_f64() >= 1.0 {
-                                            activity_window_start = now;
-                                            activity_count = 0;
+                                        escapes.activity(Instant::now());
+                                        let _ = room.game_tx.send(GameCommand::Activity {
+                                            player_id: my_id,
+                                        }).await;
+                                    }
+                                    ClientMsg::RequestKeyframe => {
+                                        tracing::trace!("Player {} requested keyframe", my_id);
+                                        awaiting_keyframe = true;
+                                        let _ = room.game_tx.send(GameCommand::RequestKeyframe).await;
+                                    }
+                                    ClientMsg::TransferAck { seq } => {
+                                        if !unacked_transfers.remove(&seq) {
+                                            tracing::trace!("Player {} acked unknown transfer {}, ignoring", my_id, seq);
+                                            continue;
                                         }
-                                        activity_count += 1;
-                                        if activity_count > MAX_ACTIVITY_PER_SEC {
+                                        let _ = room.game_tx.send(GameCommand::TransferAck {
+                                            player_id: my_id,
+                                            seq,
+                                        }).await;
+                                    }
+                                    ClientMsg::Emote { kind } => {
+                                        let _ = room.game_tx.send(GameCommand::Emote {
+                                            player_id: my_id,
+                                            kind,
+                                        }).await;
+                                    }
+                                    ClientMsg::BumperHits { count } => {
+                                        let count = bumper_hit_credit.take_up_to(count, Instant::now());
+                                        if count == 0 {
                                             continue;
                                         }
 
-                                        let _ = app_state.game_tx.send(GameCommand::Activity {
+                                        let _ = room.game_tx.send(GameCommand::BumperHits {
                                             player_id: my_id,
+                                            count,
                                         }).await;
                                     }
+                                    ClientMsg::SetName { name } => {
+                                        if name_pinned {
+                                            tracing::trace!("Player {} has a name from its join token, ignoring set_name", my_id);
+                                            continue;
+                                        }
+                                        let _ = room.game_tx.send(GameCommand::SetName {
+                                            player_id: my_id,
+                                            name,
+                                        }).await;
+                                    }
+                                    ClientMsg::Ping { client_time } => {
+                                        // Answered here rather than by the game loop so the
+                                        // round trip doesn't include waiting for a tick
+                                        let pong = ServerMsg::Pong(PongMsg {
+                                            client_time,
+                                            server_time: room.heartbeat.server_time(),
+                                        });
+                                        if let Ok(json) = serde_json::to_string(&pong) {
+                                            if tokio::time::timeout(SEND_TIMEOUT, sink.send(Message::Text(json.into())))
+                                                .await
+                                                .map_err(|_| ())
+                                                .and_then(|r| r.map_err(|_| ()))
+                                                .is_err()
+                                            {
+                                                tracing::warn!("Player {} send timeout/error on pong, disconnecting", my_id);
+                                                metrics.disconnect(DisconnectReason::SlowConsumer);
+                                                break;
+                                            }
+                                        }
+                                    }
                                 }
                             }
                             Err(e) => {
@@ -326,6 +604,7 @@ async fn handle_socket(
                                         "Player {} exceeded max parse errors ({}), disconnecting",
                                         my_id, MAX_PARSE_ERRORS
                                     );
+                                    metrics.disconnect(DisconnectReason::ParseErrors);
                                     break;
                                 }
                             }
@@ -335,6 +614,7 @@ async fn handle_socket(
                     Some(Ok(Message::Binary(_))) => {
                         // Binary frames are not expected - disconnect
                         tracing::warn!("Player {} sent binary frame, disconnecting", my_id);
+                        metrics.disconnect(DisconnectReason::BinaryFrame);
                         break;
                     }
                     _ => {} // Ignore ping/pong
@@ -344,9 +624,11 @@ async fn handle_socket(
             // Server -> Client (reliable per-client events like TransferIn)
             event = client_rx.recv() => {
                 match event {
-                    Some(ClientEvent::TransferIn { vx, vy, owner_id, color }) => {
+                    Some(ClientEvent::TransferIn { vx, vy, owner_id, color, seq, ball_id, origin_owner_id, hops }) => {
+                        unacked_transfers.insert(seq);
+                        escapes.ball_received(Instant::now());
                         let json = serde_json::to_string(&ServerMsg::TransferIn(
-                            TransferInMsg { vx, vy, owner_id, color },
+                            TransferInMsg { vx, vy, owner_id, color, seq, ball_id, origin_owner_id, hops },
                         ));
                         if let Ok(json) = json {
                             // Timeout for slow consumer protection
@@ -357,6 +639,7 @@ async fn handle_socket(
                                 .is_err()
                             {
                                 tracing::warn!("Player {} send timeout/error on TransferIn, disconnecting", my_id);
+                                metrics.disconnect(DisconnectReason::SlowConsumer);
                                 break;
                             }
                         }
@@ -374,43 +657,99 @@ async fn handle_socket(
             }
 
             // Server -> Client (broadcast - lossy, ok to drop on lag)
-            // JSON is pre-serialized as Utf8Bytes in game_loop - O(1) clone, no allocation
+            // Payloads are pre-serialized in game_loop - O(1) clone, no allocation
             result = broadcast_rx.recv() => {
                 match result {
                     Ok(broadcast) => {
-                        let utf8 = match broadcast {
-                            GameBroadcast::SpaceState(b) => b,
-                            GameBroadcast::PlayersState(b) => b,
+                        // space_state: only the buckets near our portal, in our encoding
+                        // A keyframe is only for clients that need a baseline;
+                        // they then skip the shared space_state with its seq
+                        match &broadcast {
+                            GameBroadcast::SpaceStateKeyframe(_) if !awaiting_keyframe => continue,
+                            GameBroadcast::SpaceStateKeyframe(_) => {
+                                awaiting_keyframe = false;
+                                skip_next_space_state = true;
+                            }
+                            GameBroadcast::SpaceState(_)
+                                if awaiting_keyframe || std::mem::take(&mut skip_next_space_state) =>
+                            {
+                                continue
+                            }
+                            _ => {}
+                        }
+                        let (kind, len, msg) = match broadcast {
+                            GameBroadcast::SpaceState(frags) | GameBroadcast::SpaceStateKeyframe(frags) if packed => {
+                                let b = frags.packed(&interest);
+                                (BroadcastKind::SpaceState, b.len(), Message::Binary(b))
+                            }
+                            GameBroadcast::SpaceState(frags) | GameBroadcast::SpaceStateKeyframe(frags) => {
+                                let t = frags.json(&interest);
+                                (BroadcastKind::SpaceState, t.len(), Message::Text(t))
+                            }
+                            GameBroadcast::PlayersState(t) => {
+                                (BroadcastKind::PlayersState, t.len(), Message::Text(t))
+                            }
+                            GameBroadcast::Emote(t) => (BroadcastKind::Emote, t.len(), Message::Text(t)),
+                            GameBroadcast::Leaderboard(t) => {
+                                (BroadcastKind::Leaderboard, t.len(), Message::Text(t))
+                            }
                         };
                         // Timeout for slow consumer protection
-                        if tokio::time::timeout(SEND_TIMEOUT, sink.send(Message::Text(utf8)))
+                        if tokio::time::timeout(SEND_TIMEOUT, sink.send(msg))
                             .await
                             .map_err(|_| ())
                             .and_then(|r| r.map_err(|_| ()))
                             .is_err()
                         {
                             tracing::warn!("Player {} send timeout/error on broadcast, disconnecting", my_id);
+                            metrics.disconnect(DisconnectReason::SlowConsumer);
                             break;
                         }
+                        room.metrics.sent_bytes(kind).add(len as u64);
                     }
                     Err(broadcast::error::RecvError::Lagged(n)) => {
                         tracing::warn!("Player {} lagged by {} messages", my_id, n);
+                        room.metrics.lagged_receivers.inc();
+                        room.metrics.lagged_messages.add(n);
                         // Continue - space_state is stateless, dropping is fine
                     }
                     Err(broadcast::error::RecvError::Closed) => break,
                 }
             }
 
-            // Idle timeout: disconnect clients that send nothing for IDLE_TIMEOUT
-            _ = tokio::time::sleep(idle_remaining) => {
+            // Shutdown: say why, then close with 1001 Going Away
+            // (the borrow guard from wait_for is not Send; drop it in the future)
+            _ = async { drop(shutdown.wait_for(|draining| *draining).await) } => {
+                tracing::info!("Player {} disconnecting for shutdown", my_id);
+                let going_away = ServerMsg::ServerGoingAway(ServerGoingAwayMsg {
+                    reason: GOING_AWAY_REASON.to_string(),
+                });
+                if let Ok(json) = serde_json::to_string(&going_away) {
+                    let _ = tokio::time::timeout(SEND_TIMEOUT, sink.send(Message::Text(json.into()))).await;
+                }
+                let close = Message::Close(Some(CloseFrame {
+                    code: close_code::AWAY,
+                    reason: GOING_AWAY_REASON.into(),
+                }));
+                let _ = tokio::time::timeout(SEND_TIMEOUT, sink.send(close)).await;
+                break;
+            }
+
+            // Idle timeout: disconnect players that send nothing for IDLE_TIMEOUT
+            _ = tokio::time::sleep(idle_remaining), if !spectator => {
                 tracing::info!("Player {} idle timeout ({}s), disconnecting", my_id, IDLE_TIMEOUT.as_secs());
+                metrics.disconnect(DisconnectReason::IdleTimeout);
                 break;
             }
         }
     }
 
     // Cleanup on disconnect
-    let _ = app_state
+    if spectator {
+        tracing::info!("Spectator disconnected from room '{}'", room.name);
+        return;
+    }
+    let _ = room
         .game_tx
         .send(GameCommand::PlayerLeave { id: my_id })
         .await;
@@ -582,4 +921,26 @@ mod tests {
         let result = validate_ball_escaped(0.5, -0.5, MAX_VEL);
         assert_eq!(result, BallEscapedValidation::Valid { vx: 0.5, vy: -0.5 });
     }
+
+    // --- presented_join_token tests ---
+
+    #[test]
+    fn join_token_from_query_beats_subprotocol() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            "chat, pinball-token.b.c".parse().unwrap(),
+        );
+        let mut params = WsParams::default();
+        assert_eq!(
+            presented_join_token(&params, &headers),
+            Some(("b.c", Some("pinball-token.b.c")))
+        );
+
+        params.token = Some("a.b".to_string());
+        assert_eq!(presented_join_token(&params, &headers), Some(("a.b", None)));
+
+        params.token = Some(String::new());
+        assert_eq!(presented_join_token(&params, &HeaderMap::new()), None);
+    }
 }
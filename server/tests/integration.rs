@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpListener;
-use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio::sync::Semaphore;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
 // Re-create minimal protocol types for testing (to avoid circular deps)
@@ -24,6 +24,10 @@ enum ServerMsg {
         self_id: u32,
         players: Vec<serde_json::Value>,
         config: serde_json::Value,
+        #[serde(rename = "resumeToken", default)]
+        resume_token: String,
+        #[serde(default)]
+        spectator: bool,
     },
     #[serde(rename = "players_state")]
     PlayersState { players: Vec<serde_json::Value> },
@@ -31,7 +35,13 @@ enum ServerMsg {
     SpaceState {
         #[serde(rename = "serverTime")]
         server_time: f64,
+        seq: u32,
+        keyframe: bool,
         balls: Vec<serde_json::Value>,
+        #[serde(default)]
+        unchanged: Vec<u32>,
+        #[serde(default)]
+        removed: Vec<u32>,
     },
     #[serde(rename = "transfer_in")]
     TransferIn {
@@ -40,20 +50,87 @@ enum ServerMsg {
         #[serde(rename = "ownerId")]
         owner_id: u32,
         color: u32,
+        seq: u32,
+        #[serde(rename = "ballId")]
+        ball_id: u32,
+        #[serde(rename = "originOwnerId")]
+        origin_owner_id: u32,
+        hops: u32,
+    },
+    #[serde(rename = "server_going_away")]
+    ServerGoingAway { reason: String },
+    #[serde(rename = "pong")]
+    Pong {
+        #[serde(rename = "clientTime")]
+        client_time: f64,
+        #[serde(rename = "serverTime")]
+        server_time: f64,
+    },
+    #[serde(rename = "emote")]
+    Emote {
+        #[serde(rename = "playerId")]
+        player_id: u32,
+        kind: String,
+    },
+    #[serde(rename = "leaderboard")]
+    Leaderboard {
+        entries: Vec<serde_json::Value>,
+        total: u32,
     },
 }
 
 #[derive(Debug, Serialize)]
 #[serde(tag = "type")]
 enum ClientMsg {
+    #[serde(rename = "hello")]
+    Hello {
+        #[serde(rename = "resumeToken", skip_serializing_if = "Option::is_none")]
+        resume_token: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
     #[serde(rename = "ball_escaped")]
     BallEscaped { vx: f64, vy: f64 },
+    /// `ball_escaped` with a gift target
+    #[serde(rename = "ball_escaped")]
+    GiftBall {
+        vx: f64,
+        vy: f64,
+        #[serde(rename = "targetPlayerId")]
+        target_player_id: u32,
+    },
+    /// `ball_escaped` for a ball received in `transfer_in`
+    #[serde(rename = "ball_escaped")]
+    ReEscape {
+        vx: f64,
+        vy: f64,
+        #[serde(rename = "ballId")]
+        ball_id: u32,
+    },
     #[serde(rename = "set_paused")]
     SetPaused { paused: bool },
     #[serde(rename = "activity")]
     Activity,
+    #[serde(rename = "request_keyframe")]
+    RequestKeyframe,
+    #[serde(rename = "ping")]
+    Ping {
+        #[serde(rename = "clientTime")]
+        client_time: f64,
+    },
+    #[serde(rename = "transfer_ack")]
+    TransferAck { seq: u32 },
+    #[serde(rename = "set_name")]
+    SetName { name: String },
+    #[serde(rename = "emote")]
+    Emote { kind: String },
+    #[serde(rename = "bumper_hits")]
+    BumperHits { count: u32 },
 }
 
+/// Admin API token used by every test server.
+const TEST_ADMIN_TOKEN: &str = "test-admin-token";
+
 /// Configuration overrides for test servers.
 #[derive(Default)]
 struct TestServerOptions {
@@ -61,6 +138,13 @@ struct TestServerOptions {
     max_ball_escaped_per_sec: Option<u32>,
     max_connections: Option<usize>,
     deep_space_config: Option<pinball_server::config::DeepSpaceConfig>,
+    aoi_radius: Option<f64>,
+    max_connections_per_room: Option<usize>,
+    /// Require signed join tokens
+    join_token_secret: Option<String>,
+    anti_cheat: Option<pinball_server::config::AntiCheat>,
+    /// Shared with the test so it can trigger a shutdown
+    lifecycle: Option<Arc<pinball_server::lifecycle::Lifecycle>>,
 }
 
 /// Start a test server with default options.
@@ -71,9 +155,7 @@ async fn start_test_server() -> String {
 /// Start a test server with custom options.
 async fn start_test_server_with_options(opts: TestServerOptions) -> String {
     use pinball_server::config::ServerConfig;
-    use pinball_server::game_loop::{
-        run_game_loop, run_game_loop_with_config, GameBroadcast, GameCommand,
-    };
+    use pinball_server::room::RoomRegistry;
     use pinball_server::ws::AppState;
 
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -87,42 +169,74 @@ async fn start_test_server_with_options(opts: TestServerOptions) -> String {
         cell_count: 100,
         rng_seed: 12345,
         max_velocity: 10.0,
-        max_ball_escaped_per_sec: opts.max_ball_escaped_per_sec.unwrap_or(30),
         max_connections: opts.max_connections.unwrap_or(100),
+        max_spectators: 2,
         max_balls_global: 1000,
         allowed_origins: vec![],
         bot_count: opts.bot_count.unwrap_or(0),
+        // Whole sphere unless a test is about area of interest
+        aoi_radius: opts.aoi_radius.unwrap_or(std::f64::consts::PI),
+        max_rooms: 4,
+        max_connections_per_room: opts.max_connections_per_room.unwrap_or(100),
+        rooms: Default::default(),
+        admin_token: Some(TEST_ADMIN_TOKEN.to_string()),
+        join_token_secret: opts.join_token_secret.clone(),
+        shutdown_grace_secs: 1,
+        snapshot_dir: None,
+        snapshot_interval_secs: 0,
+        record_dir: None,
+        max_name_len: 12,
+        name_blocklist: vec!["blocked".to_string()],
+        score_reset_secs: 0,
+        rate_limits: rate_limits(&opts),
+        anti_cheat: opts.anti_cheat.clone().unwrap_or_default(),
+        deep_space: opts.deep_space_config.unwrap_or_default(),
     };
 
-    let (game_tx, game_rx) = mpsc::channel::<GameCommand>(256);
-    let (broadcast_tx, _) = broadcast::channel::<GameBroadcast>(64);
-
     let app_state = AppState {
-        game_tx,
-        broadcast_tx: broadcast_tx.clone(),
+        rooms: RoomRegistry::new(config.clone()),
         max_velocity: config.max_velocity,
-        max_ball_escaped_per_sec: config.max_ball_escaped_per_sec,
+        rate_limiter: Arc::new(pinball_server::rate_limit::RateLimiter::new(
+            config.rate_limits.clone(),
+        )),
+        anti_cheat: config.anti_cheat.clone(),
         connection_semaphore: Arc::new(Semaphore::new(config.max_connections)),
+        spectator_semaphore: Arc::new(Semaphore::new(config.max_spectators)),
         allowed_origins: vec![],
+        join_token_secret: config.join_token_secret.clone(),
+        metrics: Default::default(),
+        lifecycle: opts.lifecycle.unwrap_or_default(),
     };
 
-    let game_config = config.clone();
-    let ds_config = opts.deep_space_config;
-    tokio::spawn(async move {
-        if let Some(ds) = ds_config {
-            run_game_loop_with_config(game_rx, broadcast_tx, game_config, ds).await;
-        } else {
-            run_game_loop(game_rx, broadcast_tx, game_config).await;
-        }
-    });
-
+    let rooms = app_state.rooms.clone();
     let app = axum::Router::new()
         .route("/ws", axum::routing::get(pinball_server::ws::ws_handler))
-        .with_state(app_state);
+        .route(
+            "/metrics",
+            axum::routing::get(pinball_server::metrics::metrics_handler),
+        )
+        .route(
+            "/healthz",
+            axum::routing::get(pinball_server::lifecycle::healthz_handler),
+        )
+        .route(
+            "/readyz",
+            axum::routing::get(pinball_server::lifecycle::readyz_handler),
+        )
+        .with_state(app_state)
+        .merge(pinball_server::admin::router(
+            rooms,
+            TEST_ADMIN_TOKEN.to_string(),
+        ));
 
     tokio::spawn(async move {
         let listener = TcpListener::bind(&config.listen_addr).await.unwrap();
-        axum::serve(listener, app).await.unwrap();
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+        )
+        .await
+        .unwrap();
     });
 
     tokio::time::sleep(Duration::from_millis(50)).await;
@@ -130,6 +244,15 @@ async fn start_test_server_with_options(opts: TestServerOptions) -> String {
     format!("ws://{}/ws", addr)
 }
 
+fn rate_limits(opts: &TestServerOptions) -> pinball_server::config::RateLimits {
+    let mut limits = pinball_server::config::RateLimits::default();
+    if let Some(per_sec) = opts.max_ball_escaped_per_sec {
+        limits.ball_escaped.per_sec = per_sec as f64;
+        limits.ball_escaped.burst = per_sec;
+    }
+    limits
+}
+
 /// Connect to the server and return the WebSocket stream.
 async fn connect(
     url: &str,
@@ -138,6 +261,51 @@ async fn connect(
     ws
 }
 
+/// Minimal HTTP/1.1 request against the test server behind `ws_url`.
+/// Returns (status, body).
+async fn http_request(
+    ws_url: &str,
+    method: &str,
+    path: &str,
+    token: Option<&str>,
+) -> (u16, String) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let addr = ws_url
+        .trim_start_matches("ws://")
+        .trim_end_matches("/ws")
+        .to_string();
+    let mut stream = tokio::net::TcpStream::connect(&addr).await.unwrap();
+    let auth = token
+        .map(|t| format!("Authorization: Bearer {}\r\n", t))
+        .unwrap_or_default();
+    let req = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: 0\r\n{}\r\n",
+        method, path, addr, auth
+    );
+    stream.write_all(req.as_bytes()).await.unwrap();
+    let mut raw = String::new();
+    stream.read_to_string(&mut raw).await.unwrap();
+
+    let status = raw
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .expect("HTTP status line");
+    let body = raw
+        .split_once("\r\n\r\n")
+        .map(|(_, b)| b.to_string())
+        .unwrap_or_default();
+    (status, body)
+}
+
+/// Admin API call with the test token; parses a JSON body if there is one.
+async fn admin(ws_url: &str, method: &str, path: &str) -> (u16, serde_json::Value) {
+    let (status, body) = http_request(ws_url, method, path, Some(TEST_ADMIN_TOKEN)).await;
+    let json = serde_json::from_str(&body).unwrap_or(serde_json::Value::Null);
+    (status, json)
+}
+
 /// Read the next text message and parse as ServerMsg.
 async fn recv_msg(
     ws: &mut tokio_tungstenite::WebSocketStream<
@@ -183,7 +351,7 @@ async fn test_connect_and_receive_welcome() {
             players,
             ..
         } => {
-            assert_eq!(protocol_version, 2);
+            assert_eq!(protocol_version, 5);
             assert!(self_id > 0, "self_id should be positive");
             assert!(!players.is_empty(), "players should include self");
         }
@@ -230,12 +398,12 @@ async fn test_valid_ball_escaped_is_accepted() {
     // The server broadcasts at 10Hz, so wait up to 200ms
     let mut found_ball = false;
     for _ in 0..5 {
-        if let Some(msg) = recv_msg_timeout(&mut ws, Duration::from_millis(200)).await {
-            if let ServerMsg::SpaceState { balls, .. } = msg {
-                if !balls.is_empty() {
-                    found_ball = true;
-                    break;
-                }
+        if let Some(ServerMsg::SpaceState { balls, .. }) =
+            recv_msg_timeout(&mut ws, Duration::from_millis(200)).await
+        {
+            if !balls.is_empty() {
+                found_ball = true;
+                break;
             }
         }
     }
@@ -262,10 +430,10 @@ async fn test_invalid_ball_escaped_positive_vy_ignored() {
     tokio::time::sleep(Duration::from_millis(150)).await;
 
     // Check that space is empty (invalid message was ignored)
-    if let Some(msg) = recv_msg_timeout(&mut ws, Duration::from_millis(200)).await {
-        if let ServerMsg::SpaceState { balls, .. } = msg {
-            assert!(balls.is_empty(), "Invalid ball_escaped should be ignored");
-        }
+    if let Some(ServerMsg::SpaceState { balls, .. }) =
+        recv_msg_timeout(&mut ws, Duration::from_millis(200)).await
+    {
+        assert!(balls.is_empty(), "Invalid ball_escaped should be ignored");
     }
 }
 
@@ -288,24 +456,24 @@ async fn test_set_paused_updates_player_state() {
     // Wait for players_state broadcast
     let mut found_paused = false;
     for _ in 0..10 {
-        if let Some(msg) = recv_msg_timeout(&mut ws, Duration::from_millis(200)).await {
-            if let ServerMsg::PlayersState { players } = msg {
-                // Find our player and check paused status
-                for p in &players {
-                    if let Some(id) = p.get("id").and_then(|v| v.as_u64()) {
-                        if id == self_id as u64 {
-                            if let Some(paused) = p.get("paused").and_then(|v| v.as_bool()) {
-                                if paused {
-                                    found_paused = true;
-                                    break;
-                                }
+        if let Some(ServerMsg::PlayersState { players }) =
+            recv_msg_timeout(&mut ws, Duration::from_millis(200)).await
+        {
+            // Find our player and check paused status
+            for p in &players {
+                if let Some(id) = p.get("id").and_then(|v| v.as_u64()) {
+                    if id == self_id as u64 {
+                        if let Some(paused) = p.get("paused").and_then(|v| v.as_bool()) {
+                            if paused {
+                                found_paused = true;
+                                break;
                             }
                         }
                     }
                 }
-                if found_paused {
-                    break;
-                }
+            }
+            if found_paused {
+                break;
             }
         }
     }
@@ -338,15 +506,15 @@ async fn test_player_disconnect_removes_from_players_state() {
     // Wait for players_state update on client 2
     let mut player1_removed = false;
     for _ in 0..10 {
-        if let Some(msg) = recv_msg_timeout(&mut ws2, Duration::from_millis(200)).await {
-            if let ServerMsg::PlayersState { players } = msg {
-                let has_player1 = players
-                    .iter()
-                    .any(|p| p.get("id").and_then(|v| v.as_u64()) == Some(id1 as u64));
-                if !has_player1 {
-                    player1_removed = true;
-                    break;
-                }
+        if let Some(ServerMsg::PlayersState { players }) =
+            recv_msg_timeout(&mut ws2, Duration::from_millis(200)).await
+        {
+            let has_player1 = players
+                .iter()
+                .any(|p| p.get("id").and_then(|v| v.as_u64()) == Some(id1 as u64));
+            if !has_player1 {
+                player1_removed = true;
+                break;
             }
         }
     }
@@ -540,32 +708,31 @@ async fn test_ball_lifecycle_escape_to_transfer_in() {
     // With wide portals and fast movement, this should happen within a few seconds.
     let mut got_transfer = false;
     for _ in 0..60 {
-        if let Some(msg) = recv_msg_timeout(&mut ws2, Duration::from_millis(200)).await {
-            if let ServerMsg::TransferIn {
-                vx,
-                vy,
-                owner_id,
-                color,
-            } = msg
-            {
-                assert!(vx.is_finite(), "vx should be finite");
-                assert!(vy.is_finite(), "vy should be finite");
-                assert!(owner_id > 0, "owner_id should be set");
-                assert!(color > 0, "color should be set");
-                got_transfer = true;
-                break;
-            }
+        if let Some(ServerMsg::TransferIn {
+            vx,
+            vy,
+            owner_id,
+            color,
+            ..
+        }) = recv_msg_timeout(&mut ws2, Duration::from_millis(200)).await
+        {
+            assert!(vx.is_finite(), "vx should be finite");
+            assert!(vy.is_finite(), "vy should be finite");
+            assert!(owner_id > 0, "owner_id should be set");
+            assert!(color > 0, "color should be set");
+            got_transfer = true;
+            break;
         }
     }
     // Note: with only 2 players on a 100-cell sphere, capture may go to either.
     // If client 2 didn't get it, check client 1 (self-capture is allowed for real players).
     if !got_transfer {
         for _ in 0..30 {
-            if let Some(msg) = recv_msg_timeout(&mut ws1, Duration::from_millis(200)).await {
-                if let ServerMsg::TransferIn { .. } = msg {
-                    got_transfer = true;
-                    break;
-                }
+            if let Some(ServerMsg::TransferIn { .. }) =
+                recv_msg_timeout(&mut ws1, Duration::from_millis(200)).await
+            {
+                got_transfer = true;
+                break;
             }
         }
     }
@@ -575,6 +742,214 @@ async fn test_ball_lifecycle_escape_to_transfer_in() {
     );
 }
 
+/// Deep space config where a ball is captured by the portal it left almost at once.
+fn fast_capture_config() -> pinball_server::config::DeepSpaceConfig {
+    pinball_server::config::DeepSpaceConfig {
+        portal_alpha: 1.0,
+        omega_min: 3.0,
+        omega_max: 3.0,
+        min_age_for_capture: 0.1,
+        reroute_after: 100.0,
+        reroute_cooldown: 100.0,
+        min_age_for_reroute: 100.0,
+        reroute_arrival_time_min: 4.0,
+        reroute_arrival_time_max: 10.0,
+    }
+}
+
+async fn deep_space_ball_owners(url: &str) -> Vec<u64> {
+    let (_, balls) = admin(url, "GET", "/admin/rooms/public/balls").await;
+    balls
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|b| b["owner_id"].as_u64())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_unacked_transfers_return_to_space_when_client_crashes() {
+    let url = start_test_server_with_options(TestServerOptions {
+        deep_space_config: Some(fast_capture_config()),
+        ..Default::default()
+    })
+    .await;
+
+    let mut ws = connect(&url).await;
+    let my_id = extract_self_id(recv_msg(&mut ws).await);
+    let msg = serde_json::to_string(&ClientMsg::BallEscaped { vx: 1.0, vy: -2.0 }).unwrap();
+    for _ in 0..3 {
+        ws.send(Message::Text(msg.clone().into())).await.unwrap();
+    }
+
+    // Our only portal captures all three; we never ack
+    let mut seqs = Vec::new();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(3);
+    while seqs.len() < 3 && tokio::time::Instant::now() < deadline {
+        if let Some(ServerMsg::TransferIn { seq, .. }) =
+            recv_msg_timeout(&mut ws, Duration::from_millis(200)).await
+        {
+            seqs.push(seq);
+        }
+    }
+    assert_eq!(seqs.len(), 3, "expected three transfer_in, got {:?}", seqs);
+    seqs.dedup();
+    assert_eq!(seqs.len(), 3, "transfer seqs must be distinct");
+    // In flight to us, so not in deep space: 0 + 3 pending
+    assert!(deep_space_ball_owners(&url).await.is_empty());
+
+    // Crash: the socket goes away without a close frame or any ack
+    drop(ws);
+
+    let mut owners = Vec::new();
+    for _ in 0..40 {
+        owners = deep_space_ball_owners(&url).await;
+        if owners.len() == 3 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(
+        owners,
+        vec![my_id as u64; 3],
+        "3 + 0 pending after the crash"
+    );
+}
+
+#[tokio::test]
+async fn test_acked_transfers_stay_with_the_client() {
+    let url = start_test_server_with_options(TestServerOptions {
+        deep_space_config: Some(fast_capture_config()),
+        ..Default::default()
+    })
+    .await;
+
+    let mut ws = connect(&url).await;
+    let _id = extract_self_id(recv_msg(&mut ws).await);
+    let msg = serde_json::to_string(&ClientMsg::BallEscaped { vx: 1.0, vy: -2.0 }).unwrap();
+    ws.send(Message::Text(msg.into())).await.unwrap();
+
+    let mut seq = None;
+    for _ in 0..15 {
+        if let Some(ServerMsg::TransferIn { seq: s, .. }) =
+            recv_msg_timeout(&mut ws, Duration::from_millis(200)).await
+        {
+            seq = Some(s);
+            break;
+        }
+    }
+    let seq = seq.expect("transfer_in");
+    let ack = serde_json::to_string(&ClientMsg::TransferAck { seq }).unwrap();
+    ws.send(Message::Text(ack.into())).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // The board has the ball now: leaving doesn't put it back
+    drop(ws);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(deep_space_ball_owners(&url).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_gifted_ball_keeps_valid_target_only() {
+    // Nothing gets captured, so the balls stay in deep space for the dump
+    let url = start_test_server_with_options(TestServerOptions {
+        deep_space_config: Some(pinball_server::config::DeepSpaceConfig {
+            min_age_for_capture: 100.0,
+            ..fast_capture_config()
+        }),
+        ..Default::default()
+    })
+    .await;
+
+    let mut ws1 = connect(&url).await;
+    let mut ws2 = connect(&url).await;
+    let id1 = extract_self_id(recv_msg(&mut ws1).await);
+    let id2 = extract_self_id(recv_msg(&mut ws2).await);
+
+    for target in [id2, id1, 9999] {
+        let msg = ClientMsg::GiftBall {
+            vx: 0.5,
+            vy: -1.0,
+            target_player_id: target,
+        };
+        ws1.send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+            .await
+            .unwrap();
+    }
+
+    let mut targets = Vec::new();
+    for _ in 0..40 {
+        let (_, balls) = admin(&url, "GET", "/admin/rooms/public/balls").await;
+        targets = balls
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|b| b["target_id"].as_u64())
+            .collect();
+        if targets.len() == 3 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    // Balls are listed by id, i.e. in send order; self and unknown targets are dropped
+    assert_eq!(targets, vec![Some(id2 as u64), None, None]);
+}
+
+/// Next `transfer_in` as (ball id, origin, hops), acked.
+async fn recv_acked_transfer(
+    ws: &mut tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >,
+) -> (u32, u32, u32) {
+    for _ in 0..15 {
+        if let Some(ServerMsg::TransferIn {
+            seq,
+            ball_id,
+            origin_owner_id,
+            hops,
+            ..
+        }) = recv_msg_timeout(ws, Duration::from_millis(200)).await
+        {
+            let ack = serde_json::to_string(&ClientMsg::TransferAck { seq }).unwrap();
+            ws.send(Message::Text(ack.into())).await.unwrap();
+            return (ball_id, origin_owner_id, hops);
+        }
+    }
+    panic!("no transfer_in");
+}
+
+#[tokio::test]
+async fn test_re_escaped_ball_keeps_id_origin_and_counts_hops() {
+    let url = start_test_server_with_options(TestServerOptions {
+        deep_space_config: Some(fast_capture_config()),
+        ..Default::default()
+    })
+    .await;
+
+    let mut ws = connect(&url).await;
+    let my_id = extract_self_id(recv_msg(&mut ws).await);
+    let msg = serde_json::to_string(&ClientMsg::BallEscaped { vx: 1.0, vy: -2.0 }).unwrap();
+    ws.send(Message::Text(msg.into())).await.unwrap();
+
+    let (ball_id, origin, hops) = recv_acked_transfer(&mut ws).await;
+    assert_eq!((origin, hops), (my_id, 0));
+
+    for expected_hops in 1..=2 {
+        let msg = ClientMsg::ReEscape {
+            vx: 1.0,
+            vy: -2.0,
+            ball_id,
+        };
+        ws.send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+            .await
+            .unwrap();
+        assert_eq!(
+            recv_acked_transfer(&mut ws).await,
+            (ball_id, my_id, expected_hops)
+        );
+    }
+}
+
 // ============================================================================
 // Multi-player ball visibility
 // ============================================================================
@@ -597,19 +972,19 @@ async fn test_other_player_sees_ball_in_space_state() {
     // Client 2 should see the ball in space_state with correct ownerId
     let mut found = false;
     for _ in 0..10 {
-        if let Some(msg) = recv_msg_timeout(&mut ws2, Duration::from_millis(200)).await {
-            if let ServerMsg::SpaceState { balls, .. } = msg {
-                for b in &balls {
-                    if let Some(oid) = b.get("ownerId").and_then(|v| v.as_u64()) {
-                        if oid == id1 as u64 {
-                            found = true;
-                            break;
-                        }
+        if let Some(ServerMsg::SpaceState { balls, .. }) =
+            recv_msg_timeout(&mut ws2, Duration::from_millis(200)).await
+        {
+            for b in &balls {
+                if let Some(oid) = b.get("ownerId").and_then(|v| v.as_u64()) {
+                    if oid == id1 as u64 {
+                        found = true;
+                        break;
                     }
                 }
-                if found {
-                    break;
-                }
+            }
+            if found {
+                break;
             }
         }
     }
@@ -652,12 +1027,12 @@ async fn test_bots_appear_in_player_list_and_produce_balls() {
     // Wait for bots to produce balls (they send initial balls after 2-8s)
     let mut found_bot_ball = false;
     for _ in 0..100 {
-        if let Some(msg) = recv_msg_timeout(&mut ws, Duration::from_millis(200)).await {
-            if let ServerMsg::SpaceState { balls, .. } = msg {
-                if !balls.is_empty() {
-                    found_bot_ball = true;
-                    break;
-                }
+        if let Some(ServerMsg::SpaceState { balls, .. }) =
+            recv_msg_timeout(&mut ws, Duration::from_millis(200)).await
+        {
+            if !balls.is_empty() {
+                found_bot_ball = true;
+                break;
             }
         }
     }
@@ -735,6 +1110,45 @@ async fn test_activity_message_accepted() {
     );
 }
 
+#[tokio::test]
+async fn test_ping_is_answered_with_server_clock() {
+    let url = start_test_server().await;
+    let mut ws = connect(&url).await;
+    let _id = extract_self_id(recv_msg(&mut ws).await);
+
+    // Let the game clock advance past zero
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Over the per-second limit: the excess pings go unanswered
+    for i in 0..8 {
+        let ping = ClientMsg::Ping {
+            client_time: i as f64 + 0.5,
+        };
+        ws.send(Message::Text(serde_json::to_string(&ping).unwrap().into()))
+            .await
+            .unwrap();
+    }
+
+    let mut pongs = Vec::new();
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(500);
+    while tokio::time::Instant::now() < deadline {
+        let Some(msg) = recv_msg_timeout(&mut ws, Duration::from_millis(100)).await else {
+            continue;
+        };
+        if let ServerMsg::Pong {
+            client_time,
+            server_time,
+        } = msg
+        {
+            pongs.push((client_time, server_time));
+        }
+    }
+    let client_times: Vec<f64> = pongs.iter().map(|p| p.0).collect();
+    assert_eq!(client_times, vec![0.5, 1.5, 2.5, 3.5, 4.5]);
+    assert!(pongs[0].1 > 0.1, "server clock should have advanced");
+    assert!(pongs.windows(2).all(|w| w[1].1 >= w[0].1));
+}
+
 // ============================================================================
 // Connection limit
 // ============================================================================
@@ -770,3 +1184,1057 @@ async fn test_connection_limit_rejects_excess_clients() {
         }
     }
 }
+
+// ============================================================================
+// Spectators
+// ============================================================================
+
+#[tokio::test]
+async fn test_spectator_watches_without_portal_or_connection_slot() {
+    let url = start_test_server_with_options(TestServerOptions {
+        max_connections: Some(1),
+        ..Default::default()
+    })
+    .await;
+    let spectate_url = format!("{}?spectate=1", url);
+
+    let mut spectator = connect(&spectate_url).await;
+    match recv_msg(&mut spectator).await {
+        ServerMsg::Welcome {
+            self_id,
+            players,
+            spectator,
+            ..
+        } => {
+            assert!(spectator);
+            assert_eq!(self_id, 0);
+            assert!(players.is_empty(), "spectator must not get a portal");
+        }
+        other => panic!("Expected Welcome, got {:?}", other),
+    }
+
+    // The only player slot is still free
+    let mut player = connect(&url).await;
+    let player_id = extract_self_id(recv_msg(&mut player).await);
+    assert!(connect_async(&url).await.is_err());
+
+    // Spectators have their own cap (2 in tests)
+    let mut second = connect(&spectate_url).await;
+    assert!(matches!(
+        recv_msg(&mut second).await,
+        ServerMsg::Welcome {
+            spectator: true,
+            ..
+        }
+    ));
+    assert!(connect_async(&spectate_url).await.is_err());
+
+    // A spectator's ball_escaped is ignored; the player's ball is visible
+    let msg = serde_json::to_string(&ClientMsg::BallEscaped { vx: 1.0, vy: -2.0 }).unwrap();
+    spectator
+        .send(Message::Text(msg.clone().into()))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    player.send(Message::Text(msg.into())).await.unwrap();
+
+    let mut owners = Vec::new();
+    let mut latest_players = Vec::new();
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(800);
+    while tokio::time::Instant::now() < deadline {
+        match recv_msg_timeout(&mut spectator, Duration::from_millis(200)).await {
+            Some(ServerMsg::SpaceState { balls, .. }) => owners.extend(
+                balls
+                    .iter()
+                    .filter_map(|b| b.get("ownerId").and_then(|v| v.as_u64())),
+            ),
+            Some(ServerMsg::PlayersState { players }) => {
+                latest_players = players
+                    .iter()
+                    .filter_map(|p| p.get("id").and_then(|v| v.as_u64()))
+                    .collect();
+            }
+            Some(ServerMsg::TransferIn { .. }) => panic!("spectator got transfer_in"),
+            _ => {}
+        }
+    }
+    assert_eq!(latest_players, vec![player_id as u64]);
+    owners.dedup();
+    assert_eq!(owners, vec![player_id as u64]);
+}
+
+// ============================================================================
+// Resume tokens
+// ============================================================================
+
+#[tokio::test]
+async fn test_reconnect_with_resume_token_keeps_identity() {
+    let url = start_test_server().await;
+
+    let mut ws = connect(&url).await;
+    let hello = ClientMsg::Hello {
+        resume_token: None,
+        name: None,
+    };
+    ws.send(Message::Text(serde_json::to_string(&hello).unwrap().into()))
+        .await
+        .unwrap();
+    let (id, token, player) = match recv_msg(&mut ws).await {
+        ServerMsg::Welcome {
+            self_id,
+            resume_token,
+            players,
+            ..
+        } => {
+            let me = players
+                .into_iter()
+                .find(|p| p.get("id").and_then(|v| v.as_u64()) == Some(self_id as u64))
+                .expect("welcome should include self");
+            (self_id, resume_token, me)
+        }
+        other => panic!("Expected Welcome, got {:?}", other),
+    };
+    assert!(!token.is_empty(), "welcome should carry a resume token");
+
+    // Produce a ball so balls_produced is non-zero
+    let msg = ClientMsg::BallEscaped { vx: 1.0, vy: -2.0 };
+    ws.send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    ws.close(None).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut ws = connect(&url).await;
+    let hello = ClientMsg::Hello {
+        resume_token: Some(token.clone()),
+        name: None,
+    };
+    ws.send(Message::Text(serde_json::to_string(&hello).unwrap().into()))
+        .await
+        .unwrap();
+    match recv_msg(&mut ws).await {
+        ServerMsg::Welcome {
+            self_id,
+            resume_token,
+            players,
+            ..
+        } => {
+            assert_eq!(self_id, id, "resumed player should keep their id");
+            assert_eq!(resume_token, token, "resume token should be echoed");
+            let me = players
+                .iter()
+                .find(|p| p.get("id").and_then(|v| v.as_u64()) == Some(id as u64))
+                .expect("welcome should include self");
+            assert_eq!(me.get("cellIndex"), player.get("cellIndex"));
+            assert_eq!(me.get("color"), player.get("color"));
+            assert_eq!(me.get("ballsProduced").and_then(|v| v.as_u64()), Some(1));
+        }
+        other => panic!("Expected Welcome, got {:?}", other),
+    }
+}
+
+// ============================================================================
+// Display names
+// ============================================================================
+
+/// Name of player `id` in a players list, "" if unnamed or absent.
+fn player_name(players: &[serde_json::Value], id: u32) -> String {
+    players
+        .iter()
+        .find(|p| p.get("id").and_then(|v| v.as_u64()) == Some(id as u64))
+        .and_then(|p| p.get("name").and_then(|v| v.as_str()))
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[tokio::test]
+async fn test_display_names_are_validated_and_broadcast() {
+    let url = start_test_server().await;
+
+    let mut ws = connect(&url).await;
+    let hello = ClientMsg::Hello {
+        resume_token: None,
+        name: Some("  Ada\u{200B}  ".to_string()),
+    };
+    ws.send(Message::Text(serde_json::to_string(&hello).unwrap().into()))
+        .await
+        .unwrap();
+    let id = match recv_msg(&mut ws).await {
+        ServerMsg::Welcome {
+            self_id, players, ..
+        } => {
+            assert_eq!(player_name(&players, self_id), "Ada");
+            self_id
+        }
+        other => panic!("Expected Welcome, got {:?}", other),
+    };
+
+    let mut observer = connect(&url).await;
+    let _ = recv_msg(&mut observer).await;
+
+    // Blocklisted and too-long names are dropped; the last one goes through
+    for name in ["Blo cked", "Ada Lovelace Byron", "Grace"] {
+        let msg = ClientMsg::SetName {
+            name: name.to_string(),
+        };
+        ws.send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+            .await
+            .unwrap();
+    }
+
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(3);
+    let mut seen = Vec::new();
+    while tokio::time::Instant::now() < deadline {
+        if let Some(ServerMsg::PlayersState { players }) =
+            recv_msg_timeout(&mut observer, Duration::from_millis(200)).await
+        {
+            let name = player_name(&players, id);
+            seen.push(name.clone());
+            if name == "Grace" {
+                break;
+            }
+        }
+    }
+    assert_eq!(
+        seen.last().map(String::as_str),
+        Some("Grace"),
+        "seen {:?}",
+        seen
+    );
+    assert!(
+        seen.iter().all(|n| n == "Ada" || n == "Grace"),
+        "seen {:?}",
+        seen
+    );
+}
+
+// ============================================================================
+// Emotes
+// ============================================================================
+
+#[tokio::test]
+async fn test_emotes_are_broadcast_and_rate_limited() {
+    let url = start_test_server().await;
+
+    let mut sender = connect(&url).await;
+    let sender_id = extract_self_id(recv_msg(&mut sender).await);
+    let mut observer = connect(&url).await;
+    let _ = recv_msg(&mut observer).await;
+    let mut spectator = connect(&format!("{}?spectate=1", url)).await;
+    let _ = recv_msg(&mut spectator).await;
+
+    // Spectators can't emote
+    let msg = ClientMsg::Emote {
+        kind: "cheer".to_string(),
+    };
+    spectator
+        .send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+        .await
+        .unwrap();
+
+    // A burst well over the per-second limit
+    for _ in 0..6 {
+        let msg = ClientMsg::Emote {
+            kind: "wave".to_string(),
+        };
+        sender
+            .send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+            .await
+            .unwrap();
+    }
+
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(800);
+    let mut emotes = Vec::new();
+    while tokio::time::Instant::now() < deadline {
+        if let Some(ServerMsg::Emote { player_id, kind }) =
+            recv_msg_timeout(&mut observer, Duration::from_millis(100)).await
+        {
+            emotes.push((player_id, kind));
+        }
+    }
+    assert!(!emotes.is_empty(), "observer should see the emote");
+    assert!(
+        emotes.len() <= 2,
+        "burst should be rate limited: {:?}",
+        emotes
+    );
+    assert!(emotes
+        .iter()
+        .all(|(id, kind)| *id == sender_id && kind == "wave"));
+}
+
+// ============================================================================
+// Packed wire format
+// ============================================================================
+
+#[tokio::test]
+async fn test_bumper_hits_are_capped_and_ranked_on_the_leaderboard() {
+    let url = start_test_server().await;
+    let mut ws = connect(&url).await;
+    let my_id = extract_self_id(recv_msg(&mut ws).await);
+    let mut other = connect(&url).await;
+    let _ = recv_msg(&mut other).await;
+
+    // Way more than a board can do in a second: only the cap is credited
+    let msg = serde_json::to_string(&ClientMsg::BumperHits { count: 500 }).unwrap();
+    ws.send(Message::Text(msg.into())).await.unwrap();
+
+    let mut ranked = None;
+    for _ in 0..40 {
+        if let Some(ServerMsg::Leaderboard { entries, total }) =
+            recv_msg_timeout(&mut other, Duration::from_millis(200)).await
+        {
+            if entries.first().and_then(|e| e["score"].as_u64()) > Some(0) {
+                ranked = Some((entries, total));
+                break;
+            }
+        }
+    }
+    let (entries, total) = ranked.expect("leaderboard with a non-zero score");
+    assert_eq!(total, 2);
+    assert_eq!(entries[0]["playerId"].as_u64(), Some(my_id as u64));
+    assert_eq!(entries[0]["score"].as_u64(), Some(20));
+    assert_eq!(entries[1]["score"].as_u64(), Some(0));
+}
+
+#[tokio::test]
+async fn test_packed_client_receives_binary_space_state() {
+    let url = start_test_server().await;
+
+    let mut ws1 = connect(&url).await;
+    let mut ws2 = connect(&format!("{}?wire=packed", url)).await;
+    let id1 = extract_self_id(recv_msg(&mut ws1).await);
+    let _id2 = extract_self_id(recv_msg(&mut ws2).await);
+
+    let msg = ClientMsg::BallEscaped { vx: 1.0, vy: -2.0 };
+    ws1.send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+        .await
+        .unwrap();
+
+    // space_state must arrive as binary frames; players_state stays JSON
+    let mut found = false;
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+    while !found && tokio::time::Instant::now() < deadline {
+        let frame = tokio::time::timeout(Duration::from_millis(200), ws2.next()).await;
+        match frame {
+            Ok(Some(Ok(Message::Binary(bytes)))) => {
+                let state = pinball_shared::wire::decode_space_state(&bytes)
+                    .expect("binary frame should be a packed space_state");
+                found = state.balls.iter().any(|b| b.owner_id == id1);
+            }
+            Ok(Some(Ok(Message::Text(text)))) => {
+                let msg: ServerMsg = serde_json::from_str(&text).unwrap();
+                assert!(
+                    !matches!(msg, ServerMsg::SpaceState { .. }),
+                    "packed client should not get JSON space_state"
+                );
+            }
+            Ok(Some(Ok(_))) | Err(_) => {}
+            Ok(Some(Err(e))) => panic!("WebSocket error: {}", e),
+            Ok(None) => panic!("WebSocket closed unexpectedly"),
+        }
+    }
+    assert!(found, "packed client should see client 1's ball");
+}
+
+// ============================================================================
+// Delta-compressed space_state
+// ============================================================================
+
+#[tokio::test]
+async fn test_space_state_deltas_are_sequenced_and_skip_steady_balls() {
+    let url = start_test_server().await;
+    let mut ws = connect(&url).await;
+    let _welcome = recv_msg(&mut ws).await;
+
+    let msg = ClientMsg::BallEscaped { vx: 1.0, vy: -2.0 };
+    ws.send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+        .await
+        .unwrap();
+
+    // Collect ~0.8 s of snapshots (default keyframe interval is 1 s)
+    let mut last_seq: Option<u32> = None;
+    let mut saw_id_only = false;
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(800);
+    while tokio::time::Instant::now() < deadline {
+        if let Some(ServerMsg::SpaceState {
+            seq,
+            keyframe,
+            unchanged,
+            ..
+        }) = recv_msg_timeout(&mut ws, Duration::from_millis(200)).await
+        {
+            if let Some(prev) = last_seq {
+                assert_eq!(seq, prev + 1, "space_state seq should be contiguous");
+            }
+            last_seq = Some(seq);
+            saw_id_only |= !keyframe && !unchanged.is_empty();
+        }
+    }
+    assert!(
+        saw_id_only,
+        "a ball on a steady great circle should be sent as id only"
+    );
+
+    // Requesting a keyframe yields one promptly
+    ws.send(Message::Text(
+        serde_json::to_string(&ClientMsg::RequestKeyframe)
+            .unwrap()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    let mut got_keyframe = false;
+    for _ in 0..5 {
+        if let Some(ServerMsg::SpaceState {
+            keyframe, balls, ..
+        }) = recv_msg_timeout(&mut ws, Duration::from_millis(200)).await
+        {
+            if keyframe {
+                assert_eq!(balls.len(), 1, "keyframe should carry every ball");
+                got_keyframe = true;
+                break;
+            }
+        }
+    }
+    assert!(got_keyframe, "request_keyframe should produce a keyframe");
+}
+
+#[tokio::test]
+async fn test_joining_clients_get_their_own_keyframe() {
+    let url = start_test_server().await;
+    let mut ws1 = connect(&url).await;
+    let _welcome = recv_msg(&mut ws1).await;
+
+    let first_seq = loop {
+        if let ServerMsg::SpaceState { seq, keyframe, .. } = recv_msg(&mut ws1).await {
+            assert!(
+                keyframe,
+                "a new client's first space_state should be a keyframe"
+            );
+            break seq;
+        }
+    };
+
+    // Others join across several broadcasts
+    let mut joiners = Vec::new();
+    for _ in 0..4 {
+        joiners.push(connect(&url).await);
+        tokio::time::sleep(Duration::from_millis(150)).await;
+    }
+    let mut last_seq = first_seq;
+    for ws in &mut joiners {
+        loop {
+            if let ServerMsg::SpaceState { seq, keyframe, .. } = recv_msg(ws).await {
+                assert!(
+                    keyframe,
+                    "a joiner's first space_state should be a keyframe"
+                );
+                last_seq = last_seq.max(seq);
+                break;
+            }
+        }
+    }
+
+    // Meanwhile the first client's chain carried on, with only periodic keyframes
+    let mut seq = first_seq;
+    let mut keyframes = 0;
+    while seq < last_seq {
+        if let ServerMsg::SpaceState {
+            seq: next,
+            keyframe,
+            ..
+        } = recv_msg(&mut ws1).await
+        {
+            assert_eq!(next, seq + 1, "space_state seq should be contiguous");
+            seq = next;
+            keyframes += keyframe as u32;
+        }
+    }
+    assert!(
+        keyframes <= (last_seq - first_seq) / 10 + 1,
+        "joins should not force keyframes on other clients ({} keyframes over {} snapshots)",
+        keyframes,
+        last_seq - first_seq
+    );
+}
+
+// ============================================================================
+// Area of interest
+// ============================================================================
+
+#[tokio::test]
+async fn test_space_state_is_filtered_by_area_of_interest() {
+    let url = start_test_server_with_options(TestServerOptions {
+        aoi_radius: Some(0.3),
+        ..Default::default()
+    })
+    .await;
+
+    let mut clients = Vec::new();
+    let mut players = Vec::new();
+    for _ in 0..8 {
+        let mut ws = connect(&url).await;
+        match recv_msg(&mut ws).await {
+            ServerMsg::Welcome {
+                self_id,
+                players: p,
+                ..
+            } => {
+                clients.push((self_id, ws));
+                players = p;
+            }
+            other => panic!("Expected Welcome, got {:?}", other),
+        }
+    }
+    let portal = |id: u32| -> [f64; 3] {
+        let p = players
+            .iter()
+            .find(|p| p.get("id").and_then(|v| v.as_u64()) == Some(id as u64))
+            .expect("player in welcome");
+        let v: Vec<f64> = serde_json::from_value(p["portalPos"].clone()).unwrap();
+        [v[0], v[1], v[2]]
+    };
+    let angle = |a: [f64; 3], b: [f64; 3]| {
+        (a[0] * b[0] + a[1] * b[1] + a[2] * b[2])
+            .clamp(-1.0, 1.0)
+            .acos()
+    };
+
+    // Sender is the first client; watch the one whose portal is farthest away
+    let (sender_id, mut sender) = clients.remove(0);
+    let far_idx = (0..clients.len())
+        .max_by(|&a, &b| {
+            let da = angle(portal(sender_id), portal(clients[a].0));
+            let db = angle(portal(sender_id), portal(clients[b].0));
+            da.total_cmp(&db)
+        })
+        .unwrap();
+    let (far_id, mut far) = clients.swap_remove(far_idx);
+    assert!(
+        angle(portal(sender_id), portal(far_id)) > 2.0,
+        "seeded placement should put some portal far from the sender"
+    );
+
+    let msg = ClientMsg::BallEscaped { vx: 1.0, vy: -2.0 };
+    sender
+        .send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+        .await
+        .unwrap();
+
+    let owns_ball = |balls: &[serde_json::Value]| {
+        balls
+            .iter()
+            .any(|b| b.get("ownerId").and_then(|v| v.as_u64()) == Some(sender_id as u64))
+    };
+
+    let mut sender_saw = false;
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(600);
+    while !sender_saw && tokio::time::Instant::now() < deadline {
+        if let Some(ServerMsg::SpaceState { balls, .. }) =
+            recv_msg_timeout(&mut sender, Duration::from_millis(200)).await
+        {
+            sender_saw = owns_ball(&balls);
+        }
+    }
+    assert!(
+        sender_saw,
+        "ball near the sender's portal should be sent to it"
+    );
+
+    // The far client gets space_state, but never this ball; its global
+    // count still shows up in players_state.
+    let mut far_space_states = 0;
+    let mut far_saw_count = false;
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(600);
+    while tokio::time::Instant::now() < deadline {
+        match recv_msg_timeout(&mut far, Duration::from_millis(200)).await {
+            Some(ServerMsg::SpaceState { balls, .. }) => {
+                far_space_states += 1;
+                assert!(
+                    !owns_ball(&balls),
+                    "ball far from this client's portal should be filtered out"
+                );
+            }
+            Some(ServerMsg::PlayersState { players }) => {
+                far_saw_count |= players.iter().any(|p| {
+                    p.get("id").and_then(|v| v.as_u64()) == Some(sender_id as u64)
+                        && p.get("ballsInFlight").and_then(|v| v.as_u64()) == Some(1)
+                });
+            }
+            _ => {}
+        }
+    }
+    assert!(
+        far_space_states > 0,
+        "far client should still get space_state"
+    );
+    assert!(
+        far_saw_count,
+        "players_state should report global ball counts"
+    );
+}
+
+// ============================================================================
+// Rooms
+// ============================================================================
+
+#[tokio::test]
+async fn test_rooms_are_isolated_worlds() {
+    let url = start_test_server().await;
+
+    let mut public = connect(&url).await;
+    let _public_id = extract_self_id(recv_msg(&mut public).await);
+
+    let mut office = connect(&format!("{}?room=office", url)).await;
+    let office_id = match recv_msg(&mut office).await {
+        ServerMsg::Welcome {
+            self_id, players, ..
+        } => {
+            let ids: Vec<u64> = players
+                .iter()
+                .filter_map(|p| p.get("id").and_then(|v| v.as_u64()))
+                .collect();
+            assert_eq!(ids, vec![self_id as u64], "office room should start empty");
+            self_id
+        }
+        other => panic!("Expected Welcome, got {:?}", other),
+    };
+
+    let msg = ClientMsg::BallEscaped { vx: 1.0, vy: -2.0 };
+    public
+        .send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+        .await
+        .unwrap();
+
+    // The office never hears about the public player or their ball
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(800);
+    let mut office_space_states = 0;
+    while tokio::time::Instant::now() < deadline {
+        match recv_msg_timeout(&mut office, Duration::from_millis(200)).await {
+            Some(ServerMsg::SpaceState { balls, .. }) => {
+                office_space_states += 1;
+                assert!(balls.is_empty(), "public ball leaked into office room");
+            }
+            Some(ServerMsg::PlayersState { players }) => {
+                let ids: Vec<u64> = players
+                    .iter()
+                    .filter_map(|p| p.get("id").and_then(|v| v.as_u64()))
+                    .collect();
+                assert_eq!(
+                    ids,
+                    vec![office_id as u64],
+                    "public player leaked into office room"
+                );
+            }
+            _ => {}
+        }
+    }
+    assert!(office_space_states > 0);
+}
+
+#[tokio::test]
+async fn test_room_connection_cap_and_invalid_name() {
+    let url = start_test_server_with_options(TestServerOptions {
+        max_connections_per_room: Some(1),
+        ..Default::default()
+    })
+    .await;
+
+    let mut first = connect(&format!("{}?room=tiny", url)).await;
+    let _ = recv_msg(&mut first).await;
+    assert!(
+        connect_async(format!("{}?room=tiny", url)).await.is_err(),
+        "second connection to a full room should be rejected"
+    );
+
+    // Other rooms are unaffected
+    let mut public = connect(&url).await;
+    assert!(matches!(
+        recv_msg(&mut public).await,
+        ServerMsg::Welcome { .. }
+    ));
+
+    assert!(
+        connect_async(format!("{}?room=not%20valid", url))
+            .await
+            .is_err(),
+        "invalid room names should be rejected"
+    );
+}
+
+// ============================================================================
+// Join tokens
+// ============================================================================
+
+#[tokio::test]
+async fn test_join_tokens_gate_connections_and_pin_room_and_name() {
+    use pinball_server::join_token::{self, JoinClaims};
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    const SECRET: &str = "integration-test-secret";
+    let url = start_test_server_with_options(TestServerOptions {
+        join_token_secret: Some(SECRET.to_string()),
+        ..Default::default()
+    })
+    .await;
+    let mint = |room: Option<&str>, name: Option<&str>, ttl: i64| {
+        join_token::mint(
+            SECRET,
+            &JoinClaims {
+                exp: (join_token::unix_now() as i64 + ttl) as u64,
+                room: room.map(str::to_string),
+                name: name.map(str::to_string),
+            },
+        )
+    };
+
+    assert!(connect_async(&url).await.is_err(), "token required");
+    let expired = mint(None, None, -1);
+    assert!(connect_async(format!("{}?token={}", url, expired))
+        .await
+        .is_err());
+    let foreign = join_token::mint(
+        "some other secret",
+        &JoinClaims {
+            exp: join_token::unix_now() + 60,
+            room: None,
+            name: None,
+        },
+    );
+    assert!(connect_async(format!("{}?token={}", url, foreign))
+        .await
+        .is_err());
+
+    // An office token joins the office without ?room= and under its name,
+    // whatever the client asks for
+    let office = mint(Some("office"), Some("Ada"), 60);
+    assert!(
+        connect_async(format!("{}?room=other&token={}", url, office))
+            .await
+            .is_err(),
+        "token is for another room"
+    );
+    let mut ada = connect(&format!("{}?token={}", url, office)).await;
+    let hello = ClientMsg::Hello {
+        resume_token: None,
+        name: Some("Bob".to_string()),
+    };
+    ada.send(Message::Text(serde_json::to_string(&hello).unwrap().into()))
+        .await
+        .unwrap();
+    let ada_id = match recv_msg(&mut ada).await {
+        ServerMsg::Welcome {
+            self_id, players, ..
+        } => {
+            assert_eq!(player_name(&players, self_id), "Ada");
+            self_id
+        }
+        other => panic!("Expected Welcome, got {:?}", other),
+    };
+
+    // Same token as a subprotocol; the server has to accept it
+    let mut request = format!("{}?room=office", url)
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        format!("pinball-token.{}", mint(None, None, 60))
+            .parse()
+            .unwrap(),
+    );
+    let (mut other, _) = connect_async(request).await.expect("subprotocol token");
+    match recv_msg(&mut other).await {
+        ServerMsg::Welcome { players, .. } => {
+            assert_eq!(player_name(&players, ada_id), "Ada", "joined the office");
+        }
+        other => panic!("Expected Welcome, got {:?}", other),
+    }
+}
+
+// ============================================================================
+// Admin API
+// ============================================================================
+
+#[tokio::test]
+async fn test_admin_api_requires_token() {
+    let url = start_test_server().await;
+    let (status, _) = http_request(&url, "GET", "/admin/rooms", None).await;
+    assert_eq!(status, 401);
+    let (status, _) = http_request(&url, "GET", "/admin/rooms", Some("wrong")).await;
+    assert_eq!(status, 401);
+
+    let (status, rooms) = admin(&url, "GET", "/admin/rooms").await;
+    assert_eq!(status, 200);
+    assert_eq!(rooms[0]["name"], "public");
+    let (status, _) = admin(&url, "GET", "/admin/rooms/nope/players").await;
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn test_admin_lists_players_and_manages_bots() {
+    let url = start_test_server_with_options(TestServerOptions {
+        bot_count: Some(1),
+        ..Default::default()
+    })
+    .await;
+    let mut ws = connect(&url).await;
+    let my_id = extract_self_id(recv_msg(&mut ws).await);
+
+    let (status, players) = admin(&url, "GET", "/admin/rooms/public/players").await;
+    assert_eq!(status, 200);
+    let players = players.as_array().unwrap().clone();
+    assert_eq!(players.len(), 2);
+    let me = players
+        .iter()
+        .find(|p| p["id"].as_u64() == Some(my_id as u64))
+        .unwrap();
+    assert_eq!(me["is_bot"], false);
+    assert_eq!(me["balls_in_flight"], 0);
+    assert!(players.iter().any(|p| p["is_bot"] == true));
+
+    let (status, added) = admin(&url, "POST", "/admin/rooms/public/bots").await;
+    assert_eq!(status, 201);
+    let bot_id = added["id"].as_u64().unwrap();
+    let (_, players) = admin(&url, "GET", "/admin/rooms/public/players").await;
+    assert_eq!(players.as_array().unwrap().len(), 3);
+
+    let (status, _) = admin(
+        &url,
+        "DELETE",
+        &format!("/admin/rooms/public/bots/{}", bot_id),
+    )
+    .await;
+    assert_eq!(status, 204);
+    let (status, _) = admin(
+        &url,
+        "DELETE",
+        &format!("/admin/rooms/public/bots/{}", my_id),
+    )
+    .await;
+    assert_eq!(status, 404, "real players are not removable as bots");
+    let (_, players) = admin(&url, "GET", "/admin/rooms/public/players").await;
+    assert_eq!(players.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_admin_dumps_and_clears_balls() {
+    let url = start_test_server().await;
+    let mut ws = connect(&url).await;
+    let my_id = extract_self_id(recv_msg(&mut ws).await);
+
+    let msg = ClientMsg::BallEscaped { vx: 1.0, vy: -2.0 };
+    ws.send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+        .await
+        .unwrap();
+
+    let mut balls = serde_json::Value::Null;
+    for _ in 0..20 {
+        let (_, b) = admin(&url, "GET", "/admin/rooms/public/balls").await;
+        if b.as_array().is_some_and(|a| !a.is_empty()) {
+            balls = b;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(balls[0]["owner_id"].as_u64(), Some(my_id as u64));
+
+    let (status, cleared) = admin(&url, "DELETE", "/admin/rooms/public/balls").await;
+    assert_eq!(status, 200);
+    assert_eq!(cleared["cleared"], 1);
+    let (_, balls) = admin(&url, "GET", "/admin/rooms/public/balls").await;
+    assert!(balls.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_admin_kick_disconnects_player() {
+    let url = start_test_server().await;
+    let mut ws = connect(&url).await;
+    let my_id = extract_self_id(recv_msg(&mut ws).await);
+
+    let (status, _) = admin(
+        &url,
+        "POST",
+        &format!("/admin/rooms/public/players/{}/kick", my_id),
+    )
+    .await;
+    assert_eq!(status, 204);
+
+    let closed = tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match ws.next().await {
+                Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                Some(Ok(_)) => continue,
+            }
+        }
+    })
+    .await;
+    assert!(closed.is_ok(), "kicked client should be disconnected");
+
+    let (status, _) = admin(
+        &url,
+        "POST",
+        &format!("/admin/rooms/public/players/{}/kick", my_id),
+    )
+    .await;
+    assert_eq!(status, 404);
+}
+
+// ============================================================================
+// Metrics
+// ============================================================================
+
+#[tokio::test]
+async fn test_metrics_endpoint_reports_players_and_rejections() {
+    let url = start_test_server_with_options(TestServerOptions {
+        bot_count: Some(2),
+        ..Default::default()
+    })
+    .await;
+    let mut ws = connect(&url).await;
+    let _ = recv_msg(&mut ws).await; // welcome
+
+    // vy must be negative: rejected, connection stays open
+    let msg = ClientMsg::BallEscaped { vx: 1.0, vy: 2.0 };
+    ws.send(Message::Text(serde_json::to_string(&msg).unwrap().into()))
+        .await
+        .unwrap();
+
+    let mut body = String::new();
+    for _ in 0..20 {
+        let (status, b) = http_request(&url, "GET", "/metrics", None).await;
+        assert_eq!(status, 200);
+        body = b;
+        if body.contains("pinball_connected_players{room=\"public\"} 1\n")
+            && body.contains("pinball_ball_escaped_rejected_total{reason=\"vy_positive\"} 1\n")
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(
+        body.contains("pinball_connected_players{room=\"public\"} 1\n"),
+        "{}",
+        body
+    );
+    assert!(body.contains("pinball_bot_players{room=\"public\"} 2\n"));
+    assert!(body.contains("pinball_ball_escaped_rejected_total{reason=\"vy_positive\"} 1\n"));
+    assert!(body.contains("# TYPE pinball_tick_duration_seconds histogram\n"));
+    assert!(body
+        .contains("pinball_broadcast_sent_bytes_total{room=\"public\",message=\"space_state\"}"));
+}
+
+#[tokio::test]
+async fn test_flagged_player_escapes_are_shadow_dropped() {
+    use pinball_server::config::{AntiCheat, CheatPenalty};
+
+    let url = start_test_server_with_options(TestServerOptions {
+        anti_cheat: Some(AntiCheat {
+            flag_after: 3,
+            forgive_per_sec: 0.0,
+            penalty: CheatPenalty::ShadowDrop,
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+    .await;
+    let mut ws = connect(&url).await;
+    let _ = recv_msg(&mut ws).await; // welcome
+
+    // Ten escapes at once with no balls received: the board's own two are
+    // plausible, the next three get the player flagged
+    let msg = serde_json::to_string(&ClientMsg::BallEscaped { vx: 0.5, vy: -3.0 }).unwrap();
+    for _ in 0..10 {
+        ws.send(Message::Text(msg.clone().into())).await.unwrap();
+    }
+
+    let mut body = String::new();
+    for _ in 0..20 {
+        body = http_request(&url, "GET", "/metrics", None).await.1;
+        if body.contains("pinball_escapes_penalized_total{penalty=\"shadow_drop\"} 6\n") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(
+        body.contains("pinball_escapes_penalized_total{penalty=\"shadow_drop\"} 6\n"),
+        "{}",
+        body
+    );
+    assert!(body.contains("pinball_players_flagged_total{penalty=\"shadow_drop\"} 1\n"));
+    assert!(body.contains("pinball_escape_suspicions_total{check=\"cadence\"} 8\n"));
+    // Only the escapes before the flag reached deep space, and the client
+    // is still connected
+    assert_eq!(deep_space_ball_owners(&url).await.len(), 4);
+    ws.send(Message::Text(
+        serde_json::to_string(&ClientMsg::Ping { client_time: 1.0 })
+            .unwrap()
+            .into(),
+    ))
+    .await
+    .unwrap();
+    while !matches!(recv_msg(&mut ws).await, ServerMsg::Pong { .. }) {}
+}
+
+// ============================================================================
+// Health, readiness and shutdown
+// ============================================================================
+
+#[tokio::test]
+async fn test_healthz_reports_ticking_rooms() {
+    let url = start_test_server().await;
+    let (status, body) = http_request(&url, "GET", "/healthz", None).await;
+    assert_eq!(status, 200);
+    let health: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["rooms"][0]["name"], "public");
+    assert_eq!(health["rooms"][0]["ticking"], true);
+}
+
+#[tokio::test]
+async fn test_shutdown_notifies_clients_and_fails_readiness() {
+    let lifecycle = Arc::new(pinball_server::lifecycle::Lifecycle::default());
+    let url = start_test_server_with_options(TestServerOptions {
+        lifecycle: Some(lifecycle.clone()),
+        ..Default::default()
+    })
+    .await;
+    let mut ws = connect(&url).await;
+    let _ = recv_msg(&mut ws).await; // welcome
+
+    let (status, body) = http_request(&url, "GET", "/readyz", None).await;
+    assert_eq!((status, body.as_str()), (200, "ready"));
+
+    lifecycle.begin_drain();
+
+    // server_going_away, then a 1001 close frame
+    let mut got_going_away = false;
+    let close = tokio::time::timeout(Duration::from_secs(2), async {
+        loop {
+            match ws.next().await {
+                Some(Ok(Message::Text(text))) => {
+                    if let Ok(ServerMsg::ServerGoingAway { reason }) = serde_json::from_str(&text) {
+                        assert!(!reason.is_empty());
+                        got_going_away = true;
+                    }
+                }
+                Some(Ok(Message::Close(frame))) => break frame,
+                Some(Ok(_)) => continue,
+                other => panic!("expected close frame, got {:?}", other),
+            }
+        }
+    })
+    .await
+    .expect("client should be closed on shutdown");
+    assert!(got_going_away, "server_going_away should precede the close");
+    let frame = close.expect("close frame should carry a code");
+    assert_eq!(u16::from(frame.code), 1001);
+
+    let (status, body) = http_request(&url, "GET", "/readyz", None).await;
+    assert_eq!((status, body.as_str()), (503, "draining"));
+    assert!(
+        connect_async(&url).await.is_err(),
+        "new connections are refused while draining"
+    );
+}
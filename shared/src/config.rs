@@ -34,23 +34,29 @@ impl Default for DeepSpaceConfig {
 }
 
 impl DeepSpaceConfig {
-    pub fn validate(&self) -> Result<(), String> {
+    /// Validate configuration. Returns every problem found, each prefixed
+    /// with the offending field name.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
         if !self.portal_alpha.is_finite() || self.portal_alpha <= 0.0 {
-            return Err("portal_alpha must be finite and > 0".to_string());
-        }
-        if self.portal_alpha > std::f64::consts::PI {
-            return Err("portal_alpha must be <= PI".to_string());
+            errors.push("portal_alpha: must be finite and > 0".to_string());
+        } else if self.portal_alpha > std::f64::consts::PI {
+            errors.push("portal_alpha: must be <= PI".to_string());
         }
         if !self.omega_min.is_finite() || self.omega_min < 0.0 {
-            return Err("omega_min must be finite and >= 0".to_string());
+            errors.push("omega_min: must be finite and >= 0".to_string());
         }
         if !self.omega_max.is_finite() || self.omega_max < self.omega_min {
-            return Err("omega_max must be finite and >= omega_min".to_string());
+            errors.push("omega_max: must be finite and >= omega_min".to_string());
         }
         if !self.min_age_for_capture.is_finite() || self.min_age_for_capture < 0.0 {
-            return Err("min_age_for_capture must be finite and >= 0".to_string());
+            errors.push("min_age_for_capture: must be finite and >= 0".to_string());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
-        Ok(())
     }
 }
 
@@ -66,16 +72,35 @@ mod tests {
 
     #[test]
     fn omega_max_less_than_min_invalid() {
-        let mut config = DeepSpaceConfig::default();
-        config.omega_min = 2.0;
-        config.omega_max = 1.0;
+        let config = DeepSpaceConfig {
+            omega_min: 2.0,
+            omega_max: 1.0,
+            ..Default::default()
+        };
         assert!(config.validate().is_err());
     }
 
     #[test]
     fn portal_alpha_too_large_invalid() {
-        let mut config = DeepSpaceConfig::default();
-        config.portal_alpha = 4.0;
+        let config = DeepSpaceConfig {
+            portal_alpha: 4.0,
+            ..Default::default()
+        };
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let config = DeepSpaceConfig {
+            portal_alpha: f64::NAN,
+            omega_min: -1.0,
+            min_age_for_capture: -1.0,
+            ..Default::default()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[0].starts_with("portal_alpha:"));
+        assert!(errors[1].starts_with("omega_min:"));
+        assert!(errors[2].starts_with("min_age_for_capture:"));
+    }
 }
@@ -0,0 +1,561 @@
+//! Keyframe + delta compression for `space_state`.
+//!
+//! Between reroutes a ball rotates about a fixed axis at a fixed omega, so a
+//! receiver that knows the last explicitly sent record for a ball can advance
+//! it analytically. The encoder only resends a ball in full when it is new,
+//! its axis/omega changed, or the analytic prediction has drifted; otherwise
+//! it sends just the id. Every snapshot carries a sequence number so the
+//! decoder can detect a missed delta and ask for a keyframe.
+//!
+//! Both sides run the same [`predict_pos`], so they agree exactly on what an
+//! "unchanged" ball looks like.
+//!
+//! All receivers follow one chain. One that joins or loses track midway
+//! gets its own [`DeltaEncoder::keyframe`] for the current sequence number
+//! instead of restarting the chain for everyone.
+//!
+//! The server can split each snapshot into spatial buckets
+//! ([`DeltaEncoder::encode_bucketed`]) and send each client only the buckets
+//! near its portal. A ball that crosses into another bucket is sent in full
+//! there and listed as removed in the bucket it left, so any union of
+//! buckets is itself a valid delta chain.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::protocol::{BallWire, SpaceStateMsg};
+use crate::vec3::{rotate_normalize_in_place, Vec3};
+
+/// Broadcasts between periodic keyframes (1 s at the default 10 Hz).
+pub const DEFAULT_KEYFRAME_INTERVAL: u32 = 10;
+
+/// Max angular drift (radians) between prediction and truth before a ball is
+/// resent in full. Well below what is visible at the sphere view's scale.
+const MAX_PREDICTION_ERROR: f64 = 1e-3;
+
+/// A ball as last sent explicitly, and when.
+#[derive(Debug, Clone)]
+struct Record {
+    ball: BallWire,
+    server_time: f64,
+    /// Bucket the ball was last listed in (encoder only)
+    bucket: usize,
+}
+
+/// Advance a ball record from `from_time` to `to_time` along its great circle.
+pub fn predict_pos(ball: &BallWire, from_time: f64, to_time: f64) -> [f64; 3] {
+    let mut pos = Vec3::new(ball.pos[0], ball.pos[1], ball.pos[2]);
+    let axis = Vec3::new(ball.axis[0], ball.axis[1], ball.axis[2]);
+    rotate_normalize_in_place(&mut pos, axis, ball.omega * (to_time - from_time));
+    [pos.x, pos.y, pos.z]
+}
+
+fn distance_sq(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let (dx, dy, dz) = (a[0] - b[0], a[1] - b[1], a[2] - b[2]);
+    dx * dx + dy * dy + dz * dz
+}
+
+/// Server side: turns full snapshots into keyframes and deltas.
+#[derive(Debug)]
+pub struct DeltaEncoder {
+    records: HashMap<u32, Record>,
+    seq: u32,
+    /// Time of the last snapshot encoded
+    server_time: f64,
+    keyframe_interval: u32,
+    since_keyframe: u32,
+    keyframe_requested: bool,
+}
+
+impl DeltaEncoder {
+    pub fn new(keyframe_interval: u32) -> Self {
+        Self {
+            records: HashMap::new(),
+            seq: 0,
+            server_time: 0.0,
+            keyframe_interval: keyframe_interval.max(1),
+            since_keyframe: 0,
+            // First snapshot is always a keyframe
+            keyframe_requested: true,
+        }
+    }
+
+    /// Make the next snapshot a keyframe (e.g. a client detected a gap).
+    pub fn request_keyframe(&mut self) {
+        self.keyframe_requested = true;
+    }
+
+    /// A keyframe for the last snapshot encoded (same `seq`), holding every
+    /// ball as the chain has described it so far. A receiver starting from
+    /// it can follow the deltas that come after.
+    pub fn keyframe(&self) -> SpaceStateMsg {
+        let mut out = self.keyframe_bucketed(1);
+        let bucket = out.buckets.pop().unwrap_or_default();
+        SpaceStateMsg {
+            server_time: out.server_time,
+            seq: out.seq,
+            keyframe: true,
+            balls: bucket.balls,
+            unchanged: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// [`Self::keyframe`] split into buckets, each ball in the bucket the
+    /// chain last listed it in. `bucket_count` must match `encode_bucketed`.
+    pub fn keyframe_bucketed(&self, bucket_count: usize) -> BucketedSpaceState {
+        let mut buckets = vec![BucketDelta::default(); bucket_count];
+        for rec in self.records.values() {
+            buckets[rec.bucket].balls.push(BallWire {
+                pos: predict_pos(&rec.ball, rec.server_time, self.server_time),
+                ..rec.ball.clone()
+            });
+        }
+        for bucket in &mut buckets {
+            bucket.balls.sort_unstable_by_key(|b| b.id);
+        }
+        BucketedSpaceState {
+            server_time: self.server_time,
+            seq: self.seq,
+            keyframe: true,
+            buckets,
+        }
+    }
+
+    /// Encode a full snapshot. `full.balls` must hold every ball in space.
+    pub fn encode(&mut self, full: SpaceStateMsg) -> SpaceStateMsg {
+        let mut out = self.encode_bucketed(&full, 1, |_| 0);
+        let bucket = out.buckets.pop().unwrap_or_default();
+        SpaceStateMsg {
+            server_time: out.server_time,
+            seq: out.seq,
+            keyframe: out.keyframe,
+            balls: bucket.balls,
+            unchanged: bucket.unchanged,
+            removed: bucket.removed,
+        }
+    }
+
+    /// Encode a full snapshot split into `bucket_count` buckets, where
+    /// `bucket_of` maps a ball to its bucket (must be `< bucket_count`).
+    /// `full` is only borrowed so its ball buffer can be reused.
+    pub fn encode_bucketed(
+        &mut self,
+        full: &SpaceStateMsg,
+        bucket_count: usize,
+        bucket_of: impl Fn(&BallWire) -> usize,
+    ) -> BucketedSpaceState {
+        self.seq = self.seq.wrapping_add(1);
+        self.since_keyframe += 1;
+        let keyframe = self.keyframe_requested || self.since_keyframe >= self.keyframe_interval;
+        let server_time = full.server_time;
+        self.server_time = server_time;
+        let mut buckets = vec![BucketDelta::default(); bucket_count];
+
+        if keyframe {
+            self.keyframe_requested = false;
+            self.since_keyframe = 0;
+            self.records.clear();
+        }
+
+        let mut present = HashSet::with_capacity(full.balls.len());
+        for ball in &full.balls {
+            present.insert(ball.id);
+            let bucket = bucket_of(ball);
+            let prev = self.records.get(&ball.id);
+            let same = prev.is_some_and(|rec| {
+                rec.bucket == bucket
+                    && rec.ball.axis == ball.axis
+                    && rec.ball.omega == ball.omega
+                    && rec.ball.owner_id == ball.owner_id
+                    && rec.ball.hops == ball.hops
+                    && distance_sq(
+                        predict_pos(&rec.ball, rec.server_time, server_time),
+                        ball.pos,
+                    ) <= MAX_PREDICTION_ERROR * MAX_PREDICTION_ERROR
+            });
+            if same {
+                buckets[bucket].unchanged.push(ball.id);
+                continue;
+            }
+            // Moved between buckets: clients that only see the old one drop it
+            if let Some(old) = prev.map(|rec| rec.bucket).filter(|&b| b != bucket) {
+                buckets[old].removed.push(ball.id);
+            }
+            self.records.insert(
+                ball.id,
+                Record {
+                    ball: ball.clone(),
+                    server_time,
+                    bucket,
+                },
+            );
+            buckets[bucket].balls.push(ball.clone());
+        }
+
+        let mut removed: Vec<(u32, usize)> = self
+            .records
+            .iter()
+            .filter(|(id, _)| !present.contains(*id))
+            .map(|(id, rec)| (*id, rec.bucket))
+            .collect();
+        removed.sort_unstable();
+        for (id, bucket) in removed {
+            self.records.remove(&id);
+            buckets[bucket].removed.push(id);
+        }
+        for bucket in &mut buckets {
+            bucket.removed.sort_unstable();
+        }
+
+        BucketedSpaceState {
+            server_time,
+            seq: self.seq,
+            keyframe,
+            buckets,
+        }
+    }
+}
+
+/// The part of one snapshot that falls in a single bucket.
+#[derive(Debug, Clone, Default)]
+pub struct BucketDelta {
+    pub balls: Vec<BallWire>,
+    pub unchanged: Vec<u32>,
+    pub removed: Vec<u32>,
+}
+
+/// A snapshot encoded per bucket. Concatenating any set of buckets gives a
+/// `SpaceStateMsg` for a receiver that only ever sees that set.
+#[derive(Debug, Clone)]
+pub struct BucketedSpaceState {
+    pub server_time: f64,
+    pub seq: u32,
+    pub keyframe: bool,
+    pub buckets: Vec<BucketDelta>,
+}
+
+impl Default for DeltaEncoder {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEYFRAME_INTERVAL)
+    }
+}
+
+/// Why a snapshot could not be applied. Either way the receiver should
+/// discard it and request a keyframe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeltaError {
+    /// No keyframe received yet
+    NoBaseline,
+    /// Sequence number skipped (a delta was lost)
+    Gap { expected: u32, got: u32 },
+    /// Delta referenced a ball we have no record of, or left one unaccounted for
+    Desync,
+}
+
+/// Client side: rebuilds full snapshots from keyframes and deltas.
+#[derive(Debug, Default)]
+pub struct DeltaDecoder {
+    records: HashMap<u32, Record>,
+    last_seq: Option<u32>,
+}
+
+impl DeltaDecoder {
+    /// Forget all state (e.g. on reconnect). The next snapshot must be a keyframe.
+    pub fn reset(&mut self) {
+        self.records.clear();
+        self.last_seq = None;
+    }
+
+    /// Apply a snapshot and return every ball at `msg.server_time`.
+    /// On error the decoder is reset and waits for the next keyframe.
+    pub fn apply(&mut self, msg: &SpaceStateMsg) -> Result<Vec<BallWire>, DeltaError> {
+        let result = self.try_apply(msg);
+        if result.is_err() {
+            self.reset();
+        }
+        result
+    }
+
+    fn try_apply(&mut self, msg: &SpaceStateMsg) -> Result<Vec<BallWire>, DeltaError> {
+        let server_time = msg.server_time;
+
+        if msg.keyframe {
+            self.records.clear();
+            for ball in &msg.balls {
+                self.records.insert(
+                    ball.id,
+                    Record {
+                        ball: ball.clone(),
+                        server_time,
+                        bucket: 0,
+                    },
+                );
+            }
+            self.last_seq = Some(msg.seq);
+            return Ok(msg.balls.clone());
+        }
+
+        let expected = self.last_seq.ok_or(DeltaError::NoBaseline)?.wrapping_add(1);
+        if msg.seq != expected {
+            return Err(DeltaError::Gap {
+                expected,
+                got: msg.seq,
+            });
+        }
+
+        for id in &msg.removed {
+            if self.records.remove(id).is_none() {
+                return Err(DeltaError::Desync);
+            }
+        }
+        for ball in &msg.balls {
+            self.records.insert(
+                ball.id,
+                Record {
+                    ball: ball.clone(),
+                    server_time,
+                    bucket: 0,
+                },
+            );
+        }
+        if self.records.len() != msg.balls.len() + msg.unchanged.len() {
+            return Err(DeltaError::Desync);
+        }
+
+        let mut out = Vec::with_capacity(self.records.len());
+        for id in &msg.unchanged {
+            let rec = self.records.get(id).ok_or(DeltaError::Desync)?;
+            out.push(BallWire {
+                pos: predict_pos(&rec.ball, rec.server_time, server_time),
+                ..rec.ball.clone()
+            });
+        }
+        out.extend(msg.balls.iter().cloned());
+        self.last_seq = Some(msg.seq);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::round4;
+
+    fn ball(id: u32, pos: [f64; 3], axis: [f64; 3], omega: f64) -> BallWire {
+        BallWire {
+            id,
+            owner_id: 1,
+            pos,
+            axis,
+            omega,
+            hops: 0,
+        }
+    }
+
+    /// Simulate a ball on the equator at `t` (axis z, omega 1), rounded like the server does.
+    fn equator_ball(id: u32, t: f64) -> BallWire {
+        ball(
+            id,
+            [round4(t.cos()), round4(t.sin()), 0.0],
+            [0.0, 0.0, 1.0],
+            1.0,
+        )
+    }
+
+    fn full(t: f64, balls: Vec<BallWire>) -> SpaceStateMsg {
+        SpaceStateMsg {
+            server_time: t,
+            seq: 0,
+            keyframe: false,
+            balls,
+            unchanged: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn first_snapshot_is_keyframe_then_periodic() {
+        let mut enc = DeltaEncoder::new(3);
+        let kinds: Vec<bool> = (0..7)
+            .map(|i| enc.encode(full(i as f64 * 0.1, vec![])).keyframe)
+            .collect();
+        assert_eq!(kinds, vec![true, false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn steady_ball_is_sent_as_id_only() {
+        let mut enc = DeltaEncoder::new(100);
+        enc.encode(full(0.0, vec![equator_ball(1, 0.0)]));
+        let delta = enc.encode(full(0.1, vec![equator_ball(1, 0.1)]));
+        assert!(!delta.keyframe);
+        assert!(delta.balls.is_empty());
+        assert_eq!(delta.unchanged, vec![1]);
+        assert!(delta.removed.is_empty());
+    }
+
+    #[test]
+    fn new_rerouted_and_removed_balls_are_explicit() {
+        let mut enc = DeltaEncoder::new(100);
+        enc.encode(full(0.0, vec![equator_ball(1, 0.0), equator_ball(2, 0.0)]));
+
+        let mut rerouted = equator_ball(2, 0.1);
+        rerouted.axis = [0.0, 1.0, 0.0];
+        let delta = enc.encode(full(0.1, vec![rerouted, equator_ball(3, 0.1)]));
+
+        let explicit: Vec<u32> = delta.balls.iter().map(|b| b.id).collect();
+        assert_eq!(explicit, vec![2, 3]);
+        assert!(delta.unchanged.is_empty());
+        assert_eq!(delta.removed, vec![1]);
+    }
+
+    #[test]
+    fn drifted_prediction_is_resent() {
+        let mut enc = DeltaEncoder::new(100);
+        enc.encode(full(0.0, vec![equator_ball(1, 0.0)]));
+        // Same axis/omega but the ball jumped ahead of where it should be
+        let delta = enc.encode(full(0.1, vec![equator_ball(1, 0.5)]));
+        assert_eq!(delta.balls.len(), 1);
+        assert!(delta.unchanged.is_empty());
+    }
+
+    #[test]
+    fn decoder_reconstructs_encoder_input() {
+        let mut enc = DeltaEncoder::new(5);
+        let mut dec = DeltaDecoder::default();
+
+        for i in 0..30 {
+            let t = i as f64 * 0.1;
+            let mut balls = vec![equator_ball(1, t)];
+            if i >= 10 {
+                balls.push(equator_ball(2, t));
+            }
+            let out = dec.apply(&enc.encode(full(t, balls.clone()))).unwrap();
+            assert_eq!(out.len(), balls.len(), "snapshot {}", i);
+            for truth in &balls {
+                let got = out.iter().find(|b| b.id == truth.id).unwrap();
+                assert!(distance_sq(got.pos, truth.pos) < 1e-6, "snapshot {}", i);
+                assert_eq!(got.axis, truth.axis);
+            }
+        }
+    }
+
+    #[test]
+    fn late_receiver_follows_the_chain_from_its_own_keyframe() {
+        let mut enc = DeltaEncoder::new(100);
+        let mut early = DeltaDecoder::default();
+        let mut late = DeltaDecoder::default();
+
+        for i in 0..20 {
+            let t = i as f64 * 0.1;
+            let mut balls = vec![equator_ball(1, t)];
+            if i >= 5 {
+                balls.push(equator_ball(2, t));
+            }
+            let msg = enc.encode(full(t, balls));
+            let expected = early.apply(&msg).unwrap();
+            if i == 10 {
+                let key = enc.keyframe();
+                assert!(key.keyframe);
+                assert_eq!(key.seq, msg.seq);
+                late.apply(&key).unwrap();
+            } else if i > 10 {
+                // Nobody else needed a keyframe for the late joiner
+                assert!(!msg.keyframe);
+                let got = late.apply(&msg).unwrap();
+                assert_eq!(got.len(), expected.len());
+                for want in &expected {
+                    let ball = got.iter().find(|b| b.id == want.id).unwrap();
+                    assert!(distance_sq(ball.pos, want.pos) < 1e-18, "snapshot {}", i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_bucket_decoder_follows_ball_across_buckets() {
+        // Bucket 0: x >= 0, bucket 1: x < 0. Ball 1 circles the equator,
+        // ball 2 disappears halfway through.
+        let bucket_of = |b: &BallWire| usize::from(b.pos[0] < 0.0);
+        let mut enc = DeltaEncoder::new(100);
+        let mut decs = [DeltaDecoder::default(), DeltaDecoder::default()];
+
+        for i in 0..70 {
+            let t = i as f64 * 0.1;
+            let mut balls = vec![equator_ball(1, t)];
+            if i < 35 {
+                balls.push(equator_ball(2, t + 1.0));
+            }
+            let out = enc.encode_bucketed(&full(t, balls.clone()), 2, bucket_of);
+            for (bucket, dec) in decs.iter_mut().enumerate() {
+                let part = &out.buckets[bucket];
+                let msg = SpaceStateMsg {
+                    server_time: out.server_time,
+                    seq: out.seq,
+                    keyframe: out.keyframe,
+                    balls: part.balls.clone(),
+                    unchanged: part.unchanged.clone(),
+                    removed: part.removed.clone(),
+                };
+                let got = dec.apply(&msg).unwrap();
+                let mut ids: Vec<u32> = got.iter().map(|b| b.id).collect();
+                ids.sort_unstable();
+                let expected: Vec<u32> = balls
+                    .iter()
+                    .filter(|b| bucket_of(b) == bucket)
+                    .map(|b| b.id)
+                    .collect();
+                assert_eq!(ids, expected, "snapshot {} bucket {}", i, bucket);
+            }
+        }
+    }
+
+    #[test]
+    fn decoder_rejects_delta_without_baseline() {
+        let mut enc = DeltaEncoder::new(100);
+        enc.encode(full(0.0, vec![equator_ball(1, 0.0)]));
+        let delta = enc.encode(full(0.1, vec![equator_ball(1, 0.1)]));
+
+        let mut dec = DeltaDecoder::default();
+        assert_eq!(dec.apply(&delta).unwrap_err(), DeltaError::NoBaseline);
+    }
+
+    #[test]
+    fn decoder_detects_gap_and_recovers_on_keyframe() {
+        let mut enc = DeltaEncoder::new(100);
+        let mut dec = DeltaDecoder::default();
+        dec.apply(&enc.encode(full(0.0, vec![equator_ball(1, 0.0)])))
+            .unwrap();
+
+        let _lost = enc.encode(full(0.1, vec![equator_ball(1, 0.1)]));
+        let next = enc.encode(full(0.2, vec![equator_ball(1, 0.2)]));
+        assert_eq!(
+            dec.apply(&next).unwrap_err(),
+            DeltaError::Gap {
+                expected: 2,
+                got: 3
+            }
+        );
+
+        enc.request_keyframe();
+        let key = enc.encode(full(0.3, vec![equator_ball(1, 0.3)]));
+        assert!(key.keyframe);
+        assert_eq!(dec.apply(&key).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn decoder_detects_unknown_unchanged_id() {
+        let mut dec = DeltaDecoder::default();
+        dec.apply(&SpaceStateMsg {
+            keyframe: true,
+            seq: 1,
+            ..full(0.0, vec![])
+        })
+        .unwrap();
+        let bogus = SpaceStateMsg {
+            seq: 2,
+            unchanged: vec![42],
+            ..full(0.1, vec![])
+        };
+        assert_eq!(dec.apply(&bogus).unwrap_err(), DeltaError::Desync);
+    }
+}
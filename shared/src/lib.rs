@@ -1,3 +1,5 @@
 pub mod config;
+pub mod delta;
 pub mod protocol;
 pub mod vec3;
+pub mod wire;
@@ -4,7 +4,7 @@ use ts_rs::TS;
 use crate::config::DeepSpaceConfig;
 
 /// Protocol version - increment when making breaking changes.
-pub const PROTOCOL_VERSION: u32 = 2;
+pub const PROTOCOL_VERSION: u32 = 5;
 
 // === Server -> Client ===
 
@@ -20,6 +20,14 @@ pub enum ServerMsg {
     SpaceState(SpaceStateMsg),
     #[serde(rename = "transfer_in")]
     TransferIn(TransferInMsg),
+    #[serde(rename = "server_going_away")]
+    ServerGoingAway(ServerGoingAwayMsg),
+    #[serde(rename = "pong")]
+    Pong(PongMsg),
+    #[serde(rename = "emote")]
+    Emote(EmoteMsg),
+    #[serde(rename = "leaderboard")]
+    Leaderboard(LeaderboardMsg),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -31,6 +39,14 @@ pub struct WelcomeMsg {
     pub self_id: u32,
     pub players: Vec<PlayerWire>,
     pub config: DeepSpaceConfig,
+    /// Opaque token to send back in `hello` when reconnecting, so the
+    /// player keeps their portal cell, color and stats.
+    #[serde(default)]
+    pub resume_token: String,
+    /// True for `/ws?spectate=1` connections: no portal, `self_id` is 0 and
+    /// `transfer_in` never arrives.
+    #[serde(default)]
+    pub spectator: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -45,7 +61,20 @@ pub struct PlayersStateMsg {
 pub struct SpaceStateMsg {
     /// Server elapsed time when this snapshot was created (seconds)
     pub server_time: f64,
+    /// Incremented per broadcast. A gap means a delta was missed.
+    pub seq: u32,
+    /// Keyframe: `balls` is every ball. Delta: only changes since `seq - 1`
+    /// (see `delta.rs`).
+    pub keyframe: bool,
+    /// Keyframe: all balls. Delta: new, rerouted or drifted balls.
     pub balls: Vec<BallWire>,
+    /// Delta only: balls whose axis/omega are unchanged since last sent;
+    /// advance them analytically from their last record.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unchanged: Vec<u32>,
+    /// Delta only: balls that left space since the previous snapshot.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -57,6 +86,13 @@ pub struct BallWire {
     pub pos: [f64; 3],
     pub axis: [f64; 3],
     pub omega: f64,
+    /// Boards this ball has passed through since it was first launched.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hops: u32,
+}
+
+fn is_zero(v: &u32) -> bool {
+    *v == 0
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -73,6 +109,9 @@ pub struct PlayerWire {
     pub balls_produced: u32,
     #[serde(default)]
     pub balls_in_flight: u32,
+    /// Display name, already validated by the server. Empty = no name.
+    #[serde(default)]
+    pub name: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -83,6 +122,87 @@ pub struct TransferInMsg {
     pub vy: f64,
     pub owner_id: u32,
     pub color: u32,
+    /// Echo in `transfer_ack` once the ball is on the board. Unacked
+    /// transfers go back to deep space if the client disconnects or
+    /// doesn't ack in time.
+    pub seq: u32,
+    /// Deep-space id of the ball. Echo it in `ball_escaped` when this ball
+    /// leaves the board again so it keeps its identity.
+    #[serde(default)]
+    pub ball_id: u32,
+    /// Player who first launched the ball; `ownerId` is whoever sent it
+    /// out last.
+    #[serde(default)]
+    pub origin_owner_id: u32,
+    /// Boards the ball had passed through before this one.
+    #[serde(default)]
+    pub hops: u32,
+}
+
+/// Sent to every client right before the server closes their connection
+/// for a shutdown or redeploy. Reconnecting later is expected to work.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../client/src/shared/generated/")]
+pub struct ServerGoingAwayMsg {
+    pub reason: String,
+}
+
+/// Reply to `ping`, sent straight from the connection task (not the game
+/// loop) so its latency reflects the network rather than the tick rate.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../client/src/shared/generated/")]
+#[serde(rename_all = "camelCase")]
+pub struct PongMsg {
+    /// Echo of `ping.clientTime`.
+    pub client_time: f64,
+    /// Server elapsed time (same clock as `space_state.serverTime`) when the
+    /// ping was answered.
+    pub server_time: f64,
+}
+
+/// The fixed set of emotes a player can send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../client/src/shared/generated/")]
+#[serde(rename_all = "snake_case")]
+pub enum EmoteKind {
+    Wave,
+    Cheer,
+    Thanks,
+}
+
+/// An emote from `player_id`, broadcast to everyone in the room.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../client/src/shared/generated/")]
+#[serde(rename_all = "camelCase")]
+pub struct EmoteMsg {
+    pub player_id: u32,
+    pub kind: EmoteKind,
+}
+
+/// A connected player's place in `leaderboard`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../client/src/shared/generated/")]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardEntry {
+    pub player_id: u32,
+    pub score: u32,
+}
+
+/// Scores of connected (non-bot) players, broadcast every few seconds.
+/// Points come from balls delivered to and received from other players
+/// and from reported bumper hits.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "../../client/src/shared/generated/")]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardMsg {
+    /// Best first, ties broken by lower id; cut to the top entries, so a
+    /// player missing here ranks below the last one.
+    pub entries: Vec<LeaderboardEntry>,
+    /// Number of ranked players, including those cut from `entries`
+    pub total: u32,
+    /// Seconds until every score resets to zero. Absent if they never do.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resets_in: Option<f64>,
 }
 
 // === Client -> Server ===
@@ -91,12 +211,67 @@ pub struct TransferInMsg {
 #[ts(export, export_to = "../../client/src/shared/generated/")]
 #[serde(tag = "type")]
 pub enum ClientMsg {
+    /// Optional first message on a connection. Carries the resume token from
+    /// a previous `welcome` so the server can restore the player's identity.
+    #[serde(rename = "hello")]
+    Hello {
+        #[serde(
+            default,
+            rename = "resumeToken",
+            skip_serializing_if = "Option::is_none"
+        )]
+        resume_token: Option<String>,
+        /// Display name to join with. Invalid names are ignored.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
     #[serde(rename = "ball_escaped")]
-    BallEscaped { vx: f64, vy: f64 },
+    BallEscaped {
+        vx: f64,
+        vy: f64,
+        /// Gift the ball: reroutes steer it toward this player's portal
+        /// while they're eligible. Unknown ids and yourself are ignored.
+        #[serde(
+            default,
+            rename = "targetPlayerId",
+            skip_serializing_if = "Option::is_none"
+        )]
+        target_player_id: Option<u32>,
+        /// `transfer_in.ballId` if this is a ball received from another
+        /// player re-escaping; the server keeps its origin and hop count.
+        /// Omit for balls launched on this board.
+        #[serde(default, rename = "ballId", skip_serializing_if = "Option::is_none")]
+        ball_id: Option<u32>,
+    },
     #[serde(rename = "set_paused")]
     SetPaused { paused: bool },
     #[serde(rename = "activity")]
     Activity,
+    /// Sent when a `space_state` delta can't be applied (missed seq or no
+    /// baseline yet). The next broadcast will be a keyframe.
+    #[serde(rename = "request_keyframe")]
+    RequestKeyframe,
+    /// Clock sync probe; answered with `pong`. `clientTime` is opaque to the
+    /// server and echoed back unchanged.
+    #[serde(rename = "ping")]
+    Ping {
+        #[serde(rename = "clientTime")]
+        client_time: f64,
+    },
+    /// The ball from `transfer_in` with this `seq` is now on the board.
+    #[serde(rename = "transfer_ack")]
+    TransferAck { seq: u32 },
+    /// Change display name; empty clears it. Rate limited, and names the
+    /// server rejects are dropped.
+    #[serde(rename = "set_name")]
+    SetName { name: String },
+    /// Shown at the sender's portal for everyone in the room. Rate limited.
+    #[serde(rename = "emote")]
+    Emote { kind: EmoteKind },
+    /// Bumper hits on the board since the last report. The server caps
+    /// how many it credits per second.
+    #[serde(rename = "bumper_hits")]
+    BumperHits { count: u32 },
 }
 
 // === Conversion helpers ===
@@ -125,18 +300,23 @@ mod tests {
                 paused: false,
                 balls_produced: 0,
                 balls_in_flight: 0,
+                name: String::new(),
             }],
             config: DeepSpaceConfig::default(),
+            resume_token: "abc123".to_string(),
+            spectator: false,
         });
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"type\":\"welcome\""));
-        assert!(json.contains("\"protocolVersion\":2"));
+        assert!(json.contains("\"protocolVersion\":5"));
+        assert!(json.contains("\"resumeToken\":\"abc123\""));
         let parsed: ServerMsg = serde_json::from_str(&json).unwrap();
         match parsed {
             ServerMsg::Welcome(w) => {
                 assert_eq!(w.protocol_version, PROTOCOL_VERSION);
                 assert_eq!(w.self_id, 7);
                 assert_eq!(w.players.len(), 1);
+                assert_eq!(w.resume_token, "abc123");
             }
             _ => panic!("Expected Welcome"),
         }
@@ -146,22 +326,34 @@ mod tests {
     fn server_msg_space_state_roundtrip() {
         let msg = ServerMsg::SpaceState(SpaceStateMsg {
             server_time: 12.345,
+            seq: 9,
+            keyframe: false,
             balls: vec![BallWire {
                 id: 12,
                 owner_id: 3,
                 pos: [0.5, 0.7, 0.5],
                 axis: [0.0, 0.0, 1.0],
                 omega: 0.8,
+                hops: 0,
             }],
+            unchanged: vec![4, 5],
+            removed: vec![],
         });
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"type\":\"space_state\""));
         assert!(json.contains("\"serverTime\":12.345"));
+        assert!(json.contains("\"unchanged\":[4,5]"));
+        assert!(!json.contains("removed"), "empty lists are omitted");
+        assert!(!json.contains("hops"), "zero hops are omitted");
         let parsed: ServerMsg = serde_json::from_str(&json).unwrap();
         match parsed {
             ServerMsg::SpaceState(s) => {
                 assert_eq!(s.balls.len(), 1);
                 assert!((s.server_time - 12.345).abs() < 1e-9);
+                assert_eq!(s.seq, 9);
+                assert!(!s.keyframe);
+                assert_eq!(s.unchanged, vec![4, 5]);
+                assert!(s.removed.is_empty());
             }
             _ => panic!("Expected SpaceState"),
         }
@@ -174,34 +366,213 @@ mod tests {
             vy: 1.2,
             owner_id: 5,
             color: 0xff6600,
+            seq: 17,
+            ball_id: 40,
+            origin_owner_id: 2,
+            hops: 3,
         });
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"type\":\"transfer_in\""));
+        assert!(json.contains("\"seq\":17"));
+        assert!(json.contains("\"ballId\":40"));
+        assert!(json.contains("\"originOwnerId\":2"));
         let parsed: ServerMsg = serde_json::from_str(&json).unwrap();
         match parsed {
             ServerMsg::TransferIn(t) => {
                 assert!((t.vx - 0.3).abs() < 1e-9);
                 assert!((t.vy - 1.2).abs() < 1e-9);
+                assert_eq!(t.seq, 17);
+                assert_eq!((t.ball_id, t.origin_owner_id, t.hops), (40, 2, 3));
             }
             _ => panic!("Expected TransferIn"),
         }
     }
 
+    #[test]
+    fn client_msg_transfer_ack_roundtrip() {
+        let json = serde_json::to_string(&ClientMsg::TransferAck { seq: 17 }).unwrap();
+        assert_eq!(json, r#"{"type":"transfer_ack","seq":17}"#);
+        match serde_json::from_str::<ClientMsg>(&json).unwrap() {
+            ClientMsg::TransferAck { seq } => assert_eq!(seq, 17),
+            _ => panic!("Expected TransferAck"),
+        }
+    }
+
+    #[test]
+    fn server_msg_going_away_roundtrip() {
+        let msg = ServerMsg::ServerGoingAway(ServerGoingAwayMsg {
+            reason: "shutting down".to_string(),
+        });
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"server_going_away","reason":"shutting down"}"#
+        );
+        match serde_json::from_str::<ServerMsg>(&json).unwrap() {
+            ServerMsg::ServerGoingAway(g) => assert_eq!(g.reason, "shutting down"),
+            _ => panic!("Expected ServerGoingAway"),
+        }
+    }
+
+    #[test]
+    fn ping_pong_roundtrip() {
+        let json = serde_json::to_string(&ClientMsg::Ping { client_time: 1.25 }).unwrap();
+        assert_eq!(json, r#"{"type":"ping","clientTime":1.25}"#);
+        match serde_json::from_str::<ClientMsg>(&json).unwrap() {
+            ClientMsg::Ping { client_time } => assert_eq!(client_time, 1.25),
+            _ => panic!("Expected Ping"),
+        }
+
+        let msg = ServerMsg::Pong(PongMsg {
+            client_time: 1.25,
+            server_time: 30.5,
+        });
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"pong","clientTime":1.25,"serverTime":30.5}"#
+        );
+        match serde_json::from_str::<ServerMsg>(&json).unwrap() {
+            ServerMsg::Pong(p) => {
+                assert_eq!(p.client_time, 1.25);
+                assert_eq!(p.server_time, 30.5);
+            }
+            _ => panic!("Expected Pong"),
+        }
+    }
+
     #[test]
     fn client_msg_ball_escaped_roundtrip() {
-        let msg = ClientMsg::BallEscaped { vx: 0.42, vy: -1.1 };
+        let msg = ClientMsg::BallEscaped {
+            vx: 0.42,
+            vy: -1.1,
+            target_player_id: None,
+            ball_id: None,
+        };
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"type\":\"ball_escaped\""));
+        assert!(!json.contains("targetPlayerId"));
+        assert!(!json.contains("ballId"));
         let parsed: ClientMsg = serde_json::from_str(&json).unwrap();
         match parsed {
-            ClientMsg::BallEscaped { vx, vy } => {
+            ClientMsg::BallEscaped {
+                vx,
+                vy,
+                target_player_id,
+                ball_id,
+            } => {
                 assert!((vx - 0.42).abs() < 1e-9);
                 assert!((vy - (-1.1)).abs() < 1e-9);
+                assert_eq!(target_player_id, None);
+                assert_eq!(ball_id, None);
             }
             _ => panic!("Expected BallEscaped"),
         }
     }
 
+    #[test]
+    fn client_msg_ball_escaped_with_target() {
+        let json = r#"{"type":"ball_escaped","vx":1,"vy":-2,"targetPlayerId":7}"#;
+        match serde_json::from_str::<ClientMsg>(json).unwrap() {
+            ClientMsg::BallEscaped {
+                target_player_id, ..
+            } => assert_eq!(target_player_id, Some(7)),
+            _ => panic!("Expected BallEscaped"),
+        }
+    }
+
+    #[test]
+    fn client_msg_ball_escaped_echoes_ball_id() {
+        let json = r#"{"type":"ball_escaped","vx":1,"vy":-2,"ballId":40}"#;
+        match serde_json::from_str::<ClientMsg>(json).unwrap() {
+            ClientMsg::BallEscaped { ball_id, .. } => assert_eq!(ball_id, Some(40)),
+            _ => panic!("Expected BallEscaped"),
+        }
+    }
+
+    #[test]
+    fn transfer_in_without_lineage_defaults_to_zero() {
+        let json = r#"{"type":"transfer_in","vx":0,"vy":1,"ownerId":3,"color":0,"seq":1}"#;
+        match serde_json::from_str::<ServerMsg>(json).unwrap() {
+            ServerMsg::TransferIn(t) => {
+                assert_eq!((t.ball_id, t.origin_owner_id, t.hops), (0, 0, 0))
+            }
+            _ => panic!("Expected TransferIn"),
+        }
+    }
+
+    #[test]
+    fn leaderboard_roundtrip() {
+        let msg = ServerMsg::Leaderboard(LeaderboardMsg {
+            entries: vec![
+                LeaderboardEntry {
+                    player_id: 4,
+                    score: 120,
+                },
+                LeaderboardEntry {
+                    player_id: 2,
+                    score: 35,
+                },
+            ],
+            total: 5,
+            resets_in: None,
+        });
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"leaderboard","entries":[{"playerId":4,"score":120},{"playerId":2,"score":35}],"total":5}"#
+        );
+        match serde_json::from_str::<ServerMsg>(&json).unwrap() {
+            ServerMsg::Leaderboard(lb) => {
+                assert_eq!(lb.entries.len(), 2);
+                assert_eq!(lb.total, 5);
+                assert_eq!(lb.resets_in, None);
+            }
+            _ => panic!("Expected Leaderboard"),
+        }
+    }
+
+    #[test]
+    fn client_msg_bumper_hits() {
+        let json = r#"{"type":"bumper_hits","count":3}"#;
+        match serde_json::from_str::<ClientMsg>(json).unwrap() {
+            ClientMsg::BumperHits { count } => assert_eq!(count, 3),
+            _ => panic!("Expected BumperHits"),
+        }
+    }
+
+    #[test]
+    fn client_msg_hello_roundtrip() {
+        let msg = ClientMsg::Hello {
+            resume_token: Some("abc123".to_string()),
+            name: Some("Ada".to_string()),
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert!(json.contains("\"type\":\"hello\""));
+        assert!(json.contains("\"resumeToken\":\"abc123\""));
+        assert!(json.contains("\"name\":\"Ada\""));
+        let parsed: ClientMsg = serde_json::from_str(&json).unwrap();
+        match parsed {
+            ClientMsg::Hello { resume_token, name } => {
+                assert_eq!(resume_token.as_deref(), Some("abc123"));
+                assert_eq!(name.as_deref(), Some("Ada"));
+            }
+            _ => panic!("Expected Hello"),
+        }
+    }
+
+    #[test]
+    fn client_msg_hello_without_token() {
+        let parsed: ClientMsg = serde_json::from_str(r#"{"type":"hello"}"#).unwrap();
+        match parsed {
+            ClientMsg::Hello { resume_token, name } => {
+                assert!(resume_token.is_none());
+                assert!(name.is_none());
+            }
+            _ => panic!("Expected Hello"),
+        }
+    }
+
     #[test]
     fn client_msg_set_paused_roundtrip() {
         let msg = ClientMsg::SetPaused { paused: true };
@@ -214,6 +585,55 @@ mod tests {
         }
     }
 
+    #[test]
+    fn client_msg_set_name_roundtrip() {
+        let msg = ClientMsg::SetName {
+            name: "Ada".to_string(),
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(json, r#"{"type":"set_name","name":"Ada"}"#);
+        let parsed: ClientMsg = serde_json::from_str(&json).unwrap();
+        match parsed {
+            ClientMsg::SetName { name } => assert_eq!(name, "Ada"),
+            _ => panic!("Expected SetName"),
+        }
+    }
+
+    #[test]
+    fn emote_roundtrip() {
+        let json = serde_json::to_string(&ClientMsg::Emote {
+            kind: EmoteKind::Thanks,
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"type":"emote","kind":"thanks"}"#);
+
+        let msg = ServerMsg::Emote(EmoteMsg {
+            player_id: 3,
+            kind: EmoteKind::Wave,
+        });
+        let json = serde_json::to_string(&msg).unwrap();
+        assert_eq!(json, r#"{"type":"emote","playerId":3,"kind":"wave"}"#);
+        match serde_json::from_str::<ServerMsg>(&json).unwrap() {
+            ServerMsg::Emote(e) => {
+                assert_eq!(e.player_id, 3);
+                assert_eq!(e.kind, EmoteKind::Wave);
+            }
+            _ => panic!("Expected Emote"),
+        }
+    }
+
+    #[test]
+    fn unknown_emote_kind_is_rejected() {
+        assert!(serde_json::from_str::<ClientMsg>(r#"{"type":"emote","kind":"taunt"}"#).is_err());
+    }
+
+    #[test]
+    fn player_wire_without_name_defaults_to_empty() {
+        let json = r#"{"id":1,"cellIndex":0,"portalPos":[1,0,0],"color":0}"#;
+        let parsed: PlayerWire = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.name, "");
+    }
+
     #[test]
     fn players_state_roundtrip() {
         let msg = ServerMsg::PlayersState(PlayersStateMsg {
@@ -226,6 +646,7 @@ mod tests {
                     paused: false,
                     balls_produced: 5,
                     balls_in_flight: 2,
+                    name: "Ada".to_string(),
                 },
                 PlayerWire {
                     id: 2,
@@ -235,6 +656,7 @@ mod tests {
                     paused: true,
                     balls_produced: 10,
                     balls_in_flight: 0,
+                    name: String::new(),
                 },
             ],
         });
@@ -246,6 +668,7 @@ mod tests {
                 assert!(!p.players[0].paused);
                 assert!(p.players[1].paused);
                 assert_eq!(p.players[0].balls_produced, 5);
+                assert_eq!(p.players[0].name, "Ada");
             }
             _ => panic!("Expected PlayersState"),
         }
@@ -0,0 +1,336 @@
+//! Compact binary encoding for high-rate server messages.
+//!
+//! Clients that connect with `?wire=packed` receive `space_state` as a binary
+//! WebSocket frame in the fixed layout below instead of JSON. All other
+//! messages stay JSON text frames, since they are rare and small.
+//!
+//! Layout (little-endian):
+//!
+//! ```text
+//! u8   tag            (TAG_SPACE_STATE)
+//! u32  seq
+//! u8   flags          (bit 0: keyframe)
+//! f64  server_time
+//! u32  ball count
+//! per ball (26 bytes):
+//!   u32     id
+//!   u32     owner_id
+//!   i16 x3  pos    (unit vector, snorm: v * 32767)
+//!   i16 x3  axis   (unit vector, snorm: v * 32767)
+//!   f32     omega
+//!   u16     hops   (saturates at 65535)
+//! u32  unchanged count, then u32 ids
+//! u32  removed count, then u32 ids
+//! ```
+//!
+//! Snorm quantization gives ~3e-5 resolution, finer than the `round4` used
+//! for JSON. A ball is 26 bytes packed versus ~90 bytes as JSON.
+
+use crate::protocol::{BallWire, SpaceStateMsg};
+
+/// Query parameter value (`/ws?wire=packed`) that opts a client into binary frames.
+pub const WIRE_PACKED: &str = "packed";
+
+/// First byte of a packed `space_state` frame.
+pub const TAG_SPACE_STATE: u8 = 1;
+
+const FLAG_KEYFRAME: u8 = 1;
+
+const HEADER_BYTES: usize = 1 + 4 + 1 + 8 + 4;
+const BALL_BYTES: usize = 4 + 4 + 6 + 6 + 4 + 2;
+const SNORM_SCALE: f64 = i16::MAX as f64;
+
+/// Encode a space_state snapshot into the packed binary layout.
+pub fn encode_space_state(msg: &SpaceStateMsg) -> Vec<u8> {
+    let parts = PackedParts::new(&msg.balls, &msg.unchanged, &msg.removed);
+    assemble_space_state(msg.server_time, msg.seq, msg.keyframe, &[&parts])
+}
+
+/// Pre-encoded balls and id lists for a subset of a snapshot. The server
+/// encodes each area-of-interest bucket once and concatenates the buckets
+/// each client needs with [`assemble_space_state`].
+#[derive(Debug, Clone, Default)]
+pub struct PackedParts {
+    ball_count: usize,
+    balls: Vec<u8>,
+    unchanged: Vec<u8>,
+    removed: Vec<u8>,
+}
+
+impl PackedParts {
+    pub fn new(balls: &[BallWire], unchanged: &[u32], removed: &[u32]) -> Self {
+        let mut out = Self {
+            ball_count: balls.len(),
+            balls: Vec::with_capacity(balls.len() * BALL_BYTES),
+            unchanged: Vec::with_capacity(unchanged.len() * 4),
+            removed: Vec::with_capacity(removed.len() * 4),
+        };
+        for ball in balls {
+            out.balls.extend_from_slice(&ball.id.to_le_bytes());
+            out.balls.extend_from_slice(&ball.owner_id.to_le_bytes());
+            for v in ball.pos.iter().chain(ball.axis.iter()) {
+                out.balls.extend_from_slice(&snorm16(*v).to_le_bytes());
+            }
+            out.balls
+                .extend_from_slice(&(ball.omega as f32).to_le_bytes());
+            let hops = ball.hops.min(u16::MAX as u32) as u16;
+            out.balls.extend_from_slice(&hops.to_le_bytes());
+        }
+        for (dst, ids) in [(&mut out.unchanged, unchanged), (&mut out.removed, removed)] {
+            for id in ids {
+                dst.extend_from_slice(&id.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.balls.is_empty() && self.unchanged.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Build a packed space_state frame from the concatenation of `parts`.
+pub fn assemble_space_state(
+    server_time: f64,
+    seq: u32,
+    keyframe: bool,
+    parts: &[&PackedParts],
+) -> Vec<u8> {
+    let body: usize = parts
+        .iter()
+        .map(|p| p.balls.len() + p.unchanged.len() + p.removed.len())
+        .sum();
+    let mut out = Vec::with_capacity(HEADER_BYTES + body + 8);
+    out.push(TAG_SPACE_STATE);
+    out.extend_from_slice(&seq.to_le_bytes());
+    out.push(if keyframe { FLAG_KEYFRAME } else { 0 });
+    out.extend_from_slice(&server_time.to_le_bytes());
+    let ball_count: usize = parts.iter().map(|p| p.ball_count).sum();
+    out.extend_from_slice(&(ball_count as u32).to_le_bytes());
+    for p in parts {
+        out.extend_from_slice(&p.balls);
+    }
+    let unchanged_bytes: usize = parts.iter().map(|p| p.unchanged.len()).sum();
+    out.extend_from_slice(&((unchanged_bytes / 4) as u32).to_le_bytes());
+    for p in parts {
+        out.extend_from_slice(&p.unchanged);
+    }
+    let removed_bytes: usize = parts.iter().map(|p| p.removed.len()).sum();
+    out.extend_from_slice(&((removed_bytes / 4) as u32).to_le_bytes());
+    for p in parts {
+        out.extend_from_slice(&p.removed);
+    }
+    out
+}
+
+/// Decode a packed space_state frame. Returns `None` on an unknown tag or a
+/// truncated/oversized buffer.
+pub fn decode_space_state(buf: &[u8]) -> Option<SpaceStateMsg> {
+    let mut r = Reader { buf, pos: 0 };
+    if r.u8()? != TAG_SPACE_STATE {
+        return None;
+    }
+    let seq = u32::from_le_bytes(r.array()?);
+    let keyframe = r.u8()? & FLAG_KEYFRAME != 0;
+    let server_time = f64::from_le_bytes(r.array()?);
+    let count = u32::from_le_bytes(r.array()?) as usize;
+    if r.remaining() < count.checked_mul(BALL_BYTES)? {
+        return None;
+    }
+
+    let mut balls = Vec::with_capacity(count);
+    for _ in 0..count {
+        let id = u32::from_le_bytes(r.array()?);
+        let owner_id = u32::from_le_bytes(r.array()?);
+        let mut pos = [0.0; 3];
+        for v in &mut pos {
+            *v = unsnorm16(i16::from_le_bytes(r.array()?));
+        }
+        let mut axis = [0.0; 3];
+        for v in &mut axis {
+            *v = unsnorm16(i16::from_le_bytes(r.array()?));
+        }
+        let omega = f32::from_le_bytes(r.array()?) as f64;
+        let hops = u16::from_le_bytes(r.array()?) as u32;
+        balls.push(BallWire {
+            id,
+            owner_id,
+            pos,
+            axis,
+            omega,
+            hops,
+        });
+    }
+    let unchanged = r.ids()?;
+    let removed = r.ids()?;
+    if r.remaining() != 0 {
+        return None;
+    }
+    Some(SpaceStateMsg {
+        server_time,
+        seq,
+        keyframe,
+        balls,
+        unchanged,
+        removed,
+    })
+}
+
+#[inline]
+fn snorm16(v: f64) -> i16 {
+    (v.clamp(-1.0, 1.0) * SNORM_SCALE).round() as i16
+}
+
+#[inline]
+fn unsnorm16(v: i16) -> f64 {
+    (v as f64 / SNORM_SCALE).max(-1.0)
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn u8(&mut self) -> Option<u8> {
+        let b = *self.buf.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.buf.get(self.pos..self.pos + N)?;
+        self.pos += N;
+        bytes.try_into().ok()
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    /// Length-prefixed list of u32 ids.
+    fn ids(&mut self) -> Option<Vec<u32>> {
+        let count = u32::from_le_bytes(self.array()?) as usize;
+        if self.remaining() < count.checked_mul(4)? {
+            return None;
+        }
+        (0..count)
+            .map(|_| self.array().map(u32::from_le_bytes))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SpaceStateMsg {
+        SpaceStateMsg {
+            server_time: 123.456,
+            seq: 77,
+            keyframe: false,
+            balls: vec![
+                BallWire {
+                    id: 1,
+                    owner_id: 2,
+                    pos: [0.5774, -0.5774, 0.5774],
+                    axis: [0.0, 0.0, 1.0],
+                    omega: 0.75,
+                    hops: 12,
+                },
+                BallWire {
+                    id: u32::MAX,
+                    owner_id: 0,
+                    pos: [-1.0, 0.0, 0.0],
+                    axis: [0.0, -1.0, 0.0],
+                    omega: -2.5,
+                    hops: 100_000,
+                },
+            ],
+            unchanged: vec![5, 6, 7],
+            removed: vec![9],
+        }
+    }
+
+    #[test]
+    fn space_state_roundtrip_within_quantization() {
+        let msg = sample();
+        let decoded = decode_space_state(&encode_space_state(&msg)).unwrap();
+
+        assert_eq!(decoded.server_time, msg.server_time);
+        assert_eq!(decoded.seq, msg.seq);
+        assert_eq!(decoded.keyframe, msg.keyframe);
+        assert_eq!(decoded.unchanged, msg.unchanged);
+        assert_eq!(decoded.removed, msg.removed);
+        assert_eq!(decoded.balls.len(), msg.balls.len());
+        for (a, b) in msg.balls.iter().zip(&decoded.balls) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.owner_id, b.owner_id);
+            for i in 0..3 {
+                assert!((a.pos[i] - b.pos[i]).abs() < 1e-4);
+                assert!((a.axis[i] - b.axis[i]).abs() < 1e-4);
+            }
+            assert!((a.omega - b.omega).abs() < 1e-6);
+            assert_eq!(b.hops, a.hops.min(u16::MAX as u32));
+        }
+    }
+
+    #[test]
+    fn packed_is_much_smaller_than_json() {
+        let msg = sample();
+        let packed = encode_space_state(&msg);
+        let json = serde_json::to_string(&crate::protocol::ServerMsg::SpaceState(msg)).unwrap();
+        assert_eq!(packed.len(), HEADER_BYTES + 2 * BALL_BYTES + 8 + 4 * 4);
+        assert!(packed.len() * 2 < json.len());
+    }
+
+    #[test]
+    fn empty_snapshot_roundtrip() {
+        let msg = SpaceStateMsg {
+            server_time: 0.0,
+            seq: 1,
+            keyframe: true,
+            balls: vec![],
+            unchanged: vec![],
+            removed: vec![],
+        };
+        let decoded = decode_space_state(&encode_space_state(&msg)).unwrap();
+        assert!(decoded.keyframe);
+        assert!(decoded.balls.is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_tag_and_wrong_length() {
+        let mut buf = encode_space_state(&sample());
+        assert!(decode_space_state(&buf[..buf.len() - 1]).is_none());
+
+        buf.push(0);
+        assert!(decode_space_state(&buf).is_none());
+        buf.pop();
+
+        buf[0] = 0xff;
+        assert!(decode_space_state(&buf).is_none());
+        assert!(decode_space_state(&[]).is_none());
+    }
+
+    #[test]
+    fn assembled_parts_decode_as_concatenation() {
+        let msg = sample();
+        let a = PackedParts::new(&msg.balls[..1], &msg.unchanged[..2], &[]);
+        let b = PackedParts::new(&msg.balls[1..], &msg.unchanged[2..], &msg.removed);
+        let buf = assemble_space_state(msg.server_time, msg.seq, msg.keyframe, &[&a, &b]);
+        assert_eq!(buf, encode_space_state(&msg));
+        assert!(PackedParts::default().is_empty());
+        assert!(!a.is_empty());
+    }
+
+    #[test]
+    fn huge_count_does_not_overflow_or_allocate() {
+        let mut buf = vec![TAG_SPACE_STATE];
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.push(0);
+        buf.extend_from_slice(&0.0f64.to_le_bytes());
+        buf.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode_space_state(&buf).is_none());
+    }
+}